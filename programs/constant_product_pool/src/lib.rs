@@ -0,0 +1,252 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use std::convert::TryFrom;
+
+declare_id!("7RvzgZ3W1ZjUeE1SSEEkXCFk7CewstTpsFMJ3uSdx85S");
+
+/// Minimal x * y = k pool used to exercise drift's constant product spot fulfillment locally.
+/// Liquidity is added by transferring tokens directly into the pool vaults.
+#[program]
+pub mod constant_product_pool {
+    use super::*;
+
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<()> {
+        if fee_denominator == 0 || fee_numerator >= fee_denominator {
+            return Err(ErrorCode::InvalidFee.into());
+        }
+
+        let pool_key = ctx.accounts.pool.key();
+        let (pool_signer, pool_signer_nonce) = Pubkey::find_program_address(
+            &[b"pool_signer".as_ref(), pool_key.as_ref()],
+            ctx.program_id,
+        );
+
+        let base_vault = &ctx.accounts.base_vault;
+        let quote_vault = &ctx.accounts.quote_vault;
+        if base_vault.owner != pool_signer || quote_vault.owner != pool_signer {
+            return Err(ErrorCode::InvalidVaultOwner.into());
+        }
+
+        if base_vault.mint == quote_vault.mint {
+            return Err(ErrorCode::InvalidVault.into());
+        }
+
+        let mut pool = ctx.accounts.pool.load_init()?;
+        *pool = ConstantProductPool {
+            base_mint: base_vault.mint,
+            quote_mint: quote_vault.mint,
+            base_vault: base_vault.key(),
+            quote_vault: quote_vault.key(),
+            pool_signer,
+            fee_numerator,
+            fee_denominator,
+            pool_signer_nonce,
+            padding: [0; 7],
+        };
+
+        Ok(())
+    }
+
+    /// Swaps exactly amount_in of the source token for at least minimum_amount_out of the destination token.
+    /// The direction is determined by which pool vault is passed as the source vault.
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, minimum_amount_out: u64) -> Result<()> {
+        let pool = ctx.accounts.pool.load()?;
+
+        validate_swap_vaults(&ctx, &pool)?;
+
+        let amount_out = calculate_amount_out(
+            amount_in,
+            ctx.accounts.pool_source_vault.amount,
+            ctx.accounts.pool_destination_vault.amount,
+            pool.fee_numerator,
+            pool.fee_denominator,
+        )
+        .ok_or(ErrorCode::MathError)?;
+
+        if amount_out == 0 || amount_out < minimum_amount_out {
+            return Err(ErrorCode::SlippageExceeded.into());
+        }
+
+        transfer_swap_amounts(&ctx, pool.pool_signer_nonce, amount_in, amount_out)
+    }
+
+    /// Swaps at most maximum_amount_in of the source token for exactly amount_out of the destination token.
+    pub fn swap_exact_out(
+        ctx: Context<Swap>,
+        amount_out: u64,
+        maximum_amount_in: u64,
+    ) -> Result<()> {
+        let pool = ctx.accounts.pool.load()?;
+
+        validate_swap_vaults(&ctx, &pool)?;
+
+        let amount_in = calculate_amount_in(
+            amount_out,
+            ctx.accounts.pool_source_vault.amount,
+            ctx.accounts.pool_destination_vault.amount,
+            pool.fee_numerator,
+            pool.fee_denominator,
+        )
+        .ok_or(ErrorCode::MathError)?;
+
+        if amount_out == 0 || amount_in > maximum_amount_in {
+            return Err(ErrorCode::SlippageExceeded.into());
+        }
+
+        transfer_swap_amounts(&ctx, pool.pool_signer_nonce, amount_in, amount_out)
+    }
+}
+
+fn validate_swap_vaults(ctx: &Context<Swap>, pool: &ConstantProductPool) -> Result<()> {
+    let pool_source_vault = ctx.accounts.pool_source_vault.key();
+    let pool_destination_vault = ctx.accounts.pool_destination_vault.key();
+
+    let valid_vaults = (pool_source_vault == pool.base_vault
+        && pool_destination_vault == pool.quote_vault)
+        || (pool_source_vault == pool.quote_vault && pool_destination_vault == pool.base_vault);
+
+    if !valid_vaults {
+        return Err(ErrorCode::InvalidVault.into());
+    }
+
+    Ok(())
+}
+
+fn transfer_swap_amounts(
+    ctx: &Context<Swap>,
+    pool_signer_nonce: u8,
+    amount_in: u64,
+    amount_out: u64,
+) -> Result<()> {
+    let cpi_accounts = Transfer {
+        from: ctx.accounts.user_source.to_account_info(),
+        to: ctx.accounts.pool_source_vault.to_account_info(),
+        authority: ctx.accounts.user_authority.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    token::transfer(CpiContext::new(cpi_program, cpi_accounts), amount_in)?;
+
+    let pool_key = ctx.accounts.pool.key();
+    let signature_seeds = [
+        b"pool_signer".as_ref(),
+        pool_key.as_ref(),
+        bytemuck::bytes_of(&pool_signer_nonce),
+    ];
+    let signers = &[&signature_seeds[..]];
+    let cpi_accounts = Transfer {
+        from: ctx.accounts.pool_destination_vault.to_account_info(),
+        to: ctx.accounts.user_destination.to_account_info(),
+        authority: ctx.accounts.pool_signer.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    token::transfer(
+        CpiContext::new_with_signer(cpi_program, cpi_accounts, signers),
+        amount_out,
+    )
+}
+
+pub fn calculate_amount_out(
+    amount_in: u64,
+    source_reserve: u64,
+    destination_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Option<u64> {
+    let amount_in_after_fee = (amount_in as u128)
+        .checked_mul(fee_denominator.checked_sub(fee_numerator)? as u128)?
+        .checked_div(fee_denominator as u128)?;
+
+    let amount_out = (destination_reserve as u128)
+        .checked_mul(amount_in_after_fee)?
+        .checked_div((source_reserve as u128).checked_add(amount_in_after_fee)?)?;
+
+    u64::try_from(amount_out).ok()
+}
+
+pub fn calculate_amount_in(
+    amount_out: u64,
+    source_reserve: u64,
+    destination_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Option<u64> {
+    let numerator = (source_reserve as u128).checked_mul(amount_out as u128)?;
+    let denominator = (destination_reserve as u128).checked_sub(amount_out as u128)?;
+    let amount_in_after_fee = ceil_div(numerator, denominator)?;
+
+    let amount_in = ceil_div(
+        amount_in_after_fee.checked_mul(fee_denominator as u128)?,
+        fee_denominator.checked_sub(fee_numerator)? as u128,
+    )?;
+
+    u64::try_from(amount_in).ok()
+}
+
+fn ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    let quotient = numerator.checked_div(denominator)?;
+    if numerator.checked_rem(denominator)? > 0 {
+        quotient.checked_add(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(C)]
+pub struct ConstantProductPool {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub pool_signer: Pubkey,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    pub pool_signer_nonce: u8,
+    pub padding: [u8; 7],
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(zero)]
+    pub pool: AccountLoader<'info, ConstantProductPool>,
+    pub base_vault: Box<Account<'info, TokenAccount>>,
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub pool: AccountLoader<'info, ConstantProductPool>,
+    /// CHECK: Checked by spl_token
+    pub pool_signer: AccountInfo<'info>,
+    pub user_authority: Signer<'info>,
+    #[account(mut)]
+    pub user_source: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_destination: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub pool_source_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub pool_destination_vault: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Invalid fee")]
+    InvalidFee,
+    #[msg("Vault not owned by pool signer")]
+    InvalidVaultOwner,
+    #[msg("Invalid vault")]
+    InvalidVault,
+    #[msg("Math error")]
+    MathError,
+    #[msg("Slippage exceeded")]
+    SlippageExceeded,
+}
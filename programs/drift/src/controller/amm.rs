@@ -30,7 +30,7 @@ use crate::math::{amm, amm_spread, bn, cp_curve, quote_asset::*};
 use crate::state::events::CurveRecord;
 use crate::state::oracle::OraclePriceData;
 use crate::state::perp_market::{PerpMarket, AMM};
-use crate::state::spot_market::{SpotBalance, SpotBalanceType, SpotMarket};
+use crate::state::spot_market::{ProtocolRevenueSource, SpotBalance, SpotBalanceType, SpotMarket};
 use crate::state::user::{SpotPosition, User};
 use crate::validate;
 
@@ -291,6 +291,7 @@ pub fn formulaic_update_k(
     let base_asset_reserve_before = market.amm.base_asset_reserve;
     let quote_asset_reserve_before = market.amm.quote_asset_reserve;
     let sqrt_k_before = market.amm.sqrt_k;
+    let reserve_price_before = market.amm.reserve_price()?;
 
     let funding_imbalance_cost_i64 = funding_imbalance_cost.cast::<i64>()?;
 
@@ -325,6 +326,9 @@ pub fn formulaic_update_k(
             k_pct_lower_bound,
         )?;
 
+        // user_lp_shares only counts lp shares in the amm, ranged lp shares parked while the
+        // reserve price is outside their range dont hold k up. the range math measures reserves
+        // per share, so scaling k leaves what ranged lp shares are owed unchanged
         let new_sqrt_k = bn::U192::from(market.amm.sqrt_k)
             .safe_mul(bn::U192::from(k_scale_numerator))?
             .safe_div(bn::U192::from(k_scale_denominator))?
@@ -339,6 +343,19 @@ pub fn formulaic_update_k(
         if cost_applied {
             cp_curve::update_k(market, &update_k_result)?;
 
+            // ranged lp shares enter and leave the amm on the reserve price, so a k update can't move it (1bp for rounding)
+            let reserve_price_after = market.amm.reserve_price()?;
+            validate!(
+                reserve_price_after
+                    .max(reserve_price_before)
+                    .safe_sub(reserve_price_after.min(reserve_price_before))?
+                    <= reserve_price_before / 10_000,
+                ErrorCode::InvalidUpdateK,
+                "k update moved reserve price from {} to {}",
+                reserve_price_before,
+                reserve_price_after
+            )?;
+
             let peg_multiplier_after = market.amm.peg_multiplier;
             let base_asset_reserve_after = market.amm.base_asset_reserve;
             let quote_asset_reserve_after = market.amm.quote_asset_reserve;
@@ -591,6 +608,10 @@ pub fn update_pool_balances(
                     spot_market,
                     &mut market.amm.fee_pool,
                 )?;
+                spot_market.protocol_revenue.record_revenue(
+                    ProtocolRevenueSource::AmmFee,
+                    revenue_pool_transfer.unsigned_abs(),
+                )?;
 
                 market.amm.total_fee_withdrawn = market
                     .amm
@@ -603,6 +624,9 @@ pub fn update_pool_balances(
                     spot_market,
                     &mut market.amm.fee_pool,
                 )?;
+                spot_market
+                    .protocol_revenue
+                    .record_amm_fee_pool_top_up(revenue_pool_transfer.unsigned_abs())?;
             }
             Ordering::Equal => (),
         }
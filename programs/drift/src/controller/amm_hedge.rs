@@ -0,0 +1,287 @@
+use solana_program::msg;
+
+use crate::controller::position::PositionDirection;
+use crate::controller::spot_balance::update_spot_balances;
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::amm_hedge::{
+    calculate_hedge_limit_price, calculate_hedge_order, calculate_hedge_pnl,
+    calculate_target_hedge_base_asset_amount,
+};
+use crate::math::casting::Cast;
+use crate::math::orders::validate_fill_price;
+use crate::math::safe_math::SafeMath;
+use crate::math::spot_balance::get_token_amount;
+use crate::state::amm_hedge::AmmHedgeConfig;
+use crate::state::events::AmmHedgeRecord;
+use crate::state::perp_market::PerpMarket;
+use crate::state::spot_fulfillment_params::{ExternalSpotFill, SpotFulfillmentParams};
+use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Trades the linked spot market on an external market to offset part of the amm's perp inventory
+///
+/// The amm holds tokens to offset a short perp inventory and borrows tokens to offset a long one.
+/// Quote for buying tokens comes from the perp market's fee pool and the proceeds of selling tokens
+/// are returned to it, so the pnl from hedging is booked into the fee pool
+pub fn hedge_amm_inventory(
+    amm_hedge_config: &mut AmmHedgeConfig,
+    perp_market: &mut PerpMarket,
+    base_market: &mut SpotMarket,
+    quote_market: &mut SpotMarket,
+    oracle_price: i64,
+    now: i64,
+    fulfillment_params: &mut dyn SpotFulfillmentParams,
+) -> DriftResult {
+    validate!(
+        amm_hedge_config.is_enabled(),
+        ErrorCode::AmmHedgeDisabled,
+        "amm hedge disabled for perp market {}",
+        perp_market.market_index
+    )?;
+
+    validate!(
+        amm_hedge_config.can_hedge(now)?,
+        ErrorCode::AmmHedgeTooFrequent,
+        "last hedge ts {} min hedge interval {} now {}",
+        amm_hedge_config.last_hedge_ts,
+        amm_hedge_config.min_hedge_interval,
+        now
+    )?;
+
+    validate!(
+        amm_hedge_config.perp_market_index == perp_market.market_index,
+        ErrorCode::InvalidAmmHedgeConfig,
+        "amm hedge config perp market index {} != perp market index {}",
+        amm_hedge_config.perp_market_index,
+        perp_market.market_index
+    )?;
+
+    validate!(
+        amm_hedge_config.spot_market_index() == base_market.market_index,
+        ErrorCode::InvalidAmmHedgeConfig,
+        "amm hedge config spot market index {} != spot market index {}",
+        amm_hedge_config.spot_market_index(),
+        base_market.market_index
+    )?;
+
+    validate!(
+        perp_market.quote_spot_market_index == quote_market.market_index,
+        ErrorCode::InvalidAmmHedgeConfig,
+        "perp market quote spot market index {} != quote market index {}",
+        perp_market.quote_spot_market_index,
+        quote_market.market_index
+    )?;
+
+    let hedge_base_asset_amount = amm_hedge_config.get_hedge_base_asset_amount(base_market)?;
+
+    let target_hedge_base_asset_amount = calculate_target_hedge_base_asset_amount(
+        perp_market.amm.base_asset_amount_with_amm,
+        amm_hedge_config.hedge_ratio,
+        amm_hedge_config.max_hedge_base_asset_amount,
+        base_market.get_precision(),
+    )?;
+
+    let (direction, base_asset_amount) = match calculate_hedge_order(
+        target_hedge_base_asset_amount,
+        hedge_base_asset_amount,
+        amm_hedge_config.min_hedge_base_asset_amount,
+        base_market.order_step_size,
+    )? {
+        Some(hedge_order) => hedge_order,
+        None => {
+            msg!(
+                "no hedge needed. target = {} current = {}",
+                target_hedge_base_asset_amount,
+                hedge_base_asset_amount
+            );
+            return Ok(());
+        }
+    };
+
+    let limit_price =
+        calculate_hedge_limit_price(direction, oracle_price, amm_hedge_config.max_slippage)?;
+
+    let max_quote_asset_amount = match direction {
+        PositionDirection::Long => get_token_amount(
+            perp_market.amm.fee_pool.scaled_balance,
+            quote_market,
+            &SpotBalanceType::Deposit,
+        )?
+        .cast::<u64>()?,
+        PositionDirection::Short => u64::MAX,
+    };
+
+    if max_quote_asset_amount == 0 {
+        msg!("fee pool empty, cant increase hedge");
+        return Ok(());
+    }
+
+    let ExternalSpotFill {
+        base_asset_amount_filled,
+        base_update_direction,
+        quote_asset_amount_filled,
+        fee: external_market_fee,
+        settled_referrer_rebate,
+        unsettled_referrer_rebate,
+        ..
+    } = fulfillment_params.fulfill_order(
+        direction,
+        limit_price,
+        base_asset_amount,
+        max_quote_asset_amount,
+    )?;
+
+    if base_asset_amount_filled == 0 {
+        msg!("hedge not filled on external market");
+        return Ok(());
+    }
+
+    validate!(
+        base_asset_amount_filled <= base_asset_amount,
+        ErrorCode::FailedToFillOnExternalMarket,
+        "hedge filled {} more than order {}",
+        base_asset_amount_filled,
+        base_asset_amount
+    )?;
+
+    validate_fill_price(
+        quote_asset_amount_filled,
+        base_asset_amount_filled,
+        base_market.get_precision(),
+        direction,
+        limit_price,
+        true,
+    )?;
+
+    let reduces_hedge = match direction {
+        PositionDirection::Long => hedge_base_asset_amount < 0,
+        PositionDirection::Short => hedge_base_asset_amount > 0,
+    };
+
+    let quote_asset_amount = match direction {
+        PositionDirection::Long => {
+            validate!(
+                base_update_direction == SpotBalanceType::Deposit,
+                ErrorCode::FailedToFillOnExternalMarket,
+                "hedge buy lead to unexpected update direction"
+            )?;
+
+            let quote_asset_amount = quote_asset_amount_filled
+                .safe_add(external_market_fee)?
+                .safe_add(unsettled_referrer_rebate)?
+                .safe_sub(settled_referrer_rebate)?;
+
+            update_spot_balances(
+                base_asset_amount_filled.cast()?,
+                &SpotBalanceType::Deposit,
+                base_market,
+                &mut amm_hedge_config.hedge_balance,
+                false,
+            )?;
+
+            update_spot_balances(
+                quote_asset_amount.cast()?,
+                &SpotBalanceType::Borrow,
+                quote_market,
+                &mut perp_market.amm.fee_pool,
+                false,
+            )?;
+
+            quote_asset_amount
+        }
+        PositionDirection::Short => {
+            validate!(
+                base_update_direction == SpotBalanceType::Borrow,
+                ErrorCode::FailedToFillOnExternalMarket,
+                "hedge sell lead to unexpected update direction"
+            )?;
+
+            let quote_asset_amount = quote_asset_amount_filled
+                .safe_add(settled_referrer_rebate)?
+                .safe_sub(external_market_fee)?
+                .safe_sub(unsettled_referrer_rebate)?;
+
+            update_spot_balances(
+                base_asset_amount_filled.cast()?,
+                &SpotBalanceType::Borrow,
+                base_market,
+                &mut amm_hedge_config.hedge_balance,
+                false,
+            )?;
+
+            if !reduces_hedge {
+                validate!(
+                    base_market.is_healthy_utilization()?,
+                    ErrorCode::SpotMarketInsufficientDeposits,
+                    "spot market {} utilization too high for amm hedge borrow",
+                    base_market.market_index
+                )?;
+            }
+
+            update_spot_balances(
+                quote_asset_amount.cast()?,
+                &SpotBalanceType::Deposit,
+                quote_market,
+                &mut perp_market.amm.fee_pool,
+                false,
+            )?;
+
+            quote_asset_amount
+        }
+    };
+
+    let realized_pnl = if reduces_hedge {
+        let (quote_entry_amount_removed, realized_pnl) = calculate_hedge_pnl(
+            amm_hedge_config.quote_entry_amount,
+            hedge_base_asset_amount,
+            base_asset_amount_filled,
+            quote_asset_amount,
+        )?;
+
+        amm_hedge_config.quote_entry_amount = amm_hedge_config
+            .quote_entry_amount
+            .safe_sub(quote_entry_amount_removed)?;
+
+        amm_hedge_config.total_realized_pnl =
+            amm_hedge_config.total_realized_pnl.safe_add(realized_pnl)?;
+
+        perp_market.amm.total_fee_minus_distributions = perp_market
+            .amm
+            .total_fee_minus_distributions
+            .safe_add(realized_pnl.cast()?)?;
+
+        realized_pnl
+    } else {
+        amm_hedge_config.quote_entry_amount = amm_hedge_config
+            .quote_entry_amount
+            .safe_add(quote_asset_amount)?;
+
+        0_i64
+    };
+
+    amm_hedge_config.total_fee_paid = amm_hedge_config
+        .total_fee_paid
+        .safe_add(external_market_fee)?;
+    amm_hedge_config.last_hedge_ts = now;
+
+    emit!(AmmHedgeRecord {
+        ts: now,
+        perp_market_index: perp_market.market_index,
+        spot_market_index: base_market.market_index,
+        direction,
+        base_asset_amount_filled,
+        quote_asset_amount,
+        external_market_fee,
+        hedge_base_asset_amount_after: amm_hedge_config.get_hedge_base_asset_amount(base_market)?,
+        quote_entry_amount_after: amm_hedge_config.quote_entry_amount,
+        realized_pnl,
+        base_asset_amount_with_amm: perp_market.amm.base_asset_amount_with_amm,
+        oracle_price,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,370 @@
+use std::cell::Ref;
+
+use crate::controller::amm_hedge::hedge_amm_inventory;
+use crate::controller::position::PositionDirection;
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::constants::{
+    BASE_PRECISION_I128, LAMPORTS_PER_SOL_I64, LAMPORTS_PER_SOL_U64, PERCENTAGE_PRECISION,
+    PRICE_PRECISION_I64, QUOTE_PRECISION_I128, QUOTE_PRECISION_I64, QUOTE_PRECISION_U64,
+    SPOT_BALANCE_PRECISION,
+};
+use crate::state::amm_hedge::{AmmHedgeBalance, AmmHedgeConfig};
+use crate::state::events::OrderActionExplanation;
+use crate::state::perp_market::{PerpMarket, PoolBalance, AMM};
+use crate::state::spot_fulfillment_params::{ExternalSpotFill, SpotFulfillmentParams};
+use crate::state::spot_market::{SpotBalanceType, SpotFulfillmentConfigStatus, SpotMarket};
+
+struct TestHedgeFulfillmentParams {
+    fill: Option<ExternalSpotFill>,
+}
+
+impl SpotFulfillmentParams for TestHedgeFulfillmentParams {
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn get_best_bid_and_ask(&self) -> DriftResult<(Option<u64>, Option<u64>)> {
+        Ok((None, None))
+    }
+
+    fn fulfill_order(
+        &mut self,
+        _taker_direction: PositionDirection,
+        _taker_price: u64,
+        _taker_base_asset_amount: u64,
+        _taker_max_quote_asset_amount: u64,
+    ) -> DriftResult<ExternalSpotFill> {
+        self.fill
+            .take()
+            .ok_or(ErrorCode::InvalidSpotFulfillmentParams)
+    }
+
+    fn get_order_action_explanation(&self) -> DriftResult<OrderActionExplanation> {
+        Ok(OrderActionExplanation::None)
+    }
+
+    fn validate_vault_amounts(
+        &mut self,
+        _base_market: &Ref<SpotMarket>,
+        _quote_market: &Ref<SpotMarket>,
+    ) -> DriftResult<()> {
+        Ok(())
+    }
+}
+
+fn get_markets(base_asset_amount_with_amm: i128) -> (PerpMarket, SpotMarket, SpotMarket) {
+    let perp_market = PerpMarket {
+        market_index: 0,
+        amm: AMM {
+            base_asset_amount_with_amm,
+            fee_pool: PoolBalance {
+                scaled_balance: 1000 * SPOT_BALANCE_PRECISION,
+                market_index: 0,
+                ..PoolBalance::default()
+            },
+            total_fee_minus_distributions: 1000 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        },
+        ..PerpMarket::default()
+    };
+
+    let base_market = SpotMarket {
+        order_step_size: LAMPORTS_PER_SOL_U64 / 10,
+        ..SpotMarket::default_base_market()
+    };
+
+    let quote_market = SpotMarket {
+        deposit_balance: 10000 * SPOT_BALANCE_PRECISION,
+        ..SpotMarket::default_quote_market()
+    };
+
+    (perp_market, base_market, quote_market)
+}
+
+fn get_amm_hedge_config() -> AmmHedgeConfig {
+    AmmHedgeConfig {
+        hedge_balance: AmmHedgeBalance {
+            market_index: 1,
+            ..AmmHedgeBalance::default()
+        },
+        min_hedge_base_asset_amount: LAMPORTS_PER_SOL_U64,
+        max_hedge_base_asset_amount: 100 * LAMPORTS_PER_SOL_U64,
+        hedge_ratio: PERCENTAGE_PRECISION as u32 / 2,
+        max_slippage: PERCENTAGE_PRECISION as u32 / 200,
+        min_hedge_interval: 60,
+        perp_market_index: 0,
+        status: SpotFulfillmentConfigStatus::Enabled,
+        ..AmmHedgeConfig::default()
+    }
+}
+
+#[test]
+fn increase_hedge() {
+    let (mut perp_market, mut base_market, mut quote_market) =
+        get_markets(10 * BASE_PRECISION_I128);
+    let mut amm_hedge_config = get_amm_hedge_config();
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams {
+        fill: Some(ExternalSpotFill {
+            base_asset_amount_filled: 5 * LAMPORTS_PER_SOL_U64,
+            base_update_direction: SpotBalanceType::Deposit,
+            quote_asset_amount_filled: 500 * QUOTE_PRECISION_U64,
+            quote_update_direction: SpotBalanceType::Borrow,
+            settled_referrer_rebate: 0,
+            unsettled_referrer_rebate: 0,
+            fee: QUOTE_PRECISION_U64,
+        }),
+    };
+
+    hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        100 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    )
+    .unwrap();
+
+    assert_eq!(
+        amm_hedge_config
+            .get_hedge_base_asset_amount(&base_market)
+            .unwrap(),
+        5 * LAMPORTS_PER_SOL_I64
+    );
+    assert_eq!(
+        amm_hedge_config.quote_entry_amount,
+        501 * QUOTE_PRECISION_U64
+    );
+    assert_eq!(amm_hedge_config.total_fee_paid, QUOTE_PRECISION_U64);
+    assert_eq!(amm_hedge_config.last_hedge_ts, 60);
+    assert_eq!(
+        perp_market.amm.fee_pool.scaled_balance,
+        499 * SPOT_BALANCE_PRECISION
+    );
+    assert_eq!(base_market.deposit_balance, 5 * SPOT_BALANCE_PRECISION);
+    assert_eq!(
+        perp_market.amm.total_fee_minus_distributions,
+        1000 * QUOTE_PRECISION_I128
+    );
+}
+
+#[test]
+fn decrease_hedge_books_pnl() {
+    let (mut perp_market, mut base_market, mut quote_market) = get_markets(0);
+    base_market.deposit_balance = 5 * SPOT_BALANCE_PRECISION;
+
+    let mut amm_hedge_config = get_amm_hedge_config();
+    amm_hedge_config.hedge_balance.scaled_balance = 5 * SPOT_BALANCE_PRECISION;
+    amm_hedge_config.quote_entry_amount = 501 * QUOTE_PRECISION_U64;
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams {
+        fill: Some(ExternalSpotFill {
+            base_asset_amount_filled: 5 * LAMPORTS_PER_SOL_U64,
+            base_update_direction: SpotBalanceType::Borrow,
+            quote_asset_amount_filled: 550 * QUOTE_PRECISION_U64,
+            quote_update_direction: SpotBalanceType::Deposit,
+            settled_referrer_rebate: 0,
+            unsettled_referrer_rebate: 0,
+            fee: QUOTE_PRECISION_U64,
+        }),
+    };
+
+    hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        110 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    )
+    .unwrap();
+
+    assert_eq!(amm_hedge_config.hedge_balance.scaled_balance, 0);
+    assert_eq!(amm_hedge_config.quote_entry_amount, 0);
+    assert_eq!(
+        amm_hedge_config.total_realized_pnl,
+        48 * QUOTE_PRECISION_I64
+    );
+    assert_eq!(
+        perp_market.amm.fee_pool.scaled_balance,
+        1549 * SPOT_BALANCE_PRECISION
+    );
+    assert_eq!(
+        perp_market.amm.total_fee_minus_distributions,
+        1048 * QUOTE_PRECISION_I128
+    );
+    assert_eq!(base_market.deposit_balance, 0);
+}
+
+#[test]
+fn below_min_hedge() {
+    let (mut perp_market, mut base_market, mut quote_market) = get_markets(BASE_PRECISION_I128);
+    let mut amm_hedge_config = get_amm_hedge_config();
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams { fill: None };
+
+    hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        100 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    )
+    .unwrap();
+
+    assert_eq!(amm_hedge_config.hedge_balance.scaled_balance, 0);
+    assert_eq!(amm_hedge_config.last_hedge_ts, 0);
+}
+
+#[test]
+fn disabled() {
+    let (mut perp_market, mut base_market, mut quote_market) =
+        get_markets(10 * BASE_PRECISION_I128);
+    let mut amm_hedge_config = get_amm_hedge_config();
+    amm_hedge_config.status = SpotFulfillmentConfigStatus::Disabled;
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams { fill: None };
+
+    let result = hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        100 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    );
+
+    assert_eq!(result, Err(ErrorCode::AmmHedgeDisabled));
+}
+
+#[test]
+fn too_frequent() {
+    let (mut perp_market, mut base_market, mut quote_market) =
+        get_markets(10 * BASE_PRECISION_I128);
+    let mut amm_hedge_config = get_amm_hedge_config();
+    amm_hedge_config.last_hedge_ts = 30;
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams { fill: None };
+
+    let result = hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        100 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    );
+
+    assert_eq!(result, Err(ErrorCode::AmmHedgeTooFrequent));
+}
+
+#[test]
+fn increase_short_hedge() {
+    let (mut perp_market, mut base_market, mut quote_market) =
+        get_markets(-10 * BASE_PRECISION_I128);
+    base_market.deposit_balance = 100 * SPOT_BALANCE_PRECISION;
+
+    let mut amm_hedge_config = get_amm_hedge_config();
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams {
+        fill: Some(ExternalSpotFill {
+            base_asset_amount_filled: 5 * LAMPORTS_PER_SOL_U64,
+            base_update_direction: SpotBalanceType::Borrow,
+            quote_asset_amount_filled: 500 * QUOTE_PRECISION_U64,
+            quote_update_direction: SpotBalanceType::Deposit,
+            settled_referrer_rebate: 0,
+            unsettled_referrer_rebate: 0,
+            fee: QUOTE_PRECISION_U64,
+        }),
+    };
+
+    hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        100 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    )
+    .unwrap();
+
+    assert_eq!(
+        amm_hedge_config
+            .get_hedge_base_asset_amount(&base_market)
+            .unwrap(),
+        -5 * LAMPORTS_PER_SOL_I64
+    );
+    assert_eq!(
+        amm_hedge_config.hedge_balance.balance_type,
+        SpotBalanceType::Borrow
+    );
+    assert_eq!(
+        amm_hedge_config.quote_entry_amount,
+        499 * QUOTE_PRECISION_U64
+    );
+    assert_eq!(base_market.borrow_balance, 5 * SPOT_BALANCE_PRECISION);
+    assert_eq!(
+        perp_market.amm.fee_pool.scaled_balance,
+        1499 * SPOT_BALANCE_PRECISION
+    );
+    assert_eq!(amm_hedge_config.total_realized_pnl, 0);
+}
+
+#[test]
+fn decrease_short_hedge_books_pnl() {
+    let (mut perp_market, mut base_market, mut quote_market) = get_markets(0);
+    base_market.deposit_balance = 100 * SPOT_BALANCE_PRECISION;
+    base_market.borrow_balance = 5 * SPOT_BALANCE_PRECISION;
+
+    let mut amm_hedge_config = get_amm_hedge_config();
+    amm_hedge_config.hedge_balance.scaled_balance = 5 * SPOT_BALANCE_PRECISION;
+    amm_hedge_config.hedge_balance.balance_type = SpotBalanceType::Borrow;
+    amm_hedge_config.quote_entry_amount = 499 * QUOTE_PRECISION_U64;
+
+    let mut fulfillment_params = TestHedgeFulfillmentParams {
+        fill: Some(ExternalSpotFill {
+            base_asset_amount_filled: 5 * LAMPORTS_PER_SOL_U64,
+            base_update_direction: SpotBalanceType::Deposit,
+            quote_asset_amount_filled: 450 * QUOTE_PRECISION_U64,
+            quote_update_direction: SpotBalanceType::Borrow,
+            settled_referrer_rebate: 0,
+            unsettled_referrer_rebate: 0,
+            fee: QUOTE_PRECISION_U64,
+        }),
+    };
+
+    hedge_amm_inventory(
+        &mut amm_hedge_config,
+        &mut perp_market,
+        &mut base_market,
+        &mut quote_market,
+        90 * PRICE_PRECISION_I64,
+        60,
+        &mut fulfillment_params,
+    )
+    .unwrap();
+
+    assert_eq!(amm_hedge_config.hedge_balance.scaled_balance, 0);
+    assert_eq!(amm_hedge_config.quote_entry_amount, 0);
+    assert_eq!(
+        amm_hedge_config.total_realized_pnl,
+        48 * QUOTE_PRECISION_I64
+    );
+    assert_eq!(base_market.borrow_balance, 0);
+    assert_eq!(
+        perp_market.amm.fee_pool.scaled_balance,
+        549 * SPOT_BALANCE_PRECISION
+    );
+    assert_eq!(
+        perp_market.amm.total_fee_minus_distributions,
+        1048 * QUOTE_PRECISION_I128
+    );
+}
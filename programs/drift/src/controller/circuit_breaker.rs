@@ -0,0 +1,84 @@
+use solana_program::msg;
+
+use crate::error::DriftResult;
+use crate::math::casting::Cast;
+use crate::math::circuit_breaker::is_price_move_breaching_circuit_breaker;
+use crate::math::safe_math::SafeMath;
+use crate::state::events::{CircuitBreakerAction, CircuitBreakerRecord};
+use crate::state::perp_market::PerpMarket;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Trips the market's circuit breaker if the oracle or mark price has moved too far from its 5min twap
+/// and resets it once the cooldown has passed and prices are back within the threshold
+///
+/// Must be called before the twaps are updated with the current prices
+///
+/// Returns whether the market is in cooldown
+pub fn update_perp_market_circuit_breaker(
+    perp_market: &mut PerpMarket,
+    oracle_price: i64,
+    mark_price: u64,
+    now: i64,
+) -> DriftResult<bool> {
+    if !perp_market.is_circuit_breaker_enabled() {
+        return Ok(false);
+    }
+
+    if perp_market.is_in_circuit_breaker_cooldown(now) {
+        return Ok(true);
+    }
+
+    let oracle_price_twap_5min = perp_market
+        .amm
+        .historical_oracle_data
+        .last_oracle_price_twap_5min;
+    let mark_price_twap_5min = perp_market.amm.last_mark_price_twap_5min;
+
+    let oracle_breached = is_price_move_breaching_circuit_breaker(
+        oracle_price,
+        oracle_price_twap_5min,
+        perp_market.circuit_breaker_threshold,
+    )?;
+
+    let mark_breached = is_price_move_breaching_circuit_breaker(
+        mark_price.cast()?,
+        mark_price_twap_5min.cast()?,
+        perp_market.circuit_breaker_threshold,
+    )?;
+
+    let action = if oracle_breached || mark_breached {
+        perp_market.circuit_breaker_cooldown_end_ts =
+            now.safe_add(perp_market.circuit_breaker_cooldown_period.cast()?)?;
+
+        msg!(
+            "circuit breaker tripped for perp market {}. oracle breached = {} mark breached = {}",
+            perp_market.market_index,
+            oracle_breached,
+            mark_breached
+        );
+
+        CircuitBreakerAction::Trip
+    } else if perp_market.circuit_breaker_cooldown_end_ts != 0 {
+        perp_market.circuit_breaker_cooldown_end_ts = 0;
+
+        CircuitBreakerAction::Reset
+    } else {
+        return Ok(false);
+    };
+
+    emit!(CircuitBreakerRecord {
+        ts: now,
+        market_index: perp_market.market_index,
+        action,
+        oracle_price,
+        oracle_price_twap_5min,
+        mark_price,
+        mark_price_twap_5min,
+        cooldown_end_ts: perp_market.circuit_breaker_cooldown_end_ts,
+    });
+
+    Ok(action == CircuitBreakerAction::Trip)
+}
@@ -0,0 +1,135 @@
+use crate::controller::circuit_breaker::update_perp_market_circuit_breaker;
+use crate::math::constants::{PERCENTAGE_PRECISION, PRICE_PRECISION_I64, PRICE_PRECISION_U64};
+use crate::state::oracle::HistoricalOracleData;
+use crate::state::perp_market::{PerpMarket, AMM};
+
+fn get_perp_market() -> PerpMarket {
+    PerpMarket {
+        amm: AMM {
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap_5min: 100 * PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            last_mark_price_twap_5min: 100 * PRICE_PRECISION_U64,
+            ..AMM::default()
+        },
+        circuit_breaker_threshold: PERCENTAGE_PRECISION as u32 / 10, // 10%
+        circuit_breaker_cooldown_period: 60,
+        ..PerpMarket::default()
+    }
+}
+
+#[test]
+fn disabled() {
+    let mut perp_market = PerpMarket {
+        circuit_breaker_threshold: 0,
+        ..get_perp_market()
+    };
+
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        200 * PRICE_PRECISION_I64,
+        200 * PRICE_PRECISION_U64,
+        0,
+    )
+    .unwrap();
+
+    assert!(!in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 0);
+}
+
+#[test]
+fn within_threshold() {
+    let mut perp_market = get_perp_market();
+
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        105 * PRICE_PRECISION_I64,
+        95 * PRICE_PRECISION_U64,
+        0,
+    )
+    .unwrap();
+
+    assert!(!in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 0);
+}
+
+#[test]
+fn oracle_trips() {
+    let mut perp_market = get_perp_market();
+
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        120 * PRICE_PRECISION_I64,
+        100 * PRICE_PRECISION_U64,
+        10,
+    )
+    .unwrap();
+
+    assert!(in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 70);
+    assert!(perp_market.is_in_circuit_breaker_cooldown(69));
+    assert!(!perp_market.is_in_circuit_breaker_cooldown(70));
+}
+
+#[test]
+fn mark_trips() {
+    let mut perp_market = get_perp_market();
+
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        100 * PRICE_PRECISION_I64,
+        80 * PRICE_PRECISION_U64,
+        10,
+    )
+    .unwrap();
+
+    assert!(in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 70);
+}
+
+#[test]
+fn cooldown_then_reset() {
+    let mut perp_market = get_perp_market();
+
+    update_perp_market_circuit_breaker(
+        &mut perp_market,
+        120 * PRICE_PRECISION_I64,
+        100 * PRICE_PRECISION_U64,
+        10,
+    )
+    .unwrap();
+
+    // prices back within threshold but cooldown not over
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        100 * PRICE_PRECISION_I64,
+        100 * PRICE_PRECISION_U64,
+        69,
+    )
+    .unwrap();
+    assert!(in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 70);
+
+    // cooldown over but prices still breaching, trips again
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        120 * PRICE_PRECISION_I64,
+        100 * PRICE_PRECISION_U64,
+        70,
+    )
+    .unwrap();
+    assert!(in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 130);
+
+    // cooldown over and prices back within threshold
+    let in_cooldown = update_perp_market_circuit_breaker(
+        &mut perp_market,
+        100 * PRICE_PRECISION_I64,
+        100 * PRICE_PRECISION_U64,
+        130,
+    )
+    .unwrap();
+    assert!(!in_cooldown);
+    assert_eq!(perp_market.circuit_breaker_cooldown_end_ts, 0);
+}
@@ -1,5 +1,3 @@
-use std::cmp::{max, min};
-
 use anchor_lang::prelude::*;
 use solana_program::clock::UnixTimestamp;
 
@@ -7,12 +5,14 @@ use crate::controller::amm::formulaic_update_k;
 use crate::controller::position::{
     get_position_index, update_quote_asset_and_break_even_amount, PositionDirection,
 };
-use crate::error::DriftResult;
+use crate::error::{DriftResult, ErrorCode};
 use crate::get_then_update_id;
 use crate::math::amm;
 use crate::math::casting::Cast;
-use crate::math::constants::{FUNDING_RATE_BUFFER, ONE_HOUR_I128, TWENTY_FOUR_HOUR};
-use crate::math::funding::{calculate_funding_payment, calculate_funding_rate_long_short};
+use crate::math::constants::{ONE_HOUR, TWENTY_FOUR_HOUR};
+use crate::math::funding::{
+    calculate_funding_payment, calculate_funding_rate_for_amm, calculate_funding_rate_long_short,
+};
 use crate::math::helpers::on_the_hour_update;
 use crate::math::safe_math::SafeMath;
 use crate::math::stats::calculate_new_twap;
@@ -26,12 +26,18 @@ use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::state::OracleGuardRails;
 use crate::state::user::User;
 
+#[cfg(test)]
+mod tests;
+
 pub fn settle_funding_payment(
     user: &mut User,
     user_key: &Pubkey,
     market: &mut PerpMarket,
     now: UnixTimestamp,
+    funding_paused: bool,
 ) -> DriftResult {
+    accrue_continuous_funding(market, now, funding_paused)?;
+
     let position_index = match get_position_index(&user.perp_positions, market.market_index) {
         Ok(position_index) => position_index,
         Err(_) => return Ok(()),
@@ -210,25 +216,33 @@ pub fn update_funding_rate(
             sanitize_clamp_denominator,
         )?;
 
-        let period_adjustment = (24_i128)
-            .safe_mul(ONE_HOUR_I128)?
-            .safe_div(max(ONE_HOUR_I128, market.amm.funding_period as i128))?;
-        // funding period = 1 hour, window = 1 day
-        // low periodicity => quickly updating/settled funding rates => lower funding rate payment per interval
-        let price_spread = mid_price_twap.cast::<i64>()?.safe_sub(oracle_price_twap)?;
+        let funding_rate = calculate_funding_rate_for_amm(
+            &market.amm,
+            mid_price_twap.cast()?,
+            oracle_price_twap.cast()?,
+        )?
+        .cast::<i64>()?;
 
-        // clamp price divergence to 3% for funding rate calculation
-        let max_price_spread = oracle_price_twap.safe_div(33)?; // 3%
-        let clamped_price_spread = max(-max_price_spread, min(price_spread, max_price_spread));
+        let (funding_rate_long, funding_rate_short, funding_imbalance_revenue) =
+            if market.amm.continuous_funding {
+                // the cumulative funding rates already accrued continuously, so the record
+                // only carries the current funding rate estimate for indexing
+                accrue_continuous_funding(market, now, false)?;
 
-        let funding_rate = clamped_price_spread
-            .cast::<i128>()?
-            .safe_mul(FUNDING_RATE_BUFFER.cast()?)?
-            .safe_div(period_adjustment.cast()?)?
-            .cast::<i64>()?;
+                let funding_imbalance_revenue = market
+                    .amm
+                    .funding_imbalance_revenue_since_last_funding
+                    .cast::<i128>()?;
+                market.amm.funding_imbalance_revenue_since_last_funding = 0;
 
-        let (funding_rate_long, funding_rate_short, funding_imbalance_revenue) =
-            calculate_funding_rate_long_short(market, funding_rate.cast()?)?;
+                (
+                    funding_rate.cast::<i128>()?,
+                    funding_rate.cast::<i128>()?,
+                    funding_imbalance_revenue,
+                )
+            } else {
+                calculate_funding_rate_long_short(market, funding_rate.cast()?)?
+            };
 
         if market.amm.curve_update_intensity > 0 {
             // if funding_imbalance_revenue is positive, protocol receives.
@@ -237,15 +251,17 @@ pub fn update_funding_rate(
             formulaic_update_k(market, oracle_price_data, funding_imbalance_cost, now)?;
         }
 
-        market.amm.cumulative_funding_rate_long = market
-            .amm
-            .cumulative_funding_rate_long
-            .safe_add(funding_rate_long)?;
+        if !market.amm.continuous_funding {
+            market.amm.cumulative_funding_rate_long = market
+                .amm
+                .cumulative_funding_rate_long
+                .safe_add(funding_rate_long)?;
 
-        market.amm.cumulative_funding_rate_short = market
-            .amm
-            .cumulative_funding_rate_short
-            .safe_add(funding_rate_short)?;
+            market.amm.cumulative_funding_rate_short = market
+                .amm
+                .cumulative_funding_rate_short
+                .safe_add(funding_rate_short)?;
+        }
 
         market.amm.last_funding_rate = funding_rate;
         market.amm.last_funding_rate_long = funding_rate_long.cast()?;
@@ -282,3 +298,99 @@ pub fn update_funding_rate(
 
     Ok(true)
 }
+
+/// Continuous funding only accrues when the funding rate could be updated: funding isn't paused for the
+/// exchange or market, and the oracle is valid and not too divergent from mark
+pub fn is_continuous_funding_paused(
+    market: &PerpMarket,
+    oracle_map: &mut OracleMap,
+    exchange_funding_paused: bool,
+) -> DriftResult<bool> {
+    if !market.amm.continuous_funding {
+        return Ok(false);
+    }
+
+    if exchange_funding_paused {
+        return Ok(true);
+    }
+
+    let guard_rails = oracle_map.oracle_guard_rails;
+    oracle::block_operation(
+        market,
+        oracle_map.get_price_data(&market.amm.oracle)?,
+        &guard_rails,
+        None,
+    )
+}
+
+/// For markets with continuous funding, accrues the funding rate from the current mark/oracle twap
+/// premium pro-rata to the time since the last accrual, so positions don't jump at the funding period
+/// boundary. While funding is paused the accrual timestamp still moves forward, skipping the paused time
+pub fn accrue_continuous_funding(
+    market: &mut PerpMarket,
+    now: UnixTimestamp,
+    funding_paused: bool,
+) -> DriftResult {
+    if !market.amm.continuous_funding {
+        return Ok(());
+    }
+
+    let time_since_last_accrual = now.safe_sub(market.amm.last_funding_accrual_ts)?;
+    if time_since_last_accrual <= 0 {
+        return Ok(());
+    }
+
+    if funding_paused {
+        market.amm.last_funding_accrual_ts = now;
+        return Ok(());
+    }
+
+    // funding rate per funding period, see calculate_funding_rate
+    let period_funding_rate = calculate_funding_rate_for_amm(
+        &market.amm,
+        market.amm.last_mark_price_twap.cast()?,
+        market
+            .amm
+            .historical_oracle_data
+            .last_oracle_price_twap
+            .cast()?,
+    )?;
+
+    let funding_rate = period_funding_rate
+        .safe_mul(time_since_last_accrual.cast()?)?
+        .safe_div(market.amm.funding_period.max(ONE_HOUR).cast()?)?;
+
+    if funding_rate == 0 {
+        return Ok(());
+    }
+
+    let (funding_rate_long, funding_rate_short, funding_imbalance_revenue) =
+        match calculate_funding_rate_long_short(market, funding_rate) {
+            Ok(result) => result,
+            Err(ErrorCode::InvalidFundingProfitability) => {
+                // retry once the fee pool can pay the imbalance instead of blocking amm updates
+                msg!("continuous funding accrual skipped: fee pool can't pay funding imbalance");
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+
+    market.amm.cumulative_funding_rate_long = market
+        .amm
+        .cumulative_funding_rate_long
+        .safe_add(funding_rate_long)?;
+
+    market.amm.cumulative_funding_rate_short = market
+        .amm
+        .cumulative_funding_rate_short
+        .safe_add(funding_rate_short)?;
+
+    market.amm.funding_imbalance_revenue_since_last_funding = market
+        .amm
+        .funding_imbalance_revenue_since_last_funding
+        .safe_add(funding_imbalance_revenue.cast()?)?;
+
+    market.amm.last_funding_accrual_ts = now;
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+
+use crate::controller::funding::{accrue_continuous_funding, is_continuous_funding_paused};
+use crate::create_account_info;
+use crate::math::constants::{AMM_RESERVE_PRECISION, PEG_PRECISION, PRICE_PRECISION_I64};
+use crate::state::oracle::HistoricalOracleData;
+use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_market::{PerpMarket, AMM};
+use crate::test_utils::*;
+use crate::test_utils::{create_account_info, get_pyth_price};
+
+#[test]
+fn continuous_funding_accrues_pro_rata() {
+    let mut market = PerpMarket {
+        amm: AMM {
+            // mark 1% above oracle
+            last_mark_price_twap: 101 * PRICE_PRECISION_I64 as u64,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: 100 * PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            funding_period: 3600,
+            continuous_funding: true,
+            ..AMM::default()
+        },
+        ..PerpMarket::default()
+    };
+
+    // half a funding period accrues half the hourly funding rate
+    accrue_continuous_funding(&mut market, 1800, false).unwrap();
+    assert_eq!(market.amm.cumulative_funding_rate_long, 20_833_333);
+    assert_eq!(market.amm.cumulative_funding_rate_short, 20_833_333);
+    assert_eq!(market.amm.last_funding_accrual_ts, 1800);
+
+    // nothing accrues again in the same second
+    accrue_continuous_funding(&mut market, 1800, false).unwrap();
+    assert_eq!(market.amm.cumulative_funding_rate_long, 20_833_333);
+
+    // paused time is skipped
+    accrue_continuous_funding(&mut market, 3600, true).unwrap();
+    assert_eq!(market.amm.cumulative_funding_rate_long, 20_833_333);
+    assert_eq!(market.amm.last_funding_accrual_ts, 3600);
+
+    // markets without continuous funding only update on the funding period
+    market.amm.continuous_funding = false;
+    accrue_continuous_funding(&mut market, 7200, false).unwrap();
+    assert_eq!(market.amm.cumulative_funding_rate_long, 20_833_333);
+    assert_eq!(market.amm.last_funding_accrual_ts, 3600);
+}
+
+#[test]
+fn continuous_funding_paused() {
+    let mut oracle_price = get_pyth_price(100, 6);
+    let oracle_price_key =
+        Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+    let pyth_program = crate::ids::pyth_program::id();
+    create_account_info!(
+        oracle_price,
+        &oracle_price_key,
+        &pyth_program,
+        oracle_account_info
+    );
+    let mut oracle_map = OracleMap::load_one(&oracle_account_info, 0, None).unwrap();
+
+    let mut market = PerpMarket {
+        amm: AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 100 * PEG_PRECISION,
+            oracle: oracle_price_key,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price: oracle_price.agg.price,
+                last_oracle_price_twap: oracle_price.agg.price,
+                last_oracle_price_twap_5min: oracle_price.agg.price,
+                ..HistoricalOracleData::default()
+            },
+            continuous_funding: true,
+            ..AMM::default()
+        },
+        ..PerpMarket::default()
+    };
+
+    assert!(!is_continuous_funding_paused(&market, &mut oracle_map, false).unwrap());
+
+    // paused for the exchange
+    assert!(is_continuous_funding_paused(&market, &mut oracle_map, true).unwrap());
+
+    // paused for the market
+    market.paused_operations = MarketOperation::Funding as u8;
+    assert!(is_continuous_funding_paused(&market, &mut oracle_map, false).unwrap());
+    market.paused_operations = 0;
+
+    // mark too far from the oracle twap
+    market
+        .amm
+        .historical_oracle_data
+        .last_oracle_price_twap_5min = oracle_price.agg.price / 2;
+    assert!(is_continuous_funding_paused(&market, &mut oracle_map, false).unwrap());
+
+    // markets without continuous funding never accrue, so the oracle isn't checked
+    market.amm.continuous_funding = false;
+    assert!(!is_continuous_funding_paused(&market, &mut oracle_map, true).unwrap());
+}
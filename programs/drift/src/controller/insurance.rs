@@ -2,6 +2,7 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
 use solana_program::msg;
 
+use crate::controller::amm::get_fee_pool_tokens;
 use crate::controller::spot_balance::{
     update_revenue_pool_balances, update_spot_balances, update_spot_market_cumulative_interest,
 };
@@ -11,6 +12,7 @@ use crate::error::ErrorCode;
 use crate::math::amm::calculate_net_user_pnl;
 use crate::math::casting::Cast;
 use crate::math::constants::{
+    IF_FACTOR_PRECISION, IF_REVENUE_PER_SHARE_PRECISION,
     MAX_APR_PER_REVENUE_SETTLE_TO_INSURANCE_FUND_VAULT, ONE_YEAR, PERCENTAGE_PRECISION,
     SHARE_OF_REVENUE_ALLOCATED_TO_INSURANCE_FUND_VAULT_DENOMINATOR,
     SHARE_OF_REVENUE_ALLOCATED_TO_INSURANCE_FUND_VAULT_NUMERATOR,
@@ -21,15 +23,16 @@ use crate::math::insurance::{
     calculate_if_shares_lost, calculate_rebase_info, if_shares_to_vault_amount,
     vault_amount_to_if_shares,
 };
+use crate::math::repeg::get_total_fee_lower_bound;
 use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::get_token_amount;
 use crate::math::spot_withdraw::validate_spot_market_vault_amount;
 use crate::state::events::{InsuranceFundRecord, InsuranceFundStakeRecord, StakeAction};
-use crate::state::insurance_fund_stake::InsuranceFundStake;
+use crate::state::insurance_fund_stake::{InsuranceFundStake, InsuranceFundTranche};
 use crate::state::perp_market::PerpMarket;
-use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+use crate::state::spot_market::{InsuranceFund, SpotBalanceType, SpotMarket};
 use crate::state::state::State;
-use crate::state::user::UserStats;
+use crate::state::user::{MarketType, UserStats};
 use crate::{emit, validate};
 
 #[cfg(test)]
@@ -43,24 +46,316 @@ pub fn add_insurance_fund_stake(
     spot_market: &mut SpotMarket,
     now: i64,
 ) -> DriftResult {
+    let market_index = spot_market.market_index;
+    stake_in_insurance_fund(
+        amount,
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.insurance_fund,
+        &mut spot_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn add_perp_insurance_fund_stake(
+    amount: u64,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    perp_market: &mut PerpMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = perp_market.market_index;
+    stake_in_insurance_fund(
+        amount,
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut perp_market.insurance_fund,
+        &mut perp_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Perp,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn request_remove_insurance_fund_stake(
+    n_shares: u128,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = spot_market.market_index;
+    request_unstake_from_insurance_fund(
+        n_shares,
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.insurance_fund,
+        &mut spot_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn request_remove_perp_insurance_fund_stake(
+    n_shares: u128,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    perp_market: &mut PerpMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = perp_market.market_index;
+    request_unstake_from_insurance_fund(
+        n_shares,
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut perp_market.insurance_fund,
+        &mut perp_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Perp,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn cancel_request_remove_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = spot_market.market_index;
+    cancel_request_unstake_from_insurance_fund(
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.insurance_fund,
+        &mut spot_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn cancel_request_remove_perp_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    perp_market: &mut PerpMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = perp_market.market_index;
+    cancel_request_unstake_from_insurance_fund(
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut perp_market.insurance_fund,
+        &mut perp_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Perp,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn remove_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult<u64> {
+    let market_index = spot_market.market_index;
+    unstake_from_insurance_fund(
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.insurance_fund,
+        &mut spot_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn remove_perp_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    perp_market: &mut PerpMarket,
+    now: i64,
+) -> DriftResult<u64> {
+    let market_index = perp_market.market_index;
+    unstake_from_insurance_fund(
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut perp_market.insurance_fund,
+        &mut perp_market.insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Perp,
+        InsuranceFundTranche::Senior,
+        now,
+    )
+}
+
+pub fn add_junior_insurance_fund_stake(
+    amount: u64,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = spot_market.market_index;
+    stake_in_insurance_fund(
+        amount,
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.junior_insurance_fund,
+        &mut spot_market.junior_insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Junior,
+        now,
+    )
+}
+
+pub fn request_remove_junior_insurance_fund_stake(
+    n_shares: u128,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = spot_market.market_index;
+    request_unstake_from_insurance_fund(
+        n_shares,
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.junior_insurance_fund,
+        &mut spot_market.junior_insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Junior,
+        now,
+    )
+}
+
+pub fn cancel_request_remove_junior_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult {
+    let market_index = spot_market.market_index;
+    cancel_request_unstake_from_insurance_fund(
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.junior_insurance_fund,
+        &mut spot_market.junior_insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Junior,
+        now,
+    )
+}
+
+pub fn remove_junior_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult<u64> {
+    let market_index = spot_market.market_index;
+    unstake_from_insurance_fund(
+        insurance_vault_amount,
+        insurance_fund_stake,
+        user_stats,
+        &mut spot_market.junior_insurance_fund,
+        &mut spot_market.junior_insurance_fund_revenue_per_share,
+        market_index,
+        MarketType::Spot,
+        InsuranceFundTranche::Junior,
+        now,
+    )
+}
+
+fn validate_insurance_fund_stake_pool(
+    insurance_fund_stake: &InsuranceFundStake,
+    market_type: MarketType,
+    tranche: InsuranceFundTranche,
+) -> DriftResult {
+    validate!(
+        insurance_fund_stake.market_type == market_type,
+        ErrorCode::InvalidInsuranceFundStakeMarketType,
+        "insurance_fund_stake market_type {:?} != {:?}",
+        insurance_fund_stake.market_type,
+        market_type
+    )?;
+
+    validate!(
+        insurance_fund_stake.tranche == tranche,
+        ErrorCode::InvalidInsuranceFundStakeTranche,
+        "insurance_fund_stake tranche {:?} != {:?}",
+        insurance_fund_stake.tranche,
+        tranche
+    )?;
+
+    Ok(())
+}
+
+fn stake_in_insurance_fund(
+    amount: u64,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    insurance_fund: &mut InsuranceFund,
+    revenue_per_share: &mut u128,
+    market_index: u16,
+    market_type: MarketType,
+    tranche: InsuranceFundTranche,
+    now: i64,
+) -> DriftResult {
+    validate_insurance_fund_stake_pool(insurance_fund_stake, market_type, tranche)?;
+
     validate!(
-        !(insurance_vault_amount == 0 && spot_market.insurance_fund.total_shares != 0),
+        !(insurance_vault_amount == 0 && insurance_fund.total_shares != 0),
         ErrorCode::InvalidIFForNewStakes,
         "Insurance Fund balance should be non-zero for new stakers to enter"
     )?;
 
-    apply_rebase_to_insurance_fund(insurance_vault_amount, spot_market)?;
-    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, spot_market)?;
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
 
-    let if_shares_before = insurance_fund_stake.checked_if_shares(spot_market)?;
-    let total_if_shares_before = spot_market.insurance_fund.total_shares;
-    let user_if_shares_before = spot_market.insurance_fund.user_shares;
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let total_if_shares_before = insurance_fund.total_shares;
+    let user_if_shares_before = insurance_fund.user_shares;
 
-    let n_shares = vault_amount_to_if_shares(
-        amount,
-        spot_market.insurance_fund.total_shares,
-        insurance_vault_amount,
-    )?;
+    let n_shares =
+        vault_amount_to_if_shares(amount, insurance_fund.total_shares, insurance_vault_amount)?;
 
     // reset cost basis if no shares
     insurance_fund_stake.cost_basis = if if_shares_before == 0 {
@@ -69,37 +364,40 @@ pub fn add_insurance_fund_stake(
         insurance_fund_stake.cost_basis.safe_add(amount.cast()?)?
     };
 
-    insurance_fund_stake.increase_if_shares(n_shares, spot_market)?;
+    insurance_fund_stake.increase_if_shares(n_shares, insurance_fund)?;
 
-    spot_market.insurance_fund.total_shares =
-        spot_market.insurance_fund.total_shares.safe_add(n_shares)?;
+    insurance_fund.total_shares = insurance_fund.total_shares.safe_add(n_shares)?;
 
-    spot_market.insurance_fund.user_shares =
-        spot_market.insurance_fund.user_shares.safe_add(n_shares)?;
+    insurance_fund.user_shares = insurance_fund.user_shares.safe_add(n_shares)?;
 
-    if spot_market.market_index == 0 {
+    if market_type == MarketType::Spot
+        && tranche == InsuranceFundTranche::Senior
+        && market_index == 0
+    {
         user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
-            insurance_fund_stake.checked_if_shares(spot_market)?,
-            spot_market.insurance_fund.total_shares,
+            insurance_fund_stake.checked_if_shares(insurance_fund)?,
+            insurance_fund.total_shares,
             insurance_vault_amount.safe_add(amount)?,
         )?;
     }
 
-    let if_shares_after = insurance_fund_stake.checked_if_shares(spot_market)?;
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
 
     emit!(InsuranceFundStakeRecord {
         ts: now,
         user_authority: user_stats.authority,
         action: StakeAction::Stake,
         amount,
-        market_index: spot_market.market_index,
+        market_index,
+        market_type,
+        tranche,
         insurance_vault_amount_before: insurance_vault_amount,
         if_shares_before,
         user_if_shares_before,
         total_if_shares_before,
         if_shares_after,
-        total_if_shares_after: spot_market.insurance_fund.total_shares,
-        user_if_shares_after: spot_market.insurance_fund.user_shares,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
     });
 
     Ok(())
@@ -107,34 +405,28 @@ pub fn add_insurance_fund_stake(
 
 pub fn apply_rebase_to_insurance_fund(
     insurance_fund_vault_balance: u64,
-    spot_market: &mut SpotMarket,
+    insurance_fund: &mut InsuranceFund,
+    revenue_per_share: &mut u128,
 ) -> DriftResult {
     if insurance_fund_vault_balance != 0
-        && insurance_fund_vault_balance.cast::<u128>()? < spot_market.insurance_fund.total_shares
+        && insurance_fund_vault_balance.cast::<u128>()? < insurance_fund.total_shares
     {
-        let (expo_diff, rebase_divisor) = calculate_rebase_info(
-            spot_market.insurance_fund.total_shares,
-            insurance_fund_vault_balance,
-        )?;
-
-        spot_market.insurance_fund.total_shares = spot_market
-            .insurance_fund
-            .total_shares
-            .safe_div(rebase_divisor)?;
-        spot_market.insurance_fund.user_shares = spot_market
-            .insurance_fund
-            .user_shares
-            .safe_div(rebase_divisor)?;
-        spot_market.insurance_fund.shares_base = spot_market
-            .insurance_fund
+        let (expo_diff, rebase_divisor) =
+            calculate_rebase_info(insurance_fund.total_shares, insurance_fund_vault_balance)?;
+
+        insurance_fund.total_shares = insurance_fund.total_shares.safe_div(rebase_divisor)?;
+        insurance_fund.user_shares = insurance_fund.user_shares.safe_div(rebase_divisor)?;
+        // fewer shares are each worth more, keep shares * revenue_per_share constant
+        *revenue_per_share = revenue_per_share.safe_mul(rebase_divisor)?;
+        insurance_fund.shares_base = insurance_fund
             .shares_base
             .safe_add(expo_diff.cast::<u128>()?)?;
 
         msg!("rebasing insurance fund: expo_diff={}", expo_diff);
     }
 
-    if insurance_fund_vault_balance != 0 && spot_market.insurance_fund.total_shares == 0 {
-        spot_market.insurance_fund.total_shares = insurance_fund_vault_balance.cast::<u128>()?;
+    if insurance_fund_vault_balance != 0 && insurance_fund.total_shares == 0 {
+        insurance_fund.total_shares = insurance_fund_vault_balance.cast::<u128>()?;
     }
 
     Ok(())
@@ -142,27 +434,27 @@ pub fn apply_rebase_to_insurance_fund(
 
 pub fn apply_rebase_to_insurance_fund_stake(
     insurance_fund_stake: &mut InsuranceFundStake,
-    spot_market: &mut SpotMarket,
+    insurance_fund: &mut InsuranceFund,
 ) -> DriftResult {
-    if spot_market.insurance_fund.shares_base != insurance_fund_stake.if_base {
+    if insurance_fund.shares_base != insurance_fund_stake.if_base {
         validate!(
-            spot_market.insurance_fund.shares_base > insurance_fund_stake.if_base,
+            insurance_fund.shares_base > insurance_fund_stake.if_base,
             ErrorCode::InvalidIFRebase,
             "Rebase expo out of bounds"
         )?;
 
-        let expo_diff = (spot_market.insurance_fund.shares_base - insurance_fund_stake.if_base)
-            .cast::<u32>()?;
+        let expo_diff =
+            (insurance_fund.shares_base - insurance_fund_stake.if_base).cast::<u32>()?;
 
         let rebase_divisor = 10_u128.pow(expo_diff);
 
         msg!(
             "rebasing insurance fund stake: base: {} -> {} ",
             insurance_fund_stake.if_base,
-            spot_market.insurance_fund.shares_base,
+            insurance_fund.shares_base,
         );
 
-        insurance_fund_stake.if_base = spot_market.insurance_fund.shares_base;
+        insurance_fund_stake.if_base = insurance_fund.shares_base;
 
         let old_if_shares = insurance_fund_stake.unchecked_if_shares();
         let new_if_shares = old_if_shares.safe_div(rebase_divisor)?;
@@ -172,52 +464,109 @@ pub fn apply_rebase_to_insurance_fund_stake(
             new_if_shares
         );
 
-        insurance_fund_stake.update_if_shares(new_if_shares, spot_market)?;
+        insurance_fund_stake.update_if_shares(new_if_shares, insurance_fund)?;
 
         insurance_fund_stake.last_withdraw_request_shares = insurance_fund_stake
             .last_withdraw_request_shares
             .safe_div(rebase_divisor)?;
+
+        insurance_fund_stake.last_revenue_per_share = insurance_fund_stake
+            .last_revenue_per_share
+            .safe_mul(rebase_divisor)?;
     }
 
     Ok(())
 }
 
-pub fn request_remove_insurance_fund_stake(
+pub fn accrue_insurance_fund_stake_revenue(
+    insurance_fund_stake: &mut InsuranceFundStake,
+    insurance_fund: &InsuranceFund,
+    revenue_per_share: u128,
+) -> DriftResult {
+    let revenue_per_share_delta =
+        revenue_per_share.safe_sub(insurance_fund_stake.last_revenue_per_share)?;
+
+    if revenue_per_share_delta > 0 {
+        let revenue = insurance_fund_stake
+            .checked_if_shares(insurance_fund)?
+            .safe_mul(revenue_per_share_delta)?
+            .safe_div(IF_REVENUE_PER_SHARE_PRECISION)?
+            .cast::<u64>()?;
+
+        insurance_fund_stake.cumulative_revenue =
+            insurance_fund_stake.cumulative_revenue.safe_add(revenue)?;
+
+        if insurance_fund_stake.claim_revenue {
+            insurance_fund_stake.unclaimed_revenue =
+                insurance_fund_stake.unclaimed_revenue.safe_add(revenue)?;
+        }
+    }
+
+    insurance_fund_stake.last_revenue_per_share = revenue_per_share;
+
+    Ok(())
+}
+
+fn update_insurance_fund_revenue_per_share(
+    revenue_per_share: &mut u128,
+    revenue: u64,
+    total_if_shares: u128,
+) -> DriftResult {
+    if total_if_shares > 0 {
+        *revenue_per_share = revenue_per_share.safe_add(
+            revenue
+                .cast::<u128>()?
+                .safe_mul(IF_REVENUE_PER_SHARE_PRECISION)?
+                .safe_div(total_if_shares)?,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn request_unstake_from_insurance_fund(
     n_shares: u128,
     insurance_vault_amount: u64,
     insurance_fund_stake: &mut InsuranceFundStake,
     user_stats: &mut UserStats,
-    spot_market: &mut SpotMarket,
+    insurance_fund: &mut InsuranceFund,
+    revenue_per_share: &mut u128,
+    market_index: u16,
+    market_type: MarketType,
+    tranche: InsuranceFundTranche,
     now: i64,
 ) -> DriftResult {
+    validate_insurance_fund_stake_pool(insurance_fund_stake, market_type, tranche)?;
+
     msg!("n_shares {}", n_shares);
     insurance_fund_stake.last_withdraw_request_shares = n_shares;
 
-    apply_rebase_to_insurance_fund(insurance_vault_amount, spot_market)?;
-    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, spot_market)?;
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
 
-    let if_shares_before = insurance_fund_stake.checked_if_shares(spot_market)?;
-    let total_if_shares_before = spot_market.insurance_fund.total_shares;
-    let user_if_shares_before = spot_market.insurance_fund.user_shares;
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let total_if_shares_before = insurance_fund.total_shares;
+    let user_if_shares_before = insurance_fund.user_shares;
 
     validate!(
         insurance_fund_stake.last_withdraw_request_shares
-            <= insurance_fund_stake.checked_if_shares(spot_market)?,
+            <= insurance_fund_stake.checked_if_shares(insurance_fund)?,
         ErrorCode::InvalidInsuranceUnstakeSize,
         "last_withdraw_request_shares exceeds if_shares {} > {}",
         insurance_fund_stake.last_withdraw_request_shares,
-        insurance_fund_stake.checked_if_shares(spot_market)?
+        insurance_fund_stake.checked_if_shares(insurance_fund)?
     )?;
 
     validate!(
-        insurance_fund_stake.if_base == spot_market.insurance_fund.shares_base,
+        insurance_fund_stake.if_base == insurance_fund.shares_base,
         ErrorCode::InvalidIFRebase,
-        "if stake base != spot market base"
+        "if stake base != insurance fund base"
     )?;
 
     insurance_fund_stake.last_withdraw_request_value = if_shares_to_vault_amount(
         insurance_fund_stake.last_withdraw_request_shares,
-        spot_market.insurance_fund.total_shares,
+        insurance_fund.total_shares,
         insurance_vault_amount,
     )?
     .min(insurance_vault_amount.saturating_sub(1));
@@ -229,12 +578,15 @@ pub fn request_remove_insurance_fund_stake(
         "Requested withdraw value is not below Insurance Fund balance"
     )?;
 
-    let if_shares_after = insurance_fund_stake.checked_if_shares(spot_market)?;
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
 
-    if spot_market.market_index == 0 {
+    if market_type == MarketType::Spot
+        && tranche == InsuranceFundTranche::Senior
+        && market_index == 0
+    {
         user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
-            insurance_fund_stake.checked_if_shares(spot_market)?,
-            spot_market.insurance_fund.total_shares,
+            insurance_fund_stake.checked_if_shares(insurance_fund)?,
+            insurance_fund.total_shares,
             insurance_vault_amount,
         )?;
     }
@@ -244,14 +596,16 @@ pub fn request_remove_insurance_fund_stake(
         user_authority: user_stats.authority,
         action: StakeAction::UnstakeRequest,
         amount: insurance_fund_stake.last_withdraw_request_value,
-        market_index: spot_market.market_index,
+        market_index,
+        market_type,
+        tranche,
         insurance_vault_amount_before: insurance_vault_amount,
         if_shares_before,
         user_if_shares_before,
         total_if_shares_before,
         if_shares_after,
-        total_if_shares_after: spot_market.insurance_fund.total_shares,
-        user_if_shares_after: spot_market.insurance_fund.user_shares,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
     });
 
     insurance_fund_stake.last_withdraw_request_ts = now;
@@ -259,24 +613,31 @@ pub fn request_remove_insurance_fund_stake(
     Ok(())
 }
 
-pub fn cancel_request_remove_insurance_fund_stake(
+fn cancel_request_unstake_from_insurance_fund(
     insurance_vault_amount: u64,
     insurance_fund_stake: &mut InsuranceFundStake,
     user_stats: &mut UserStats,
-    spot_market: &mut SpotMarket,
+    insurance_fund: &mut InsuranceFund,
+    revenue_per_share: &mut u128,
+    market_index: u16,
+    market_type: MarketType,
+    tranche: InsuranceFundTranche,
     now: i64,
 ) -> DriftResult {
-    apply_rebase_to_insurance_fund(insurance_vault_amount, spot_market)?;
-    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, spot_market)?;
+    validate_insurance_fund_stake_pool(insurance_fund_stake, market_type, tranche)?;
 
-    let if_shares_before = insurance_fund_stake.checked_if_shares(spot_market)?;
-    let total_if_shares_before = spot_market.insurance_fund.total_shares;
-    let user_if_shares_before = spot_market.insurance_fund.user_shares;
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
+
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let total_if_shares_before = insurance_fund.total_shares;
+    let user_if_shares_before = insurance_fund.user_shares;
 
     validate!(
-        insurance_fund_stake.if_base == spot_market.insurance_fund.shares_base,
+        insurance_fund_stake.if_base == insurance_fund.shares_base,
         ErrorCode::InvalidIFRebase,
-        "if stake base != spot market base"
+        "if stake base != insurance fund base"
     )?;
 
     validate!(
@@ -286,26 +647,23 @@ pub fn cancel_request_remove_insurance_fund_stake(
     )?;
 
     let if_shares_lost =
-        calculate_if_shares_lost(insurance_fund_stake, spot_market, insurance_vault_amount)?;
+        calculate_if_shares_lost(insurance_fund_stake, insurance_fund, insurance_vault_amount)?;
 
-    insurance_fund_stake.decrease_if_shares(if_shares_lost, spot_market)?;
+    insurance_fund_stake.decrease_if_shares(if_shares_lost, insurance_fund)?;
 
-    spot_market.insurance_fund.total_shares = spot_market
-        .insurance_fund
-        .total_shares
-        .safe_sub(if_shares_lost)?;
+    insurance_fund.total_shares = insurance_fund.total_shares.safe_sub(if_shares_lost)?;
 
-    spot_market.insurance_fund.user_shares = spot_market
-        .insurance_fund
-        .user_shares
-        .safe_sub(if_shares_lost)?;
+    insurance_fund.user_shares = insurance_fund.user_shares.safe_sub(if_shares_lost)?;
 
-    let if_shares_after = insurance_fund_stake.checked_if_shares(spot_market)?;
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
 
-    if spot_market.market_index == 0 {
+    if market_type == MarketType::Spot
+        && tranche == InsuranceFundTranche::Senior
+        && market_index == 0
+    {
         user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
             if_shares_after,
-            spot_market.insurance_fund.total_shares,
+            insurance_fund.total_shares,
             insurance_vault_amount,
         )?;
     }
@@ -315,14 +673,16 @@ pub fn cancel_request_remove_insurance_fund_stake(
         user_authority: user_stats.authority,
         action: StakeAction::UnstakeCancelRequest,
         amount: 0,
-        market_index: spot_market.market_index,
+        market_index,
+        market_type,
+        tranche,
         insurance_vault_amount_before: insurance_vault_amount,
         if_shares_before,
         user_if_shares_before,
         total_if_shares_before,
         if_shares_after,
-        total_if_shares_after: spot_market.insurance_fund.total_shares,
-        user_if_shares_after: spot_market.insurance_fund.user_shares,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
     });
 
     insurance_fund_stake.last_withdraw_request_shares = 0;
@@ -332,27 +692,34 @@ pub fn cancel_request_remove_insurance_fund_stake(
     Ok(())
 }
 
-pub fn remove_insurance_fund_stake(
+fn unstake_from_insurance_fund(
     insurance_vault_amount: u64,
     insurance_fund_stake: &mut InsuranceFundStake,
     user_stats: &mut UserStats,
-    spot_market: &mut SpotMarket,
+    insurance_fund: &mut InsuranceFund,
+    revenue_per_share: &mut u128,
+    market_index: u16,
+    market_type: MarketType,
+    tranche: InsuranceFundTranche,
     now: i64,
 ) -> DriftResult<u64> {
+    validate_insurance_fund_stake_pool(insurance_fund_stake, market_type, tranche)?;
+
     let time_since_withdraw_request =
         now.safe_sub(insurance_fund_stake.last_withdraw_request_ts)?;
 
     validate!(
-        time_since_withdraw_request >= spot_market.insurance_fund.unstaking_period,
+        time_since_withdraw_request >= insurance_fund.unstaking_period,
         ErrorCode::TryingToRemoveLiquidityTooFast
     )?;
 
-    apply_rebase_to_insurance_fund(insurance_vault_amount, spot_market)?;
-    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, spot_market)?;
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
 
-    let if_shares_before = insurance_fund_stake.checked_if_shares(spot_market)?;
-    let total_if_shares_before = spot_market.insurance_fund.total_shares;
-    let user_if_shares_before = spot_market.insurance_fund.user_shares;
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let total_if_shares_before = insurance_fund.total_shares;
+    let user_if_shares_before = insurance_fund.user_shares;
 
     let n_shares = insurance_fund_stake.last_withdraw_request_shares;
 
@@ -369,38 +736,39 @@ pub fn remove_insurance_fund_stake(
 
     let amount = if_shares_to_vault_amount(
         n_shares,
-        spot_market.insurance_fund.total_shares,
+        insurance_fund.total_shares,
         insurance_vault_amount,
     )?;
 
     let _if_shares_lost =
-        calculate_if_shares_lost(insurance_fund_stake, spot_market, insurance_vault_amount)?;
+        calculate_if_shares_lost(insurance_fund_stake, insurance_fund, insurance_vault_amount)?;
 
     let withdraw_amount = amount.min(insurance_fund_stake.last_withdraw_request_value);
 
-    insurance_fund_stake.decrease_if_shares(n_shares, spot_market)?;
+    insurance_fund_stake.decrease_if_shares(n_shares, insurance_fund)?;
 
     insurance_fund_stake.cost_basis = insurance_fund_stake
         .cost_basis
         .safe_sub(withdraw_amount.cast()?)?;
 
-    spot_market.insurance_fund.total_shares =
-        spot_market.insurance_fund.total_shares.safe_sub(n_shares)?;
+    insurance_fund.total_shares = insurance_fund.total_shares.safe_sub(n_shares)?;
 
-    spot_market.insurance_fund.user_shares =
-        spot_market.insurance_fund.user_shares.safe_sub(n_shares)?;
+    insurance_fund.user_shares = insurance_fund.user_shares.safe_sub(n_shares)?;
 
     // reset insurance_fund_stake withdraw request info
     insurance_fund_stake.last_withdraw_request_shares = 0;
     insurance_fund_stake.last_withdraw_request_value = 0;
     insurance_fund_stake.last_withdraw_request_ts = now;
 
-    let if_shares_after = insurance_fund_stake.checked_if_shares(spot_market)?;
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
 
-    if spot_market.market_index == 0 {
+    if market_type == MarketType::Spot
+        && tranche == InsuranceFundTranche::Senior
+        && market_index == 0
+    {
         user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
             if_shares_after,
-            spot_market.insurance_fund.total_shares,
+            insurance_fund.total_shares,
             insurance_vault_amount.safe_sub(amount)?,
         )?;
     }
@@ -410,64 +778,426 @@ pub fn remove_insurance_fund_stake(
         user_authority: user_stats.authority,
         action: StakeAction::Unstake,
         amount: withdraw_amount,
-        market_index: spot_market.market_index,
+        market_index,
+        market_type,
+        tranche,
         insurance_vault_amount_before: insurance_vault_amount,
         if_shares_before,
         user_if_shares_before,
         total_if_shares_before,
         if_shares_after,
-        total_if_shares_after: spot_market.insurance_fund.total_shares,
-        user_if_shares_after: spot_market.insurance_fund.user_shares,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
     });
 
     Ok(withdraw_amount)
 }
 
-pub fn admin_remove_insurance_fund_stake(
-    insurance_vault_amount: u64,
+pub fn tokenize_insurance_fund_stake(
     n_shares: u128,
+    insurance_vault_amount: u64,
+    share_token_supply: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    tokenized_insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
     spot_market: &mut SpotMarket,
     now: i64,
-    admin_pubkey: Pubkey,
 ) -> DriftResult<u64> {
-    apply_rebase_to_insurance_fund(insurance_vault_amount, spot_market)?;
+    let market_index = spot_market.market_index;
+    let insurance_fund = &mut spot_market.insurance_fund;
+    let revenue_per_share = &mut spot_market.insurance_fund_revenue_per_share;
+
+    validate_insurance_fund_stake_pool(
+        insurance_fund_stake,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+    )?;
+    validate_insurance_fund_stake_pool(
+        tokenized_insurance_fund_stake,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+    )?;
 
-    let total_if_shares_before = spot_market.insurance_fund.total_shares;
-    let user_if_shares_before = spot_market.insurance_fund.user_shares;
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0
+            && insurance_fund_stake.last_withdraw_request_value == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "withdraw request in progress"
+    )?;
 
-    let if_shares_before = total_if_shares_before.safe_sub(user_if_shares_before)?;
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(tokenized_insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(
+        tokenized_insurance_fund_stake,
+        insurance_fund,
+        *revenue_per_share,
+    )?;
+
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let tokenized_if_shares = tokenized_insurance_fund_stake.checked_if_shares(insurance_fund)?;
 
     validate!(
-        if_shares_before >= n_shares,
+        n_shares > 0 && if_shares_before >= n_shares,
         ErrorCode::InsufficientIFShares,
-        "if_shares_before={} < n_shares={}",
-        if_shares_before,
+        "cant tokenize {} if shares with {} if shares staked",
+        n_shares,
+        if_shares_before
+    )?;
+
+    // first mint prices one share token at one if share, later mints are pro rata
+    let token_amount = if share_token_supply == 0 {
+        n_shares
+    } else {
+        validate!(
+            tokenized_if_shares > 0,
+            ErrorCode::InvalidInsuranceFundShareTokenAmount,
+            "share token supply {} outstanding with no tokenized if shares",
+            share_token_supply
+        )?;
+
+        get_proportion_u128(n_shares, share_token_supply.cast()?, tokenized_if_shares)?
+    }
+    .cast::<u64>()?;
+
+    validate!(
+        token_amount > 0,
+        ErrorCode::InvalidInsuranceFundShareTokenAmount,
+        "n_shares {} mints zero share tokens",
         n_shares
     )?;
 
-    let withdraw_amount = if_shares_to_vault_amount(
+    let amount = if_shares_to_vault_amount(
         n_shares,
-        spot_market.insurance_fund.total_shares,
+        insurance_fund.total_shares,
         insurance_vault_amount,
     )?;
 
-    spot_market.insurance_fund.total_shares =
-        spot_market.insurance_fund.total_shares.safe_sub(n_shares)?;
+    insurance_fund_stake.decrease_if_shares(n_shares, insurance_fund)?;
+    insurance_fund_stake.cost_basis = insurance_fund_stake.cost_basis.safe_sub(amount.cast()?)?;
 
-    let if_shares_after = spot_market.insurance_fund.total_shares;
+    tokenized_insurance_fund_stake.increase_if_shares(n_shares, insurance_fund)?;
+    tokenized_insurance_fund_stake.cost_basis = tokenized_insurance_fund_stake
+        .cost_basis
+        .safe_add(amount.cast()?)?;
+
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+
+    if market_index == 0 {
+        user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
+            if_shares_after,
+            insurance_fund.total_shares,
+            insurance_vault_amount,
+        )?;
+    }
 
     emit!(InsuranceFundStakeRecord {
         ts: now,
-        user_authority: admin_pubkey,
-        action: StakeAction::Unstake,
-        amount: withdraw_amount,
-        market_index: spot_market.market_index,
+        user_authority: user_stats.authority,
+        action: StakeAction::Tokenize,
+        amount,
+        market_index,
+        market_type: MarketType::Spot,
+        tranche: InsuranceFundTranche::Senior,
         insurance_vault_amount_before: insurance_vault_amount,
         if_shares_before,
-        user_if_shares_before,
-        total_if_shares_before,
+        user_if_shares_before: insurance_fund.user_shares,
+        total_if_shares_before: insurance_fund.total_shares,
         if_shares_after,
-        total_if_shares_after: spot_market.insurance_fund.total_shares,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
+    });
+
+    Ok(token_amount)
+}
+
+pub fn redeem_insurance_fund_stake_tokens(
+    token_amount: u64,
+    insurance_vault_amount: u64,
+    share_token_supply: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    tokenized_insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult<u128> {
+    let market_index = spot_market.market_index;
+    let insurance_fund = &mut spot_market.insurance_fund;
+    let revenue_per_share = &mut spot_market.insurance_fund_revenue_per_share;
+
+    validate_insurance_fund_stake_pool(
+        insurance_fund_stake,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+    )?;
+    validate_insurance_fund_stake_pool(
+        tokenized_insurance_fund_stake,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0
+            && insurance_fund_stake.last_withdraw_request_value == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "withdraw request in progress"
+    )?;
+
+    validate!(
+        token_amount > 0 && token_amount <= share_token_supply,
+        ErrorCode::InvalidInsuranceFundShareTokenAmount,
+        "cant redeem {} share tokens with supply {}",
+        token_amount,
+        share_token_supply
+    )?;
+
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(tokenized_insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(
+        tokenized_insurance_fund_stake,
+        insurance_fund,
+        *revenue_per_share,
+    )?;
+
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let tokenized_if_shares = tokenized_insurance_fund_stake.checked_if_shares(insurance_fund)?;
+
+    let n_shares = get_proportion_u128(
+        tokenized_if_shares,
+        token_amount.cast()?,
+        share_token_supply.cast()?,
+    )?;
+
+    validate!(
+        n_shares > 0,
+        ErrorCode::InvalidInsuranceFundShareTokenAmount,
+        "{} share tokens redeem zero if shares",
+        token_amount
+    )?;
+
+    let amount = if_shares_to_vault_amount(
+        n_shares,
+        insurance_fund.total_shares,
+        insurance_vault_amount,
+    )?;
+
+    tokenized_insurance_fund_stake.decrease_if_shares(n_shares, insurance_fund)?;
+    tokenized_insurance_fund_stake.cost_basis = tokenized_insurance_fund_stake
+        .cost_basis
+        .safe_sub(amount.cast()?)?;
+
+    insurance_fund_stake.increase_if_shares(n_shares, insurance_fund)?;
+    insurance_fund_stake.cost_basis = insurance_fund_stake.cost_basis.safe_add(amount.cast()?)?;
+
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+
+    if market_index == 0 {
+        user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
+            if_shares_after,
+            insurance_fund.total_shares,
+            insurance_vault_amount,
+        )?;
+    }
+
+    emit!(InsuranceFundStakeRecord {
+        ts: now,
+        user_authority: user_stats.authority,
+        action: StakeAction::RedeemTokens,
+        amount,
+        market_index,
+        market_type: MarketType::Spot,
+        tranche: InsuranceFundTranche::Senior,
+        insurance_vault_amount_before: insurance_vault_amount,
+        if_shares_before,
+        user_if_shares_before: insurance_fund.user_shares,
+        total_if_shares_before: insurance_fund.total_shares,
+        if_shares_after,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
+    });
+
+    Ok(n_shares)
+}
+
+pub fn update_insurance_fund_stake_claim_revenue(
+    claim_revenue: bool,
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    spot_market: &mut SpotMarket,
+) -> DriftResult {
+    let insurance_fund = &mut spot_market.insurance_fund;
+    let revenue_per_share = &mut spot_market.insurance_fund_revenue_per_share;
+
+    validate_insurance_fund_stake_pool(
+        insurance_fund_stake,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+    )?;
+
+    // revenue earned so far is accounted for under the previous mode
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
+
+    if !claim_revenue {
+        // unclaimed revenue stays in the stake and compounds
+        insurance_fund_stake.unclaimed_revenue = 0;
+    }
+
+    insurance_fund_stake.claim_revenue = claim_revenue;
+
+    Ok(())
+}
+
+pub fn claim_insurance_fund_revenue(
+    insurance_vault_amount: u64,
+    insurance_fund_stake: &mut InsuranceFundStake,
+    user_stats: &mut UserStats,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult<u64> {
+    let market_index = spot_market.market_index;
+    let insurance_fund = &mut spot_market.insurance_fund;
+    let revenue_per_share = &mut spot_market.insurance_fund_revenue_per_share;
+
+    validate_insurance_fund_stake_pool(
+        insurance_fund_stake,
+        MarketType::Spot,
+        InsuranceFundTranche::Senior,
+    )?;
+
+    validate!(
+        insurance_fund_stake.claim_revenue,
+        ErrorCode::InvalidInsuranceFundRevenueClaim,
+        "insurance_fund_stake is not in claim revenue mode"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0
+            && insurance_fund_stake.last_withdraw_request_value == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "withdraw request in progress"
+    )?;
+
+    apply_rebase_to_insurance_fund(insurance_vault_amount, insurance_fund, revenue_per_share)?;
+    apply_rebase_to_insurance_fund_stake(insurance_fund_stake, insurance_fund)?;
+    accrue_insurance_fund_stake_revenue(insurance_fund_stake, insurance_fund, *revenue_per_share)?;
+
+    let if_shares_before = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+    let total_if_shares_before = insurance_fund.total_shares;
+    let user_if_shares_before = insurance_fund.user_shares;
+
+    let n_shares = vault_amount_to_if_shares(
+        insurance_fund_stake.unclaimed_revenue,
+        insurance_fund.total_shares,
+        insurance_vault_amount,
+    )?
+    .min(if_shares_before);
+
+    validate!(
+        n_shares > 0,
+        ErrorCode::InvalidInsuranceFundRevenueClaim,
+        "no revenue to claim"
+    )?;
+
+    let amount = if_shares_to_vault_amount(
+        n_shares,
+        insurance_fund.total_shares,
+        insurance_vault_amount,
+    )?;
+
+    // paying out revenue leaves the cost basis untouched
+    insurance_fund_stake.decrease_if_shares(n_shares, insurance_fund)?;
+    insurance_fund_stake.unclaimed_revenue = 0;
+
+    insurance_fund.total_shares = insurance_fund.total_shares.safe_sub(n_shares)?;
+
+    insurance_fund.user_shares = insurance_fund.user_shares.safe_sub(n_shares)?;
+
+    let if_shares_after = insurance_fund_stake.checked_if_shares(insurance_fund)?;
+
+    if market_index == 0 {
+        user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
+            if_shares_after,
+            insurance_fund.total_shares,
+            insurance_vault_amount.safe_sub(amount)?,
+        )?;
+    }
+
+    emit!(InsuranceFundStakeRecord {
+        ts: now,
+        user_authority: user_stats.authority,
+        action: StakeAction::ClaimRevenue,
+        amount,
+        market_index,
+        market_type: MarketType::Spot,
+        tranche: InsuranceFundTranche::Senior,
+        insurance_vault_amount_before: insurance_vault_amount,
+        if_shares_before,
+        user_if_shares_before,
+        total_if_shares_before,
+        if_shares_after,
+        total_if_shares_after: insurance_fund.total_shares,
+        user_if_shares_after: insurance_fund.user_shares,
+    });
+
+    Ok(amount)
+}
+
+pub fn admin_remove_insurance_fund_stake(
+    insurance_vault_amount: u64,
+    n_shares: u128,
+    spot_market: &mut SpotMarket,
+    now: i64,
+    admin_pubkey: Pubkey,
+) -> DriftResult<u64> {
+    apply_rebase_to_insurance_fund(
+        insurance_vault_amount,
+        &mut spot_market.insurance_fund,
+        &mut spot_market.insurance_fund_revenue_per_share,
+    )?;
+
+    let total_if_shares_before = spot_market.insurance_fund.total_shares;
+    let user_if_shares_before = spot_market.insurance_fund.user_shares;
+
+    let if_shares_before = total_if_shares_before.safe_sub(user_if_shares_before)?;
+
+    validate!(
+        if_shares_before >= n_shares,
+        ErrorCode::InsufficientIFShares,
+        "if_shares_before={} < n_shares={}",
+        if_shares_before,
+        n_shares
+    )?;
+
+    let withdraw_amount = if_shares_to_vault_amount(
+        n_shares,
+        spot_market.insurance_fund.total_shares,
+        insurance_vault_amount,
+    )?;
+
+    spot_market.insurance_fund.total_shares =
+        spot_market.insurance_fund.total_shares.safe_sub(n_shares)?;
+
+    let if_shares_after = spot_market.insurance_fund.total_shares;
+
+    emit!(InsuranceFundStakeRecord {
+        ts: now,
+        user_authority: admin_pubkey,
+        action: StakeAction::Unstake,
+        amount: withdraw_amount,
+        market_index: spot_market.market_index,
+        market_type: MarketType::Spot,
+        tranche: InsuranceFundTranche::Senior,
+        insurance_vault_amount_before: insurance_vault_amount,
+        if_shares_before,
+        user_if_shares_before,
+        total_if_shares_before,
+        if_shares_after,
+        total_if_shares_after: spot_market.insurance_fund.total_shares,
         user_if_shares_after: spot_market.insurance_fund.user_shares,
     });
 
@@ -603,19 +1333,34 @@ pub fn settle_revenue_to_insurance_fund(
         .total_factor
         .safe_sub(spot_market.insurance_fund.user_factor)?;
 
+    let total_if_shares_before_protocol_cut = spot_market.insurance_fund.total_shares;
+
     // give protocol its cut
-    if protocol_if_factor > 0 {
+    let protocol_if_revenue = if protocol_if_factor > 0 {
+        let protocol_if_revenue = insurance_fund_token_amount
+            .safe_mul(protocol_if_factor.cast()?)?
+            .safe_div(spot_market.insurance_fund.total_factor.cast()?)?;
+
         let n_shares = vault_amount_to_if_shares(
-            insurance_fund_token_amount
-                .safe_mul(protocol_if_factor.cast()?)?
-                .safe_div(spot_market.insurance_fund.total_factor.cast()?)?,
+            protocol_if_revenue,
             spot_market.insurance_fund.total_shares,
             insurance_vault_amount,
         )?;
 
         spot_market.insurance_fund.total_shares =
             spot_market.insurance_fund.total_shares.safe_add(n_shares)?;
-    }
+
+        protocol_if_revenue
+    } else {
+        0
+    };
+
+    // the rest accrues to the shares outstanding before the protocol's cut
+    update_insurance_fund_revenue_per_share(
+        &mut spot_market.insurance_fund_revenue_per_share,
+        insurance_fund_token_amount.safe_sub(protocol_if_revenue)?,
+        total_if_shares_before_protocol_cut,
+    )?;
 
     let total_if_shares_before = spot_market.insurance_fund.total_shares;
 
@@ -624,6 +1369,9 @@ pub fn settle_revenue_to_insurance_fund(
         &SpotBalanceType::Borrow,
         spot_market,
     )?;
+    spot_market
+        .protocol_revenue
+        .record_settle_to_insurance_fund(insurance_fund_token_amount);
 
     emit!(InsuranceFundRecord {
         ts: now,
@@ -642,6 +1390,279 @@ pub fn settle_revenue_to_insurance_fund(
     insurance_fund_token_amount.cast()
 }
 
+pub fn settle_revenue_to_junior_insurance_fund(
+    spot_market_vault_amount: u64,
+    insurance_vault_amount: u64,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult<u64> {
+    validate!(
+        spot_market.has_junior_insurance_fund(),
+        ErrorCode::JuniorInsuranceFundNotInitialized,
+        "spot market {} has no junior insurance fund",
+        spot_market.market_index
+    )?;
+
+    update_spot_market_cumulative_interest(spot_market, None, now)?;
+
+    validate!(
+        spot_market.junior_insurance_fund.revenue_settle_period > 0,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "invalid revenue_settle_period settings on junior insurance fund"
+    )?;
+
+    validate!(
+        spot_market.junior_insurance_fund.user_factor
+            <= spot_market.junior_insurance_fund.total_factor,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "invalid if_factor settings on junior insurance fund"
+    )?;
+
+    let depositors_claim =
+        validate_spot_market_vault_amount(spot_market, spot_market_vault_amount)?.cast::<u128>()?;
+
+    let mut revenue_pool_token_amount = get_token_amount(
+        spot_market.revenue_pool.scaled_balance,
+        spot_market,
+        &SpotBalanceType::Deposit,
+    )?;
+
+    if depositors_claim < revenue_pool_token_amount {
+        // only allow half of withdraw available when utilization is high
+        revenue_pool_token_amount = depositors_claim.safe_div(2)?;
+    }
+
+    // junior tranche takes its total_factor of the revenue pool
+    let mut token_amount = revenue_pool_token_amount
+        .safe_mul(spot_market.junior_insurance_fund.total_factor.cast()?)?
+        .safe_div(IF_FACTOR_PRECISION)?;
+
+    if spot_market.junior_insurance_fund.user_shares > 0 {
+        // only allow MAX_APR_PER_REVENUE_SETTLE_TO_INSURANCE_FUND_VAULT to be settled
+        let capped_apr_amount = insurance_vault_amount
+            .cast::<u128>()?
+            .safe_mul(MAX_APR_PER_REVENUE_SETTLE_TO_INSURANCE_FUND_VAULT.cast::<u128>()?)?
+            .safe_div(PERCENTAGE_PRECISION)?
+            .safe_div(
+                ONE_YEAR
+                    .safe_div(
+                        spot_market
+                            .junior_insurance_fund
+                            .revenue_settle_period
+                            .cast()?,
+                    )?
+                    .max(1),
+            )?;
+        token_amount = token_amount.min(capped_apr_amount);
+    }
+
+    let insurance_fund_token_amount = token_amount.cast::<u64>()?;
+
+    validate!(
+        insurance_fund_token_amount != 0,
+        ErrorCode::NoRevenueToSettleToIF,
+        "no amount to settle to junior insurance fund"
+    )?;
+
+    spot_market.junior_insurance_fund.last_revenue_settle_ts = now;
+
+    let protocol_if_factor = spot_market
+        .junior_insurance_fund
+        .total_factor
+        .safe_sub(spot_market.junior_insurance_fund.user_factor)?;
+
+    let total_if_shares_before_protocol_cut = spot_market.junior_insurance_fund.total_shares;
+
+    // give protocol its cut
+    let protocol_if_revenue = if protocol_if_factor > 0 {
+        let protocol_if_revenue = insurance_fund_token_amount
+            .safe_mul(protocol_if_factor.cast()?)?
+            .safe_div(spot_market.junior_insurance_fund.total_factor.cast()?)?;
+
+        let n_shares = vault_amount_to_if_shares(
+            protocol_if_revenue,
+            spot_market.junior_insurance_fund.total_shares,
+            insurance_vault_amount,
+        )?;
+
+        spot_market.junior_insurance_fund.total_shares = spot_market
+            .junior_insurance_fund
+            .total_shares
+            .safe_add(n_shares)?;
+
+        protocol_if_revenue
+    } else {
+        0
+    };
+
+    // the rest accrues to the shares outstanding before the protocol's cut
+    update_insurance_fund_revenue_per_share(
+        &mut spot_market.junior_insurance_fund_revenue_per_share,
+        insurance_fund_token_amount.safe_sub(protocol_if_revenue)?,
+        total_if_shares_before_protocol_cut,
+    )?;
+
+    let total_if_shares_before = spot_market.junior_insurance_fund.total_shares;
+
+    update_revenue_pool_balances(
+        insurance_fund_token_amount.cast::<u128>()?,
+        &SpotBalanceType::Borrow,
+        spot_market,
+    )?;
+    spot_market
+        .protocol_revenue
+        .record_settle_to_insurance_fund(insurance_fund_token_amount);
+
+    emit!(InsuranceFundRecord {
+        ts: now,
+        spot_market_index: spot_market.market_index,
+        perp_market_index: 0,
+        amount: insurance_fund_token_amount.cast()?,
+
+        user_if_factor: spot_market.junior_insurance_fund.user_factor,
+        total_if_factor: spot_market.junior_insurance_fund.total_factor,
+        vault_amount_before: spot_market_vault_amount,
+        insurance_vault_amount_before: insurance_vault_amount,
+        total_if_shares_before,
+        total_if_shares_after: spot_market.junior_insurance_fund.total_shares,
+    });
+
+    Ok(insurance_fund_token_amount)
+}
+
+pub fn settle_revenue_to_perp_insurance_fund(
+    spot_market_vault_amount: u64,
+    insurance_vault_amount: u64,
+    quote_spot_market: &mut SpotMarket,
+    perp_market: &mut PerpMarket,
+    now: i64,
+) -> DriftResult<u64> {
+    validate!(
+        perp_market.has_insurance_fund(),
+        ErrorCode::PerpMarketInsuranceFundNotInitialized,
+        "perp market {} has no insurance fund",
+        perp_market.market_index
+    )?;
+
+    validate!(
+        perp_market.insurance_fund.revenue_settle_period > 0,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "invalid revenue_settle_period settings on perp market"
+    )?;
+
+    validate!(
+        perp_market.insurance_fund.user_factor <= perp_market.insurance_fund.total_factor,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "invalid if_factor settings on perp market"
+    )?;
+
+    update_spot_market_cumulative_interest(quote_spot_market, None, now)?;
+
+    // only fees above what the amm must retain are shared with the perp market's insurance fund
+    let fee_pool_token_amount = get_fee_pool_tokens(perp_market, quote_spot_market)?;
+    let amm_target_min_fee_pool_token_amount = get_total_fee_lower_bound(perp_market)?
+        .safe_add(perp_market.amm.total_liquidation_fee)?
+        .cast::<i128>()?;
+
+    let excess_fee_pool_token_amount = fee_pool_token_amount
+        .safe_sub(amm_target_min_fee_pool_token_amount)?
+        .max(0)
+        .unsigned_abs();
+
+    let mut token_amount = excess_fee_pool_token_amount
+        .safe_mul(perp_market.insurance_fund.total_factor.cast()?)?
+        .safe_div(IF_FACTOR_PRECISION)?;
+
+    if perp_market.insurance_fund.user_shares > 0 {
+        // only allow MAX_APR_PER_REVENUE_SETTLE_TO_INSURANCE_FUND_VAULT to be settled
+        let capped_apr_amount = insurance_vault_amount
+            .cast::<u128>()?
+            .safe_mul(MAX_APR_PER_REVENUE_SETTLE_TO_INSURANCE_FUND_VAULT.cast::<u128>()?)?
+            .safe_div(PERCENTAGE_PRECISION)?
+            .safe_div(
+                ONE_YEAR
+                    .safe_div(perp_market.insurance_fund.revenue_settle_period.cast()?)?
+                    .max(1),
+            )?;
+        token_amount = token_amount.min(capped_apr_amount);
+    }
+
+    let insurance_fund_token_amount = token_amount.cast::<u64>()?;
+
+    validate!(
+        insurance_fund_token_amount != 0,
+        ErrorCode::NoRevenueToSettleToIF,
+        "no amount to settle to perp market insurance fund"
+    )?;
+
+    perp_market.insurance_fund.last_revenue_settle_ts = now;
+
+    let protocol_if_factor = perp_market
+        .insurance_fund
+        .total_factor
+        .safe_sub(perp_market.insurance_fund.user_factor)?;
+
+    let total_if_shares_before_protocol_cut = perp_market.insurance_fund.total_shares;
+
+    // give protocol its cut
+    let protocol_if_revenue = if protocol_if_factor > 0 {
+        let protocol_if_revenue = insurance_fund_token_amount
+            .safe_mul(protocol_if_factor.cast()?)?
+            .safe_div(perp_market.insurance_fund.total_factor.cast()?)?;
+
+        let n_shares = vault_amount_to_if_shares(
+            protocol_if_revenue,
+            perp_market.insurance_fund.total_shares,
+            insurance_vault_amount,
+        )?;
+
+        perp_market.insurance_fund.total_shares =
+            perp_market.insurance_fund.total_shares.safe_add(n_shares)?;
+
+        protocol_if_revenue
+    } else {
+        0
+    };
+
+    // the rest accrues to the shares outstanding before the protocol's cut
+    update_insurance_fund_revenue_per_share(
+        &mut perp_market.insurance_fund_revenue_per_share,
+        insurance_fund_token_amount.safe_sub(protocol_if_revenue)?,
+        total_if_shares_before_protocol_cut,
+    )?;
+
+    let total_if_shares_before = perp_market.insurance_fund.total_shares;
+
+    update_spot_balances(
+        insurance_fund_token_amount.cast()?,
+        &SpotBalanceType::Borrow,
+        quote_spot_market,
+        &mut perp_market.amm.fee_pool,
+        false,
+    )?;
+
+    perp_market.amm.total_fee_withdrawn = perp_market
+        .amm
+        .total_fee_withdrawn
+        .safe_add(insurance_fund_token_amount.cast()?)?;
+
+    emit!(InsuranceFundRecord {
+        ts: now,
+        spot_market_index: quote_spot_market.market_index,
+        perp_market_index: perp_market.market_index,
+        amount: insurance_fund_token_amount.cast()?,
+
+        user_if_factor: perp_market.insurance_fund.user_factor,
+        total_if_factor: perp_market.insurance_fund.total_factor,
+        vault_amount_before: spot_market_vault_amount,
+        insurance_vault_amount_before: insurance_vault_amount,
+        total_if_shares_before,
+        total_if_shares_after: perp_market.insurance_fund.total_shares,
+    });
+
+    Ok(insurance_fund_token_amount)
+}
+
 pub fn resolve_perp_pnl_deficit(
     vault_amount: u64,
     insurance_vault_amount: u64,
@@ -4,12 +4,12 @@ use anchor_lang::prelude::*;
 use solana_program::msg;
 
 use crate::controller::amm::get_fee_pool_tokens;
-use crate::controller::funding::settle_funding_payment;
+use crate::controller::funding::{is_continuous_funding_paused, settle_funding_payment};
 use crate::controller::lp::burn_lp_shares;
 use crate::controller::orders;
 use crate::controller::position::{
-    get_position_index, update_position_and_market, update_quote_asset_amount,
-    update_quote_asset_and_break_even_amount, PositionDirection,
+    get_position_index, increase_open_bids_and_asks, update_position_and_market,
+    update_quote_asset_amount, update_quote_asset_and_break_even_amount, PositionDirection,
 };
 use crate::controller::repeg::update_amm_and_check_validity;
 use crate::controller::spot_balance::{
@@ -17,23 +17,26 @@ use crate::controller::spot_balance::{
     update_spot_market_cumulative_interest,
 };
 use crate::controller::spot_position::update_spot_balances_and_cumulative_deposits;
+use crate::controller::user_health::update_user_health;
 use crate::error::{DriftResult, ErrorCode};
 use crate::get_then_update_id;
+use crate::math::amm::calculate_amm_available_liquidity;
 use crate::math::bankruptcy::is_user_bankrupt;
 use crate::math::casting::Cast;
 use crate::math::constants::{
-    LIQUIDATION_FEE_PRECISION_U128, LIQUIDATION_PCT_PRECISION, QUOTE_PRECISION,
+    BASE_PRECISION_U64, LIQUIDATION_FEE_PRECISION_U128, LIQUIDATION_PCT_PRECISION, QUOTE_PRECISION,
     QUOTE_PRECISION_I128, QUOTE_PRECISION_U64, QUOTE_SPOT_MARKET_INDEX, SPOT_WEIGHT_PRECISION,
 };
 use crate::math::liquidation::{
-    calculate_asset_transfer_for_liability_transfer,
-    calculate_base_asset_amount_to_cover_margin_shortage,
+    calculate_asset_transfer_for_liability_transfer, calculate_auto_deleverage_bankruptcy_price,
+    calculate_auto_deleverage_score, calculate_base_asset_amount_to_cover_margin_shortage,
     calculate_cumulative_deposit_interest_delta_to_resolve_bankruptcy,
     calculate_funding_rate_deltas_to_resolve_bankruptcy,
     calculate_liability_transfer_implied_by_asset_amount,
-    calculate_liability_transfer_to_cover_margin_shortage, calculate_liquidation_multiplier,
-    calculate_margin_shortage, calculate_max_pct_to_liquidate,
-    validate_transfer_satisfies_limit_price, LiquidationMultiplierType,
+    calculate_liability_transfer_to_cover_margin_shortage, calculate_liquidation_auction_fee,
+    calculate_liquidation_multiplier, calculate_margin_shortage, calculate_max_pct_to_liquidate,
+    calculate_shared_collateral_transfer, validate_transfer_satisfies_limit_price,
+    LiquidationMultiplierType,
 };
 use crate::math::margin::{
     calculate_margin_requirement_and_total_collateral, calculate_user_safest_position_tiers,
@@ -41,26 +44,33 @@ use crate::math::margin::{
 };
 use crate::math::oracle::DriftAction;
 use crate::math::orders::{
-    get_position_delta_for_fill, is_multiple_of_step_size, standardize_base_asset_amount,
-    standardize_base_asset_amount_ceil,
+    calculate_fill_price, get_position_delta_for_fill, is_multiple_of_step_size,
+    standardize_base_asset_amount, standardize_base_asset_amount_ceil,
+    validate_fill_price_within_price_bands,
+};
+use crate::math::position::{
+    calculate_base_asset_value_and_pnl_with_oracle_price,
+    calculate_base_asset_value_with_oracle_price,
 };
-use crate::math::position::calculate_base_asset_value_with_oracle_price;
 use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::get_token_value;
 use crate::state::events::{
-    emit_stack, LPAction, LPRecord, LiquidateBorrowForPerpPnlRecord,
+    emit_stack, AutoDeleverageRecord, DepositDirection, DepositExplanation, DepositRecord,
+    LPAction, LPRecord, LiquidateBorrowForPerpPnlRecord, LiquidatePerpLpRecord,
     LiquidatePerpPnlForDepositRecord, LiquidatePerpRecord, LiquidateSpotRecord, LiquidationRecord,
     LiquidationType, OrderAction, OrderActionExplanation, OrderActionRecord, OrderRecord,
     PerpBankruptcyRecord, SpotBankruptcyRecord,
 };
 use crate::state::oracle_map::OracleMap;
-use crate::state::perp_market::MarketStatus;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_market::{AMMLiquiditySplit, MarketStatus};
 use crate::state::perp_market_map::PerpMarketMap;
-use crate::state::spot_market::SpotBalanceType;
+use crate::state::spot_market::{ProtocolRevenueSource, SpotBalanceType};
 use crate::state::spot_market_map::SpotMarketMap;
-use crate::state::state::State;
+use crate::state::state::{FeeStructure, State};
 use crate::state::traits::Size;
-use crate::state::user::{MarketType, Order, OrderStatus, OrderType, User, UserStats};
+use crate::state::user::{MarketType, Order, OrderStatus, OrderType, User, UserStats, UserStatus};
+use crate::state::user_map::UserMap;
 use crate::validate;
 
 #[cfg(test)]
@@ -86,6 +96,7 @@ pub fn liquidate_perp(
     let liquidation_margin_buffer_ratio = state.liquidation_margin_buffer_ratio;
     let initial_pct_to_liquidate = state.initial_pct_to_liquidate as u128;
     let liquidation_duration = state.liquidation_duration as u128;
+    let liquidation_auction_duration = state.liquidation_auction_duration;
 
     validate!(
         !user.is_bankrupt(),
@@ -99,12 +110,27 @@ pub fn liquidate_perp(
         "liquidator bankrupt",
     )?;
 
+    validate!(
+        !perp_market_map
+            .get_ref(&market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for perp market {}",
+        market_index
+    )?;
+
+    let funding_paused = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        is_continuous_funding_paused(&market, oracle_map, state.funding_paused()?)?
+    };
+
     // Settle user's funding payments so that collateral is up to date
     settle_funding_payment(
         user,
         user_key,
         perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
         now,
+        funding_paused,
     )?;
 
     // Settle user's funding payments so that collateral is up to date
@@ -113,6 +139,7 @@ pub fn liquidate_perp(
         liquidator_key,
         perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
         now,
+        funding_paused,
     )?;
 
     let (margin_requirement, total_collateral, margin_requirement_plus_buffer, _) =
@@ -163,64 +190,20 @@ pub fn liquidate_perp(
         ErrorCode::PositionDoesntHaveOpenPositionOrOrders
     )?;
 
-    let canceled_order_ids = orders::cancel_orders(
+    let (canceled_order_ids, oracle_price, lp_shares) = prepare_perp_position_for_liquidation(
+        market_index,
+        position_index,
         user,
         user_key,
         Some(liquidator_key),
         perp_market_map,
         spot_market_map,
         oracle_map,
-        now,
         slot,
-        OrderActionExplanation::Liquidation,
-        None,
-        None,
-        None,
-    )?;
-
-    let mut market = perp_market_map.get_ref_mut(&market_index)?;
-    let oracle_price_data = oracle_map.get_price_data(&market.amm.oracle)?;
-
-    update_amm_and_check_validity(
-        &mut market,
-        oracle_price_data,
-        state,
         now,
-        slot,
-        Some(DriftAction::Liquidate),
+        state,
     )?;
 
-    let oracle_price = if market.status == MarketStatus::Settlement {
-        market.expiry_price
-    } else {
-        oracle_price_data.price
-    };
-
-    drop(market);
-
-    // burning lp shares = removing open bids/asks
-    let lp_shares = user.perp_positions[position_index].lp_shares;
-    if lp_shares > 0 {
-        let (position_delta, pnl) = burn_lp_shares(
-            &mut user.perp_positions[position_index],
-            perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
-            lp_shares,
-            oracle_price,
-        )?;
-
-        // emit LP record for shares removed
-        emit_stack::<_, { LPRecord::SIZE }>(LPRecord {
-            ts: now,
-            action: LPAction::RemoveLiquidity,
-            user: *user_key,
-            n_shares: lp_shares,
-            market_index,
-            delta_base_asset_amount: position_delta.base_asset_amount,
-            delta_quote_asset_amount: position_delta.quote_asset_amount,
-            pnl,
-        })?;
-    }
-
     // check if user exited liquidation territory
     let (intermediate_total_collateral, intermediate_margin_requirement_with_buffer) =
         if !canceled_order_ids.is_empty() || lp_shares > 0 {
@@ -297,77 +280,45 @@ pub fn liquidate_perp(
         "liquidator_max_base_asset_amount cant be 0"
     )?;
 
-    let user_base_asset_amount = user.perp_positions[position_index]
-        .base_asset_amount
-        .unsigned_abs();
-
-    let worst_case_base_asset_amount =
-        user.perp_positions[position_index].worst_case_base_asset_amount()?;
-
-    let margin_ratio = perp_market_map.get_ref(&market_index)?.get_margin_ratio(
-        worst_case_base_asset_amount.unsigned_abs(),
-        MarginRequirementType::Maintenance,
-    )?;
-
-    let margin_ratio_with_buffer = margin_ratio.safe_add(liquidation_margin_buffer_ratio)?;
-
     let margin_shortage = calculate_margin_shortage(
         intermediate_margin_requirement_with_buffer,
         intermediate_total_collateral,
     )?;
 
-    let market = perp_market_map.get_ref(&market_index)?;
-    let liquidation_fee = market.liquidator_fee;
-    let if_liquidation_fee = market.if_liquidation_fee;
-    let quote_spot_market = spot_market_map.get_ref(&market.quote_spot_market_index)?;
-    let quote_oracle_price = oracle_map.get_price_data(&quote_spot_market.oracle)?.price;
-    let base_asset_amount_to_cover_margin_shortage = standardize_base_asset_amount_ceil(
-        calculate_base_asset_amount_to_cover_margin_shortage(
+    let (liquidation_fee, if_liquidation_fee) = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        (
+            calculate_liquidation_auction_fee(
+                market.liquidator_fee,
+                user,
+                slot,
+                liquidation_auction_duration,
+            )?,
+            market.if_liquidation_fee,
+        )
+    };
+
+    let (base_asset_amount_to_cover_margin_shortage, max_base_asset_amount) =
+        calculate_perp_base_asset_amounts_to_liquidate(
+            user,
+            position_index,
             margin_shortage,
-            margin_ratio_with_buffer,
             liquidation_fee,
-            if_liquidation_fee,
             oracle_price,
-            quote_oracle_price,
-        )?,
-        market.amm.order_step_size,
-    )?;
-    drop(market);
-    drop(quote_spot_market);
-
-    let max_pct_allowed = calculate_max_pct_to_liquidate(
-        user,
-        margin_shortage,
-        slot,
-        initial_pct_to_liquidate,
-        liquidation_duration,
-    )?;
-    let max_base_asset_amount_allowed_to_be_transferred =
-        base_asset_amount_to_cover_margin_shortage
-            .cast::<u128>()?
-            .saturating_mul(max_pct_allowed)
-            .safe_div(LIQUIDATION_PCT_PRECISION)?
-            .cast::<u64>()?;
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            slot,
+            liquidation_margin_buffer_ratio,
+            initial_pct_to_liquidate,
+            liquidation_duration,
+        )?;
 
-    if max_base_asset_amount_allowed_to_be_transferred == 0 {
-        msg!("max_base_asset_amount_allowed_to_be_transferred == 0");
+    if max_base_asset_amount == 0 {
         return Ok(());
     }
 
-    let base_asset_value =
-        calculate_base_asset_value_with_oracle_price(user_base_asset_amount.cast()?, oracle_price)?
-            .cast::<u64>()?;
-
-    // if position is less than $10, liquidator can liq all of it
-    let min_base_asset_amount = if base_asset_value > 10 * QUOTE_PRECISION_U64 {
-        0_u64
-    } else {
-        user_base_asset_amount
-    };
-
-    let base_asset_amount = user_base_asset_amount
-        .min(liquidator_max_base_asset_amount)
-        .min(max_base_asset_amount_allowed_to_be_transferred.max(min_base_asset_amount));
+    let base_asset_amount = max_base_asset_amount.min(liquidator_max_base_asset_amount);
     let base_asset_amount = standardize_base_asset_amount_ceil(
         base_asset_amount,
         perp_market_map.get_ref(&market_index)?.amm.order_step_size,
@@ -624,7 +575,765 @@ pub fn liquidate_perp(
         ..LiquidationRecord::default()
     });
 
-    Ok(())
+    Ok(())
+}
+
+/// Burns a user's lp shares in a perp market as its own liquidation step
+///
+/// The liquidator is paid the market's liquidator fee on the margin freed by burning the shares
+pub fn liquidate_perp_lp(
+    market_index: u16,
+    user: &mut User,
+    user_key: &Pubkey,
+    liquidator: &mut User,
+    liquidator_key: &Pubkey,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    slot: u64,
+    now: i64,
+    state: &State,
+) -> DriftResult {
+    let liquidation_margin_buffer_ratio = state.liquidation_margin_buffer_ratio;
+
+    validate!(
+        !user.is_bankrupt(),
+        ErrorCode::UserBankrupt,
+        "user bankrupt",
+    )?;
+
+    validate!(
+        !liquidator.is_bankrupt(),
+        ErrorCode::UserBankrupt,
+        "liquidator bankrupt",
+    )?;
+
+    validate!(
+        !perp_market_map
+            .get_ref(&market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for perp market {}",
+        market_index
+    )?;
+
+    let funding_paused = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        is_continuous_funding_paused(&market, oracle_map, state.funding_paused()?)?
+    };
+
+    settle_funding_payment(
+        user,
+        user_key,
+        perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+        now,
+        funding_paused,
+    )?;
+
+    let (margin_requirement, total_collateral, margin_requirement_plus_buffer, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            Some(liquidation_margin_buffer_ratio as u128),
+        )?;
+
+    if !user.is_being_liquidated() && total_collateral >= margin_requirement.cast()? {
+        return Err(ErrorCode::SufficientCollateral);
+    } else if user.is_being_liquidated()
+        && total_collateral >= margin_requirement_plus_buffer.cast()?
+    {
+        user.exit_liquidation();
+        return Ok(());
+    }
+
+    let position_index = get_position_index(&user.perp_positions, market_index).map_err(|e| {
+        msg!(
+            "User does not have a position for perp market {}",
+            market_index
+        );
+        e
+    })?;
+
+    validate!(
+        user.perp_positions[position_index].is_lp(),
+        ErrorCode::PositionDoesntHaveOpenPositionOrOrders,
+        "User has no lp shares in perp market {}",
+        market_index
+    )?;
+
+    liquidator
+        .force_get_perp_position_mut(market_index)
+        .map_err(|e| {
+            msg!(
+                "Liquidator has no available positions to receive fee in perp market {}",
+                market_index
+            );
+            e
+        })?;
+
+    let liquidation_id = user.enter_liquidation(slot)?;
+
+    let mut market = perp_market_map.get_ref_mut(&market_index)?;
+    let oracle_price_data = oracle_map.get_price_data(&market.amm.oracle)?;
+
+    update_amm_and_check_validity(
+        &mut market,
+        oracle_price_data,
+        state,
+        now,
+        slot,
+        Some(DriftAction::Liquidate),
+    )?;
+
+    let oracle_price = if market.status == MarketStatus::Settlement {
+        market.expiry_price
+    } else {
+        oracle_price_data.price
+    };
+
+    let lp_shares = user.perp_positions[position_index].lp_shares;
+    let (position_delta, pnl) = burn_lp_shares(
+        &mut user.perp_positions[position_index],
+        &mut market,
+        lp_shares,
+        oracle_price,
+    )?;
+    let liquidator_fee_ratio = market.liquidator_fee;
+    drop(market);
+
+    emit_stack::<_, { LPRecord::SIZE }>(LPRecord {
+        ts: now,
+        action: LPAction::RemoveLiquidity,
+        user: *user_key,
+        n_shares: lp_shares,
+        market_index,
+        delta_base_asset_amount: position_delta.base_asset_amount,
+        delta_quote_asset_amount: position_delta.quote_asset_amount,
+        pnl,
+        ..LPRecord::default()
+    })?;
+
+    let margin_shortage =
+        calculate_margin_shortage(margin_requirement_plus_buffer, total_collateral)?;
+    let margin_freed = calculate_margin_freed(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        liquidation_margin_buffer_ratio,
+        margin_shortage,
+    )?;
+
+    let liquidator_fee = margin_freed
+        .cast::<u128>()?
+        .safe_mul(liquidator_fee_ratio.cast()?)?
+        .safe_div(LIQUIDATION_FEE_PRECISION_U128)?
+        .cast::<u64>()?;
+
+    if liquidator_fee > 0 {
+        let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+        update_quote_asset_and_break_even_amount(
+            &mut user.perp_positions[position_index],
+            &mut market,
+            -liquidator_fee.cast::<i64>()?,
+        )?;
+
+        let liquidator_position = liquidator.force_get_perp_position_mut(market_index)?;
+        update_quote_asset_and_break_even_amount(
+            liquidator_position,
+            &mut market,
+            liquidator_fee.cast()?,
+        )?;
+    }
+
+    user.increment_margin_freed(margin_freed)?;
+
+    let (_, total_collateral_after, margin_requirement_plus_buffer_after, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            Some(liquidation_margin_buffer_ratio as u128),
+        )?;
+
+    if total_collateral_after >= margin_requirement_plus_buffer_after.cast()? {
+        user.exit_liquidation();
+    } else if is_user_bankrupt(user) {
+        user.enter_bankruptcy();
+    }
+
+    emit!(LiquidationRecord {
+        ts: now,
+        liquidation_id,
+        liquidation_type: LiquidationType::LiquidatePerpLp,
+        user: *user_key,
+        liquidator: *liquidator_key,
+        margin_requirement,
+        total_collateral,
+        bankrupt: user.is_bankrupt(),
+        margin_freed,
+        liquidate_perp_lp: LiquidatePerpLpRecord {
+            market_index,
+            oracle_price,
+            lp_shares,
+            base_asset_amount: position_delta.base_asset_amount,
+            quote_asset_amount: position_delta.quote_asset_amount,
+            pnl,
+            liquidator_fee,
+        },
+        ..LiquidationRecord::default()
+    });
+
+    Ok(())
+}
+
+/// Backstop for perp liquidations when no liquidator steps in
+///
+/// Once a user has been in liquidation for the market's amm liquidation grace period, anyone can
+/// close part of the user's position against the amm. The fill is bounded by the amm's available
+/// liquidity and the oracle price bands. The liquidator fee is paid to the market's fee pool and the
+/// insurance fund fee is charged as in liquidate_perp
+pub fn liquidate_perp_with_amm(
+    market_index: u16,
+    user: &mut User,
+    user_key: &Pubkey,
+    user_stats: &mut UserStats,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    slot: u64,
+    now: i64,
+    state: &State,
+) -> DriftResult {
+    let liquidation_margin_buffer_ratio = state.liquidation_margin_buffer_ratio;
+    let initial_pct_to_liquidate = state.initial_pct_to_liquidate as u128;
+    let liquidation_duration = state.liquidation_duration as u128;
+
+    validate!(
+        !user.is_bankrupt(),
+        ErrorCode::UserBankrupt,
+        "user bankrupt",
+    )?;
+
+    validate!(
+        user.is_being_liquidated(),
+        ErrorCode::AmmLiquidationUnavailable,
+        "user must be being liquidated",
+    )?;
+
+    {
+        let market = perp_market_map.get_ref(&market_index)?;
+
+        validate!(
+            !market.is_operation_paused(MarketOperation::Liquidation)?,
+            ErrorCode::MarketActionPaused,
+            "Liquidations paused for perp market {}",
+            market_index
+        )?;
+
+        validate!(
+            market.amm_liquidation_grace_period != 0,
+            ErrorCode::AmmLiquidationUnavailable,
+            "amm liquidations disabled for perp market {}",
+            market_index
+        )?;
+
+        validate!(
+            market.status != MarketStatus::Settlement,
+            ErrorCode::AmmLiquidationUnavailable,
+            "perp market {} in settlement",
+            market_index
+        )?;
+
+        let slots_being_liquidated = slot.safe_sub(user.last_active_slot)?;
+        validate!(
+            slots_being_liquidated >= market.amm_liquidation_grace_period.cast()?,
+            ErrorCode::AmmLiquidationUnavailable,
+            "user in liquidation for {} slots, amm liquidation grace period is {} slots",
+            slots_being_liquidated,
+            market.amm_liquidation_grace_period
+        )?;
+    }
+
+    let funding_paused = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        is_continuous_funding_paused(&market, oracle_map, state.funding_paused()?)?
+    };
+
+    settle_funding_payment(
+        user,
+        user_key,
+        perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+        now,
+        funding_paused,
+    )?;
+
+    let position_index = get_position_index(&user.perp_positions, market_index).map_err(|e| {
+        msg!(
+            "User does not have a position for perp market {}",
+            market_index
+        );
+        e
+    })?;
+
+    let liquidation_id = user.enter_liquidation(slot)?;
+
+    let (canceled_order_ids, oracle_price, lp_shares) = prepare_perp_position_for_liquidation(
+        market_index,
+        position_index,
+        user,
+        user_key,
+        None,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        slot,
+        now,
+        state,
+    )?;
+
+    let (margin_requirement, total_collateral, margin_requirement_plus_buffer, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            Some(liquidation_margin_buffer_ratio as u128),
+        )?;
+
+    if total_collateral >= margin_requirement_plus_buffer.cast()? {
+        user.exit_liquidation();
+        return Ok(());
+    }
+
+    if user.perp_positions[position_index].base_asset_amount == 0 {
+        msg!("User has no base asset amount");
+        return Ok(());
+    }
+
+    let margin_shortage =
+        calculate_margin_shortage(margin_requirement_plus_buffer, total_collateral)?;
+
+    let (liquidation_fee, if_liquidation_fee) = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        (market.liquidator_fee, market.if_liquidation_fee)
+    };
+
+    let (base_asset_amount_to_cover_margin_shortage, max_base_asset_amount) =
+        calculate_perp_base_asset_amounts_to_liquidate(
+            user,
+            position_index,
+            margin_shortage,
+            liquidation_fee,
+            oracle_price,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            slot,
+            liquidation_margin_buffer_ratio,
+            initial_pct_to_liquidate,
+            liquidation_duration,
+        )?;
+
+    if max_base_asset_amount == 0 {
+        return Ok(());
+    }
+
+    let user_position_direction_to_close =
+        user.perp_positions[position_index].get_direction_to_close();
+
+    let base_asset_amount = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        let amm_available_liquidity =
+            calculate_amm_available_liquidity(&market.amm, &user_position_direction_to_close)?;
+        let base_asset_amount = standardize_base_asset_amount(
+            max_base_asset_amount.min(amm_available_liquidity),
+            market.amm.order_step_size,
+        )?;
+
+        if base_asset_amount < market.amm.min_order_size {
+            0
+        } else {
+            base_asset_amount
+        }
+    };
+
+    if base_asset_amount == 0 {
+        msg!("amm has no liquidity available to liquidate user");
+        return Ok(());
+    }
+
+    // the amm earns the liquidator fee in place of a liquidator
+    let total_fee_paid_before = user_stats.fees.total_fee_paid;
+
+    let (user_order_id, quote_asset_amount, fill_record_id) = fill_perp_position_with_amm(
+        market_index,
+        position_index,
+        base_asset_amount,
+        None,
+        &FeeStructure::amm_liquidation(liquidation_fee),
+        user,
+        user_key,
+        user_stats,
+        perp_market_map,
+        oracle_map,
+        oracle_price,
+        slot,
+        now,
+        state,
+    )?;
+
+    let if_fee = {
+        let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+        let if_fee = quote_asset_amount
+            .cast::<u128>()?
+            .safe_mul(if_liquidation_fee.cast()?)?
+            .safe_div(LIQUIDATION_FEE_PRECISION_U128)?
+            .cast::<u64>()?;
+
+        update_quote_asset_and_break_even_amount(
+            &mut user.perp_positions[position_index],
+            &mut market,
+            -if_fee.cast::<i64>()?,
+        )?;
+
+        market.amm.total_liquidation_fee =
+            market.amm.total_liquidation_fee.safe_add(if_fee.cast()?)?;
+
+        if_fee
+    };
+
+    let liquidator_fee = user_stats
+        .fees
+        .total_fee_paid
+        .safe_sub(total_fee_paid_before)?;
+
+    let margin_freed = calculate_margin_freed(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        liquidation_margin_buffer_ratio,
+        margin_shortage,
+    )?;
+    user.increment_margin_freed(margin_freed)?;
+
+    if base_asset_amount >= base_asset_amount_to_cover_margin_shortage {
+        user.exit_liquidation();
+    } else if is_user_bankrupt(user) {
+        user.enter_bankruptcy();
+    }
+
+    let user_position_delta = get_position_delta_for_fill(
+        base_asset_amount,
+        quote_asset_amount,
+        user_position_direction_to_close,
+    )?;
+
+    emit!(LiquidationRecord {
+        ts: now,
+        liquidation_id,
+        liquidation_type: LiquidationType::LiquidatePerpWithAmm,
+        user: *user_key,
+        liquidator: Pubkey::default(),
+        margin_requirement,
+        total_collateral,
+        bankrupt: user.is_bankrupt(),
+        canceled_order_ids,
+        margin_freed,
+        liquidate_perp: LiquidatePerpRecord {
+            market_index,
+            oracle_price,
+            base_asset_amount: user_position_delta.base_asset_amount,
+            quote_asset_amount: user_position_delta.quote_asset_amount,
+            lp_shares,
+            user_order_id,
+            liquidator_order_id: 0,
+            fill_record_id,
+            liquidator_fee,
+            if_fee,
+        },
+        ..LiquidationRecord::default()
+    });
+
+    Ok(())
+}
+
+/// Closes base_asset_amount of the user's perp position with a reduce only market order filled by
+/// the amm, so lps take their share and the fee and surplus land in the market's fee accounting.
+/// A fill price fills at that price rather than along the amm's curve. Returns the order id, the
+/// quote asset amount filled and the fill record id
+fn fill_perp_position_with_amm(
+    market_index: u16,
+    position_index: usize,
+    base_asset_amount: u64,
+    fill_price: Option<u64>,
+    fee_structure: &FeeStructure,
+    user: &mut User,
+    user_key: &Pubkey,
+    user_stats: &mut UserStats,
+    perp_market_map: &PerpMarketMap,
+    oracle_map: &mut OracleMap,
+    oracle_price: i64,
+    slot: u64,
+    now: i64,
+    state: &State,
+) -> DriftResult<(u32, u64, u64)> {
+    let existing_position_direction = user.perp_positions[position_index].get_direction();
+    let direction_to_close = user.perp_positions[position_index].get_direction_to_close();
+
+    let order_index = user
+        .orders
+        .iter()
+        .position(|order| order.status == OrderStatus::Init)
+        .ok_or(ErrorCode::MaxNumberOfOrders)?;
+    let order_id = get_then_update_id!(user, next_order_id);
+    let order = Order {
+        slot,
+        base_asset_amount,
+        order_id,
+        market_index,
+        status: OrderStatus::Open,
+        order_type: OrderType::Market,
+        market_type: MarketType::Perp,
+        direction: direction_to_close,
+        existing_position_direction,
+        reduce_only: true,
+        ..Order::default()
+    };
+
+    user.increment_open_orders(false);
+    user.orders[order_index] = order;
+    user.perp_positions[position_index].open_orders += 1;
+    increase_open_bids_and_asks(
+        &mut user.perp_positions[position_index],
+        &direction_to_close,
+        base_asset_amount,
+    )?;
+
+    emit!(OrderRecord {
+        ts: now,
+        user: *user_key,
+        order
+    });
+
+    let mut market = perp_market_map.get_ref_mut(&market_index)?;
+    let reserve_price_before = market.amm.reserve_price()?;
+
+    let (base_asset_amount_filled, quote_asset_amount) = orders::fulfill_perp_order_with_amm(
+        user,
+        user_stats,
+        order_index,
+        &mut market,
+        oracle_map,
+        reserve_price_before,
+        now,
+        slot,
+        Some(oracle_price),
+        user_key,
+        &Pubkey::default(),
+        &mut None,
+        &mut None,
+        &mut None,
+        &mut None,
+        fee_structure,
+        Some(base_asset_amount),
+        fill_price,
+        AMMLiquiditySplit::Shared,
+    )?;
+
+    validate!(
+        base_asset_amount_filled == base_asset_amount,
+        ErrorCode::InvalidAmmForFillDetected,
+        "amm filled {} of {} base asset amount",
+        base_asset_amount_filled,
+        base_asset_amount
+    )?;
+
+    validate_fill_price_within_price_bands(
+        calculate_fill_price(quote_asset_amount, base_asset_amount, BASE_PRECISION_U64)?,
+        direction_to_close,
+        oracle_price,
+        market
+            .amm
+            .historical_oracle_data
+            .last_oracle_price_twap_5min,
+        market.margin_ratio_initial,
+        state
+            .oracle_guard_rails
+            .max_oracle_twap_5min_percent_divergence(),
+    )?;
+
+    Ok((
+        order_id,
+        quote_asset_amount,
+        market.next_fill_record_id.safe_sub(1)?,
+    ))
+}
+
+/// Cancels the user's orders, updates the amm and burns the user's lp shares ahead of liquidating
+/// its perp position. Returns the canceled order ids, the oracle price and the lp shares burned
+fn prepare_perp_position_for_liquidation(
+    market_index: u16,
+    position_index: usize,
+    user: &mut User,
+    user_key: &Pubkey,
+    liquidator_key: Option<&Pubkey>,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    slot: u64,
+    now: i64,
+    state: &State,
+) -> DriftResult<(Vec<u32>, i64, u64)> {
+    let canceled_order_ids = orders::cancel_orders(
+        user,
+        user_key,
+        liquidator_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+        OrderActionExplanation::Liquidation,
+        None,
+        None,
+        None,
+    )?;
+
+    let mut market = perp_market_map.get_ref_mut(&market_index)?;
+    let oracle_price_data = oracle_map.get_price_data(&market.amm.oracle)?;
+
+    update_amm_and_check_validity(
+        &mut market,
+        oracle_price_data,
+        state,
+        now,
+        slot,
+        Some(DriftAction::Liquidate),
+    )?;
+
+    let oracle_price = if market.status == MarketStatus::Settlement {
+        market.expiry_price
+    } else {
+        oracle_price_data.price
+    };
+
+    drop(market);
+
+    // burning lp shares = removing open bids/asks
+    let lp_shares = user.perp_positions[position_index].lp_shares;
+    if lp_shares > 0 {
+        let (position_delta, pnl) = burn_lp_shares(
+            &mut user.perp_positions[position_index],
+            perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+            lp_shares,
+            oracle_price,
+        )?;
+
+        // emit LP record for shares removed
+        emit_stack::<_, { LPRecord::SIZE }>(LPRecord {
+            ts: now,
+            action: LPAction::RemoveLiquidity,
+            user: *user_key,
+            n_shares: lp_shares,
+            market_index,
+            delta_base_asset_amount: position_delta.base_asset_amount,
+            delta_quote_asset_amount: position_delta.quote_asset_amount,
+            pnl,
+            ..LPRecord::default()
+        })?;
+    }
+
+    Ok((canceled_order_ids, oracle_price, lp_shares))
+}
+
+/// Calculates the base asset amount that covers the user's margin shortage and the most that can
+/// be liquidated given how long the user has been in liquidation. The latter is 0 when nothing
+/// can be liquidated yet
+fn calculate_perp_base_asset_amounts_to_liquidate(
+    user: &User,
+    position_index: usize,
+    margin_shortage: u128,
+    liquidation_fee: u32,
+    oracle_price: i64,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    slot: u64,
+    liquidation_margin_buffer_ratio: u32,
+    initial_pct_to_liquidate: u128,
+    liquidation_duration: u128,
+) -> DriftResult<(u64, u64)> {
+    let user_position = &user.perp_positions[position_index];
+    let user_base_asset_amount = user_position.base_asset_amount.unsigned_abs();
+
+    let market = perp_market_map.get_ref(&user_position.market_index)?;
+    let margin_ratio = market.get_margin_ratio(
+        user_position.worst_case_base_asset_amount()?.unsigned_abs(),
+        MarginRequirementType::Maintenance,
+    )?;
+    let margin_ratio_with_buffer = margin_ratio.safe_add(liquidation_margin_buffer_ratio)?;
+
+    let quote_spot_market = spot_market_map.get_ref(&market.quote_spot_market_index)?;
+    let quote_oracle_price = oracle_map.get_price_data(&quote_spot_market.oracle)?.price;
+    let base_asset_amount_to_cover_margin_shortage = standardize_base_asset_amount_ceil(
+        calculate_base_asset_amount_to_cover_margin_shortage(
+            margin_shortage,
+            margin_ratio_with_buffer,
+            liquidation_fee,
+            market.if_liquidation_fee,
+            oracle_price,
+            quote_oracle_price,
+        )?,
+        market.amm.order_step_size,
+    )?;
+    drop(market);
+    drop(quote_spot_market);
+
+    let max_pct_allowed = calculate_max_pct_to_liquidate(
+        user,
+        margin_shortage,
+        slot,
+        initial_pct_to_liquidate,
+        liquidation_duration,
+    )?;
+    let max_base_asset_amount_allowed_to_be_transferred =
+        base_asset_amount_to_cover_margin_shortage
+            .cast::<u128>()?
+            .saturating_mul(max_pct_allowed)
+            .safe_div(LIQUIDATION_PCT_PRECISION)?
+            .cast::<u64>()?;
+
+    if max_base_asset_amount_allowed_to_be_transferred == 0 {
+        msg!("max_base_asset_amount_allowed_to_be_transferred == 0");
+        return Ok((base_asset_amount_to_cover_margin_shortage, 0));
+    }
+
+    let base_asset_value =
+        calculate_base_asset_value_with_oracle_price(user_base_asset_amount.cast()?, oracle_price)?
+            .cast::<u64>()?;
+
+    // if position is less than $10, it can all be liquidated
+    let min_base_asset_amount = if base_asset_value > 10 * QUOTE_PRECISION_U64 {
+        0_u64
+    } else {
+        user_base_asset_amount
+    };
+
+    Ok((
+        base_asset_amount_to_cover_margin_shortage,
+        user_base_asset_amount
+            .min(max_base_asset_amount_allowed_to_be_transferred.max(min_base_asset_amount)),
+    ))
 }
 
 pub fn liquidate_spot(
@@ -657,6 +1366,24 @@ pub fn liquidate_spot(
         "liquidator bankrupt",
     )?;
 
+    validate!(
+        !spot_market_map
+            .get_ref(&asset_market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for spot market {}",
+        asset_market_index
+    )?;
+
+    validate!(
+        !spot_market_map
+            .get_ref(&liability_market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for spot market {}",
+        liability_market_index
+    )?;
+
     // validate user and liquidator have spot balances
     user.get_spot_position(asset_market_index).map_err(|_| {
         msg!(
@@ -1003,6 +1730,9 @@ pub fn liquidate_spot(
         )?;
 
         update_revenue_pool_balances(if_fee, &SpotBalanceType::Deposit, &mut liability_market)?;
+        liability_market
+            .protocol_revenue
+            .record_revenue(ProtocolRevenueSource::LiquidationFee, if_fee)?;
 
         update_spot_balances_and_cumulative_deposits(
             liability_transfer,
@@ -1104,6 +1834,7 @@ pub fn liquidate_borrow_for_perp_pnl(
     liquidation_margin_buffer_ratio: u32,
     initial_pct_to_liquidate: u128,
     liquidation_duration: u128,
+    exchange_funding_paused: bool,
 ) -> DriftResult {
     // liquidator takes over a user borrow in exchange for that user's positive perpetual pnl
     // can only be done once a user's perpetual position size is 0
@@ -1121,6 +1852,24 @@ pub fn liquidate_borrow_for_perp_pnl(
         "liquidator bankrupt",
     )?;
 
+    validate!(
+        !perp_market_map
+            .get_ref(&perp_market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for perp market {}",
+        perp_market_index
+    )?;
+
+    validate!(
+        !spot_market_map
+            .get_ref(&liability_market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for spot market {}",
+        liability_market_index
+    )?;
+
     user.get_perp_position(perp_market_index).map_err(|e| {
         msg!(
             "User does not have a position for perp market {}",
@@ -1152,11 +1901,17 @@ pub fn liquidate_borrow_for_perp_pnl(
             e
         })?;
 
+    let funding_paused = {
+        let market = perp_market_map.get_ref(&perp_market_index)?;
+        is_continuous_funding_paused(&market, oracle_map, exchange_funding_paused)?
+    };
+
     settle_funding_payment(
         user,
         user_key,
         perp_market_map.get_ref_mut(&perp_market_index)?.deref_mut(),
         now,
+        funding_paused,
     )?;
 
     settle_funding_payment(
@@ -1164,6 +1919,7 @@ pub fn liquidate_borrow_for_perp_pnl(
         liquidator_key,
         perp_market_map.get_ref_mut(&perp_market_index)?.deref_mut(),
         now,
+        funding_paused,
     )?;
 
     let (pnl, quote_price, quote_decimals, pnl_asset_weight, pnl_liquidation_multiplier) = {
@@ -1567,6 +2323,7 @@ pub fn liquidate_perp_pnl_for_deposit(
     liquidation_margin_buffer_ratio: u32,
     initial_pct_to_liquidate: u128,
     liquidation_duration: u128,
+    exchange_funding_paused: bool,
 ) -> DriftResult {
     // liquidator takes over remaining negative perpetual pnl in exchange for a user deposit
     // can only be done once the perpetual position's size is 0
@@ -1585,6 +2342,24 @@ pub fn liquidate_perp_pnl_for_deposit(
         "liquidator bankrupt",
     )?;
 
+    validate!(
+        !perp_market_map
+            .get_ref(&perp_market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for perp market {}",
+        perp_market_index
+    )?;
+
+    validate!(
+        !spot_market_map
+            .get_ref(&asset_market_index)?
+            .is_operation_paused(MarketOperation::Liquidation)?,
+        ErrorCode::MarketActionPaused,
+        "Liquidations paused for spot market {}",
+        asset_market_index
+    )?;
+
     user.get_perp_position(perp_market_index).map_err(|e| {
         msg!(
             "User does not have a position for perp market {}",
@@ -1615,11 +2390,17 @@ pub fn liquidate_perp_pnl_for_deposit(
             e
         })?;
 
+    let funding_paused = {
+        let market = perp_market_map.get_ref(&perp_market_index)?;
+        is_continuous_funding_paused(&market, oracle_map, exchange_funding_paused)?
+    };
+
     settle_funding_payment(
         user,
         user_key,
         perp_market_map.get_ref_mut(&perp_market_index)?.deref_mut(),
         now,
+        funding_paused,
     )?;
 
     settle_funding_payment(
@@ -1627,6 +2408,7 @@ pub fn liquidate_perp_pnl_for_deposit(
         liquidator_key,
         perp_market_map.get_ref_mut(&perp_market_index)?.deref_mut(),
         now,
+        funding_paused,
     )?;
 
     let (
@@ -2049,7 +2831,10 @@ pub fn resolve_perp_bankruptcy(
     oracle_map: &mut OracleMap,
     now: i64,
     insurance_fund_vault_balance: u64,
-) -> DriftResult<u64> {
+    perp_insurance_fund_vault_balance: u64,
+    counterparties: &UserMap,
+    state: &State,
+) -> DriftResult<(u64, u64)> {
     if !user.is_bankrupt() && is_user_bankrupt(user) {
         user.enter_bankruptcy();
     }
@@ -2101,6 +2886,35 @@ pub fn resolve_perp_bankruptcy(
             None,
         )?;
 
+    // perp market's dedicated insurance fund draw attempt here (before the shared insurance fund)
+    // subtract 1 from available perp_insurance_fund_vault_balance so deposits in its vault always remains >= 1
+
+    let perp_if_payment = {
+        let perp_if_payment = loss
+            .unsigned_abs()
+            .min(perp_insurance_fund_vault_balance.saturating_sub(1).cast()?);
+
+        if perp_if_payment > 0 {
+            // move perp if payment to pnl pool
+            let mut perp_market = perp_market_map.get_ref_mut(&market_index)?;
+            let spot_market = &mut spot_market_map.get_ref_mut(&QUOTE_SPOT_MARKET_INDEX)?;
+            let oracle_price_data = oracle_map.get_price_data(&spot_market.oracle)?;
+            update_spot_market_cumulative_interest(spot_market, Some(oracle_price_data), now)?;
+
+            update_spot_balances(
+                perp_if_payment,
+                &SpotBalanceType::Deposit,
+                spot_market,
+                &mut perp_market.pnl_pool,
+                false,
+            )?;
+        }
+
+        perp_if_payment
+    };
+
+    let loss_after_perp_if_payment = loss.safe_add(perp_if_payment.cast::<i128>()?)?;
+
     // spot market's insurance fund draw attempt here (before social loss)
     // subtract 1 from available insurance_fund_vault_balance so deposits in insurance vault always remains >= 1
 
@@ -2112,7 +2926,7 @@ pub fn resolve_perp_bankruptcy(
             .safe_sub(perp_market.insurance_claim.quote_settled_insurance)?
             .cast::<u128>()?;
 
-        let if_payment = loss
+        let if_payment = loss_after_perp_if_payment
             .unsigned_abs()
             .min(insurance_fund_vault_balance.saturating_sub(1).cast()?)
             .min(max_insurance_withdraw);
@@ -2138,7 +2952,7 @@ pub fn resolve_perp_bankruptcy(
         if_payment
     };
 
-    let losses_remaining: i128 = loss.safe_add(if_payment.cast::<i128>()?)?;
+    let losses_remaining: i128 = loss_after_perp_if_payment.safe_add(if_payment.cast::<i128>()?)?;
     validate!(
         losses_remaining <= 0,
         ErrorCode::InvalidPerpPositionToLiquidate,
@@ -2174,7 +2988,29 @@ pub fn resolve_perp_bankruptcy(
         )?;
     }
 
-    let loss_to_socialize = losses_remaining.safe_add(fee_pool_payment.cast::<i128>()?)?;
+    let loss_to_deleverage = losses_remaining.safe_add(fee_pool_payment.cast::<i128>()?)?;
+
+    let auto_deleverage_payment = if loss_to_deleverage < 0
+        && state.is_auto_deleverage_enabled(perp_market_map.get_ref(&market_index)?.contract_tier)
+    {
+        auto_deleverage_counterparties(
+            market_index,
+            loss_to_deleverage.unsigned_abs(),
+            user_key,
+            liquidator,
+            liquidator_key,
+            counterparties,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            state,
+        )?
+    } else {
+        0
+    };
+
+    let loss_to_socialize = loss_to_deleverage.safe_add(auto_deleverage_payment.cast::<i128>()?)?;
     validate!(
         loss_to_socialize <= 0,
         ErrorCode::InvalidPerpPositionToLiquidate,
@@ -2243,11 +3079,273 @@ pub fn resolve_perp_bankruptcy(
             clawback_user: None,
             clawback_user_payment: None,
             cumulative_funding_rate_delta,
+            auto_deleverage_payment,
+            perp_if_payment,
         },
         ..LiquidationRecord::default()
     });
 
-    if_payment.cast()
+    Ok((perp_if_payment.cast()?, if_payment.cast()?))
+}
+
+/// Covers a bankrupt user's remaining loss by closing the most profitable, highest leverage positions
+/// among the counterparties, ranked by pnl percentage times leverage. The liquidator chooses which
+/// counterparties are passed in, so the ranking only orders that set. Counterparties scoring below
+/// the state's auto deleverage score threshold are skipped
+///
+/// Each position is settled directly at the bankruptcy price without touching the amm: the
+/// liquidator takes over the base closed at the oracle price and the counterparty gives up the pnl
+/// on it. Returns the loss covered
+fn auto_deleverage_counterparties(
+    market_index: u16,
+    loss_to_cover: u128,
+    bankrupt_user_key: &Pubkey,
+    liquidator: &mut User,
+    liquidator_key: &Pubkey,
+    counterparties: &UserMap,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    state: &State,
+) -> DriftResult<u128> {
+    let oracle_price = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        oracle_map.get_price_data(&market.amm.oracle)?.price
+    };
+
+    let funding_paused = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        is_continuous_funding_paused(&market, oracle_map, state.funding_paused()?)?
+    };
+
+    let mut ranked_counterparties = Vec::with_capacity(counterparties.0.len());
+    for counterparty_key in counterparties.0.keys() {
+        validate!(
+            counterparty_key != bankrupt_user_key && counterparty_key != liquidator_key,
+            ErrorCode::InvalidUserAccount,
+            "bankrupt user and liquidator cant be auto deleveraged"
+        )?;
+
+        let mut counterparty = counterparties.get_ref_mut(counterparty_key)?;
+
+        if counterparty.is_being_liquidated() || counterparty.is_bankrupt() {
+            continue;
+        }
+
+        let position_index = match get_position_index(&counterparty.perp_positions, market_index) {
+            Ok(position_index) => position_index,
+            Err(_) => continue,
+        };
+
+        if counterparty.perp_positions[position_index].base_asset_amount == 0
+            || counterparty.perp_positions[position_index].is_lp()
+        {
+            continue;
+        }
+
+        settle_funding_payment(
+            &mut counterparty,
+            counterparty_key,
+            perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+            now,
+            funding_paused,
+        )?;
+
+        let (base_asset_value, unrealized_pnl) =
+            calculate_base_asset_value_and_pnl_with_oracle_price(
+                &counterparty.perp_positions[position_index],
+                oracle_price,
+            )?;
+
+        if unrealized_pnl <= 0 {
+            continue;
+        }
+
+        let (_, total_collateral, _, _) = calculate_margin_requirement_and_total_collateral(
+            &counterparty,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            None,
+        )?;
+
+        let score = calculate_auto_deleverage_score(
+            unrealized_pnl,
+            counterparty.perp_positions[position_index].quote_entry_amount,
+            base_asset_value,
+            total_collateral,
+        )?;
+
+        if score < state.auto_deleverage_score_threshold.cast::<u128>()? {
+            msg!(
+                "user {} auto deleverage score {} below threshold {}",
+                counterparty_key,
+                score,
+                state.auto_deleverage_score_threshold
+            );
+            continue;
+        }
+
+        ranked_counterparties.push((
+            score,
+            *counterparty_key,
+            position_index,
+            unrealized_pnl.unsigned_abs(),
+        ));
+    }
+
+    ranked_counterparties.sort_by(|a, b| b.0.cmp(&a.0));
+
+    if ranked_counterparties.is_empty() {
+        return Ok(0);
+    }
+
+    settle_funding_payment(
+        liquidator,
+        liquidator_key,
+        perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+        now,
+        funding_paused,
+    )?;
+
+    let mut loss_covered = 0_u128;
+    for (_, counterparty_key, position_index, unrealized_pnl) in ranked_counterparties {
+        if loss_covered >= loss_to_cover {
+            break;
+        }
+
+        let loss_remaining = loss_to_cover.safe_sub(loss_covered)?;
+
+        let mut counterparty = counterparties.get_ref_mut(&counterparty_key)?;
+
+        let position_base_asset_amount = counterparty.perp_positions[position_index]
+            .base_asset_amount
+            .unsigned_abs();
+        let existing_direction = counterparty.perp_positions[position_index].get_direction();
+        let direction_to_close =
+            counterparty.perp_positions[position_index].get_direction_to_close();
+
+        // close enough of the position for its pnl to cover the remaining loss
+        let base_asset_amount = {
+            let market = perp_market_map.get_ref(&market_index)?;
+            standardize_base_asset_amount_ceil(
+                position_base_asset_amount
+                    .cast::<u128>()?
+                    .safe_mul(loss_remaining)?
+                    .safe_div_ceil(unrealized_pnl)?
+                    .min(position_base_asset_amount.cast()?)
+                    .cast()?,
+                market.amm.order_step_size,
+            )?
+            .min(position_base_asset_amount)
+        };
+
+        if base_asset_amount == 0 {
+            continue;
+        }
+
+        let quote_asset_amount =
+            calculate_base_asset_value_with_oracle_price(base_asset_amount.cast()?, oracle_price)?
+                .cast::<u64>()?;
+
+        let counterparty_position_delta =
+            get_position_delta_for_fill(base_asset_amount, quote_asset_amount, direction_to_close)?;
+
+        let liquidator_position_delta =
+            get_position_delta_for_fill(base_asset_amount, quote_asset_amount, existing_direction)?;
+
+        let (pnl, counterparty_loss_covered) = {
+            let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+            let counterparty_position = &mut counterparty.perp_positions[position_index];
+            let pnl = update_position_and_market(
+                counterparty_position,
+                &mut market,
+                &counterparty_position_delta,
+            )?;
+
+            // giving up the pnl on the base closed exits the counterparty at the bankruptcy price
+            let counterparty_loss_covered = pnl.max(0).cast::<u128>()?.min(loss_remaining);
+            update_quote_asset_and_break_even_amount(
+                counterparty_position,
+                &mut market,
+                -counterparty_loss_covered.cast::<i64>()?,
+            )?;
+
+            let liquidator_position = liquidator.force_get_perp_position_mut(market_index)?;
+            update_position_and_market(
+                liquidator_position,
+                &mut market,
+                &liquidator_position_delta,
+            )?;
+
+            validate!(
+                is_multiple_of_step_size(
+                    liquidator_position.base_asset_amount.unsigned_abs(),
+                    market.amm.order_step_size
+                )?,
+                ErrorCode::InvalidPerpPosition,
+                "base asset amount {} step size {}",
+                liquidator_position.base_asset_amount,
+                market.amm.order_step_size
+            )?;
+
+            (pnl, counterparty_loss_covered)
+        };
+
+        update_user_health(
+            &mut counterparty,
+            &counterparty_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            state,
+            now,
+        )?;
+
+        loss_covered = loss_covered.safe_add(counterparty_loss_covered)?;
+
+        emit!(AutoDeleverageRecord {
+            ts: now,
+            user: counterparty_key,
+            bankrupt_user: *bankrupt_user_key,
+            market_index,
+            oracle_price,
+            bankruptcy_price: calculate_auto_deleverage_bankruptcy_price(
+                oracle_price,
+                base_asset_amount,
+                counterparty_loss_covered,
+                direction_to_close,
+            )?,
+            base_asset_amount: counterparty_position_delta.base_asset_amount,
+            quote_asset_amount,
+            pnl,
+            loss_covered: counterparty_loss_covered.cast()?,
+        });
+    }
+
+    let liquidator_meets_initial_margin_requirement =
+        meets_initial_margin_requirement(liquidator, perp_market_map, spot_market_map, oracle_map)?;
+
+    validate!(
+        liquidator_meets_initial_margin_requirement,
+        ErrorCode::InsufficientCollateral,
+        "Liquidator doesnt have enough collateral to take over auto deleveraged positions"
+    )?;
+
+    update_user_health(
+        liquidator,
+        liquidator_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        state,
+        now,
+    )?;
+
+    Ok(loss_covered)
 }
 
 pub fn resolve_spot_bankruptcy(
@@ -2416,3 +3514,191 @@ pub fn calculate_margin_freed(
         .saturating_sub(new_margin_shortage)
         .cast::<u64>()
 }
+
+/// Moves quote deposits from the other members of a user's shared collateral group into the user
+/// to cover its maintenance margin shortage, so the group's collateral backs the user before it is
+/// liquidated or its losses are socialized. A bankrupt user that receives a deposit goes back to
+/// being liquidated. Returns whether the user meets the maintenance margin requirement afterwards
+pub fn transfer_shared_collateral_to_user(
+    user: &mut User,
+    user_key: &Pubkey,
+    shared_collateral_group: &UserMap,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+) -> DriftResult<bool> {
+    if shared_collateral_group.0.is_empty() {
+        return Ok(false);
+    }
+
+    let (margin_requirement, total_collateral, _, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            None,
+        )?;
+
+    if total_collateral >= margin_requirement.cast()? {
+        return Ok(true);
+    }
+
+    let mut margin_shortage = calculate_margin_shortage(margin_requirement, total_collateral)?;
+
+    let (quote_oracle_price, quote_decimals, quote_precision) = {
+        let quote_spot_market = spot_market_map.get_ref(&QUOTE_SPOT_MARKET_INDEX)?;
+        (
+            oracle_map.get_price_data(&quote_spot_market.oracle)?.price,
+            quote_spot_market.decimals,
+            quote_spot_market.get_precision(),
+        )
+    };
+
+    for member_key in shared_collateral_group.0.keys() {
+        if margin_shortage == 0 {
+            break;
+        }
+
+        let mut member = shared_collateral_group.get_ref_mut(member_key)?;
+
+        if member.is_being_liquidated() {
+            continue;
+        }
+
+        let member_token_amount = {
+            let quote_spot_market = spot_market_map.get_ref(&QUOTE_SPOT_MARKET_INDEX)?;
+            match member.get_spot_position(QUOTE_SPOT_MARKET_INDEX) {
+                Ok(spot_position) if spot_position.balance_type == SpotBalanceType::Deposit => {
+                    spot_position.get_token_amount(&quote_spot_market)?
+                }
+                _ => 0,
+            }
+        };
+
+        if member_token_amount == 0 {
+            continue;
+        }
+
+        let (member_margin_requirement, member_total_collateral, _, _) =
+            calculate_margin_requirement_and_total_collateral(
+                &member,
+                perp_market_map,
+                MarginRequirementType::Maintenance,
+                spot_market_map,
+                oracle_map,
+                None,
+            )?;
+
+        let member_free_collateral = member_total_collateral
+            .safe_sub(member_margin_requirement.cast()?)?
+            .max(0)
+            .unsigned_abs();
+
+        let transfer_amount = calculate_shared_collateral_transfer(
+            margin_shortage,
+            member_free_collateral,
+            member_token_amount,
+            quote_decimals,
+            quote_oracle_price,
+        )?;
+
+        if transfer_amount == 0 {
+            continue;
+        }
+
+        let quote_spot_market = &mut spot_market_map.get_ref_mut(&QUOTE_SPOT_MARKET_INDEX)?;
+
+        member.increment_total_withdraws(
+            transfer_amount.cast()?,
+            quote_oracle_price,
+            quote_precision.cast()?,
+        )?;
+
+        update_spot_balances_and_cumulative_deposits(
+            transfer_amount,
+            &SpotBalanceType::Borrow,
+            quote_spot_market,
+            member.get_spot_position_mut(QUOTE_SPOT_MARKET_INDEX)?,
+            false,
+            None,
+        )?;
+
+        let deposit_record_id = get_then_update_id!(quote_spot_market, next_deposit_record_id);
+        emit!(DepositRecord {
+            ts: now,
+            deposit_record_id,
+            user_authority: member.authority,
+            user: *member_key,
+            direction: DepositDirection::Withdraw,
+            amount: transfer_amount.cast()?,
+            oracle_price: quote_oracle_price,
+            market_index: QUOTE_SPOT_MARKET_INDEX,
+            market_deposit_balance: quote_spot_market.deposit_balance,
+            market_withdraw_balance: quote_spot_market.borrow_balance,
+            market_cumulative_deposit_interest: quote_spot_market.cumulative_deposit_interest,
+            market_cumulative_borrow_interest: quote_spot_market.cumulative_borrow_interest,
+            total_deposits_after: member.total_deposits,
+            total_withdraws_after: member.total_withdraws,
+            explanation: DepositExplanation::Transfer,
+            transfer_user: Some(*user_key),
+        });
+
+        user.increment_total_deposits(
+            transfer_amount.cast()?,
+            quote_oracle_price,
+            quote_precision.cast()?,
+        )?;
+
+        update_spot_balances_and_cumulative_deposits(
+            transfer_amount,
+            &SpotBalanceType::Deposit,
+            quote_spot_market,
+            user.force_get_spot_position_mut(QUOTE_SPOT_MARKET_INDEX)?,
+            false,
+            None,
+        )?;
+
+        let deposit_record_id = get_then_update_id!(quote_spot_market, next_deposit_record_id);
+        emit!(DepositRecord {
+            ts: now,
+            deposit_record_id,
+            user_authority: user.authority,
+            user: *user_key,
+            direction: DepositDirection::Deposit,
+            amount: transfer_amount.cast()?,
+            oracle_price: quote_oracle_price,
+            market_index: QUOTE_SPOT_MARKET_INDEX,
+            market_deposit_balance: quote_spot_market.deposit_balance,
+            market_withdraw_balance: quote_spot_market.borrow_balance,
+            market_cumulative_deposit_interest: quote_spot_market.cumulative_deposit_interest,
+            market_cumulative_borrow_interest: quote_spot_market.cumulative_borrow_interest,
+            total_deposits_after: user.total_deposits,
+            total_withdraws_after: user.total_withdraws,
+            explanation: DepositExplanation::Transfer,
+            transfer_user: Some(*member_key),
+        });
+
+        let transfer_value =
+            get_token_value(transfer_amount.cast()?, quote_decimals, quote_oracle_price)?;
+        margin_shortage = margin_shortage.saturating_sub(transfer_value.unsigned_abs());
+    }
+
+    if user.is_bankrupt() && !is_user_bankrupt(user) {
+        user.status = UserStatus::BeingLiquidated;
+    }
+
+    let (margin_requirement, total_collateral, _, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            None,
+        )?;
+
+    Ok(total_collateral >= margin_requirement.cast()?)
+}
@@ -184,6 +184,152 @@ pub mod liquidate_perp {
         assert_eq!(market_after.amm.total_liquidation_fee, QUOTE_PRECISION);
     }
 
+    #[test]
+    pub fn successful_liquidation_long_perp_with_auction_fee() {
+        let now = 0_i64;
+        let slot = 0_u64;
+        let liquidation_slot = 75_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I128,
+                base_asset_amount_with_amm: BASE_PRECISION_I128,
+                oracle: oracle_price_key,
+                historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            number_of_users_with_base: 1,
+            status: MarketStatus::Initialized,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            if_liquidation_fee: LIQUIDATION_FEE_PRECISION / 100,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let perp_market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        let mut user = User {
+            orders: get_orders(Order {
+                market_index: 0,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                direction: PositionDirection::Long,
+                base_asset_amount: BASE_PRECISION_U64,
+                slot: 0,
+                ..Order::default()
+            }),
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -150 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -150 * QUOTE_PRECISION_I64,
+                open_orders: 1,
+                open_bids: BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            status: UserStatus::BeingLiquidated,
+            next_liquidation_id: 1,
+            ..User::default()
+        };
+
+        let mut liquidator = User {
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 50 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            ..User::default()
+        };
+
+        let user_key = Pubkey::default();
+        let liquidator_key = Pubkey::default();
+
+        let mut user_stats = UserStats::default();
+        let mut liquidator_stats = UserStats::default();
+        let state = State {
+            liquidation_margin_buffer_ratio: 10,
+            initial_pct_to_liquidate: LIQUIDATION_PCT_PRECISION as u16,
+            liquidation_duration: 150,
+            liquidation_auction_duration: 150,
+            ..Default::default()
+        };
+        liquidate_perp(
+            0,
+            BASE_PRECISION_U64,
+            None,
+            &mut user,
+            &user_key,
+            &mut user_stats,
+            &mut liquidator,
+            &liquidator_key,
+            &mut liquidator_stats,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            liquidation_slot,
+            now,
+            &state,
+        )
+        .unwrap();
+
+        assert_eq!(user.perp_positions[0].base_asset_amount, 0);
+        assert_eq!(user.perp_positions[0].quote_asset_amount, -51500000);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_bids, 0);
+
+        assert_eq!(
+            liquidator.perp_positions[0].base_asset_amount,
+            BASE_PRECISION_I64
+        );
+        assert_eq!(liquidator.perp_positions[0].quote_asset_amount, -99500000);
+
+        let market_after = perp_market_map.get_ref(&0).unwrap();
+        assert_eq!(market_after.amm.total_liquidation_fee, QUOTE_PRECISION);
+    }
+
     #[test]
     pub fn successful_liquidation_short_perp() {
         let now = 0_i64;
@@ -1795,118 +1941,675 @@ pub mod liquidate_perp {
     }
 }
 
-pub mod liquidate_spot {
-    use std::ops::Deref;
+pub mod liquidate_perp_lp {
     use std::str::FromStr;
 
     use anchor_lang::Owner;
     use solana_program::pubkey::Pubkey;
 
-    use crate::controller::liquidation::liquidate_spot;
+    use crate::controller::liquidation::liquidate_perp_lp;
+    use crate::controller::lp::mint_lp_shares;
     use crate::create_anchor_account_info;
     use crate::error::ErrorCode;
     use crate::math::constants::{
-        LIQUIDATION_FEE_PRECISION, LIQUIDATION_PCT_PRECISION, MARGIN_PRECISION,
-        MARGIN_PRECISION_U128, PERCENTAGE_PRECISION, PRICE_PRECISION, PRICE_PRECISION_U64,
-        SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION,
-        SPOT_WEIGHT_PRECISION,
-    };
-    use crate::math::margin::{
-        calculate_margin_requirement_and_total_collateral, MarginRequirementType,
+        AMM_RESERVE_PRECISION, BASE_PRECISION_U64, LIQUIDATION_FEE_PRECISION,
+        LIQUIDATION_PCT_PRECISION, PEG_PRECISION, QUOTE_PRECISION_I64,
+        SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
     };
-    use crate::math::spot_balance::{get_strict_token_value, get_token_amount, get_token_value};
-    use crate::state::oracle::HistoricalOracleData;
-    use crate::state::oracle::OracleSource;
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
     use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
     use crate::state::perp_market_map::PerpMarketMap;
-    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market::SpotMarket;
     use crate::state::spot_market_map::SpotMarketMap;
-    use crate::state::user::{Order, PerpPosition, SpotPosition, User};
+    use crate::state::state::State;
+    use crate::state::user::{PerpPosition, SpotPosition, User};
     use crate::test_utils::*;
-    use crate::test_utils::{get_pyth_price, get_spot_positions};
-    use crate::{create_account_info, QUOTE_PRECISION_I64};
+    use crate::test_utils::{get_positions, get_pyth_price};
+    use crate::{create_account_info, PRICE_PRECISION_I64};
 
     #[test]
-    pub fn successful_liquidation_liability_transfer_implied_by_asset_amount() {
+    pub fn successful_liquidation() {
         let now = 0_i64;
         let slot = 0_u64;
 
-        let mut sol_oracle_price = get_pyth_price(100, 6);
-        let sol_oracle_price_key =
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
             Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
         let pyth_program = crate::ids::pyth_program::id();
         create_account_info!(
-            sol_oracle_price,
-            &sol_oracle_price_key,
+            oracle_price,
+            &oracle_price_key,
             &pyth_program,
             oracle_account_info
         );
         let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
 
-        let perp_market_map = PerpMarketMap::empty();
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                oracle: oracle_price_key,
+                concentration_coef: 1000001,
+                historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            number_of_users: 1,
+            status: MarketStatus::Initialized,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            if_liquidation_fee: LIQUIDATION_FEE_PRECISION / 100,
+            ..PerpMarket::default()
+        };
 
-        let mut usdc_market = SpotMarket {
+        let mut position = PerpPosition {
+            quote_asset_amount: -QUOTE_PRECISION_I64,
+            ..PerpPosition::default()
+        };
+        mint_lp_shares(&mut position, &mut market, 10 * BASE_PRECISION_U64).unwrap();
+
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let perp_market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
             market_index: 0,
             oracle_source: OracleSource::QuoteAsset,
             cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
             decimals: 6,
             initial_asset_weight: SPOT_WEIGHT_PRECISION,
-            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
-            deposit_balance: 200 * SPOT_BALANCE_PRECISION,
-            liquidator_fee: 0,
-            historical_oracle_data: HistoricalOracleData {
-                last_oracle_price_twap: QUOTE_PRECISION_I64,
-                last_oracle_price_twap_5min: QUOTE_PRECISION_I64,
-                ..HistoricalOracleData::default()
-            },
-            ..SpotMarket::default()
-        };
-        create_anchor_account_info!(usdc_market, SpotMarket, usdc_spot_market_account_info);
-        let mut sol_market = SpotMarket {
-            market_index: 1,
-            oracle_source: OracleSource::Pyth,
-            oracle: sol_oracle_price_key,
-            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
-            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
-            decimals: 6,
-            initial_asset_weight: 8 * SPOT_WEIGHT_PRECISION / 10,
-            maintenance_asset_weight: 9 * SPOT_WEIGHT_PRECISION / 10,
-            initial_liability_weight: 12 * SPOT_WEIGHT_PRECISION / 10,
-            maintenance_liability_weight: 11 * SPOT_WEIGHT_PRECISION / 10,
-            deposit_balance: SPOT_BALANCE_PRECISION,
-            borrow_balance: SPOT_BALANCE_PRECISION,
-            liquidator_fee: LIQUIDATION_FEE_PRECISION / 1000,
             historical_oracle_data: HistoricalOracleData {
-                last_oracle_price_twap: (sol_oracle_price.agg.price * 99 / 100),
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
                 ..HistoricalOracleData::default()
             },
             ..SpotMarket::default()
         };
-        create_anchor_account_info!(sol_market, SpotMarket, sol_spot_market_account_info);
-        let spot_market_account_infos = Vec::from([
-            &usdc_spot_market_account_info,
-            &sol_spot_market_account_info,
-        ]);
-        let spot_market_map =
-            SpotMarketMap::load_multiple(spot_market_account_infos, true).unwrap();
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
 
-        let mut spot_positions = [SpotPosition::default(); 8];
-        spot_positions[0] = SpotPosition {
-            market_index: 0,
-            balance_type: SpotBalanceType::Deposit,
-            scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
-            ..SpotPosition::default()
-        };
-        spot_positions[1] = SpotPosition {
-            market_index: 1,
-            balance_type: SpotBalanceType::Borrow,
-            scaled_balance: SPOT_BALANCE_PRECISION_U64,
-            ..SpotPosition::default()
-        };
         let mut user = User {
-            orders: [Order::default(); 32],
-            perp_positions: [PerpPosition::default(); 8],
-            spot_positions,
+            perp_positions: get_positions(position),
+            spot_positions: [SpotPosition::default(); 8],
+            ..User::default()
+        };
+
+        let mut liquidator = User::default();
+
+        let user_key = Pubkey::default();
+        let liquidator_key = Pubkey::new_unique();
+
+        let state = State {
+            liquidation_margin_buffer_ratio: 10,
+            initial_pct_to_liquidate: LIQUIDATION_PCT_PRECISION as u16,
+            liquidation_duration: 150,
+            ..Default::default()
+        };
+
+        liquidate_perp_lp(
+            0,
+            &mut user,
+            &user_key,
+            &mut liquidator,
+            &liquidator_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            slot,
+            now,
+            &state,
+        )
+        .unwrap();
+
+        assert_eq!(user.perp_positions[0].lp_shares, 0);
+        assert!(user.liquidation_margin_freed > 0);
+
+        // liquidator is paid a fee out of the user's quote
+        let liquidator_fee = liquidator.perp_positions[0].quote_asset_amount;
+        assert!(liquidator_fee > 0);
+        assert_eq!(
+            user.perp_positions[0].quote_asset_amount,
+            -QUOTE_PRECISION_I64 - liquidator_fee
+        );
+
+        let market_after = perp_market_map.get_ref(&0).unwrap();
+        assert_eq!(market_after.amm.user_lp_shares, 0);
+        assert_eq!(market_after.amm.sqrt_k, 100 * AMM_RESERVE_PRECISION);
+
+        // no lp shares left to liquidate
+        drop(market_after);
+        let result = liquidate_perp_lp(
+            0,
+            &mut user,
+            &user_key,
+            &mut liquidator,
+            &liquidator_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            slot,
+            now,
+            &state,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub fn sufficient_collateral() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                oracle: oracle_price_key,
+                ..AMM::default_test()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let perp_market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let spot_market_map = SpotMarketMap::load_multiple(vec![], true).unwrap();
+
+        let mut user = User::default();
+        let mut liquidator = User::default();
+
+        let result = liquidate_perp_lp(
+            0,
+            &mut user,
+            &Pubkey::default(),
+            &mut liquidator,
+            &Pubkey::new_unique(),
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            slot,
+            now,
+            &State::default(),
+        );
+        assert_eq!(result, Err(ErrorCode::SufficientCollateral));
+    }
+}
+
+pub mod liquidate_perp_with_amm {
+    use std::str::FromStr;
+
+    use anchor_lang::Owner;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::controller::liquidation::liquidate_perp_with_amm;
+    use crate::create_anchor_account_info;
+    use crate::error::ErrorCode;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_I64, LIQUIDATION_FEE_PRECISION,
+        LIQUIDATION_PCT_PRECISION, PEG_PRECISION, QUOTE_PRECISION_I128, QUOTE_PRECISION_I64,
+        SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::SpotMarket;
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::state::State;
+    use crate::state::user::{PerpPosition, SpotPosition, User, UserStats, UserStatus};
+    use crate::test_utils::*;
+    use crate::test_utils::{get_positions, get_pyth_price};
+    use crate::{create_account_info, PRICE_PRECISION_I64};
+
+    #[test]
+    pub fn successful_liquidation_long_perp() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                min_base_asset_reserve: 50 * AMM_RESERVE_PRECISION,
+                max_base_asset_reserve: 200 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I128,
+                base_asset_amount_with_amm: BASE_PRECISION_I128,
+                base_asset_amount_long: BASE_PRECISION_I128,
+                oracle: oracle_price_key,
+                historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            number_of_users_with_base: 1,
+            number_of_users: 1,
+            status: MarketStatus::Initialized,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            if_liquidation_fee: LIQUIDATION_FEE_PRECISION / 100,
+            amm_liquidation_grace_period: 100,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let perp_market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        let mut user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -150 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -150 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            status: UserStatus::BeingLiquidated,
+            next_liquidation_id: 1,
+            ..User::default()
+        };
+
+        let user_key = Pubkey::default();
+        let mut user_stats = UserStats::default();
+        let state = State {
+            liquidation_margin_buffer_ratio: 10,
+            initial_pct_to_liquidate: LIQUIDATION_PCT_PRECISION as u16,
+            liquidation_duration: 150,
+            ..Default::default()
+        };
+
+        // grace period hasn't passed
+        let result = liquidate_perp_with_amm(
+            0,
+            &mut user,
+            &user_key,
+            &mut user_stats,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            99,
+            now,
+            &state,
+        );
+        assert_eq!(result, Err(ErrorCode::AmmLiquidationUnavailable));
+
+        liquidate_perp_with_amm(
+            0,
+            &mut user,
+            &user_key,
+            &mut user_stats,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            100,
+            now,
+            &state,
+        )
+        .unwrap();
+
+        assert_eq!(user.perp_positions[0].base_asset_amount, 0);
+        // user receives less than the oracle value after fees
+        assert!(user.perp_positions[0].quote_asset_amount < -50 * QUOTE_PRECISION_I64);
+
+        // the fill order is cleaned up
+        assert_eq!(user.open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_asks, 0);
+        assert!(user_stats.fees.total_fee_paid > 0);
+
+        let market_after = perp_market_map.get_ref(&0).unwrap();
+        assert_eq!(market_after.amm.base_asset_amount_with_amm, 0);
+        assert!(market_after.amm.total_liquidation_fee > 0);
+        assert!(market_after.amm.total_fee_minus_distributions > 0);
+        // fee and surplus are booked to every fee counter
+        assert_eq!(
+            market_after.amm.total_fee,
+            market_after.amm.total_fee_minus_distributions
+        );
+        assert_eq!(
+            market_after.amm.net_revenue_since_last_funding,
+            market_after.amm.total_fee_minus_distributions as i64
+        );
+    }
+
+    #[test]
+    pub fn lps_take_share_of_fill() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                min_base_asset_reserve: 50 * AMM_RESERVE_PRECISION,
+                max_base_asset_reserve: 200 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                user_lp_shares: 50 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I128,
+                base_asset_amount_with_amm: BASE_PRECISION_I128,
+                base_asset_amount_long: BASE_PRECISION_I128,
+                oracle: oracle_price_key,
+                historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            number_of_users_with_base: 1,
+            number_of_users: 1,
+            status: MarketStatus::Initialized,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            if_liquidation_fee: LIQUIDATION_FEE_PRECISION / 100,
+            amm_liquidation_grace_period: 100,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let perp_market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        let mut user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -150 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -150 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            status: UserStatus::BeingLiquidated,
+            next_liquidation_id: 1,
+            ..User::default()
+        };
+
+        let user_key = Pubkey::default();
+        let mut user_stats = UserStats::default();
+        let state = State {
+            liquidation_margin_buffer_ratio: 10,
+            initial_pct_to_liquidate: LIQUIDATION_PCT_PRECISION as u16,
+            liquidation_duration: 150,
+            ..Default::default()
+        };
+
+        liquidate_perp_with_amm(
+            0,
+            &mut user,
+            &user_key,
+            &mut user_stats,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            100,
+            now,
+            &state,
+        )
+        .unwrap();
+
+        assert_eq!(user.perp_positions[0].base_asset_amount, 0);
+
+        // lps take half of the amm's side of the fill and a share of the fee
+        let market_after = perp_market_map.get_ref(&0).unwrap();
+        assert!(market_after.amm.base_asset_amount_per_lp > 0);
+        assert!(market_after.amm.quote_asset_amount_per_lp < 0);
+        assert!(market_after.amm.fee_per_lp > 0);
+        assert_eq!(
+            market_after.amm.base_asset_amount_with_unsettled_lp,
+            BASE_PRECISION_I128 / -2
+        );
+    }
+
+    #[test]
+    pub fn disabled() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                oracle: oracle_price_key,
+                ..AMM::default()
+            },
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let perp_market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let spot_market_map = SpotMarketMap::load_multiple(vec![], true).unwrap();
+
+        let mut user = User {
+            status: UserStatus::BeingLiquidated,
+            ..User::default()
+        };
+
+        let result = liquidate_perp_with_amm(
+            0,
+            &mut user,
+            &Pubkey::default(),
+            &mut UserStats::default(),
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            1000,
+            now,
+            &State::default(),
+        );
+        assert_eq!(result, Err(ErrorCode::AmmLiquidationUnavailable));
+    }
+}
+
+pub mod liquidate_spot {
+    use std::ops::Deref;
+    use std::str::FromStr;
+
+    use anchor_lang::Owner;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::controller::liquidation::liquidate_spot;
+    use crate::create_anchor_account_info;
+    use crate::error::ErrorCode;
+    use crate::math::constants::{
+        LIQUIDATION_FEE_PRECISION, LIQUIDATION_PCT_PRECISION, MARGIN_PRECISION,
+        MARGIN_PRECISION_U128, PERCENTAGE_PRECISION, PRICE_PRECISION, PRICE_PRECISION_U64,
+        SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION,
+        SPOT_WEIGHT_PRECISION,
+    };
+    use crate::math::margin::{
+        calculate_margin_requirement_and_total_collateral, MarginRequirementType,
+    };
+    use crate::math::spot_balance::{get_strict_token_value, get_token_amount, get_token_value};
+    use crate::state::oracle::HistoricalOracleData;
+    use crate::state::oracle::OracleSource;
+    use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::user::{Order, PerpPosition, SpotPosition, User};
+    use crate::test_utils::*;
+    use crate::test_utils::{get_pyth_price, get_spot_positions};
+    use crate::{create_account_info, QUOTE_PRECISION_I64};
+
+    #[test]
+    pub fn successful_liquidation_liability_transfer_implied_by_asset_amount() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut sol_oracle_price = get_pyth_price(100, 6);
+        let sol_oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            sol_oracle_price,
+            &sol_oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let perp_market_map = PerpMarketMap::empty();
+
+        let mut usdc_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            deposit_balance: 200 * SPOT_BALANCE_PRECISION,
+            liquidator_fee: 0,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: QUOTE_PRECISION_I64,
+                last_oracle_price_twap_5min: QUOTE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(usdc_market, SpotMarket, usdc_spot_market_account_info);
+        let mut sol_market = SpotMarket {
+            market_index: 1,
+            oracle_source: OracleSource::Pyth,
+            oracle: sol_oracle_price_key,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: 8 * SPOT_WEIGHT_PRECISION / 10,
+            maintenance_asset_weight: 9 * SPOT_WEIGHT_PRECISION / 10,
+            initial_liability_weight: 12 * SPOT_WEIGHT_PRECISION / 10,
+            maintenance_liability_weight: 11 * SPOT_WEIGHT_PRECISION / 10,
+            deposit_balance: SPOT_BALANCE_PRECISION,
+            borrow_balance: SPOT_BALANCE_PRECISION,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 1000,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: (sol_oracle_price.agg.price * 99 / 100),
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(sol_market, SpotMarket, sol_spot_market_account_info);
+        let spot_market_account_infos = Vec::from([
+            &usdc_spot_market_account_info,
+            &sol_spot_market_account_info,
+        ]);
+        let spot_market_map =
+            SpotMarketMap::load_multiple(spot_market_account_infos, true).unwrap();
+
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        spot_positions[1] = SpotPosition {
+            market_index: 1,
+            balance_type: SpotBalanceType::Borrow,
+            scaled_balance: SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        let mut user = User {
+            orders: [Order::default(); 32],
+            perp_positions: [PerpPosition::default(); 8],
+            spot_positions,
             ..User::default()
         };
 
@@ -3082,6 +3785,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -3238,6 +3942,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             liquidation_buffer,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -3430,6 +4135,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -3585,6 +4291,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         );
 
         assert_eq!(result, Err(ErrorCode::LiquidationDoesntSatisfyLimitPrice));
@@ -3731,6 +4438,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         );
 
         assert_eq!(result, Ok(()));
@@ -3878,6 +4586,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             liquidation_buffer,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -4034,6 +4743,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             liquidation_buffer,
             LIQUIDATION_PCT_PRECISION / 10,
             150,
+            false,
         )
         .unwrap();
 
@@ -4078,6 +4788,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             liquidation_buffer,
             LIQUIDATION_PCT_PRECISION / 10,
             150,
+            false,
         )
         .unwrap();
 
@@ -4122,6 +4833,7 @@ pub mod liquidate_borrow_for_perp_pnl {
             liquidation_buffer,
             LIQUIDATION_PCT_PRECISION / 10,
             150,
+            false,
         )
         .unwrap();
 
@@ -4301,6 +5013,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -4456,6 +5169,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             MARGIN_PRECISION as u32 / 50,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -4613,6 +5327,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -4768,6 +5483,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         );
 
         assert_eq!(result, Err(ErrorCode::LiquidationDoesntSatisfyLimitPrice));
@@ -4914,6 +5630,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         );
 
         assert_eq!(result, Ok(()));
@@ -5060,6 +5777,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             MARGIN_PRECISION as u32 / 50,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
 
@@ -5216,6 +5934,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             liquidation_buffer,
             LIQUIDATION_PCT_PRECISION / 10,
             150,
+            false,
         )
         .unwrap();
 
@@ -5260,6 +5979,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             liquidation_buffer,
             LIQUIDATION_PCT_PRECISION / 10,
             150,
+            false,
         )
         .unwrap();
 
@@ -5304,6 +6024,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             liquidation_buffer,
             LIQUIDATION_PCT_PRECISION / 10,
             150,
+            false,
         )
         .unwrap();
 
@@ -5462,6 +6183,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .is_err());
 
@@ -5504,6 +6226,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
         assert_eq!(user.perp_positions[0].quote_asset_amount, -50000000);
@@ -5527,6 +6250,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .unwrap();
         assert_eq!(user.spot_positions[0].scaled_balance, 0);
@@ -5726,6 +6450,7 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
         )
         .is_err());
         assert_eq!(user.perp_positions[0].quote_asset_amount, -100000000);
@@ -5747,15 +6472,198 @@ pub mod liquidate_perp_pnl_for_deposit {
             10,
             PERCENTAGE_PRECISION,
             150,
+            false,
+        )
+        .unwrap();
+        assert_eq!(user.perp_positions[0].quote_asset_amount, 0);
+
+        liquidate_perp_pnl_for_deposit(
+            1,
+            0,
+            50 * 10_u128.pow(6), // .8
+            None,
+            &mut user,
+            &user_key,
+            &mut liquidator,
+            &liquidator_key,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            slot,
+            10,
+            PERCENTAGE_PRECISION,
+            150,
+            false,
         )
         .unwrap();
+
+        assert_eq!(user.spot_positions[0].scaled_balance, 48484849000);
         assert_eq!(user.perp_positions[0].quote_asset_amount, 0);
+        assert_eq!(user.perp_positions[1].quote_asset_amount, -100000000);
+
+        assert_eq!(
+            liquidator.spot_positions[1].balance_type,
+            SpotBalanceType::Deposit
+        );
+        assert_eq!(liquidator.spot_positions[1].scaled_balance, 0);
+        assert_eq!(liquidator.perp_positions[0].quote_asset_amount, -100000000);
+    }
+}
+
+pub mod resolve_perp_bankruptcy {
+    use std::str::FromStr;
+
+    use anchor_lang::prelude::AccountLoader;
+    use anchor_lang::Owner;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::controller::funding::settle_funding_payment;
+    use crate::controller::liquidation::resolve_perp_bankruptcy;
+    use crate::controller::position::PositionDirection;
+    use crate::create_anchor_account_info;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_I64, BASE_PRECISION_U64,
+        FUNDING_RATE_PRECISION_I128, FUNDING_RATE_PRECISION_I64, LIQUIDATION_FEE_PRECISION,
+        PEG_PRECISION, PERCENTAGE_PRECISION_U64, QUOTE_PRECISION_I128, QUOTE_PRECISION_I64,
+        QUOTE_PRECISION_U64, QUOTE_SPOT_MARKET_INDEX, SPOT_BALANCE_PRECISION,
+        SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market::{
+        ContractTier, InsuranceClaim, MarketStatus, PerpMarket, PoolBalance, AMM,
+    };
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{InsuranceFund, SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::state::State;
+    use crate::state::user::{
+        Order, OrderStatus, OrderType, PerpPosition, SpotPosition, User, UserStatus,
+    };
+    use crate::state::user_map::UserMap;
+    use crate::test_utils::*;
+    use crate::test_utils::{get_orders, get_positions, get_pyth_price, get_spot_positions};
+    use crate::{create_account_info, PRICE_PRECISION_I64};
+
+    #[test]
+    pub fn successful_resolve_perp_bankruptcy() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I128,
+                base_asset_amount_long: 5 * BASE_PRECISION_I128,
+                base_asset_amount_short: -5 * BASE_PRECISION_I128,
+                base_asset_amount_with_amm: BASE_PRECISION_I128,
+                oracle: oracle_price_key,
+                cumulative_funding_rate_long: 1000 * FUNDING_RATE_PRECISION_I128,
+                cumulative_funding_rate_short: -1000 * FUNDING_RATE_PRECISION_I128,
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            number_of_users: 1,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        let mut user = User {
+            orders: get_orders(Order {
+                market_index: 0,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                direction: PositionDirection::Long,
+                base_asset_amount: BASE_PRECISION_U64,
+                slot: 0,
+                ..Order::default()
+            }),
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 0,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -100 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -100 * QUOTE_PRECISION_I64,
+                open_orders: 1,
+                open_bids: BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            status: UserStatus::Bankrupt,
+            next_liquidation_id: 2,
+            ..User::default()
+        };
+
+        let mut liquidator = User {
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 50 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            ..User::default()
+        };
+
+        let user_key = Pubkey::default();
+        let liquidator_key = Pubkey::default();
+
+        let mut expected_user = user;
+        expected_user.status = UserStatus::Active;
+        expected_user.perp_positions[0].quote_asset_amount = 0;
+        expected_user.total_social_loss = 100000000;
+
+        let mut expected_market = market;
+        expected_market.amm.cumulative_funding_rate_long = 1010 * FUNDING_RATE_PRECISION_I128;
+        expected_market.amm.cumulative_funding_rate_short = -1010 * FUNDING_RATE_PRECISION_I128;
+        expected_market.amm.total_social_loss = 100000000;
+        expected_market.amm.quote_asset_amount = -50 * QUOTE_PRECISION_I128;
+        expected_market.number_of_users = 0;
 
-        liquidate_perp_pnl_for_deposit(
-            1,
+        resolve_perp_bankruptcy(
             0,
-            50 * 10_u128.pow(6), // .8
-            None,
             &mut user,
             &user_key,
             &mut liquidator,
@@ -5764,58 +6672,97 @@ pub mod liquidate_perp_pnl_for_deposit {
             &spot_market_map,
             &mut oracle_map,
             now,
-            slot,
-            10,
-            PERCENTAGE_PRECISION,
-            150,
+            0,
+            0,
+            &UserMap::empty(),
+            &State::default(),
         )
         .unwrap();
 
-        assert_eq!(user.spot_positions[0].scaled_balance, 48484849000);
-        assert_eq!(user.perp_positions[0].quote_asset_amount, 0);
-        assert_eq!(user.perp_positions[1].quote_asset_amount, -100000000);
+        assert_eq!(expected_user, user);
+        assert_eq!(expected_market, market_map.get_ref(&0).unwrap().clone());
 
-        assert_eq!(
-            liquidator.spot_positions[1].balance_type,
-            SpotBalanceType::Deposit
-        );
-        assert_eq!(liquidator.spot_positions[1].scaled_balance, 0);
-        assert_eq!(liquidator.perp_positions[0].quote_asset_amount, -100000000);
-    }
-}
+        let mut affected_long_user = User {
+            orders: [Order::default(); 32],
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 5 * BASE_PRECISION_I64,
+                quote_asset_amount: -500 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -500 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -500 * QUOTE_PRECISION_I64,
+                open_bids: BASE_PRECISION_I64,
+                last_cumulative_funding_rate: 1000 * FUNDING_RATE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            ..User::default()
+        };
 
-pub mod resolve_perp_bankruptcy {
-    use std::str::FromStr;
+        let mut expected_affected_long_user = affected_long_user;
+        expected_affected_long_user.perp_positions[0].quote_asset_amount =
+            -550 * QUOTE_PRECISION_I64; // loses $50
+        expected_affected_long_user.perp_positions[0].quote_break_even_amount =
+            -550 * QUOTE_PRECISION_I64; // loses $50
+        expected_affected_long_user.perp_positions[0].last_cumulative_funding_rate =
+            1010 * FUNDING_RATE_PRECISION_I64;
+        expected_affected_long_user.cumulative_perp_funding = -50 * QUOTE_PRECISION_I64;
 
-    use anchor_lang::Owner;
-    use solana_program::pubkey::Pubkey;
+        {
+            let mut market = market_map.get_ref_mut(&0).unwrap();
+            settle_funding_payment(
+                &mut affected_long_user,
+                &Pubkey::default(),
+                &mut market,
+                now,
+                false,
+            )
+            .unwrap()
+        }
 
-    use crate::controller::funding::settle_funding_payment;
-    use crate::controller::liquidation::resolve_perp_bankruptcy;
-    use crate::controller::position::PositionDirection;
-    use crate::create_anchor_account_info;
-    use crate::math::constants::{
-        AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_I64, BASE_PRECISION_U64,
-        FUNDING_RATE_PRECISION_I128, FUNDING_RATE_PRECISION_I64, LIQUIDATION_FEE_PRECISION,
-        PEG_PRECISION, QUOTE_PRECISION_I128, QUOTE_PRECISION_I64, QUOTE_SPOT_MARKET_INDEX,
-        SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION,
-        SPOT_WEIGHT_PRECISION,
-    };
-    use crate::state::oracle::{HistoricalOracleData, OracleSource};
-    use crate::state::oracle_map::OracleMap;
-    use crate::state::perp_market::{MarketStatus, PerpMarket, PoolBalance, AMM};
-    use crate::state::perp_market_map::PerpMarketMap;
-    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
-    use crate::state::spot_market_map::SpotMarketMap;
-    use crate::state::user::{
-        Order, OrderStatus, OrderType, PerpPosition, SpotPosition, User, UserStatus,
-    };
-    use crate::test_utils::*;
-    use crate::test_utils::{get_orders, get_positions, get_pyth_price, get_spot_positions};
-    use crate::{create_account_info, PRICE_PRECISION_I64};
+        assert_eq!(expected_affected_long_user, affected_long_user);
+
+        let mut affected_short_user = User {
+            orders: [Order::default(); 32],
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: -5 * BASE_PRECISION_I64,
+                quote_asset_amount: 500 * QUOTE_PRECISION_I64,
+                quote_entry_amount: 500 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: 500 * QUOTE_PRECISION_I64,
+                open_bids: BASE_PRECISION_I64,
+                last_cumulative_funding_rate: -1000 * FUNDING_RATE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            ..User::default()
+        };
+
+        let mut expected_affected_short_user = affected_short_user;
+        expected_affected_short_user.perp_positions[0].quote_asset_amount =
+            450 * QUOTE_PRECISION_I64; // loses $50
+        expected_affected_short_user.perp_positions[0].quote_break_even_amount =
+            450 * QUOTE_PRECISION_I64; // loses $50
+        expected_affected_short_user.perp_positions[0].last_cumulative_funding_rate =
+            -1010 * FUNDING_RATE_PRECISION_I64;
+        expected_affected_short_user.cumulative_perp_funding = -50 * QUOTE_PRECISION_I64;
+
+        {
+            let mut market = market_map.get_ref_mut(&0).unwrap();
+            settle_funding_payment(
+                &mut affected_short_user,
+                &Pubkey::default(),
+                &mut market,
+                now,
+                false,
+            )
+            .unwrap()
+        }
+
+        assert_eq!(expected_affected_short_user, affected_short_user);
+    }
 
     #[test]
-    pub fn successful_resolve_perp_bankruptcy() {
+    pub fn successful_resolve_perp_bankruptcy_with_fee_pool() {
         let now = 0_i64;
         let slot = 0_u64;
 
@@ -5851,6 +6798,11 @@ pub mod resolve_perp_bankruptcy {
                 oracle: oracle_price_key,
                 cumulative_funding_rate_long: 1000 * FUNDING_RATE_PRECISION_I128,
                 cumulative_funding_rate_short: -1000 * FUNDING_RATE_PRECISION_I128,
+                fee_pool: PoolBalance {
+                    scaled_balance: 50 * SPOT_BALANCE_PRECISION,
+                    market_index: QUOTE_SPOT_MARKET_INDEX,
+                    ..PoolBalance::default()
+                },
                 ..AMM::default()
             },
             margin_ratio_initial: 1000,
@@ -5865,15 +6817,11 @@ pub mod resolve_perp_bankruptcy {
 
         let mut spot_market = SpotMarket {
             market_index: 0,
+            deposit_balance: 500 * SPOT_BALANCE_PRECISION,
             oracle_source: OracleSource::QuoteAsset,
             cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
             decimals: 6,
             initial_asset_weight: SPOT_WEIGHT_PRECISION,
-            historical_oracle_data: HistoricalOracleData {
-                last_oracle_price_twap: PRICE_PRECISION_I64,
-                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
-                ..HistoricalOracleData::default()
-            },
             ..SpotMarket::default()
         };
         create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
@@ -5924,11 +6872,12 @@ pub mod resolve_perp_bankruptcy {
         expected_user.total_social_loss = 100000000;
 
         let mut expected_market = market;
-        expected_market.amm.cumulative_funding_rate_long = 1010 * FUNDING_RATE_PRECISION_I128;
-        expected_market.amm.cumulative_funding_rate_short = -1010 * FUNDING_RATE_PRECISION_I128;
-        expected_market.amm.total_social_loss = 100000000;
+        expected_market.amm.cumulative_funding_rate_long = 1005 * FUNDING_RATE_PRECISION_I128;
+        expected_market.amm.cumulative_funding_rate_short = -1005 * FUNDING_RATE_PRECISION_I128;
+        expected_market.amm.total_social_loss = 50000000;
         expected_market.amm.quote_asset_amount = -50 * QUOTE_PRECISION_I128;
         expected_market.number_of_users = 0;
+        expected_market.amm.fee_pool.scaled_balance = 0;
 
         resolve_perp_bankruptcy(
             0,
@@ -5941,9 +6890,13 @@ pub mod resolve_perp_bankruptcy {
             &mut oracle_map,
             now,
             0,
+            0,
+            &UserMap::empty(),
+            &State::default(),
         )
         .unwrap();
 
+        assert_eq!(user.total_social_loss, 100000000);
         assert_eq!(expected_user, user);
         assert_eq!(expected_market, market_map.get_ref(&0).unwrap().clone());
 
@@ -5965,12 +6918,12 @@ pub mod resolve_perp_bankruptcy {
 
         let mut expected_affected_long_user = affected_long_user;
         expected_affected_long_user.perp_positions[0].quote_asset_amount =
-            -550 * QUOTE_PRECISION_I64; // loses $50
+            -525 * QUOTE_PRECISION_I64; // loses $50
         expected_affected_long_user.perp_positions[0].quote_break_even_amount =
-            -550 * QUOTE_PRECISION_I64; // loses $50
+            -525 * QUOTE_PRECISION_I64; // loses $50
         expected_affected_long_user.perp_positions[0].last_cumulative_funding_rate =
-            1010 * FUNDING_RATE_PRECISION_I64;
-        expected_affected_long_user.cumulative_perp_funding = -50 * QUOTE_PRECISION_I64;
+            1005 * FUNDING_RATE_PRECISION_I64;
+        expected_affected_long_user.cumulative_perp_funding = -25 * QUOTE_PRECISION_I64;
 
         {
             let mut market = market_map.get_ref_mut(&0).unwrap();
@@ -5979,6 +6932,7 @@ pub mod resolve_perp_bankruptcy {
                 &Pubkey::default(),
                 &mut market,
                 now,
+                false,
             )
             .unwrap()
         }
@@ -6001,31 +6955,171 @@ pub mod resolve_perp_bankruptcy {
             ..User::default()
         };
 
-        let mut expected_affected_short_user = affected_short_user;
-        expected_affected_short_user.perp_positions[0].quote_asset_amount =
-            450 * QUOTE_PRECISION_I64; // loses $50
-        expected_affected_short_user.perp_positions[0].quote_break_even_amount =
-            450 * QUOTE_PRECISION_I64; // loses $50
-        expected_affected_short_user.perp_positions[0].last_cumulative_funding_rate =
-            -1010 * FUNDING_RATE_PRECISION_I64;
-        expected_affected_short_user.cumulative_perp_funding = -50 * QUOTE_PRECISION_I64;
+        let mut expected_affected_short_user = affected_short_user;
+        expected_affected_short_user.perp_positions[0].quote_asset_amount =
+            475 * QUOTE_PRECISION_I64; // loses $50
+        expected_affected_short_user.perp_positions[0].quote_break_even_amount =
+            475 * QUOTE_PRECISION_I64; // loses $50
+        expected_affected_short_user.perp_positions[0].last_cumulative_funding_rate =
+            -1005 * FUNDING_RATE_PRECISION_I64;
+        expected_affected_short_user.cumulative_perp_funding = -25 * QUOTE_PRECISION_I64;
+
+        {
+            let mut market = market_map.get_ref_mut(&0).unwrap();
+            settle_funding_payment(
+                &mut affected_short_user,
+                &Pubkey::default(),
+                &mut market,
+                now,
+                false,
+            )
+            .unwrap()
+        }
+
+        assert_eq!(expected_affected_short_user, affected_short_user);
+    }
+
+    #[test]
+    pub fn perp_insurance_fund_drawn_before_shared_insurance_fund() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                quote_asset_amount: -150 * QUOTE_PRECISION_I128,
+                base_asset_amount_long: 5 * BASE_PRECISION_I128,
+                base_asset_amount_short: -5 * BASE_PRECISION_I128,
+                base_asset_amount_with_amm: BASE_PRECISION_I128,
+                oracle: oracle_price_key,
+                cumulative_funding_rate_long: 1000 * FUNDING_RATE_PRECISION_I128,
+                cumulative_funding_rate_short: -1000 * FUNDING_RATE_PRECISION_I128,
+                ..AMM::default()
+            },
+            insurance_claim: InsuranceClaim {
+                quote_max_insurance: 100 * QUOTE_PRECISION_U64,
+                ..InsuranceClaim::default()
+            },
+            insurance_fund: InsuranceFund {
+                vault: Pubkey::new_unique(),
+                ..InsuranceFund::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            number_of_users: 1,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        let mut user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 0,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -100 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -100 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            status: UserStatus::Bankrupt,
+            next_liquidation_id: 2,
+            ..User::default()
+        };
+
+        let mut liquidator = User {
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 50 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            ..User::default()
+        };
 
-        {
-            let mut market = market_map.get_ref_mut(&0).unwrap();
-            settle_funding_payment(
-                &mut affected_short_user,
-                &Pubkey::default(),
-                &mut market,
-                now,
-            )
-            .unwrap()
-        }
+        let user_key = Pubkey::default();
+        let liquidator_key = Pubkey::default();
 
-        assert_eq!(expected_affected_short_user, affected_short_user);
+        let (perp_if_payment, if_payment) = resolve_perp_bankruptcy(
+            0,
+            &mut user,
+            &user_key,
+            &mut liquidator,
+            &liquidator_key,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            1000 * QUOTE_PRECISION_U64,
+            60 * QUOTE_PRECISION_U64,
+            &UserMap::empty(),
+            &State::default(),
+        )
+        .unwrap();
+
+        // dedicated pool pays all but 1 of its balance, shared insurance fund covers the rest
+        assert_eq!(perp_if_payment, 60 * QUOTE_PRECISION_U64 - 1);
+        assert_eq!(if_payment, 40 * QUOTE_PRECISION_U64 + 1);
+
+        assert_eq!(user.status, UserStatus::Active);
+        assert_eq!(user.perp_positions[0].quote_asset_amount, 0);
+        assert_eq!(user.total_social_loss, 0);
+
+        let market = market_map.get_ref(&0).unwrap();
+        assert_eq!(market.amm.total_social_loss, 0);
+        assert_eq!(
+            market.amm.cumulative_funding_rate_long,
+            1000 * FUNDING_RATE_PRECISION_I128
+        );
+        assert_eq!(
+            market.insurance_claim.quote_settled_insurance,
+            40 * QUOTE_PRECISION_U64 + 1
+        );
+        assert_eq!(market.pnl_pool.scaled_balance, 100 * SPOT_BALANCE_PRECISION);
     }
 
     #[test]
-    pub fn successful_resolve_perp_bankruptcy_with_fee_pool() {
+    pub fn auto_deleverage_profitable_counterparty() {
         let now = 0_i64;
         let slot = 0_u64;
 
@@ -6049,30 +7143,29 @@ pub mod resolve_perp_bankruptcy {
                 bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
                 ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
                 ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                min_base_asset_reserve: 50 * AMM_RESERVE_PRECISION,
+                max_base_asset_reserve: 200 * AMM_RESERVE_PRECISION,
                 sqrt_k: 100 * AMM_RESERVE_PRECISION,
                 peg_multiplier: 100 * PEG_PRECISION,
                 max_slippage_ratio: 50,
-                max_fill_reserve_fraction: 100,
+                max_fill_reserve_fraction: 10,
                 order_step_size: 10000000,
-                quote_asset_amount: -150 * QUOTE_PRECISION_I128,
+                quote_asset_amount: 250 * QUOTE_PRECISION_I128,
+                quote_entry_amount_long: -100 * QUOTE_PRECISION_I128,
+                quote_entry_amount_short: 450 * QUOTE_PRECISION_I128,
+                quote_break_even_amount_long: -100 * QUOTE_PRECISION_I128,
+                quote_break_even_amount_short: 450 * QUOTE_PRECISION_I128,
                 base_asset_amount_long: 5 * BASE_PRECISION_I128,
                 base_asset_amount_short: -5 * BASE_PRECISION_I128,
-                base_asset_amount_with_amm: BASE_PRECISION_I128,
                 oracle: oracle_price_key,
-                cumulative_funding_rate_long: 1000 * FUNDING_RATE_PRECISION_I128,
-                cumulative_funding_rate_short: -1000 * FUNDING_RATE_PRECISION_I128,
-                fee_pool: PoolBalance {
-                    scaled_balance: 50 * SPOT_BALANCE_PRECISION,
-                    market_index: QUOTE_SPOT_MARKET_INDEX,
-                    ..PoolBalance::default()
-                },
+                historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
                 ..AMM::default()
             },
             margin_ratio_initial: 1000,
             margin_ratio_maintenance: 500,
-            status: MarketStatus::Initialized,
-            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
-            number_of_users: 1,
+            status: MarketStatus::Active,
+            number_of_users_with_base: 2,
+            number_of_users: 3,
             ..PerpMarket::default()
         };
         create_anchor_account_info!(market, PerpMarket, market_account_info);
@@ -6080,34 +7173,24 @@ pub mod resolve_perp_bankruptcy {
 
         let mut spot_market = SpotMarket {
             market_index: 0,
-            deposit_balance: 500 * SPOT_BALANCE_PRECISION,
             oracle_source: OracleSource::QuoteAsset,
             cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
             decimals: 6,
             initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
             ..SpotMarket::default()
         };
         create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
         let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
 
         let mut user = User {
-            orders: get_orders(Order {
-                market_index: 0,
-                status: OrderStatus::Open,
-                order_type: OrderType::Limit,
-                direction: PositionDirection::Long,
-                base_asset_amount: BASE_PRECISION_U64,
-                slot: 0,
-                ..Order::default()
-            }),
             perp_positions: get_positions(PerpPosition {
                 market_index: 0,
-                base_asset_amount: 0,
                 quote_asset_amount: -100 * QUOTE_PRECISION_I64,
-                quote_entry_amount: -100 * QUOTE_PRECISION_I64,
-                quote_break_even_amount: -100 * QUOTE_PRECISION_I64,
-                open_orders: 1,
-                open_bids: BASE_PRECISION_I64,
                 ..PerpPosition::default()
             }),
             spot_positions: [SpotPosition::default(); 8],
@@ -6115,32 +7198,75 @@ pub mod resolve_perp_bankruptcy {
             next_liquidation_id: 2,
             ..User::default()
         };
+        let user_key = Pubkey::default();
 
+        // liquidator takes over the base closed
         let mut liquidator = User {
             spot_positions: get_spot_positions(SpotPosition {
                 market_index: 0,
                 balance_type: SpotBalanceType::Deposit,
-                scaled_balance: 50 * SPOT_BALANCE_PRECISION_U64,
+                scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
                 ..SpotPosition::default()
             }),
             ..User::default()
         };
-
-        let user_key = Pubkey::default();
         let liquidator_key = Pubkey::default();
 
-        let mut expected_user = user;
-        expected_user.status = UserStatus::Active;
-        expected_user.perp_positions[0].quote_asset_amount = 0;
-        expected_user.total_social_loss = 100000000;
+        // long with $400 of unrealized pnl
+        let profitable_user_key =
+            Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap();
+        let mut profitable_user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 5 * BASE_PRECISION_I64,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -100 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -100 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            ..User::default()
+        };
+        create_anchor_account_info!(
+            profitable_user,
+            &profitable_user_key,
+            User,
+            profitable_user_account_info
+        );
 
-        let mut expected_market = market;
-        expected_market.amm.cumulative_funding_rate_long = 1005 * FUNDING_RATE_PRECISION_I128;
-        expected_market.amm.cumulative_funding_rate_short = -1005 * FUNDING_RATE_PRECISION_I128;
-        expected_market.amm.total_social_loss = 50000000;
-        expected_market.amm.quote_asset_amount = -50 * QUOTE_PRECISION_I128;
-        expected_market.number_of_users = 0;
-        expected_market.amm.fee_pool.scaled_balance = 0;
+        // short with $50 of unrealized losses
+        let unprofitable_user_key = Pubkey::new_unique();
+        let mut unprofitable_user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: -5 * BASE_PRECISION_I64,
+                quote_asset_amount: 450 * QUOTE_PRECISION_I64,
+                quote_entry_amount: 450 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: 450 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: [SpotPosition::default(); 8],
+            ..User::default()
+        };
+        create_anchor_account_info!(
+            unprofitable_user,
+            &unprofitable_user_key,
+            User,
+            unprofitable_user_account_info
+        );
+
+        let mut counterparties = UserMap::load_one(&profitable_user_account_info).unwrap();
+        counterparties
+            .insert(
+                unprofitable_user_key,
+                AccountLoader::try_from(&unprofitable_user_account_info).unwrap(),
+            )
+            .unwrap();
+
+        let state = State {
+            auto_deleverage_contract_tiers: 1 << ContractTier::Speculative as u8,
+            ..State::default()
+        };
 
         resolve_perp_bankruptcy(
             0,
@@ -6153,88 +7279,247 @@ pub mod resolve_perp_bankruptcy {
             &mut oracle_map,
             now,
             0,
+            0,
+            &counterparties,
+            &state,
         )
         .unwrap();
 
-        assert_eq!(user.total_social_loss, 100000000);
-        assert_eq!(expected_user, user);
-        assert_eq!(expected_market, market_map.get_ref(&0).unwrap().clone());
+        assert_eq!(user.status, UserStatus::Active);
+        assert_eq!(user.perp_positions[0].quote_asset_amount, 0);
 
-        let mut affected_long_user = User {
-            orders: [Order::default(); 32],
+        // enough of the profitable long is closed for its pnl to cover the loss
+        let profitable_user = counterparties.get_ref(&profitable_user_key).unwrap();
+        assert_eq!(
+            profitable_user.perp_positions[0].base_asset_amount,
+            375 * BASE_PRECISION_I64 / 100
+        );
+        // 1.25 closed for $125 at the oracle price, less the $100 covered, exits at the $20 bankruptcy price
+        assert_eq!(
+            profitable_user.perp_positions[0].quote_asset_amount,
+            -75 * QUOTE_PRECISION_I64
+        );
+        assert_eq!(
+            profitable_user.perp_positions[0].quote_entry_amount,
+            -75 * QUOTE_PRECISION_I64
+        );
+        assert_eq!(profitable_user.perp_positions[0].open_orders, 0);
+        assert_eq!(profitable_user.orders[0], Order::default());
+
+        let unprofitable_user = counterparties.get_ref(&unprofitable_user_key).unwrap();
+        assert_eq!(
+            unprofitable_user.perp_positions[0].base_asset_amount,
+            -5 * BASE_PRECISION_I64
+        );
+        assert_eq!(
+            unprofitable_user.perp_positions[0].quote_asset_amount,
+            450 * QUOTE_PRECISION_I64
+        );
+
+        // liquidator takes over the 1.25 closed at the oracle price
+        assert_eq!(
+            liquidator.perp_positions[0].base_asset_amount,
+            125 * BASE_PRECISION_I64 / 100
+        );
+        assert_eq!(
+            liquidator.perp_positions[0].quote_asset_amount,
+            -125 * QUOTE_PRECISION_I64
+        );
+
+        // nothing is left to socialize and the amm is untouched
+        let market = market_map.get_ref(&0).unwrap();
+        assert_eq!(market.amm.total_social_loss, 0);
+        assert_eq!(market.amm.base_asset_amount_long, 5 * BASE_PRECISION_I128);
+        assert_eq!(market.amm.base_asset_amount_with_amm, 0);
+        assert_eq!(market.amm.base_asset_reserve, 100 * AMM_RESERVE_PRECISION);
+        assert_eq!(market.amm.quote_asset_reserve, 100 * AMM_RESERVE_PRECISION);
+    }
+
+    #[test]
+    pub fn auto_deleverage_skips_counterparty_below_score_threshold() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 99 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 101 * AMM_RESERVE_PRECISION,
+                min_base_asset_reserve: 50 * AMM_RESERVE_PRECISION,
+                max_base_asset_reserve: 200 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 10,
+                order_step_size: 10000000,
+                quote_asset_amount: 250 * QUOTE_PRECISION_I128,
+                quote_entry_amount_long: -100 * QUOTE_PRECISION_I128,
+                quote_entry_amount_short: 450 * QUOTE_PRECISION_I128,
+                quote_break_even_amount_long: -100 * QUOTE_PRECISION_I128,
+                quote_break_even_amount_short: 450 * QUOTE_PRECISION_I128,
+                base_asset_amount_long: 5 * BASE_PRECISION_I128,
+                base_asset_amount_short: -5 * BASE_PRECISION_I128,
+                oracle: oracle_price_key,
+                historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Active,
+            number_of_users_with_base: 2,
+            number_of_users: 3,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        let mut user = User {
             perp_positions: get_positions(PerpPosition {
                 market_index: 0,
-                base_asset_amount: 5 * BASE_PRECISION_I64,
-                quote_asset_amount: -500 * QUOTE_PRECISION_I64,
-                quote_break_even_amount: -500 * QUOTE_PRECISION_I64,
-                quote_entry_amount: -500 * QUOTE_PRECISION_I64,
-                open_bids: BASE_PRECISION_I64,
-                last_cumulative_funding_rate: 1000 * FUNDING_RATE_PRECISION_I64,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
                 ..PerpPosition::default()
             }),
             spot_positions: [SpotPosition::default(); 8],
+            status: UserStatus::Bankrupt,
+            next_liquidation_id: 2,
             ..User::default()
         };
+        let user_key = Pubkey::default();
 
-        let mut expected_affected_long_user = affected_long_user;
-        expected_affected_long_user.perp_positions[0].quote_asset_amount =
-            -525 * QUOTE_PRECISION_I64; // loses $50
-        expected_affected_long_user.perp_positions[0].quote_break_even_amount =
-            -525 * QUOTE_PRECISION_I64; // loses $50
-        expected_affected_long_user.perp_positions[0].last_cumulative_funding_rate =
-            1005 * FUNDING_RATE_PRECISION_I64;
-        expected_affected_long_user.cumulative_perp_funding = -25 * QUOTE_PRECISION_I64;
-
-        {
-            let mut market = market_map.get_ref_mut(&0).unwrap();
-            settle_funding_payment(
-                &mut affected_long_user,
-                &Pubkey::default(),
-                &mut market,
-                now,
-            )
-            .unwrap()
-        }
+        let mut liquidator = User::default();
+        let liquidator_key = Pubkey::default();
 
-        assert_eq!(expected_affected_long_user, affected_long_user);
+        // long with $400 of unrealized pnl on $100 of collateral scores 400% pnl at 5x leverage
+        let profitable_user_key =
+            Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap();
+        let mut profitable_user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 5 * BASE_PRECISION_I64,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -100 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -100 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            ..User::default()
+        };
+        create_anchor_account_info!(
+            profitable_user,
+            &profitable_user_key,
+            User,
+            profitable_user_account_info
+        );
 
-        let mut affected_short_user = User {
-            orders: [Order::default(); 32],
+        // short with $50 of unrealized losses
+        let unprofitable_user_key = Pubkey::new_unique();
+        let mut unprofitable_user = User {
             perp_positions: get_positions(PerpPosition {
                 market_index: 0,
                 base_asset_amount: -5 * BASE_PRECISION_I64,
-                quote_asset_amount: 500 * QUOTE_PRECISION_I64,
-                quote_entry_amount: 500 * QUOTE_PRECISION_I64,
-                quote_break_even_amount: 500 * QUOTE_PRECISION_I64,
-                open_bids: BASE_PRECISION_I64,
-                last_cumulative_funding_rate: -1000 * FUNDING_RATE_PRECISION_I64,
+                quote_asset_amount: 450 * QUOTE_PRECISION_I64,
+                quote_entry_amount: 450 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: 450 * QUOTE_PRECISION_I64,
                 ..PerpPosition::default()
             }),
             spot_positions: [SpotPosition::default(); 8],
             ..User::default()
         };
+        create_anchor_account_info!(
+            unprofitable_user,
+            &unprofitable_user_key,
+            User,
+            unprofitable_user_account_info
+        );
 
-        let mut expected_affected_short_user = affected_short_user;
-        expected_affected_short_user.perp_positions[0].quote_asset_amount =
-            475 * QUOTE_PRECISION_I64; // loses $50
-        expected_affected_short_user.perp_positions[0].quote_break_even_amount =
-            475 * QUOTE_PRECISION_I64; // loses $50
-        expected_affected_short_user.perp_positions[0].last_cumulative_funding_rate =
-            -1005 * FUNDING_RATE_PRECISION_I64;
-        expected_affected_short_user.cumulative_perp_funding = -25 * QUOTE_PRECISION_I64;
-
-        {
-            let mut market = market_map.get_ref_mut(&0).unwrap();
-            settle_funding_payment(
-                &mut affected_short_user,
-                &Pubkey::default(),
-                &mut market,
-                now,
+        let mut counterparties = UserMap::load_one(&profitable_user_account_info).unwrap();
+        counterparties
+            .insert(
+                unprofitable_user_key,
+                AccountLoader::try_from(&unprofitable_user_account_info).unwrap(),
             )
-            .unwrap()
-        }
+            .unwrap();
 
-        assert_eq!(expected_affected_short_user, affected_short_user);
+        let state = State {
+            auto_deleverage_contract_tiers: 1 << ContractTier::Speculative as u8,
+            auto_deleverage_score_threshold: 25 * PERCENTAGE_PRECISION_U64 as u32,
+            ..State::default()
+        };
+
+        resolve_perp_bankruptcy(
+            0,
+            &mut user,
+            &user_key,
+            &mut liquidator,
+            &liquidator_key,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            0,
+            0,
+            &counterparties,
+            &state,
+        )
+        .unwrap();
+
+        assert_eq!(user.status, UserStatus::Active);
+        assert_eq!(user.perp_positions[0].quote_asset_amount, 0);
+
+        // the long scores 20x, below the threshold, so it's left untouched
+        let profitable_user = counterparties.get_ref(&profitable_user_key).unwrap();
+        assert_eq!(
+            profitable_user.perp_positions[0].base_asset_amount,
+            5 * BASE_PRECISION_I64
+        );
+        assert_eq!(
+            profitable_user.perp_positions[0].quote_asset_amount,
+            -100 * QUOTE_PRECISION_I64
+        );
+
+        // and the whole loss is socialized
+        let market = market_map.get_ref(&0).unwrap();
+        assert_eq!(
+            market.amm.total_social_loss,
+            100 * QUOTE_PRECISION_I64 as u128
+        );
     }
 }
 
@@ -6403,3 +7688,283 @@ pub mod resolve_spot_bankruptcy {
         assert_eq!(deposit_token_amount, 900 * QUOTE_PRECISION);
     }
 }
+
+pub mod transfer_shared_collateral_to_user {
+    use std::str::FromStr;
+
+    use anchor_lang::Owner;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::controller::liquidation::transfer_shared_collateral_to_user;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I64, PEG_PRECISION, PRICE_PRECISION, QUOTE_PRECISION,
+        QUOTE_PRECISION_I64, SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64,
+        SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::user::{Order, PerpPosition, SpotPosition, User, UserStatus};
+    use crate::state::user_map::UserMap;
+    use crate::test_utils::*;
+    use crate::test_utils::{get_positions, get_pyth_price};
+    use crate::{create_account_info, create_anchor_account_info, PRICE_PRECISION_I64};
+
+    #[test]
+    pub fn sibling_deposit_covers_shortage() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 2000,
+            margin_ratio_maintenance: 1000,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut usdc_spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            deposit_balance: 15 * SPOT_BALANCE_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(usdc_spot_market, SpotMarket, usdc_spot_market_account_info);
+        let spot_market_map =
+            SpotMarketMap::load_multiple(Vec::from([&usdc_spot_market_account_info]), true)
+                .unwrap();
+
+        // $100 long with no collateral, maintenance requirement is $10
+        let mut user = User {
+            orders: [Order::default(); 32],
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+        let user_key = Pubkey::new_unique();
+
+        // $15 deposit in sibling
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 15 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        let mut sibling = User {
+            orders: [Order::default(); 32],
+            spot_positions,
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+        let sibling_key = Pubkey::new_unique();
+        create_anchor_account_info!(sibling, &sibling_key, User, sibling_account_info);
+        let shared_collateral_group = UserMap::load_one(&sibling_account_info).unwrap();
+
+        let covered = transfer_shared_collateral_to_user(
+            &mut user,
+            &user_key,
+            &shared_collateral_group,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )
+        .unwrap();
+        assert!(covered);
+
+        let spot_market = spot_market_map.get_ref(&0).unwrap();
+        assert_eq!(
+            user.get_quote_spot_position()
+                .get_token_amount(&spot_market)
+                .unwrap(),
+            10 * QUOTE_PRECISION
+        );
+
+        let sibling = shared_collateral_group.get_ref(&sibling_key).unwrap();
+        assert_eq!(
+            sibling
+                .get_quote_spot_position()
+                .get_token_amount(&spot_market)
+                .unwrap(),
+            5 * QUOTE_PRECISION
+        );
+        assert_eq!(spot_market.deposit_balance, 15 * SPOT_BALANCE_PRECISION);
+    }
+
+    #[test]
+    pub fn bankrupt_user_returns_to_liquidation() {
+        let now = 0_i64;
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 2000,
+            margin_ratio_maintenance: 1000,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut usdc_spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            deposit_balance: 100 * SPOT_BALANCE_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(usdc_spot_market, SpotMarket, usdc_spot_market_account_info);
+        let spot_market_map =
+            SpotMarketMap::load_multiple(Vec::from([&usdc_spot_market_account_info]), true)
+                .unwrap();
+
+        // $5 of unsettled losses and nothing to cover them
+        let mut user = User {
+            orders: [Order::default(); 32],
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                quote_asset_amount: -5 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            status: UserStatus::Bankrupt,
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+        let user_key = Pubkey::new_unique();
+
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        let mut sibling = User {
+            orders: [Order::default(); 32],
+            spot_positions,
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+        let sibling_key = Pubkey::new_unique();
+        create_anchor_account_info!(sibling, &sibling_key, User, sibling_account_info);
+        let shared_collateral_group = UserMap::load_one(&sibling_account_info).unwrap();
+
+        let covered = transfer_shared_collateral_to_user(
+            &mut user,
+            &user_key,
+            &shared_collateral_group,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )
+        .unwrap();
+        assert!(covered);
+
+        // the deficit is now backed by a deposit the liquidator can take instead of being socialized
+        assert_eq!(user.status, UserStatus::BeingLiquidated);
+        assert!(!user.is_bankrupt());
+
+        let spot_market = spot_market_map.get_ref(&0).unwrap();
+        let sibling = shared_collateral_group.get_ref(&sibling_key).unwrap();
+        assert!(
+            sibling
+                .get_quote_spot_position()
+                .get_token_amount(&spot_market)
+                .unwrap()
+                < 100 * QUOTE_PRECISION
+        );
+    }
+}
@@ -4,20 +4,33 @@ use crate::bn::U192;
 use crate::controller;
 use crate::controller::position::PositionDelta;
 use crate::controller::position::{update_position_and_market, update_quote_asset_amount};
+use crate::controller::spot_position::update_spot_balances_and_cumulative_deposits;
 use crate::emit;
 use crate::error::{DriftResult, ErrorCode};
 use crate::get_struct_values;
+use crate::get_then_update_id;
 use crate::math::casting::Cast;
+use crate::math::constants::QUOTE_SPOT_MARKET_INDEX;
 use crate::math::cp_curve::{get_update_k_result, update_k};
-use crate::math::lp::calculate_settle_lp_metrics;
+use crate::math::helpers::{get_proportion_i128, get_proportion_u128};
+use crate::math::lp::{
+    calculate_lp_attribution, calculate_settle_lp_metrics, LPAttribution, LPMetrics,
+};
 use crate::math::position::calculate_base_asset_value_with_oracle_price;
 use crate::math::safe_math::SafeMath;
 
-use crate::state::events::{LPAction, LPRecord};
+use crate::state::events::{
+    DepositDirection, DepositExplanation, DepositRecord, LPAction, LPRecord,
+};
 use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_lp_range::PerpLpRange;
+use crate::state::perp_lp_stats::PerpLpStats;
 use crate::state::perp_market::PerpMarket;
 use crate::state::perp_market_map::PerpMarketMap;
+use crate::state::spot_market::{SpotBalanceType, SpotMarket};
 use crate::state::state::State;
+use crate::state::tokenized_lp_pool::TokenizedLpPool;
 use crate::state::user::PerpPosition;
 use crate::state::user::User;
 use crate::validate;
@@ -31,6 +44,12 @@ pub fn mint_lp_shares(
     market: &mut PerpMarket,
     n_shares: u64,
 ) -> DriftResult<()> {
+    validate!(
+        !market.is_operation_paused(MarketOperation::Lp)?,
+        ErrorCode::MarketActionPaused,
+        "Market lp actions are paused"
+    )?;
+
     let amm = market.amm;
 
     let (sqrt_k,) = get_struct_values!(amm, sqrt_k);
@@ -63,6 +82,24 @@ pub fn mint_lp_shares(
 pub fn settle_lp_position(
     position: &mut PerpPosition,
     market: &mut PerpMarket,
+) -> DriftResult<(PositionDelta, i64)> {
+    _settle_lp_position(position, market, None)
+}
+
+/// Settles lp shares that follow a range. What the shares took on after the reserve price left
+/// the range stays with the amm
+pub fn settle_ranged_lp_position(
+    position: &mut PerpPosition,
+    market: &mut PerpMarket,
+    perp_lp_range: &PerpLpRange,
+) -> DriftResult<(PositionDelta, i64)> {
+    _settle_lp_position(position, market, Some(perp_lp_range))
+}
+
+fn _settle_lp_position(
+    position: &mut PerpPosition,
+    market: &mut PerpMarket,
+    perp_lp_range: Option<&PerpLpRange>,
 ) -> DriftResult<(PositionDelta, i64)> {
     if position.base_asset_amount > 0 {
         validate!(
@@ -78,9 +115,25 @@ pub fn settle_lp_position(
         )?;
     }
 
-    let mut lp_metrics: crate::math::lp::LPMetrics =
-        calculate_settle_lp_metrics(&market.amm, position)?;
+    let lp_metrics: crate::math::lp::LPMetrics =
+        calculate_settle_lp_metrics(&market.amm, position, perp_lp_range)?;
+
+    let (position_delta, pnl) = apply_lp_metrics_to_position(position, market, lp_metrics)?;
+
+    position.last_base_asset_amount_per_lp = market.amm.base_asset_amount_per_lp.cast()?;
+    position.last_quote_asset_amount_per_lp = market.amm.quote_asset_amount_per_lp.cast()?;
+
+    crate::validation::perp_market::validate_perp_market(market)?;
+    crate::validation::position::validate_perp_position_with_perp_market(position, market)?;
+
+    Ok((position_delta, pnl))
+}
 
+fn apply_lp_metrics_to_position(
+    position: &mut PerpPosition,
+    market: &mut PerpMarket,
+    mut lp_metrics: LPMetrics,
+) -> DriftResult<(PositionDelta, i64)> {
     let new_remainder_base_asset_amount = position
         .remainder_base_asset_amount
         .cast::<i64>()?
@@ -116,13 +169,422 @@ pub fn settle_lp_position(
         .base_asset_amount_with_unsettled_lp
         .safe_add(lp_metrics.base_asset_amount)?;
 
-    position.last_base_asset_amount_per_lp = market.amm.base_asset_amount_per_lp.cast()?;
-    position.last_quote_asset_amount_per_lp = market.amm.quote_asset_amount_per_lp.cast()?;
+    // the amm keeps what the lp shares took on out of their range
+    if lp_metrics.out_of_range_base_asset_amount != 0 {
+        market.amm.base_asset_amount_with_unsettled_lp = market
+            .amm
+            .base_asset_amount_with_unsettled_lp
+            .safe_add(lp_metrics.out_of_range_base_asset_amount)?;
+
+        market.amm.base_asset_amount_with_amm = market
+            .amm
+            .base_asset_amount_with_amm
+            .safe_sub(lp_metrics.out_of_range_base_asset_amount)?;
+    }
+
+    Ok((position_delta, pnl))
+}
+
+/// Moves `n_shares` of the position's lp shares to the tokenized lp pool's user and returns the
+/// number of tokens to mint. The position hands the pool user the slice of its perp position that
+/// comes with each share in the pool, so existing token holders aren't diluted
+pub fn tokenize_lp_shares(
+    position: &mut PerpPosition,
+    pool_position: &mut PerpPosition,
+    market: &mut PerpMarket,
+    tokenized_lp_pool: &mut TokenizedLpPool,
+    n_shares: u64,
+) -> DriftResult<(u64, PositionDelta, i64)> {
+    validate!(
+        !market.is_operation_paused(MarketOperation::Lp)?,
+        ErrorCode::MarketActionPaused,
+        "Market lp actions are paused"
+    )?;
+
+    validate!(
+        n_shares > 0 && position.lp_shares >= n_shares,
+        ErrorCode::InsufficientLPTokens,
+        "position has {} lp shares, trying to tokenize {}",
+        position.lp_shares,
+        n_shares
+    )?;
+
+    settle_lp_position(position, market)?;
+
+    let pool_lp_shares = pool_position.lp_shares;
+    let n_tokens = if tokenized_lp_pool.token_supply == 0 {
+        n_shares
+    } else {
+        validate!(
+            pool_lp_shares > 0,
+            ErrorCode::InsufficientLPTokens,
+            "pool has {} tokens but no lp shares",
+            tokenized_lp_pool.token_supply
+        )?;
+
+        get_proportion_u128(
+            tokenized_lp_pool.token_supply.cast()?,
+            n_shares.cast()?,
+            pool_lp_shares.cast()?,
+        )?
+        .cast::<u64>()?
+    };
+
+    validate!(
+        n_tokens > 0,
+        ErrorCode::InsufficientLPTokens,
+        "tokenizing {} lp shares mints no tokens",
+        n_shares
+    )?;
+
+    let pool_delta = if pool_lp_shares > 0 {
+        settle_lp_position(pool_position, market)?;
+        calculate_tokenized_lp_pool_delta(
+            pool_position,
+            n_shares.cast()?,
+            pool_lp_shares.cast()?,
+            market.amm.order_step_size,
+        )?
+    } else {
+        pool_position.last_base_asset_amount_per_lp = market.amm.base_asset_amount_per_lp.cast()?;
+        pool_position.last_quote_asset_amount_per_lp =
+            market.amm.quote_asset_amount_per_lp.cast()?;
+        PositionDelta::default()
+    };
+
+    let position_delta = PositionDelta {
+        base_asset_amount: -pool_delta.base_asset_amount,
+        quote_asset_amount: -pool_delta.quote_asset_amount,
+    };
+
+    let pnl = update_position_and_market(position, market, &position_delta)?;
+    update_position_and_market(pool_position, market, &pool_delta)?;
+
+    position.lp_shares = position.lp_shares.safe_sub(n_shares)?;
+    pool_position.lp_shares = pool_position.lp_shares.safe_add(n_shares)?;
+    tokenized_lp_pool.token_supply = tokenized_lp_pool.token_supply.safe_add(n_tokens)?;
 
     crate::validation::perp_market::validate_perp_market(market)?;
     crate::validation::position::validate_perp_position_with_perp_market(position, market)?;
+    crate::validation::position::validate_perp_position_with_perp_market(pool_position, market)?;
 
-    Ok((position_delta, pnl))
+    Ok((n_tokens, position_delta, pnl))
+}
+
+/// Burns `n_tokens` of the tokenized lp pool's tokens and moves the slice of the pool user's lp
+/// shares and perp position they are a claim on to the position. Returns the lp shares moved
+pub fn redeem_lp_shares(
+    position: &mut PerpPosition,
+    pool_position: &mut PerpPosition,
+    market: &mut PerpMarket,
+    tokenized_lp_pool: &mut TokenizedLpPool,
+    n_tokens: u64,
+) -> DriftResult<(u64, PositionDelta, i64)> {
+    validate!(
+        !market.is_operation_paused(MarketOperation::Lp)?,
+        ErrorCode::MarketActionPaused,
+        "Market lp actions are paused"
+    )?;
+
+    validate!(
+        n_tokens > 0 && tokenized_lp_pool.token_supply >= n_tokens,
+        ErrorCode::InsufficientLPTokens,
+        "pool has {} tokens, trying to redeem {}",
+        tokenized_lp_pool.token_supply,
+        n_tokens
+    )?;
+
+    if pool_position.lp_shares > 0 {
+        settle_lp_position(pool_position, market)?;
+    }
+
+    if position.lp_shares > 0 {
+        settle_lp_position(position, market)?;
+    } else {
+        position.last_base_asset_amount_per_lp = market.amm.base_asset_amount_per_lp.cast()?;
+        position.last_quote_asset_amount_per_lp = market.amm.quote_asset_amount_per_lp.cast()?;
+    }
+
+    let n_shares = crate::math::orders::standardize_base_asset_amount(
+        get_proportion_u128(
+            pool_position.lp_shares.cast()?,
+            n_tokens.cast()?,
+            tokenized_lp_pool.token_supply.cast()?,
+        )?
+        .cast()?,
+        market.amm.order_step_size,
+    )?;
+
+    let position_delta = calculate_tokenized_lp_pool_delta(
+        pool_position,
+        n_tokens.cast()?,
+        tokenized_lp_pool.token_supply.cast()?,
+        market.amm.order_step_size,
+    )?;
+
+    let pool_delta = PositionDelta {
+        base_asset_amount: -position_delta.base_asset_amount,
+        quote_asset_amount: -position_delta.quote_asset_amount,
+    };
+
+    update_position_and_market(pool_position, market, &pool_delta)?;
+    let pnl = update_position_and_market(position, market, &position_delta)?;
+
+    pool_position.lp_shares = pool_position.lp_shares.safe_sub(n_shares)?;
+    position.lp_shares = position.lp_shares.safe_add(n_shares)?;
+    tokenized_lp_pool.token_supply = tokenized_lp_pool.token_supply.safe_sub(n_tokens)?;
+
+    crate::validation::perp_market::validate_perp_market(market)?;
+    crate::validation::position::validate_perp_position_with_perp_market(position, market)?;
+    crate::validation::position::validate_perp_position_with_perp_market(pool_position, market)?;
+
+    Ok((n_shares, position_delta, pnl))
+}
+
+/// The `numerator / denominator` slice of the pool user's perp position, with the base rounded
+/// down to the order step size so both sides keep standard sized positions
+fn calculate_tokenized_lp_pool_delta(
+    pool_position: &PerpPosition,
+    numerator: u128,
+    denominator: u128,
+    order_step_size: u64,
+) -> DriftResult<PositionDelta> {
+    let (base_asset_amount, _) =
+        crate::math::orders::standardize_base_asset_amount_with_remainder_i128(
+            get_proportion_i128(
+                pool_position.base_asset_amount.cast()?,
+                numerator,
+                denominator,
+            )?,
+            order_step_size.cast()?,
+        )?;
+
+    let quote_asset_amount = get_proportion_i128(
+        pool_position.quote_asset_amount.cast()?,
+        numerator,
+        denominator,
+    )?;
+
+    Ok(PositionDelta {
+        base_asset_amount: base_asset_amount.cast()?,
+        quote_asset_amount: quote_asset_amount.cast()?,
+    })
+}
+
+/// Moves `token_amount` of quote from one user's spot balance to another's. The sender's balance
+/// can go negative, so a pro rata slice of a borrow moves the same way as a deposit
+pub fn transfer_tokenized_lp_pool_collateral(
+    from_user: &mut User,
+    from_user_key: &Pubkey,
+    to_user: &mut User,
+    to_user_key: &Pubkey,
+    quote_spot_market: &mut SpotMarket,
+    token_amount: u128,
+    oracle_price: i64,
+    now: i64,
+) -> DriftResult {
+    if token_amount == 0 {
+        return Ok(());
+    }
+
+    let precision = quote_spot_market.get_precision();
+
+    from_user.increment_total_withdraws(token_amount.cast()?, oracle_price, precision.cast()?)?;
+
+    update_spot_balances_and_cumulative_deposits(
+        token_amount,
+        &SpotBalanceType::Borrow,
+        quote_spot_market,
+        from_user.force_get_spot_position_mut(QUOTE_SPOT_MARKET_INDEX)?,
+        false,
+        None,
+    )?;
+
+    let deposit_record_id = get_then_update_id!(quote_spot_market, next_deposit_record_id);
+    emit!(DepositRecord {
+        ts: now,
+        deposit_record_id,
+        user_authority: from_user.authority,
+        user: *from_user_key,
+        direction: DepositDirection::Withdraw,
+        amount: token_amount.cast()?,
+        oracle_price,
+        market_index: QUOTE_SPOT_MARKET_INDEX,
+        market_deposit_balance: quote_spot_market.deposit_balance,
+        market_withdraw_balance: quote_spot_market.borrow_balance,
+        market_cumulative_deposit_interest: quote_spot_market.cumulative_deposit_interest,
+        market_cumulative_borrow_interest: quote_spot_market.cumulative_borrow_interest,
+        total_deposits_after: from_user.total_deposits,
+        total_withdraws_after: from_user.total_withdraws,
+        explanation: DepositExplanation::Transfer,
+        transfer_user: Some(*to_user_key),
+    });
+
+    to_user.increment_total_deposits(token_amount.cast()?, oracle_price, precision.cast()?)?;
+
+    update_spot_balances_and_cumulative_deposits(
+        token_amount,
+        &SpotBalanceType::Deposit,
+        quote_spot_market,
+        to_user.force_get_spot_position_mut(QUOTE_SPOT_MARKET_INDEX)?,
+        false,
+        None,
+    )?;
+
+    let deposit_record_id = get_then_update_id!(quote_spot_market, next_deposit_record_id);
+    emit!(DepositRecord {
+        ts: now,
+        deposit_record_id,
+        user_authority: to_user.authority,
+        user: *to_user_key,
+        direction: DepositDirection::Deposit,
+        amount: token_amount.cast()?,
+        oracle_price,
+        market_index: QUOTE_SPOT_MARKET_INDEX,
+        market_deposit_balance: quote_spot_market.deposit_balance,
+        market_withdraw_balance: quote_spot_market.borrow_balance,
+        market_cumulative_deposit_interest: quote_spot_market.cumulative_deposit_interest,
+        market_cumulative_borrow_interest: quote_spot_market.cumulative_borrow_interest,
+        total_deposits_after: to_user.total_deposits,
+        total_withdraws_after: to_user.total_withdraws,
+        explanation: DepositExplanation::Transfer,
+        transfer_user: Some(*from_user_key),
+    });
+
+    Ok(())
+}
+
+/// Moves a ranged lp's shares out of the amm when the reserve price leaves their range
+/// and back in when it returns. Shares out of the amm are not in sqrt_k or user_lp_shares, so
+/// settling, open bids/asks and k updates only see the liquidity that is in range
+pub fn update_perp_lp_range_status(
+    position: &mut PerpPosition,
+    market: &mut PerpMarket,
+    perp_lp_range: &mut PerpLpRange,
+    oracle_price: i64,
+    last_add_perp_lp_shares_ts: i64,
+    lp_cooldown_time: i64,
+    now: i64,
+) -> DriftResult<(LPAction, u64, PositionDelta, i64)> {
+    validate!(
+        !market.is_operation_paused(MarketOperation::Lp)?,
+        ErrorCode::MarketActionPaused,
+        "Market lp actions are paused"
+    )?;
+
+    let reserve_price = market.amm.reserve_price()?;
+    let in_range = perp_lp_range.is_in_range(reserve_price);
+
+    let result = if !in_range && position.lp_shares > 0 {
+        // parking the shares removes them from the amm, so it is subject to the same cooldown as removing them
+        let time_since_last_add_liquidity = now.safe_sub(last_add_perp_lp_shares_ts)?;
+        validate!(
+            time_since_last_add_liquidity >= lp_cooldown_time,
+            ErrorCode::TryingToRemoveLiquidityTooFast
+        )?;
+
+        let n_shares = position.lp_shares;
+        let (position_delta, pnl) = settle_ranged_lp_position(position, market, perp_lp_range)?;
+        burn_lp_shares(position, market, n_shares, oracle_price)?;
+
+        perp_lp_range.parked_lp_shares = perp_lp_range.parked_lp_shares.safe_add(n_shares)?;
+
+        (LPAction::DeactivateLiquidity, n_shares, position_delta, pnl)
+    } else if in_range && perp_lp_range.parked_lp_shares > 0 {
+        let n_shares = perp_lp_range.parked_lp_shares;
+        mint_lp_shares(position, market, n_shares)?;
+
+        perp_lp_range.parked_lp_shares = 0;
+
+        (
+            LPAction::ActivateLiquidity,
+            n_shares,
+            PositionDelta::default(),
+            0,
+        )
+    } else {
+        msg!(
+            "reserve price {} in range [{}, {}]: {}, lp shares {}, parked lp shares {}",
+            reserve_price,
+            perp_lp_range.lower_reserve_price,
+            perp_lp_range.upper_reserve_price,
+            in_range,
+            position.lp_shares,
+            perp_lp_range.parked_lp_shares
+        );
+        return Err(ErrorCode::PerpLpRangeUnchanged);
+    };
+
+    perp_lp_range.last_update_ts = now;
+
+    Ok(result)
+}
+
+/// Attributes what the lp shares earned since the stats were last updated to fees, funding on the
+/// lp inventory and inventory pnl marked to the oracle price, and adds them to the stats' running
+/// totals. The position must be settled first so the lp inventory is in its base asset amount.
+/// The stats keep their own amm checkpoints, so settles without them don't change the
+/// attribution. Shares must not change in between though: instructions that change shares update
+/// the stats before the change and set `lp_shares` after it. If shares changed without the
+/// stats, e.g. in a liquidation, attribution restarts from the current amm
+pub fn update_perp_lp_stats(
+    position: &PerpPosition,
+    market: &PerpMarket,
+    perp_lp_stats: &mut PerpLpStats,
+    oracle_price: i64,
+    now: i64,
+) -> DriftResult<LPAttribution> {
+    let attribution = if perp_lp_stats.lp_shares == position.lp_shares {
+        calculate_lp_attribution(&market.amm, perp_lp_stats, oracle_price)?
+    } else {
+        msg!(
+            "lp shares changed from {} to {} without the perp lp stats, restarting attribution",
+            perp_lp_stats.lp_shares,
+            position.lp_shares
+        );
+        LPAttribution::default()
+    };
+
+    let base_asset_amount = perp_lp_stats
+        .base_asset_amount
+        .cast::<i128>()?
+        .safe_add(attribution.base_asset_amount)?;
+
+    // the lp inventory can't be more than the position, e.g. after the user trades out of it
+    let base_asset_amount = if position.base_asset_amount == 0
+        || base_asset_amount.signum() != position.base_asset_amount.signum().cast()?
+    {
+        0
+    } else if base_asset_amount.unsigned_abs() > position.base_asset_amount.unsigned_abs().cast()? {
+        position.base_asset_amount.cast()?
+    } else {
+        base_asset_amount
+    };
+
+    perp_lp_stats.fee_revenue = perp_lp_stats
+        .fee_revenue
+        .safe_add(attribution.fee_revenue.cast()?)?;
+    perp_lp_stats.funding_payment = perp_lp_stats
+        .funding_payment
+        .safe_add(attribution.funding_payment.cast()?)?;
+    perp_lp_stats.inventory_pnl = perp_lp_stats
+        .inventory_pnl
+        .safe_add(attribution.inventory_pnl.cast()?)?;
+
+    perp_lp_stats.base_asset_amount = base_asset_amount.cast()?;
+    perp_lp_stats.lp_shares = position.lp_shares;
+    perp_lp_stats.last_base_asset_amount_per_lp = market.amm.base_asset_amount_per_lp.cast()?;
+    perp_lp_stats.last_quote_asset_amount_per_lp = market.amm.quote_asset_amount_per_lp.cast()?;
+    perp_lp_stats.last_fee_per_lp = market.amm.fee_per_lp;
+    perp_lp_stats.last_cumulative_funding_rate = if base_asset_amount < 0 {
+        market.amm.cumulative_funding_rate_short.cast()?
+    } else {
+        market.amm.cumulative_funding_rate_long.cast()?
+    };
+    perp_lp_stats.last_oracle_price = oracle_price;
+    perp_lp_stats.last_update_ts = now;
+
+    Ok(attribution)
 }
 
 pub fn settle_lp(
@@ -144,7 +606,8 @@ pub fn settle_lp(
                     delta_base_asset_amount: position_delta.base_asset_amount,
                     delta_quote_asset_amount: position_delta.quote_asset_amount,
                     pnl,
-                    n_shares: 0
+                    n_shares: 0,
+                    ..LPRecord::default()
                 });
             }
         }
@@ -161,8 +624,15 @@ pub fn settle_funding_payment_then_lp(
     user_key: &Pubkey,
     market: &mut PerpMarket,
     now: i64,
+    funding_paused: bool,
 ) -> DriftResult {
-    crate::controller::funding::settle_funding_payment(user, user_key, market, now)?;
+    crate::controller::funding::settle_funding_payment(
+        user,
+        user_key,
+        market,
+        now,
+        funding_paused,
+    )?;
     settle_lp(user, user_key, market, now)
 }
 
@@ -249,6 +719,7 @@ pub fn remove_perp_lp_shares(
     user_key: Pubkey,
     shares_to_burn: u64,
     market_index: u16,
+    mut perp_lp_stats: Option<&mut PerpLpStats>,
     now: i64,
 ) -> DriftResult<()> {
     // standardize n shares to burn
@@ -267,6 +738,12 @@ pub fn remove_perp_lp_shares(
 
     let mut market = perp_market_map.get_ref_mut(&market_index)?;
 
+    validate!(
+        !market.is_operation_paused(MarketOperation::Lp)?,
+        ErrorCode::MarketActionPaused,
+        "Market lp actions are paused"
+    )?;
+
     let time_since_last_add_liquidity = now.safe_sub(user.last_add_perp_lp_shares_ts)?;
 
     validate!(
@@ -274,7 +751,12 @@ pub fn remove_perp_lp_shares(
         ErrorCode::TryingToRemoveLiquidityTooFast
     )?;
 
-    controller::funding::settle_funding_payment(user, &user_key, &mut market, now)?;
+    let funding_paused = controller::funding::is_continuous_funding_paused(
+        &market,
+        oracle_map,
+        state.funding_paused()?,
+    )?;
+    controller::funding::settle_funding_payment(user, &user_key, &mut market, now, funding_paused)?;
 
     let position = user.get_perp_position_mut(market_index)?;
 
@@ -284,8 +766,22 @@ pub fn remove_perp_lp_shares(
     )?;
 
     let oracle_price = oracle_map.get_price_data(&market.amm.oracle)?.price;
-    let (position_delta, pnl) =
-        burn_lp_shares(position, &mut market, shares_to_burn, oracle_price)?;
+
+    // settle before burning so the stats attribute with the shares held until now
+    let (position_delta, pnl) = settle_lp_position(position, &mut market)?;
+
+    let attribution = match perp_lp_stats.as_deref_mut() {
+        Some(perp_lp_stats) => {
+            update_perp_lp_stats(position, &market, perp_lp_stats, oracle_price, now)?
+        }
+        None => LPAttribution::default(),
+    };
+
+    burn_lp_shares(position, &mut market, shares_to_burn, oracle_price)?;
+
+    if let Some(perp_lp_stats) = perp_lp_stats {
+        perp_lp_stats.lp_shares = position.lp_shares;
+    }
 
     emit!(LPRecord {
         ts: now,
@@ -296,6 +792,9 @@ pub fn remove_perp_lp_shares(
         delta_base_asset_amount: position_delta.base_asset_amount,
         delta_quote_asset_amount: position_delta.quote_asset_amount,
         pnl,
+        fee_revenue: attribution.fee_revenue.cast()?,
+        funding_payment: attribution.funding_payment.cast()?,
+        inventory_pnl: attribution.inventory_pnl.cast()?,
     });
 
     Ok(())
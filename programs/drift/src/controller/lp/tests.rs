@@ -1,5 +1,6 @@
 use crate::controller::lp::*;
 use crate::controller::pnl::settle_pnl;
+use crate::controller::position::PositionDelta;
 use crate::state::perp_market::AMM;
 use crate::state::user::PerpPosition;
 use std::str::FromStr;
@@ -11,21 +12,26 @@ use crate::create_account_info;
 use crate::create_anchor_account_info;
 use crate::math::casting::Cast;
 use crate::math::constants::{
-    AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_U64, LIQUIDATION_FEE_PRECISION,
-    PEG_PRECISION, QUOTE_PRECISION_I128, QUOTE_SPOT_MARKET_INDEX, SPOT_BALANCE_PRECISION,
+    AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_I64, BASE_PRECISION_U64,
+    FUNDING_RATE_PRECISION_I128, LIQUIDATION_FEE_PRECISION, PEG_PRECISION, PRICE_PRECISION_I64,
+    QUOTE_PRECISION_I128, QUOTE_PRECISION_I64, QUOTE_SPOT_MARKET_INDEX, SPOT_BALANCE_PRECISION,
     SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
 };
+use crate::math::lp::LPAttribution;
 use crate::math::margin::{
     calculate_perp_position_value_and_pnl, meets_maintenance_margin_requirement,
 };
 use crate::state::oracle::OraclePriceData;
 use crate::state::oracle::{HistoricalOracleData, OracleSource};
 use crate::state::oracle_map::OracleMap;
+use crate::state::perp_lp_range::PerpLpRange;
+use crate::state::perp_lp_stats::PerpLpStats;
 use crate::state::perp_market::{MarketStatus, PerpMarket, PoolBalance};
 use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_market::{SpotBalanceType, SpotMarket};
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::state::{OracleGuardRails, State, ValidityGuardRails};
+use crate::state::tokenized_lp_pool::TokenizedLpPool;
 use crate::state::user::{SpotPosition, User};
 use crate::test_utils::*;
 use crate::test_utils::{get_positions, get_pyth_price, get_spot_positions};
@@ -100,6 +106,288 @@ fn test_full_long_settle() {
     assert_eq!(og_market.amm.sqrt_k, market.amm.sqrt_k);
 }
 
+#[test]
+fn test_tokenize_and_redeem_lp_shares() {
+    let mut position = PerpPosition {
+        ..PerpPosition::default()
+    };
+    let mut other_position = PerpPosition {
+        ..PerpPosition::default()
+    };
+    let mut pool_position = PerpPosition {
+        ..PerpPosition::default()
+    };
+
+    let amm = AMM {
+        order_step_size: 1,
+        ..AMM::default_test()
+    };
+    let mut market = PerpMarket {
+        amm,
+        ..PerpMarket::default_test()
+    };
+    let og_market = market;
+
+    let mut tokenized_lp_pool = TokenizedLpPool::default();
+
+    mint_lp_shares(&mut position, &mut market, BASE_PRECISION_U64).unwrap();
+
+    market.amm.base_asset_amount_per_lp = 10;
+    market.amm.quote_asset_amount_per_lp = -10;
+    market.amm.base_asset_amount_with_unsettled_lp = -10;
+    market.amm.base_asset_amount_short = -10;
+
+    // cant tokenize more shares than the position has
+    assert!(tokenize_lp_shares(
+        &mut position,
+        &mut pool_position,
+        &mut market,
+        &mut tokenized_lp_pool,
+        2 * BASE_PRECISION_U64
+    )
+    .is_err());
+
+    let (n_tokens, position_delta, _) = tokenize_lp_shares(
+        &mut position,
+        &mut pool_position,
+        &mut market,
+        &mut tokenized_lp_pool,
+        BASE_PRECISION_U64,
+    )
+    .unwrap();
+
+    // the first shares into the pool mint one token per share and the position keeps what it earned
+    assert_eq!(n_tokens, BASE_PRECISION_U64);
+    assert_eq!(position_delta, PositionDelta::default());
+    assert_eq!(position.lp_shares, 0);
+    assert_eq!(position.base_asset_amount, 10);
+    assert_eq!(position.quote_asset_amount, -10);
+    assert_eq!(pool_position.lp_shares, BASE_PRECISION_U64);
+    assert_eq!(pool_position.base_asset_amount, 0);
+    assert_eq!(pool_position.last_base_asset_amount_per_lp, 10);
+    assert_eq!(pool_position.last_quote_asset_amount_per_lp, -10);
+    assert_eq!(tokenized_lp_pool.token_supply, BASE_PRECISION_U64);
+    assert_eq!(market.amm.base_asset_amount_with_unsettled_lp, 0);
+
+    market.amm.base_asset_amount_per_lp = 30;
+    market.amm.quote_asset_amount_per_lp = -30;
+    market.amm.base_asset_amount_with_unsettled_lp = -20;
+    market.amm.base_asset_amount_short = -30;
+
+    // half the tokens are a claim on half the pool's shares and the inventory it took on
+    let (n_shares, position_delta, _) = redeem_lp_shares(
+        &mut other_position,
+        &mut pool_position,
+        &mut market,
+        &mut tokenized_lp_pool,
+        BASE_PRECISION_U64 / 2,
+    )
+    .unwrap();
+
+    assert_eq!(n_shares, BASE_PRECISION_U64 / 2);
+    assert_eq!(position_delta.base_asset_amount, 10);
+    assert_eq!(position_delta.quote_asset_amount, -10);
+    assert_eq!(other_position.lp_shares, BASE_PRECISION_U64 / 2);
+    assert_eq!(other_position.base_asset_amount, 10);
+    assert_eq!(other_position.quote_asset_amount, -10);
+    assert_eq!(other_position.last_base_asset_amount_per_lp, 30);
+    assert_eq!(other_position.last_quote_asset_amount_per_lp, -30);
+    assert_eq!(pool_position.lp_shares, BASE_PRECISION_U64 / 2);
+    assert_eq!(pool_position.base_asset_amount, 10);
+    assert_eq!(pool_position.quote_asset_amount, -10);
+    assert_eq!(tokenized_lp_pool.token_supply, BASE_PRECISION_U64 / 2);
+    assert_eq!(market.amm.base_asset_amount_with_unsettled_lp, 0);
+
+    // tokenizing into a pool with inventory hands the pool the slice that comes with the shares
+    let (n_tokens, position_delta, _) = tokenize_lp_shares(
+        &mut other_position,
+        &mut pool_position,
+        &mut market,
+        &mut tokenized_lp_pool,
+        BASE_PRECISION_U64 / 2,
+    )
+    .unwrap();
+
+    assert_eq!(n_tokens, BASE_PRECISION_U64 / 2);
+    assert_eq!(position_delta.base_asset_amount, -10);
+    assert_eq!(position_delta.quote_asset_amount, 10);
+    assert_eq!(other_position.lp_shares, 0);
+    assert_eq!(other_position.base_asset_amount, 0);
+    assert_eq!(other_position.quote_asset_amount, 0);
+    assert_eq!(pool_position.lp_shares, BASE_PRECISION_U64);
+    assert_eq!(pool_position.base_asset_amount, 20);
+    assert_eq!(pool_position.quote_asset_amount, -20);
+    assert_eq!(tokenized_lp_pool.token_supply, BASE_PRECISION_U64);
+
+    // cant redeem more tokens than the pool has
+    assert!(redeem_lp_shares(
+        &mut other_position,
+        &mut pool_position,
+        &mut market,
+        &mut tokenized_lp_pool,
+        2 * BASE_PRECISION_U64
+    )
+    .is_err());
+
+    // total shares never change while tokenized
+    assert_eq!(market.amm.user_lp_shares, BASE_PRECISION_U64 as u128);
+    assert_eq!(
+        og_market.amm.sqrt_k + BASE_PRECISION_U64 as u128,
+        market.amm.sqrt_k
+    );
+}
+
+#[test]
+fn test_perp_lp_range_status() {
+    let mut position = PerpPosition {
+        ..PerpPosition::default()
+    };
+
+    let amm = AMM {
+        order_step_size: 1,
+        ..AMM::default_test()
+    };
+    let mut market = PerpMarket {
+        amm,
+        ..PerpMarket::default_test()
+    };
+    let og_market = market;
+
+    mint_lp_shares(&mut position, &mut market, BASE_PRECISION_U64).unwrap();
+
+    let reserve_price = market.amm.reserve_price().unwrap();
+    let mut perp_lp_range = PerpLpRange::default();
+    perp_lp_range
+        .update_range(reserve_price - 2, reserve_price - 1)
+        .unwrap();
+
+    // lp actions paused
+    market.paused_operations = MarketOperation::Lp as u8;
+    assert!(matches!(
+        update_perp_lp_range_status(&mut position, &mut market, &mut perp_lp_range, 0, 0, 2, 2),
+        Err(ErrorCode::MarketActionPaused)
+    ));
+    market.paused_operations = 0;
+
+    // price below range but the shares were added within the lp cooldown
+    assert!(matches!(
+        update_perp_lp_range_status(&mut position, &mut market, &mut perp_lp_range, 0, 0, 2, 1),
+        Err(ErrorCode::TryingToRemoveLiquidityTooFast)
+    ));
+
+    // price below range, shares leave the amm
+    let (action, n_shares, _, _) =
+        update_perp_lp_range_status(&mut position, &mut market, &mut perp_lp_range, 0, 0, 2, 2)
+            .unwrap();
+    assert!(action == LPAction::DeactivateLiquidity);
+    assert_eq!(n_shares, BASE_PRECISION_U64);
+    assert_eq!(position.lp_shares, 0);
+    assert_eq!(perp_lp_range.parked_lp_shares, BASE_PRECISION_U64);
+    assert_eq!(perp_lp_range.last_update_ts, 2);
+    assert_eq!(market.amm.user_lp_shares, 0);
+    assert_eq!(market.amm.sqrt_k, og_market.amm.sqrt_k);
+
+    // still out of range
+    assert!(matches!(
+        update_perp_lp_range_status(&mut position, &mut market, &mut perp_lp_range, 0, 0, 2, 3),
+        Err(ErrorCode::PerpLpRangeUnchanged)
+    ));
+
+    // price back in range, shares return to the amm
+    perp_lp_range
+        .update_range(reserve_price - 1, reserve_price + 1)
+        .unwrap();
+    let (action, n_shares, _, _) =
+        update_perp_lp_range_status(&mut position, &mut market, &mut perp_lp_range, 0, 0, 2, 4)
+            .unwrap();
+    assert!(action == LPAction::ActivateLiquidity);
+    assert_eq!(n_shares, BASE_PRECISION_U64);
+    assert_eq!(position.lp_shares, BASE_PRECISION_U64);
+    assert_eq!(perp_lp_range.parked_lp_shares, 0);
+    assert_eq!(market.amm.user_lp_shares, BASE_PRECISION_U64 as u128);
+    assert_eq!(
+        market.amm.sqrt_k,
+        og_market.amm.sqrt_k + BASE_PRECISION_U64 as u128
+    );
+
+    assert!(perp_lp_range
+        .update_range(reserve_price, reserve_price)
+        .is_err());
+}
+
+#[test]
+fn test_update_perp_lp_stats() {
+    let mut perp_lp_stats = PerpLpStats {
+        lp_shares: 100 * BASE_PRECISION_U64,
+        base_asset_amount: BASE_PRECISION_I64,
+        last_oracle_price: 100 * PRICE_PRECISION_I64,
+        ..PerpLpStats::default()
+    };
+
+    // lp shares bought 1 base for 100 and earned 1 in fees, on top of the 1 base they held
+    let mut market = PerpMarket {
+        amm: AMM {
+            base_asset_amount_per_lp: 10_000_000,
+            quote_asset_amount_per_lp: -990_000,
+            fee_per_lp: 10_000,
+            cumulative_funding_rate_long: FUNDING_RATE_PRECISION_I128,
+            ..AMM::default_test()
+        },
+        ..PerpMarket::default_test()
+    };
+
+    let mut position = PerpPosition {
+        lp_shares: 100 * BASE_PRECISION_U64,
+        base_asset_amount: 2 * BASE_PRECISION_I64,
+        ..PerpPosition::default()
+    };
+
+    let oracle_price = 101 * PRICE_PRECISION_I64;
+    let attribution =
+        update_perp_lp_stats(&position, &market, &mut perp_lp_stats, oracle_price, 1).unwrap();
+
+    assert_eq!(attribution.fee_revenue, QUOTE_PRECISION_I128);
+    assert_eq!(perp_lp_stats.fee_revenue, QUOTE_PRECISION_I64);
+    assert_eq!(perp_lp_stats.funding_payment, -QUOTE_PRECISION_I64);
+    assert_eq!(perp_lp_stats.inventory_pnl, 2 * QUOTE_PRECISION_I64);
+    assert_eq!(perp_lp_stats.base_asset_amount, 2 * BASE_PRECISION_I64);
+    assert_eq!(perp_lp_stats.last_fee_per_lp, 10_000);
+    assert_eq!(
+        perp_lp_stats.last_cumulative_funding_rate as i128,
+        FUNDING_RATE_PRECISION_I128
+    );
+
+    // nothing new to attribute
+    let attribution =
+        update_perp_lp_stats(&position, &market, &mut perp_lp_stats, oracle_price, 2).unwrap();
+    assert_eq!(attribution, LPAttribution::default());
+
+    // the user trades out of half the position, so only half is lp inventory
+    position.base_asset_amount = BASE_PRECISION_I64;
+    update_perp_lp_stats(&position, &market, &mut perp_lp_stats, oracle_price, 3).unwrap();
+    assert_eq!(perp_lp_stats.base_asset_amount, BASE_PRECISION_I64);
+
+    // shares burned without the stats (e.g. liquidation) restart the attribution
+    position.lp_shares = 50 * BASE_PRECISION_U64;
+    market.amm.fee_per_lp = 20_000;
+    market.amm.quote_asset_amount_per_lp = -980_000;
+    let attribution =
+        update_perp_lp_stats(&position, &market, &mut perp_lp_stats, oracle_price, 4).unwrap();
+    assert_eq!(attribution, LPAttribution::default());
+    assert_eq!(perp_lp_stats.fee_revenue, QUOTE_PRECISION_I64);
+    assert_eq!(perp_lp_stats.lp_shares, 50 * BASE_PRECISION_U64);
+    assert_eq!(perp_lp_stats.last_fee_per_lp, 20_000);
+
+    // fees after the restart are attributed to the remaining shares
+    market.amm.fee_per_lp = 30_000;
+    market.amm.quote_asset_amount_per_lp = -970_000;
+    let attribution =
+        update_perp_lp_stats(&position, &market, &mut perp_lp_stats, oracle_price, 5).unwrap();
+    assert_eq!(attribution.fee_revenue, QUOTE_PRECISION_I128 / 2);
+    assert_eq!(perp_lp_stats.fee_revenue, 3 * QUOTE_PRECISION_I64 / 2);
+    assert_eq!(perp_lp_stats.inventory_pnl, 2 * QUOTE_PRECISION_I64);
+}
+
 #[test]
 fn test_full_short_settle() {
     let mut position = PerpPosition {
@@ -1,4 +1,6 @@
 pub mod amm;
+pub mod amm_hedge;
+pub mod circuit_breaker;
 pub mod funding;
 pub mod insurance;
 pub mod liquidation;
@@ -11,3 +13,4 @@ pub mod repeg;
 pub mod spot_balance;
 pub mod spot_position;
 pub mod token;
+pub mod user_health;
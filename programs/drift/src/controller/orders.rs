@@ -1,5 +1,5 @@
 use std::cell::RefMut;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::DerefMut;
 use std::u64;
 
@@ -24,19 +24,20 @@ use crate::controller::spot_position::{
 use crate::error::DriftResult;
 use crate::error::ErrorCode;
 use crate::instructions::{OrderParams, PlaceOrderOptions};
-use crate::load_mut;
 use crate::math::amm_jit::calculate_amm_jit_liquidity;
 use crate::math::auction::calculate_auction_prices;
 use crate::math::casting::Cast;
 use crate::math::constants::{
-    BASE_PRECISION_U64, FEE_POOL_TO_REVENUE_POOL_THRESHOLD, FIVE_MINUTE, ONE_HOUR, PERP_DECIMALS,
-    QUOTE_SPOT_MARKET_INDEX,
+    BASE_PRECISION_U64, FEE_POOL_TO_REVENUE_POOL_THRESHOLD, FIVE_MINUTE, LP_HEDGE_MIN_INTERVAL,
+    ONE_HOUR, PERP_DECIMALS, QUOTE_SPOT_MARKET_INDEX,
 };
 use crate::math::fees::{ExternalFillFees, FillFees};
 use crate::math::fulfillment::{
     determine_perp_fulfillment_methods, determine_spot_fulfillment_methods,
 };
-use crate::math::liquidation::validate_user_not_being_liquidated;
+use crate::math::liquidation::{
+    calculate_base_asset_amount_to_cover_margin_shortage, validate_user_not_being_liquidated,
+};
 use crate::math::matching::{
     are_orders_same_market_but_different_sides, calculate_fill_for_matched_orders,
     calculate_filler_multiplier_for_matched_orders, do_orders_cross, is_maker_for_taker,
@@ -50,6 +51,7 @@ use crate::math::{amm, fees, margin::*, orders::*};
 use crate::{controller, PostOnlyParam};
 use crate::{get_struct_values, ModifyOrderParams};
 use crate::{get_then_update_id, ModifyOrderPolicy};
+use crate::{load, load_mut};
 
 use crate::math::amm::calculate_amm_available_liquidity;
 use crate::math::safe_unwrap::SafeUnwrap;
@@ -59,10 +61,12 @@ use crate::state::events::{OrderAction, OrderActionExplanation};
 use crate::state::fulfillment::{PerpFulfillmentMethod, SpotFulfillmentMethod};
 use crate::state::oracle::OraclePriceData;
 use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_lp_hedge::PerpLpHedge;
 use crate::state::perp_market::{AMMLiquiditySplit, MarketStatus, PerpMarket};
 use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_fulfillment_params::{ExternalSpotFill, SpotFulfillmentParams};
-use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+use crate::state::spot_market::{ProtocolRevenueSource, SpotBalanceType, SpotMarket};
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::state::FeeStructure;
 use crate::state::state::*;
@@ -71,9 +75,11 @@ use crate::state::user::{
     AssetType, Order, OrderStatus, OrderTriggerCondition, OrderType, UserStats,
 };
 use crate::state::user::{MarketType, User};
+use crate::state::user_delegate::UserDelegate;
 use crate::state::user_map::{UserMap, UserStatsMap};
 use crate::validate;
 use crate::validation;
+use crate::validation::delegate::validate_delegate_order_params;
 use crate::validation::order::{validate_order, validate_spot_order};
 
 #[cfg(test)]
@@ -249,7 +255,8 @@ pub fn place_perp_order(
         auction_end_price,
         auction_duration,
         max_ts,
-        padding: [0; 3],
+        is_lp_hedge: options.explanation == OrderActionExplanation::LpHedgeOrder,
+        padding: [0; 2],
     };
 
     let valid_oracle_price = get_valid_oracle_price(
@@ -309,6 +316,16 @@ pub fn place_perp_order(
         if !meets_initial_margin_requirement {
             return Err(ErrorCode::InvalidOrderForInitialMarginReq);
         }
+
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            state,
+            now,
+        )?;
     }
 
     if force_reduce_only && !risk_decreasing {
@@ -345,7 +362,7 @@ pub fn place_perp_order(
     let order_action_record = get_order_action_record(
         now,
         OrderAction::Place,
-        OrderActionExplanation::None,
+        options.explanation,
         market_index,
         None,
         None,
@@ -683,6 +700,7 @@ pub fn modify_order(
     spot_market_map: &SpotMarketMap,
     oracle_map: &mut OracleMap,
     clock: &Clock,
+    user_delegate: Option<&UserDelegate>,
 ) -> DriftResult {
     let user_key = user_loader.key();
     let mut user = load_mut!(user_loader)?;
@@ -738,6 +756,16 @@ pub fn modify_order(
     let order_params =
         merge_modify_order_params_with_existing_order(&existing_order, &modify_order_params)?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            user_delegate,
+            &order_params,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+        )?;
+    }
+
     if order_params.market_type == MarketType::Perp {
         place_perp_order(
             state,
@@ -885,20 +913,33 @@ pub fn fill_perp_order(
 
     // settle lp position so its tradeable
     let mut market = perp_market_map.get_ref_mut(&market_index)?;
-    controller::lp::settle_funding_payment_then_lp(user, &user_key, &mut market, now)?;
+    let funding_paused = controller::funding::is_continuous_funding_paused(
+        &market,
+        oracle_map,
+        state.funding_paused()?,
+    )?;
+    controller::lp::settle_funding_payment_then_lp(
+        user,
+        &user_key,
+        &mut market,
+        now,
+        funding_paused,
+    )?;
 
     validate!(
-        matches!(
-            market.status,
-            MarketStatus::Active
-                | MarketStatus::FundingPaused
-                | MarketStatus::ReduceOnly
-                | MarketStatus::WithdrawPaused
-        ),
+        market.status.is_active_or_legacy_paused() || market.status == MarketStatus::ReduceOnly,
         ErrorCode::MarketFillOrderPaused,
         "Market unavailable for fills"
     )?;
 
+    let match_fill_paused = market.is_operation_paused(MarketOperation::MatchFill)?;
+
+    validate!(
+        !(match_fill_paused && market.is_operation_paused(MarketOperation::AmmFill)?),
+        ErrorCode::MarketFillOrderPaused,
+        "Market fills are paused"
+    )?;
+
     drop(market);
 
     validate!(
@@ -937,10 +978,11 @@ pub fn fill_perp_order(
     let oracle_validity: OracleValidity;
     let oracle_price: i64;
     let oracle_twap_5min: i64;
+    let in_circuit_breaker_cooldown: bool;
     let mut amm_is_available = !state.amm_paused()?;
     {
         let market = &mut perp_market_map.get_ref_mut(&market_index)?;
-        amm_is_available &= market.status != MarketStatus::AmmPaused;
+        amm_is_available &= !market.is_operation_paused(MarketOperation::AmmFill)?;
         validation::perp_market::validate_perp_market(market)?;
         validate!(
             market.is_active(now)?,
@@ -964,6 +1006,14 @@ pub fn fill_perp_order(
             .amm
             .historical_oracle_data
             .last_oracle_price_twap_5min;
+
+        in_circuit_breaker_cooldown =
+            controller::circuit_breaker::update_perp_market_circuit_breaker(
+                market,
+                oracle_price,
+                reserve_price_before,
+                now,
+            )?;
     }
 
     // allow oracle price to be used to calculate limit price if it's valid or stale for amm
@@ -986,7 +1036,7 @@ pub fn fill_perp_order(
         (None, None)
     };
 
-    let maker_orders_info = get_maker_orders_info(
+    let mut maker_orders_info = get_maker_orders_info(
         perp_market_map,
         spot_market_map,
         oracle_map,
@@ -1000,8 +1050,20 @@ pub fn fill_perp_order(
         jit_maker_order_id,
         now,
         slot,
+        funding_paused,
     )?;
 
+    if match_fill_paused {
+        msg!("perp market {} match fills paused", market_index);
+        maker_orders_info.clear();
+    } else if in_circuit_breaker_cooldown {
+        maker_orders_info = get_position_reducing_maker_orders_info(
+            maker_orders_info,
+            makers_and_referrer,
+            market_index,
+        )?;
+    }
+
     let referrer_info = get_referrer_info(
         user_stats,
         makers_and_referrer,
@@ -1069,6 +1131,23 @@ pub fn fill_perp_order(
         return Ok(0);
     }
 
+    if in_circuit_breaker_cooldown
+        && !determine_if_user_order_is_position_decreasing(user, market_index, order_index)?
+    {
+        msg!(
+            "perp market {} in circuit breaker cooldown, only position reducing orders can be filled",
+            market_index
+        );
+        // update filler last active so tx doesn't revert
+        if let Some(filler) = filler.as_deref_mut() {
+            filler.update_last_active_slot(slot);
+        }
+        return Ok(0);
+    }
+
+    let is_lp_hedge_order = user.orders[order_index].is_lp_hedge;
+    let base_asset_amount_filled_before = user.orders[order_index].base_asset_amount_filled;
+
     let (base_asset_amount, quote_asset_amount) = fulfill_perp_order(
         user,
         order_index,
@@ -1107,6 +1186,18 @@ pub fn fill_perp_order(
                 .oracle_guard_rails
                 .max_oracle_twap_5min_percent_divergence(),
         )?;
+
+        // keepers are paid for lp hedge orders from the lp's pnl once the hedge starts filling
+        if is_lp_hedge_order && base_asset_amount_filled_before == 0 {
+            let mut market = perp_market_map.get_ref_mut(&market_index)?;
+            pay_keeper_flat_reward_for_perps(
+                user,
+                filler.as_deref_mut(),
+                market.deref_mut(),
+                state.perp_fee_structure.flat_filler_fee,
+                slot,
+            )?;
+        }
     }
 
     let base_asset_amount_after = user.perp_positions[position_index].base_asset_amount;
@@ -1166,7 +1257,7 @@ pub fn fill_perp_order(
     {
         let market = &mut perp_market_map.get_ref_mut(&market_index)?;
         let funding_paused =
-            state.funding_paused()? || matches!(market.status, MarketStatus::FundingPaused);
+            state.funding_paused()? || market.is_operation_paused(MarketOperation::Funding)?;
 
         controller::funding::update_funding_rate(
             market_index,
@@ -1179,6 +1270,32 @@ pub fn fill_perp_order(
         )?;
     }
 
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        state,
+        now,
+    )?;
+
+    let maker_keys: BTreeSet<Pubkey> = maker_orders_info
+        .iter()
+        .map(|(maker_key, _, _)| *maker_key)
+        .collect();
+    for maker_key in maker_keys {
+        controller::user_health::update_user_health(
+            &mut makers_and_referrer.get_ref_mut(&maker_key)?,
+            &maker_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            state,
+            now,
+        )?;
+    }
+
     user.update_last_active_slot(slot);
 
     Ok(base_asset_amount)
@@ -1244,6 +1361,27 @@ pub fn validate_market_within_price_band(
     Ok(true)
 }
 
+fn get_position_reducing_maker_orders_info(
+    maker_orders_info: Vec<(Pubkey, usize, u64)>,
+    makers_and_referrer: &UserMap,
+    market_index: u16,
+) -> DriftResult<Vec<(Pubkey, usize, u64)>> {
+    let mut position_reducing_maker_orders_info = Vec::with_capacity(maker_orders_info.len());
+    for (maker_key, maker_order_index, maker_order_price) in maker_orders_info {
+        let maker = makers_and_referrer.get_ref(&maker_key)?;
+        if determine_if_user_order_is_position_decreasing(&maker, market_index, maker_order_index)?
+        {
+            position_reducing_maker_orders_info.push((
+                maker_key,
+                maker_order_index,
+                maker_order_price,
+            ));
+        }
+    }
+
+    Ok(position_reducing_maker_orders_info)
+}
+
 #[allow(clippy::type_complexity)]
 fn get_maker_orders_info(
     perp_market_map: &PerpMarketMap,
@@ -1259,6 +1397,7 @@ fn get_maker_orders_info(
     jit_maker_order_id: Option<u32>,
     now: i64,
     slot: u64,
+    funding_paused: bool,
 ) -> DriftResult<Vec<(Pubkey, usize, u64)>> {
     let maker_direction = taker_order.direction.opposite();
 
@@ -1292,7 +1431,7 @@ fn get_maker_orders_info(
 
         maker.update_last_active_slot(slot);
 
-        settle_funding_payment(&mut maker, maker_key, &mut market, now)?;
+        settle_funding_payment(&mut maker, maker_key, &mut market, now, funding_paused)?;
 
         let initial_margin_ratio = market.margin_ratio_initial;
         let maintenance_margin_ratio = market.margin_ratio_maintenance;
@@ -2610,6 +2749,28 @@ pub fn force_cancel_orders(
     oracle_map: &mut OracleMap,
     filler: &AccountLoader<User>,
     clock: &Clock,
+) -> DriftResult {
+    _force_cancel_orders(
+        state,
+        user,
+        spot_market_map,
+        perp_market_map,
+        oracle_map,
+        filler,
+        clock,
+        true,
+    )
+}
+
+fn _force_cancel_orders(
+    state: &State,
+    user: &AccountLoader<User>,
+    spot_market_map: &SpotMarketMap,
+    perp_market_map: &PerpMarketMap,
+    oracle_map: &mut OracleMap,
+    filler: &AccountLoader<User>,
+    clock: &Clock,
+    pay_keeper_reward: bool,
 ) -> DriftResult {
     let now = clock.unix_timestamp;
     let slot = clock.slot;
@@ -2677,6 +2838,7 @@ pub fn force_cancel_orders(
                 state.perp_fee_structure.flat_filler_fee
             }
         };
+        let fee = if pay_keeper_reward { fee } else { 0 };
 
         total_fee = total_fee.safe_add(fee)?;
 
@@ -2708,6 +2870,249 @@ pub fn force_cancel_orders(
     Ok(())
 }
 
+/// Places a reduce only auction order for a user between the initial and maintenance margin
+/// requirement, so they can deleverage before they cross into liquidation
+///
+/// Risk increasing orders are force canceled first without a cancel reward. The keeper is only
+/// paid the flat filler fee for placing the order
+pub fn force_place_deleverage_order(
+    state: &State,
+    user: &AccountLoader<User>,
+    spot_market_map: &SpotMarketMap,
+    perp_market_map: &PerpMarketMap,
+    oracle_map: &mut OracleMap,
+    filler: &AccountLoader<User>,
+    clock: &Clock,
+    market_index: u16,
+) -> DriftResult {
+    _force_cancel_orders(
+        state,
+        user,
+        spot_market_map,
+        perp_market_map,
+        oracle_map,
+        filler,
+        clock,
+        false,
+    )?;
+
+    let params = {
+        let user = &load!(user)?;
+
+        validate!(
+            meets_maintenance_margin_requirement(
+                user,
+                perp_market_map,
+                spot_market_map,
+                oracle_map
+            )?,
+            ErrorCode::InvalidDeleverageOrder,
+            "user doesnt meet maintenance margin requirement and must be liquidated"
+        )?;
+
+        let position = user.get_perp_position(market_index)?;
+
+        validate!(
+            position.base_asset_amount != 0,
+            ErrorCode::InvalidDeleverageOrder,
+            "user has no position in perp market {}",
+            market_index
+        )?;
+
+        validate!(
+            !position.has_open_order(),
+            ErrorCode::InvalidDeleverageOrder,
+            "user already has open orders in perp market {}",
+            market_index
+        )?;
+
+        let (margin_requirement, total_collateral, _, _) =
+            calculate_margin_requirement_and_total_collateral(
+                user,
+                perp_market_map,
+                MarginRequirementType::Initial,
+                spot_market_map,
+                oracle_map,
+                None,
+            )?;
+
+        let margin_shortage = margin_requirement
+            .cast::<i128>()?
+            .safe_sub(total_collateral)?
+            .unsigned_abs();
+
+        let market = perp_market_map.get_ref(&market_index)?;
+        let oracle_price = oracle_map.get_price_data(&market.amm.oracle)?.price;
+        let quote_oracle_price = oracle_map
+            .get_price_data(&spot_market_map.get_quote_spot_market()?.oracle)?
+            .price;
+
+        let margin_ratio = market.get_margin_ratio(
+            position.base_asset_amount.unsigned_abs().cast()?,
+            MarginRequirementType::Initial,
+        )?;
+
+        let base_asset_amount_to_cover_margin_shortage = standardize_base_asset_amount_ceil(
+            calculate_base_asset_amount_to_cover_margin_shortage(
+                margin_shortage,
+                margin_ratio,
+                0,
+                0,
+                oracle_price,
+                quote_oracle_price,
+            )?,
+            market.amm.order_step_size,
+        )?;
+
+        OrderParams {
+            order_type: OrderType::Market,
+            market_type: MarketType::Perp,
+            direction: position.get_direction_to_close(),
+            base_asset_amount: base_asset_amount_to_cover_margin_shortage
+                .min(position.base_asset_amount.unsigned_abs()),
+            market_index,
+            reduce_only: true,
+            ..OrderParams::default()
+        }
+    };
+
+    place_perp_order(
+        state,
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        clock,
+        params,
+        PlaceOrderOptions {
+            explanation: OrderActionExplanation::DeleverageOrder,
+            ..PlaceOrderOptions::default()
+        },
+    )?;
+
+    let user = &mut load_mut!(user)?;
+    let filler = &mut load_mut!(filler)?;
+
+    pay_keeper_flat_reward_for_spot(
+        user,
+        Some(filler),
+        spot_market_map.get_quote_spot_market_mut()?.deref_mut(),
+        state.perp_fee_structure.flat_filler_fee,
+    )?;
+
+    Ok(())
+}
+
+pub fn place_perp_lp_hedge_order(
+    state: &State,
+    user: &AccountLoader<User>,
+    perp_lp_hedge: &mut PerpLpHedge,
+    spot_market_map: &SpotMarketMap,
+    perp_market_map: &PerpMarketMap,
+    oracle_map: &mut OracleMap,
+    clock: &Clock,
+    market_index: u16,
+) -> DriftResult {
+    let now = clock.unix_timestamp;
+
+    validate!(
+        now.safe_sub(perp_lp_hedge.last_hedge_ts)? >= LP_HEDGE_MIN_INTERVAL,
+        ErrorCode::InvalidLpHedgeOrder,
+        "last hedge at {}, must wait {}s between hedges",
+        perp_lp_hedge.last_hedge_ts,
+        LP_HEDGE_MIN_INTERVAL
+    )?;
+
+    {
+        let user_key = user.key();
+        let user = &mut load_mut!(user)?;
+        let market = &mut perp_market_map.get_ref_mut(&market_index)?;
+        let funding_paused = controller::funding::is_continuous_funding_paused(
+            market,
+            oracle_map,
+            state.funding_paused()?,
+        )?;
+        controller::lp::settle_funding_payment_then_lp(
+            user,
+            &user_key,
+            market,
+            now,
+            funding_paused,
+        )?;
+    }
+
+    let params = {
+        let user = &load!(user)?;
+        let position = user.get_perp_position(market_index)?;
+
+        validate!(
+            position.is_lp(),
+            ErrorCode::InvalidLpHedgeOrder,
+            "user has no lp shares in perp market {}",
+            market_index
+        )?;
+
+        validate!(
+            !position.has_open_order(),
+            ErrorCode::InvalidLpHedgeOrder,
+            "user already has open orders in perp market {}",
+            market_index
+        )?;
+
+        let base_asset_amount = position.base_asset_amount.unsigned_abs();
+
+        validate!(
+            base_asset_amount > perp_lp_hedge.base_asset_amount_threshold,
+            ErrorCode::InvalidLpHedgeOrder,
+            "base asset amount {} not above hedge threshold {}",
+            base_asset_amount,
+            perp_lp_hedge.base_asset_amount_threshold
+        )?;
+
+        // only hedge the excess so the lp keeps the exposure it opted into
+        let market = perp_market_map.get_ref(&market_index)?;
+        let base_asset_amount = standardize_base_asset_amount(
+            base_asset_amount.safe_sub(perp_lp_hedge.base_asset_amount_threshold)?,
+            market.amm.order_step_size,
+        )?;
+
+        validate!(
+            base_asset_amount > 0,
+            ErrorCode::InvalidLpHedgeOrder,
+            "excess over hedge threshold below order step size {}",
+            market.amm.order_step_size
+        )?;
+
+        OrderParams {
+            order_type: OrderType::Market,
+            market_type: MarketType::Perp,
+            direction: position.get_direction_to_close(),
+            base_asset_amount,
+            market_index,
+            reduce_only: true,
+            ..OrderParams::default()
+        }
+    };
+
+    place_perp_order(
+        state,
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        clock,
+        params,
+        PlaceOrderOptions {
+            explanation: OrderActionExplanation::LpHedgeOrder,
+            ..PlaceOrderOptions::default()
+        },
+    )?;
+
+    perp_lp_hedge.last_hedge_ts = now;
+
+    Ok(())
+}
+
 pub fn can_reward_user_with_perp_pnl(user: &mut Option<&mut User>, market_index: u16) -> bool {
     match user.as_mut() {
         Some(user) => user.force_get_perp_position_mut(market_index).is_ok(),
@@ -2967,7 +3372,8 @@ pub fn place_spot_order(
         auction_end_price,
         auction_duration,
         max_ts,
-        padding: [0; 3],
+        is_lp_hedge: false,
+        padding: [0; 2],
     };
 
     let valid_oracle_price = Some(oracle_price_data.price);
@@ -3016,6 +3422,16 @@ pub fn place_spot_order(
         if !meets_initial_margin_requirement {
             return Err(ErrorCode::InvalidOrderForInitialMarginReq);
         }
+
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            state,
+            now,
+        )?;
     }
 
     validate_spot_margin_trading(user, spot_market_map, oracle_map)?;
@@ -3076,7 +3492,7 @@ pub fn fill_spot_order(
     maker_stats: Option<&AccountLoader<UserStats>>,
     maker_order_id: Option<u32>,
     clock: &Clock,
-    fulfillment_params: &mut dyn SpotFulfillmentParams,
+    fulfillment_params: &mut [&mut dyn SpotFulfillmentParams],
 ) -> DriftResult<u64> {
     let now = clock.unix_timestamp;
     let slot = clock.slot;
@@ -3103,7 +3519,7 @@ pub fn fill_spot_order(
     {
         let spot_market = spot_market_map.get_ref(&order_market_index)?;
         validate!(
-            spot_market.fills_enabled(),
+            spot_market.fills_enabled()?,
             ErrorCode::MarketFillOrderPaused,
             "Market unavailable for fills"
         )?;
@@ -3359,6 +3775,28 @@ pub fn fill_spot_order(
         .get_ref(&order_market_index)?
         .validate_max_token_deposits()?;
 
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        state,
+        now,
+    )?;
+
+    if let (Some(maker), Some(maker_key)) = (maker.as_deref_mut(), maker_key.as_ref()) {
+        controller::user_health::update_user_health(
+            maker,
+            maker_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            state,
+            now,
+        )?;
+    }
+
     user.update_last_active_slot(slot);
 
     Ok(base_asset_amount)
@@ -3540,15 +3978,55 @@ fn fulfill_spot_order(
     now: i64,
     slot: u64,
     fee_structure: &FeeStructure,
-    fulfillment_params: &mut dyn SpotFulfillmentParams,
+    fulfillment_params: &mut [&mut dyn SpotFulfillmentParams],
 ) -> DriftResult<(u64, u64)> {
     let base_market_index = user.orders[user_order_index].market_index;
 
-    let fulfillment_methods = determine_spot_fulfillment_methods(
-        &user.orders[user_order_index],
-        maker.is_some(),
-        fulfillment_params.is_external(),
-    )?;
+    let fulfillment_methods = {
+        let base_market = spot_market_map.get_ref(&base_market_index)?;
+        let oracle_price = oracle_map.get_price_data(&base_market.oracle)?.price;
+        let taker_order = &user.orders[user_order_index];
+
+        let taker_price = taker_order.get_limit_price(
+            Some(oracle_price),
+            None,
+            slot,
+            base_market.order_tick_size,
+        )?;
+
+        let maker_price = match (maker.as_deref(), maker_order_index) {
+            (Some(maker), Some(maker_order_index)) => {
+                Some(maker.orders[maker_order_index].force_get_limit_price(
+                    Some(oracle_price),
+                    None,
+                    slot,
+                    base_market.order_tick_size,
+                )?)
+            }
+            _ => None,
+        };
+
+        let mut external_market_prices = Vec::with_capacity(fulfillment_params.len());
+        for params in fulfillment_params.iter() {
+            let price = if params.is_external() {
+                let (best_bid, best_ask) = params.get_best_bid_and_ask()?;
+                match taker_order.direction {
+                    PositionDirection::Long => best_ask,
+                    PositionDirection::Short => best_bid,
+                }
+            } else {
+                None
+            };
+            external_market_prices.push(price);
+        }
+
+        determine_spot_fulfillment_methods(
+            taker_order,
+            taker_price,
+            maker_price,
+            &external_market_prices,
+        )?
+    };
 
     let mut quote_market = spot_market_map.get_quote_spot_market_mut()?;
     let mut base_market = spot_market_map.get_ref_mut(&base_market_index)?;
@@ -3580,22 +4058,25 @@ fn fulfill_spot_order(
                 oracle_map,
                 fee_structure,
             )?,
-            SpotFulfillmentMethod::ExternalMarket => fulfill_spot_order_with_external_market(
-                &mut base_market,
-                &mut quote_market,
-                user,
-                user_stats,
-                user_order_index,
-                user_key,
-                filler.as_deref_mut(),
-                filler_stats.as_deref_mut(),
-                filler_key,
-                now,
-                slot,
-                oracle_map,
-                fee_structure,
-                fulfillment_params,
-            )?,
+            SpotFulfillmentMethod::ExternalMarket(index, price_limit) => {
+                fulfill_spot_order_with_external_market(
+                    &mut base_market,
+                    &mut quote_market,
+                    user,
+                    user_stats,
+                    user_order_index,
+                    user_key,
+                    filler.as_deref_mut(),
+                    filler_stats.as_deref_mut(),
+                    filler_key,
+                    now,
+                    slot,
+                    oracle_map,
+                    fee_structure,
+                    *price_limit,
+                    &mut **fulfillment_params.get_mut(*index).safe_unwrap()?,
+                )?
+            }
         };
 
         base_asset_amount = base_asset_amount.safe_add(base_filled)?;
@@ -3955,11 +4436,15 @@ pub fn fulfill_spot_order_with_match(
     )?;
 
     if fee_pool_amount > FEE_POOL_TO_REVENUE_POOL_THRESHOLD * 2 {
+        let revenue_pool_transfer = fee_pool_amount - FEE_POOL_TO_REVENUE_POOL_THRESHOLD;
         transfer_spot_balance_to_revenue_pool(
-            fee_pool_amount - FEE_POOL_TO_REVENUE_POOL_THRESHOLD,
+            revenue_pool_transfer,
             quote_market,
             &mut base_market.spot_fee_pool,
         )?;
+        quote_market
+            .protocol_revenue
+            .record_revenue(ProtocolRevenueSource::TakerFee, revenue_pool_transfer)?;
     }
 
     let fill_record_id = get_then_update_id!(base_market, next_fill_record_id);
@@ -4021,6 +4506,7 @@ pub fn fulfill_spot_order_with_external_market(
     slot: u64,
     oracle_map: &mut OracleMap,
     fee_structure: &FeeStructure,
+    price_limit: Option<u64>,
     fulfillment_params: &mut dyn SpotFulfillmentParams,
 ) -> DriftResult<(u64, u64)> {
     let oracle_price = oracle_map.get_price_data(&base_market.oracle)?.price;
@@ -4111,6 +4597,13 @@ pub fn fulfill_spot_order_with_external_market(
         }
     };
 
+    // only take liquidity up to the price of the next best venue
+    let taker_price = match (price_limit, order_direction) {
+        (Some(price_limit), PositionDirection::Long) => taker_price.min(price_limit),
+        (Some(price_limit), PositionDirection::Short) => taker_price.max(price_limit),
+        (None, _) => taker_price,
+    };
+
     let ExternalSpotFill {
         base_asset_amount_filled,
         base_update_direction,
@@ -4154,11 +4647,15 @@ pub fn fulfill_spot_order_with_external_market(
     )?;
 
     if fee_pool_amount > FEE_POOL_TO_REVENUE_POOL_THRESHOLD * 2 {
+        let revenue_pool_transfer = fee_pool_amount - FEE_POOL_TO_REVENUE_POOL_THRESHOLD;
         transfer_spot_balance_to_revenue_pool(
-            fee_pool_amount - FEE_POOL_TO_REVENUE_POOL_THRESHOLD,
+            revenue_pool_transfer,
             quote_market,
             &mut base_market.spot_fee_pool,
         )?;
+        quote_market
+            .protocol_revenue
+            .record_revenue(ProtocolRevenueSource::TakerFee, revenue_pool_transfer)?;
     }
 
     let ExternalFillFees {
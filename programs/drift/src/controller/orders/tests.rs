@@ -4670,6 +4670,191 @@ pub mod fill_order {
 
         assert_eq!(err, Err(ErrorCode::MaxOpenInterest));
     }
+
+    #[test]
+    fn lp_hedge_order_pays_keeper_on_fill() {
+        let clock = Clock {
+            slot: 6,
+            epoch_start_timestamp: 0,
+            epoch: 0,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 0,
+        };
+
+        let state = State {
+            min_perp_auction_duration: 1,
+            default_market_order_time_in_force: 10,
+            ..State::default()
+        };
+
+        let mut quote_asset_amounts = vec![];
+        for is_lp_hedge in [false, true] {
+            let mut oracle_price = get_pyth_price(100, 6);
+            let oracle_price_key =
+                Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+            let pyth_program = crate::ids::pyth_program::id();
+            create_account_info!(
+                oracle_price,
+                &oracle_price_key,
+                &pyth_program,
+                oracle_account_info
+            );
+            let mut oracle_map =
+                OracleMap::load_one(&oracle_account_info, clock.slot, None).unwrap();
+
+            let mut market = PerpMarket {
+                amm: AMM {
+                    base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                    quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                    terminal_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                    sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                    peg_multiplier: 100 * PEG_PRECISION,
+                    max_slippage_ratio: 100,
+                    max_fill_reserve_fraction: 100,
+                    order_step_size: 1000,
+                    order_tick_size: 1,
+                    oracle: oracle_price_key,
+                    max_spread: 1000,
+                    base_spread: 0,
+                    long_spread: 0,
+                    short_spread: 0,
+                    historical_oracle_data: HistoricalOracleData {
+                        last_oracle_price_twap: oracle_price.twap as i64,
+                        last_oracle_price_twap_5min: oracle_price.twap as i64,
+                        last_oracle_price: oracle_price.agg.price as i64,
+                        ..HistoricalOracleData::default()
+                    },
+                    ..AMM::default()
+                },
+                margin_ratio_initial: 1000,
+                margin_ratio_maintenance: 500,
+                status: MarketStatus::Active,
+                ..PerpMarket::default()
+            };
+            market.amm.max_base_asset_reserve = u128::MAX;
+            market.amm.min_base_asset_reserve = 0;
+            let (new_ask_base_asset_reserve, new_ask_quote_asset_reserve) =
+                crate::math::amm_spread::calculate_spread_reserves(
+                    &market.amm,
+                    PositionDirection::Long,
+                )
+                .unwrap();
+            let (new_bid_base_asset_reserve, new_bid_quote_asset_reserve) =
+                crate::math::amm_spread::calculate_spread_reserves(
+                    &market.amm,
+                    PositionDirection::Short,
+                )
+                .unwrap();
+            market.amm.ask_base_asset_reserve = new_ask_base_asset_reserve;
+            market.amm.bid_base_asset_reserve = new_bid_base_asset_reserve;
+            market.amm.ask_quote_asset_reserve = new_ask_quote_asset_reserve;
+            market.amm.bid_quote_asset_reserve = new_bid_quote_asset_reserve;
+            create_anchor_account_info!(market, PerpMarket, market_account_info);
+            let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+            let mut spot_market = SpotMarket {
+                market_index: 0,
+                oracle_source: OracleSource::QuoteAsset,
+                cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+                decimals: 6,
+                initial_asset_weight: SPOT_WEIGHT_PRECISION,
+                maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+                historical_oracle_data: HistoricalOracleData::default_price(QUOTE_PRECISION_I64),
+                ..SpotMarket::default()
+            };
+            create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+            let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+            // auction already complete so the order fills against the amm
+            let mut user = User {
+                orders: get_orders(Order {
+                    market_index: 0,
+                    order_id: 1,
+                    status: OrderStatus::Open,
+                    order_type: OrderType::Market,
+                    direction: PositionDirection::Long,
+                    base_asset_amount: BASE_PRECISION_U64,
+                    slot: 0,
+                    auction_start_price: 0,
+                    auction_end_price: 102 * PRICE_PRECISION_I64,
+                    auction_duration: 5,
+                    price: 102 * PRICE_PRECISION_U64,
+                    is_lp_hedge,
+                    ..Order::default()
+                }),
+                perp_positions: get_positions(PerpPosition {
+                    market_index: 0,
+                    open_orders: 1,
+                    open_bids: BASE_PRECISION_I64,
+                    ..PerpPosition::default()
+                }),
+                spot_positions: get_spot_positions(SpotPosition {
+                    market_index: 0,
+                    balance_type: SpotBalanceType::Deposit,
+                    scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+                    ..SpotPosition::default()
+                }),
+                ..User::default()
+            };
+            create_anchor_account_info!(user, User, user_account_info);
+            let user_account_loader: AccountLoader<User> =
+                AccountLoader::try_from(&user_account_info).unwrap();
+
+            create_anchor_account_info!(UserStats::default(), UserStats, user_stats_account_info);
+            let user_stats_account_loader: AccountLoader<UserStats> =
+                AccountLoader::try_from(&user_stats_account_info).unwrap();
+
+            let filler_key =
+                Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap();
+            let mut filler = User {
+                authority: filler_key,
+                ..User::default()
+            };
+            create_anchor_account_info!(filler, &filler_key, User, filler_account_info);
+            let filler_account_loader: AccountLoader<User> =
+                AccountLoader::try_from(&filler_account_info).unwrap();
+
+            create_anchor_account_info!(UserStats::default(), UserStats, filler_stats_account_info);
+            let filler_stats_account_loader: AccountLoader<UserStats> =
+                AccountLoader::try_from(&filler_stats_account_info).unwrap();
+
+            let base_asset_amount = fill_perp_order(
+                1,
+                &state,
+                &user_account_loader,
+                &user_stats_account_loader,
+                &spot_market_map,
+                &market_map,
+                &mut oracle_map,
+                &filler_account_loader,
+                &filler_stats_account_loader,
+                &UserMap::empty(),
+                &UserStatsMap::empty(),
+                None,
+                &clock,
+            )
+            .unwrap();
+            assert_eq!(base_asset_amount, BASE_PRECISION_U64);
+
+            let user_after = user_account_loader.load().unwrap();
+            let filler_after = filler_account_loader.load().unwrap();
+            quote_asset_amounts.push((
+                user_after.perp_positions[0].quote_asset_amount,
+                filler_after.perp_positions[0].quote_asset_amount,
+            ));
+        }
+
+        // hedge fill moves the flat keeper reward from the lp's pnl to the filler
+        let flat_filler_fee = state.perp_fee_structure.flat_filler_fee as i64;
+        assert_eq!(
+            quote_asset_amounts[1].0,
+            quote_asset_amounts[0].0 - flat_filler_fee
+        );
+        assert_eq!(
+            quote_asset_amounts[1].1,
+            quote_asset_amounts[0].1 + flat_filler_fee
+        );
+    }
 }
 
 #[cfg(test)]
@@ -7564,7 +7749,7 @@ pub mod fulfill_spot_order {
             Some(&maker_stats_account_loader),
             Some(1),
             &clock,
-            &mut TestFulfillmentParams {},
+            &mut [&mut TestFulfillmentParams {}],
         )
         .unwrap();
 
@@ -7754,7 +7939,7 @@ pub mod fill_spot_order {
             Some(&maker_stats_account_loader),
             Some(1),
             &clock,
-            &mut TestFulfillmentParams {},
+            &mut [&mut TestFulfillmentParams {}],
         )
         .unwrap();
 
@@ -8019,6 +8204,444 @@ pub mod force_cancel_orders {
     }
 }
 
+pub mod force_place_deleverage_order {
+    use std::str::FromStr;
+
+    use anchor_lang::prelude::{AccountLoader, Clock};
+
+    use crate::controller::orders::force_place_deleverage_order;
+    use crate::controller::position::PositionDirection;
+    use crate::create_account_info;
+    use crate::create_anchor_account_info;
+    use crate::error::ErrorCode;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I64, BASE_PRECISION_U64, PEG_PRECISION,
+        PRICE_PRECISION_U64, QUOTE_PRECISION_I64, SPOT_BALANCE_PRECISION,
+        SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::HistoricalOracleData;
+    use crate::state::oracle::OracleSource;
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::state::State;
+    use crate::state::user::{MarketType, OrderStatus, OrderType, SpotPosition, User};
+    use crate::test_utils::*;
+    use crate::test_utils::{create_account_info, get_positions, get_pyth_price};
+
+    use super::*;
+
+    #[test]
+    fn place_reduce_only_order_to_cover_initial_margin_shortage() {
+        let clock = Clock {
+            slot: 6,
+            epoch_start_timestamp: 0,
+            epoch: 0,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 0,
+        };
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, clock.slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                terminal_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 100,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                max_spread: 1000,
+                base_spread: 0,
+                long_spread: 0,
+                short_spread: 0,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: oracle_price.twap as i64,
+                    last_oracle_price_twap_5min: oracle_price.twap as i64,
+                    last_oracle_price: oracle_price.agg.price as i64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut usdc_spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            deposit_balance: 100 * SPOT_BALANCE_PRECISION,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(usdc_spot_market, SpotMarket, usdc_spot_market_account_info);
+        let spot_market_map =
+            SpotMarketMap::load_one(&usdc_spot_market_account_info, true).unwrap();
+
+        // 10 SOL long at $100 with $75 of collateral: below the $100 initial margin requirement
+        // but above the $50 maintenance margin requirement
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 75 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        // risk increasing bid that gets force canceled
+        let mut orders = [Order::default(); 32];
+        orders[0] = Order {
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            market_index: 0,
+            direction: PositionDirection::Long,
+            base_asset_amount: BASE_PRECISION_U64,
+            price: 90 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+        let mut user = User {
+            orders,
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 10 * BASE_PRECISION_I64,
+                quote_asset_amount: -1000 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -1000 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -1000 * QUOTE_PRECISION_I64,
+                open_orders: 1,
+                open_bids: BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions,
+            ..User::default()
+        };
+        create_anchor_account_info!(user, User, user_account_info);
+        let user_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&user_account_info).unwrap();
+
+        let filler_key = Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap();
+        create_anchor_account_info!(User::default(), &filler_key, User, filler_account_info);
+        let filler_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&filler_account_info).unwrap();
+
+        let state = State {
+            min_perp_auction_duration: 1,
+            default_market_order_time_in_force: 10,
+            perp_fee_structure: FeeStructure {
+                flat_filler_fee: 10_000,
+                ..FeeStructure::test_default()
+            },
+            spot_fee_structure: FeeStructure {
+                flat_filler_fee: 10_000,
+                ..FeeStructure::test_default()
+            },
+            ..State::default()
+        };
+
+        force_place_deleverage_order(
+            &state,
+            &user_account_loader,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &filler_account_loader,
+            &clock,
+            0,
+        )
+        .unwrap();
+
+        let user = user_account_loader.load().unwrap();
+        let order = user.orders[0];
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(order.order_type, OrderType::Market);
+        assert_eq!(order.market_type, MarketType::Perp);
+        assert_eq!(order.direction, PositionDirection::Short);
+        assert!(order.reduce_only);
+        // $25 initial margin shortage / ($100 * 10% initial margin ratio)
+        assert_eq!(order.base_asset_amount, 25 * BASE_PRECISION_U64 / 10);
+        assert_eq!(user.perp_positions[0].open_orders, 1);
+        assert_eq!(user.perp_positions[0].open_bids, 0);
+
+        // keeper is paid a single flat fee, not a cancel reward on top
+        let filler = filler_account_loader.load().unwrap();
+        assert_eq!(filler.cumulative_spot_fees, 10_000);
+        assert_eq!(user.cumulative_spot_fees, -10_000);
+        drop(filler);
+
+        // cant place a second deleverage order while one is open
+        drop(user);
+        let result = force_place_deleverage_order(
+            &state,
+            &user_account_loader,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &filler_account_loader,
+            &clock,
+            0,
+        );
+        assert_eq!(result, Err(ErrorCode::InvalidDeleverageOrder));
+    }
+}
+
+pub mod place_perp_lp_hedge_order {
+    use std::str::FromStr;
+
+    use anchor_lang::prelude::{AccountLoader, Clock};
+
+    use crate::controller::orders::place_perp_lp_hedge_order;
+    use crate::controller::position::PositionDirection;
+    use crate::create_account_info;
+    use crate::create_anchor_account_info;
+    use crate::error::ErrorCode;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I64, BASE_PRECISION_U64, PEG_PRECISION,
+        QUOTE_PRECISION_I64, SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64,
+        SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::HistoricalOracleData;
+    use crate::state::oracle::OracleSource;
+    use crate::state::perp_lp_hedge::PerpLpHedge;
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::state::State;
+    use crate::state::user::{MarketType, OrderStatus, OrderType, SpotPosition, User};
+    use crate::test_utils::*;
+    use crate::test_utils::{create_account_info, get_positions, get_pyth_price};
+
+    use super::*;
+
+    #[test]
+    fn place_reduce_only_order_above_threshold() {
+        let clock = Clock {
+            slot: 6,
+            epoch_start_timestamp: 0,
+            epoch: 0,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 60,
+        };
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, clock.slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                terminal_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                user_lp_shares: 10 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 100,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                max_spread: 1000,
+                base_spread: 0,
+                long_spread: 0,
+                short_spread: 0,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: oracle_price.twap as i64,
+                    last_oracle_price_twap_5min: oracle_price.twap as i64,
+                    last_oracle_price: oracle_price.agg.price as i64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut usdc_spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            deposit_balance: 1000 * SPOT_BALANCE_PRECISION,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(usdc_spot_market, SpotMarket, usdc_spot_market_account_info);
+        let spot_market_map =
+            SpotMarketMap::load_one(&usdc_spot_market_account_info, true).unwrap();
+
+        // lp that has taken on a 10 SOL long as the amm's counterparty
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 1000 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        let mut user = User {
+            orders: [Order::default(); 32],
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                lp_shares: 10 * BASE_PRECISION_U64,
+                base_asset_amount: 10 * BASE_PRECISION_I64,
+                quote_asset_amount: -1000 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -1000 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -1000 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions,
+            ..User::default()
+        };
+        create_anchor_account_info!(user, User, user_account_info);
+        let user_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&user_account_info).unwrap();
+
+        let state = State {
+            min_perp_auction_duration: 1,
+            default_market_order_time_in_force: 10,
+            ..State::default()
+        };
+
+        let mut perp_lp_hedge = PerpLpHedge {
+            base_asset_amount_threshold: 20 * BASE_PRECISION_U64,
+            ..PerpLpHedge::default()
+        };
+
+        // position is below the threshold
+        let result = place_perp_lp_hedge_order(
+            &state,
+            &user_account_loader,
+            &mut perp_lp_hedge,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &clock,
+            0,
+        );
+        assert_eq!(result, Err(ErrorCode::InvalidLpHedgeOrder));
+
+        // excess over the threshold is below the order step size
+        perp_lp_hedge.base_asset_amount_threshold = 10 * BASE_PRECISION_U64 - 500;
+        let result = place_perp_lp_hedge_order(
+            &state,
+            &user_account_loader,
+            &mut perp_lp_hedge,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &clock,
+            0,
+        );
+        assert_eq!(result, Err(ErrorCode::InvalidLpHedgeOrder));
+
+        perp_lp_hedge.base_asset_amount_threshold = 5 * BASE_PRECISION_U64;
+
+        place_perp_lp_hedge_order(
+            &state,
+            &user_account_loader,
+            &mut perp_lp_hedge,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &clock,
+            0,
+        )
+        .unwrap();
+
+        let user = user_account_loader.load().unwrap();
+        let order = user.orders[0];
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(order.order_type, OrderType::Market);
+        assert_eq!(order.market_type, MarketType::Perp);
+        assert_eq!(order.direction, PositionDirection::Short);
+        assert!(order.reduce_only);
+        assert!(order.is_lp_hedge);
+        // only the excess over the threshold is hedged
+        assert_eq!(order.base_asset_amount, 5 * BASE_PRECISION_U64);
+        assert_eq!(user.perp_positions[0].open_orders, 1);
+        assert_eq!(perp_lp_hedge.last_hedge_ts, 60);
+
+        // keeper isnt paid until the hedge fills
+        assert_eq!(
+            user.spot_positions[0].scaled_balance,
+            1000 * SPOT_BALANCE_PRECISION_U64
+        );
+        assert_eq!(
+            user.perp_positions[0].quote_asset_amount,
+            -1000 * QUOTE_PRECISION_I64
+        );
+        drop(user);
+
+        // too soon after the last hedge
+        let mut clock = clock;
+        clock.unix_timestamp = 90;
+        let result = place_perp_lp_hedge_order(
+            &state,
+            &user_account_loader,
+            &mut perp_lp_hedge,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &clock,
+            0,
+        );
+        assert_eq!(result, Err(ErrorCode::InvalidLpHedgeOrder));
+
+        // cant place a second hedge order while one is open
+        clock.unix_timestamp = 120;
+        let result = place_perp_lp_hedge_order(
+            &state,
+            &user_account_loader,
+            &mut perp_lp_hedge,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &clock,
+            0,
+        );
+        assert_eq!(result, Err(ErrorCode::InvalidLpHedgeOrder));
+    }
+}
+
 pub mod insert_maker_order_info {
     use crate::controller::orders::insert_maker_order_info;
     use crate::controller::position::PositionDirection;
@@ -8277,6 +8900,7 @@ pub mod get_maker_orders_info {
             None,
             clock.unix_timestamp,
             clock.slot,
+            false,
         )
         .unwrap();
 
@@ -8472,6 +9096,7 @@ pub mod get_maker_orders_info {
             None,
             clock.unix_timestamp,
             clock.slot,
+            false,
         )
         .unwrap();
 
@@ -8656,6 +9281,7 @@ pub mod get_maker_orders_info {
             None,
             clock.unix_timestamp,
             clock.slot,
+            false,
         )
         .unwrap();
 
@@ -8903,6 +9529,7 @@ pub mod get_maker_orders_info {
             None,
             clock.unix_timestamp,
             clock.slot,
+            false,
         )
         .unwrap();
 
@@ -9105,6 +9732,7 @@ pub mod get_maker_orders_info {
             Some(2),
             clock.unix_timestamp,
             clock.slot,
+            false,
         )
         .unwrap();
 
@@ -9329,6 +9957,7 @@ pub mod get_maker_orders_info {
             None,
             clock.unix_timestamp,
             clock.slot,
+            false,
         )
         .unwrap();
 
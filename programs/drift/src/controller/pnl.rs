@@ -19,6 +19,7 @@ use crate::math::spot_balance::get_token_amount;
 
 use crate::state::events::{OrderActionExplanation, SettlePnlExplanation, SettlePnlRecord};
 use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
 use crate::state::perp_market::MarketStatus;
 use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_market::{SpotBalance, SpotBalanceType};
@@ -59,7 +60,18 @@ pub fn settle_pnl(
 
     validate_market_within_price_band(&market, state, true, None)?;
 
-    crate::controller::lp::settle_funding_payment_then_lp(user, user_key, &mut market, now)?;
+    let funding_paused = crate::controller::funding::is_continuous_funding_paused(
+        &market,
+        oracle_map,
+        state.funding_paused()?,
+    )?;
+    crate::controller::lp::settle_funding_payment_then_lp(
+        user,
+        user_key,
+        &mut market,
+        now,
+        funding_paused,
+    )?;
 
     let oracle_price = oracle_map.get_price_data(&market.amm.oracle)?.price;
 
@@ -106,6 +118,12 @@ pub fn settle_pnl(
         "Cannot settle pnl under current market status"
     )?;
 
+    validate!(
+        !perp_market.is_operation_paused(MarketOperation::SettlePnl)?,
+        ErrorCode::MarketActionPaused,
+        "Market settle pnl is paused"
+    )?;
+
     let pnl_pool_token_amount = get_token_amount(
         perp_market.pnl_pool.scaled_balance,
         spot_market,
@@ -220,12 +238,15 @@ pub fn settle_expired_position(
         update_spot_market_cumulative_interest(quote_spot_market, None, now)?;
     }
 
-    settle_funding_payment(
-        user,
-        user_key,
-        perp_market_map.get_ref_mut(&perp_market_index)?.deref_mut(),
-        now,
-    )?;
+    {
+        let mut market = perp_market_map.get_ref_mut(&perp_market_index)?;
+        let funding_paused = crate::controller::funding::is_continuous_funding_paused(
+            &market,
+            oracle_map,
+            state.funding_paused()?,
+        )?;
+        settle_funding_payment(user, user_key, market.deref_mut(), now, funding_paused)?;
+    }
 
     cancel_orders(
         user,
@@ -47,6 +47,7 @@ pub mod delisting_test {
     use crate::state::spot_market_map::SpotMarketMap;
     use crate::state::state::{OracleGuardRails, State, ValidityGuardRails};
     use crate::state::user::{OrderStatus, OrderType, SpotPosition, User, UserStats};
+    use crate::state::user_map::UserMap;
     use crate::test_utils::*;
     use crate::test_utils::{get_orders, get_positions, get_pyth_price, get_spot_positions};
 
@@ -2468,6 +2469,7 @@ pub mod delisting_test {
                 10,
                 PERCENTAGE_PRECISION,
                 150,
+                false,
             )
             .unwrap();
 
@@ -2560,6 +2562,7 @@ pub mod delisting_test {
                 10,
                 PERCENTAGE_PRECISION,
                 150,
+                false,
             )
             .unwrap();
 
@@ -2688,6 +2691,9 @@ pub mod delisting_test {
                 &mut oracle_map,
                 clock.unix_timestamp,
                 0,
+                0,
+                &UserMap::empty(),
+                &State::default(),
             )
             .unwrap();
 
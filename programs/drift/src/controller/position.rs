@@ -448,6 +448,8 @@ pub fn update_lp_market_position(
         .quote_asset_amount_per_lp
         .safe_add(per_lp_fee.cast()?)?;
 
+    market.amm.fee_per_lp = market.amm.fee_per_lp.safe_add(per_lp_fee.cast()?)?;
+
     market.amm.base_asset_amount_with_amm = market
         .amm
         .base_asset_amount_with_amm
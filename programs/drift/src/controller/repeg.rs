@@ -5,6 +5,7 @@ use anchor_lang::prelude::*;
 use solana_program::msg;
 
 use crate::controller::amm::update_spreads;
+use crate::controller::funding::accrue_continuous_funding;
 use crate::controller::spot_balance::update_spot_balances;
 use crate::error::ErrorCode;
 use crate::error::*;
@@ -26,6 +27,7 @@ use crate::math::spot_balance::get_token_amount;
 
 use crate::state::oracle::OraclePriceData;
 use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
 use crate::state::perp_market::{MarketStatus, PerpMarket};
 use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_market::SpotBalanceType;
@@ -202,6 +204,13 @@ pub fn _update_amm(
         )?;
     }
 
+    if market.amm.continuous_funding {
+        let funding_paused = state.funding_paused()?
+            || market.is_operation_paused(MarketOperation::Funding)?
+            || !is_oracle_valid_for_action(oracle_validity, Some(DriftAction::UpdateFunding))?;
+        accrue_continuous_funding(market, now, funding_paused)?;
+    }
+
     if is_oracle_valid_for_action(oracle_validity, Some(DriftAction::FillOrderAmm))? {
         if !amm_not_successfully_updated {
             market.amm.last_update_slot = clock_slot;
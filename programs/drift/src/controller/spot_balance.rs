@@ -18,10 +18,11 @@ use crate::math::spot_balance::{
 };
 use crate::math::stats::{calculate_new_twap, calculate_weighted_average};
 
-use crate::state::events::SpotInterestRecord;
+use crate::state::events::{ProtocolRevenueRecord, SpotInterestRecord};
 use crate::state::oracle::OraclePriceData;
-use crate::state::perp_market::{MarketStatus, PerpMarket};
-use crate::state::spot_market::{SpotBalance, SpotBalanceType, SpotMarket};
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_market::PerpMarket;
+use crate::state::spot_market::{ProtocolRevenueSource, SpotBalance, SpotBalanceType, SpotMarket};
 use crate::validate;
 
 use crate::math::oracle::{is_oracle_valid_for_action, DriftAction};
@@ -124,7 +125,7 @@ pub fn update_spot_market_cumulative_interest(
     oracle_price_data: Option<&OraclePriceData>,
     now: i64,
 ) -> DriftResult {
-    if spot_market.status == MarketStatus::FundingPaused {
+    if spot_market.is_operation_paused(MarketOperation::Funding)? {
         update_spot_market_twap_stats(spot_market, oracle_price_data, now)?;
         return Ok(());
     }
@@ -161,6 +162,9 @@ pub fn update_spot_market_cumulative_interest(
             )?;
 
             update_revenue_pool_balances(token_amount, &SpotBalanceType::Deposit, spot_market)?;
+            spot_market
+                .protocol_revenue
+                .record_revenue(ProtocolRevenueSource::BorrowInterest, token_amount)?;
 
             emit!(SpotInterestRecord {
                 ts: now,
@@ -328,6 +332,50 @@ pub fn transfer_spot_balances(
     Ok(())
 }
 
+pub fn withdraw_protocol_revenue(
+    amount: u64,
+    spot_market_vault_amount: u64,
+    spot_market: &mut SpotMarket,
+    now: i64,
+) -> DriftResult {
+    let revenue_pool_amount = get_token_amount(
+        spot_market.revenue_pool.scaled_balance,
+        spot_market,
+        &SpotBalanceType::Deposit,
+    )?
+    .cast::<u64>()?;
+
+    validate!(
+        amount > 0 && amount <= revenue_pool_amount,
+        ErrorCode::ProtocolRevenueWithdrawLimitExceeded,
+        "amount {} must be > 0 and <= revenue pool amount {}",
+        amount,
+        revenue_pool_amount
+    )?;
+
+    spot_market.protocol_revenue.update_withdraw(amount, now)?;
+
+    update_revenue_pool_balances(amount.cast()?, &SpotBalanceType::Borrow, spot_market)?;
+
+    let protocol_revenue = &spot_market.protocol_revenue;
+    emit!(ProtocolRevenueRecord {
+        ts: now,
+        spot_market_index: spot_market.market_index,
+        amount,
+        revenue_pool_amount_before: revenue_pool_amount,
+        vault_amount_before: spot_market_vault_amount,
+        taker_fees: protocol_revenue.taker_fees,
+        liquidation_fees: protocol_revenue.liquidation_fees,
+        borrow_interest: protocol_revenue.borrow_interest,
+        amm_fees: protocol_revenue.amm_fees,
+        amm_fee_pool_top_ups: protocol_revenue.amm_fee_pool_top_ups,
+        settled_to_insurance_fund: protocol_revenue.settled_to_insurance_fund,
+        withdrawn: protocol_revenue.withdrawn,
+    });
+
+    Ok(())
+}
+
 pub fn transfer_revenue_pool_to_spot_balance(
     token_amount: u128,
     spot_market: &mut SpotMarket,
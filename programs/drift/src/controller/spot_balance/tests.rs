@@ -25,9 +25,11 @@ use crate::math::spot_withdraw::{
 use crate::math::stats::calculate_weighted_average;
 use crate::state::oracle::{HistoricalOracleData, OracleSource};
 use crate::state::oracle_map::OracleMap;
-use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+use crate::state::perp_market::{MarketStatus, PerpMarket, PoolBalance, AMM};
 use crate::state::perp_market_map::PerpMarketMap;
-use crate::state::spot_market::{InsuranceFund, SpotBalanceType, SpotMarket};
+use crate::state::spot_market::{
+    InsuranceFund, ProtocolRevenue, ProtocolRevenueSource, SpotBalanceType, SpotMarket,
+};
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::user::{Order, PerpPosition, SpotPosition, User};
 use crate::test_utils::*;
@@ -1585,3 +1587,68 @@ fn check_usdc_spot_market_twap() {
             .unwrap();
     assert_eq!(wa_res2, PRICE_PRECISION_I64 + 1);
 }
+
+#[test]
+fn check_withdraw_protocol_revenue() {
+    let mut spot_market = SpotMarket {
+        market_index: 0,
+        decimals: 6,
+        deposit_balance: 100 * SPOT_BALANCE_PRECISION,
+        cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+        cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+        revenue_pool: PoolBalance {
+            market_index: 0,
+            scaled_balance: 100 * SPOT_BALANCE_PRECISION,
+            ..PoolBalance::default()
+        },
+        ..SpotMarket::default()
+    };
+
+    spot_market
+        .protocol_revenue
+        .record_revenue(ProtocolRevenueSource::TakerFee, 60 * QUOTE_PRECISION)
+        .unwrap();
+    spot_market
+        .protocol_revenue
+        .record_revenue(ProtocolRevenueSource::BorrowInterest, 40 * QUOTE_PRECISION)
+        .unwrap();
+
+    // withdrawals disabled until a limit is set
+    assert!(withdraw_protocol_revenue(QUOTE_PRECISION_U64, 0, &mut spot_market, 100).is_err());
+
+    spot_market.protocol_revenue.max_withdraw_per_period = 10 * QUOTE_PRECISION_U64;
+    spot_market.protocol_revenue.withdraw_period = 3600;
+
+    withdraw_protocol_revenue(6 * QUOTE_PRECISION_U64, 0, &mut spot_market, 100).unwrap();
+
+    // over the limit for the period
+    assert!(withdraw_protocol_revenue(5 * QUOTE_PRECISION_U64, 0, &mut spot_market, 200).is_err());
+    withdraw_protocol_revenue(4 * QUOTE_PRECISION_U64, 0, &mut spot_market, 200).unwrap();
+
+    // limit resets once the period has passed
+    withdraw_protocol_revenue(10 * QUOTE_PRECISION_U64, 0, &mut spot_market, 3700).unwrap();
+
+    assert_eq!(
+        spot_market.protocol_revenue,
+        ProtocolRevenue {
+            taker_fees: 60 * QUOTE_PRECISION_U64,
+            borrow_interest: 40 * QUOTE_PRECISION_U64,
+            withdrawn: 20 * QUOTE_PRECISION_U64,
+            max_withdraw_per_period: 10 * QUOTE_PRECISION_U64,
+            withdrawn_in_period: 10 * QUOTE_PRECISION_U64,
+            withdraw_period: 3600,
+            last_withdraw_period_start_ts: 3700,
+            ..ProtocolRevenue::default()
+        }
+    );
+    assert_eq!(
+        spot_market.revenue_pool.scaled_balance,
+        80 * SPOT_BALANCE_PRECISION
+    );
+
+    // cant withdraw more than is in the revenue pool
+    spot_market.protocol_revenue.max_withdraw_per_period = 1000 * QUOTE_PRECISION_U64;
+    assert!(
+        withdraw_protocol_revenue(81 * QUOTE_PRECISION_U64, 0, &mut spot_market, 3800).is_err()
+    );
+}
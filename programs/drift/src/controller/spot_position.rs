@@ -10,6 +10,7 @@ use crate::math::spot_withdraw::check_withdraw_limits;
 use crate::math_error;
 use crate::safe_decrement;
 use crate::safe_increment;
+use crate::state::paused_operations::MarketOperation;
 use crate::state::perp_market::MarketStatus;
 use crate::state::spot_market::{AssetTier, SpotBalance, SpotBalanceType, SpotMarket};
 use crate::state::user::{SpotPosition, User};
@@ -125,15 +126,12 @@ pub fn update_spot_balances_and_cumulative_deposits_with_limits(
     )?;
 
     validate!(
-        matches!(
-            spot_market.status,
-            MarketStatus::Active
-                | MarketStatus::AmmPaused
-                | MarketStatus::FundingPaused
-                | MarketStatus::FillPaused
-                | MarketStatus::ReduceOnly
-                | MarketStatus::Settlement
-        ),
+        (spot_market.status.is_active_or_legacy_paused()
+            || matches!(
+                spot_market.status,
+                MarketStatus::ReduceOnly | MarketStatus::Settlement
+            ))
+            && !spot_market.is_operation_paused(MarketOperation::Withdraw)?,
         ErrorCode::MarketWithdrawPaused,
         "Spot Market {} withdraws are currently paused",
         spot_market.market_index
@@ -0,0 +1,122 @@
+use solana_program::msg;
+
+use crate::error::DriftResult;
+use crate::math::casting::Cast;
+use crate::math::margin::{
+    calculate_margin_requirement_and_total_collateral, calculate_user_health, MarginRequirementType,
+};
+use crate::math::safe_math::SafeMath;
+use crate::state::events::HealthRecord;
+use crate::state::oracle_map::OracleMap;
+use crate::state::perp_market_map::PerpMarketMap;
+use crate::state::spot_market_map::SpotMarketMap;
+use crate::state::state::State;
+use crate::state::user::User;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Updates the health snapshot of a user that opted into health tracking and emits a HealthRecord
+/// if their health crossed one of the state's health bands
+pub fn update_user_health(
+    user: &mut User,
+    user_key: &Pubkey,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    state: &State,
+    now: i64,
+) -> DriftResult {
+    if !user.is_health_tracking_enabled {
+        return Ok(());
+    }
+
+    let (margin_requirement, total_collateral, _, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            MarginRequirementType::Maintenance,
+            spot_market_map,
+            oracle_map,
+            None,
+        )?;
+
+    let health = calculate_user_health(margin_requirement, total_collateral)?;
+
+    update_health_snapshot(
+        user,
+        user_key,
+        health,
+        margin_requirement,
+        total_collateral,
+        &state.health_bands,
+        oracle_map.slot,
+        now,
+    )
+}
+
+pub fn update_health_snapshot(
+    user: &mut User,
+    user_key: &Pubkey,
+    health: u8,
+    margin_requirement: u128,
+    total_collateral: i128,
+    health_bands: &[u8; 4],
+    slot: u64,
+    now: i64,
+) -> DriftResult {
+    let health_before = user.last_health;
+    let health_threshold = health_bands[0];
+
+    user.slots_below_health_threshold = if health < health_threshold {
+        if health_before < health_threshold {
+            user.slots_below_health_threshold
+                .safe_add(slot.saturating_sub(user.last_health_slot))?
+        } else {
+            0
+        }
+    } else {
+        0
+    };
+
+    user.last_health = health;
+    user.last_health_slot = slot;
+
+    let health_band_before = get_health_band(health_before, health_bands)?;
+    let health_band = get_health_band(health, health_bands)?;
+
+    if health_band != health_band_before {
+        msg!(
+            "user {} health {} -> {} crossed health band {} -> {}",
+            user_key,
+            health_before,
+            health,
+            health_band_before,
+            health_band
+        );
+
+        emit!(HealthRecord {
+            ts: now,
+            user: *user_key,
+            health_before,
+            health,
+            health_band_before,
+            health_band,
+            margin_requirement,
+            total_collateral,
+            slots_below_health_threshold: user.slots_below_health_threshold,
+        });
+    }
+
+    Ok(())
+}
+
+/// The number of health bands the health is below
+pub fn get_health_band(health: u8, health_bands: &[u8; 4]) -> DriftResult<u8> {
+    health_bands
+        .iter()
+        .filter(|health_band| **health_band != 0 && health < **health_band)
+        .count()
+        .cast()
+}
@@ -0,0 +1,83 @@
+use anchor_lang::prelude::Pubkey;
+
+use crate::controller::user_health::{get_health_band, update_health_snapshot};
+use crate::state::user::User;
+
+const HEALTH_BANDS: [u8; 4] = [50, 25, 10, 0];
+
+#[test]
+fn health_band() {
+    assert_eq!(get_health_band(100, &HEALTH_BANDS).unwrap(), 0);
+    assert_eq!(get_health_band(50, &HEALTH_BANDS).unwrap(), 0);
+    assert_eq!(get_health_band(49, &HEALTH_BANDS).unwrap(), 1);
+    assert_eq!(get_health_band(10, &HEALTH_BANDS).unwrap(), 2);
+    assert_eq!(get_health_band(0, &HEALTH_BANDS).unwrap(), 3);
+
+    assert_eq!(get_health_band(0, &[0; 4]).unwrap(), 0);
+}
+
+#[test]
+fn slots_below_health_threshold() {
+    let mut user = User {
+        is_health_tracking_enabled: true,
+        last_health: 100,
+        ..User::default()
+    };
+
+    update_health_snapshot(
+        &mut user,
+        &Pubkey::default(),
+        40,
+        0,
+        0,
+        &HEALTH_BANDS,
+        10,
+        0,
+    )
+    .unwrap();
+    assert_eq!(user.last_health, 40);
+    assert_eq!(user.last_health_slot, 10);
+    assert_eq!(user.slots_below_health_threshold, 0);
+
+    update_health_snapshot(
+        &mut user,
+        &Pubkey::default(),
+        20,
+        0,
+        0,
+        &HEALTH_BANDS,
+        25,
+        0,
+    )
+    .unwrap();
+    assert_eq!(user.last_health, 20);
+    assert_eq!(user.slots_below_health_threshold, 15);
+
+    update_health_snapshot(
+        &mut user,
+        &Pubkey::default(),
+        30,
+        0,
+        0,
+        &HEALTH_BANDS,
+        30,
+        0,
+    )
+    .unwrap();
+    assert_eq!(user.slots_below_health_threshold, 20);
+
+    // back above the threshold
+    update_health_snapshot(
+        &mut user,
+        &Pubkey::default(),
+        60,
+        0,
+        0,
+        &HEALTH_BANDS,
+        40,
+        0,
+    )
+    .unwrap();
+    assert_eq!(user.last_health, 60);
+    assert_eq!(user.slots_below_health_threshold, 0);
+}
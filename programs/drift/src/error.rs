@@ -509,6 +509,54 @@ pub enum ErrorCode {
     SpotMarketReduceOnly,
     #[msg("FundingWasNotUpdated")]
     FundingWasNotUpdated,
+    #[msg("InvalidConstantProductPool")]
+    InvalidConstantProductPool,
+    #[msg("FailedConstantProductPoolCPI")]
+    FailedConstantProductPoolCPI,
+    #[msg("AmmHedgeDisabled")]
+    AmmHedgeDisabled,
+    #[msg("InvalidAmmHedgeConfig")]
+    InvalidAmmHedgeConfig,
+    #[msg("InvalidUserDelegate")]
+    InvalidUserDelegate,
+    #[msg("DelegatePermissionDenied")]
+    DelegatePermissionDenied,
+    #[msg("InvalidWithdrawPolicy")]
+    InvalidWithdrawPolicy,
+    #[msg("WithdrawDestinationNotAllowed")]
+    WithdrawDestinationNotAllowed,
+    #[msg("AmmLiquidationUnavailable")]
+    AmmLiquidationUnavailable,
+    #[msg("InvalidDeleverageOrder")]
+    InvalidDeleverageOrder,
+    #[msg("InvalidHealthBands")]
+    InvalidHealthBands,
+    #[msg("InvalidInsuranceFundStakeMarketType")]
+    InvalidInsuranceFundStakeMarketType,
+    #[msg("PerpMarketInsuranceFundNotInitialized")]
+    PerpMarketInsuranceFundNotInitialized,
+    #[msg("InvalidInsuranceFundShareTokenAmount")]
+    InvalidInsuranceFundShareTokenAmount,
+    #[msg("InvalidInsuranceFundStakeTranche")]
+    InvalidInsuranceFundStakeTranche,
+    #[msg("JuniorInsuranceFundNotInitialized")]
+    JuniorInsuranceFundNotInitialized,
+    #[msg("InvalidInsuranceFundRevenueClaim")]
+    InvalidInsuranceFundRevenueClaim,
+    #[msg("ProtocolRevenueWithdrawLimitExceeded")]
+    ProtocolRevenueWithdrawLimitExceeded,
+    #[msg("InvalidPerpLpRange")]
+    InvalidPerpLpRange,
+    #[msg("PerpLpRangeUnchanged")]
+    PerpLpRangeUnchanged,
+    #[msg("InvalidLpHedgeOrder")]
+    InvalidLpHedgeOrder,
+    #[msg("InvalidPerpMarketFundingParams")]
+    InvalidPerpMarketFundingParams,
+    #[msg("AmmHedgeTooFrequent")]
+    AmmHedgeTooFrequent,
+    #[msg("InvalidPerpLpStats")]
+    InvalidPerpLpStats,
 }
 
 #[macro_export]
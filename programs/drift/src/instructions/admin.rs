@@ -18,8 +18,9 @@ use crate::math::constants::{
     DEFAULT_QUOTE_ASSET_AMOUNT_TICK_SIZE, FEE_POOL_TO_REVENUE_POOL_THRESHOLD, IF_FACTOR_PRECISION,
     INSURANCE_A_MAX, INSURANCE_B_MAX, INSURANCE_C_MAX, INSURANCE_SPECULATIVE_MAX,
     LIQUIDATION_FEE_PRECISION, MAX_CONCENTRATION_COEFFICIENT, MAX_SQRT_K,
-    MAX_UPDATE_K_PRICE_CHANGE, QUOTE_SPOT_MARKET_INDEX, SPOT_CUMULATIVE_INTEREST_PRECISION,
-    SPOT_IMF_PRECISION, SPOT_WEIGHT_PRECISION, THIRTEEN_DAY, TWENTY_FOUR_HOUR,
+    MAX_UPDATE_K_PRICE_CHANGE, PERCENTAGE_PRECISION, QUOTE_SPOT_MARKET_INDEX,
+    SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_IMF_PRECISION, SPOT_WEIGHT_PRECISION, THIRTEEN_DAY,
+    TWENTY_FOUR_HOUR,
 };
 use crate::math::cp_curve::get_update_k_result;
 use crate::math::oracle::{is_oracle_valid_for_action, DriftAction};
@@ -29,27 +30,39 @@ use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::get_token_amount;
 use crate::math::{amm, bn, oracle};
 use crate::math_error;
-use crate::state::events::CurveRecord;
+use crate::state::amm_hedge::{AmmHedgeBalance, AmmHedgeConfig};
+use crate::state::events::{CurveRecord, NewUserRecord};
+use crate::state::fulfillment_params::constant_product_pool::{
+    load_constant_product_pool, ConstantProductPoolFulfillmentConfig,
+};
 use crate::state::fulfillment_params::phoenix::PhoenixMarketContext;
 use crate::state::fulfillment_params::phoenix::PhoenixV1FulfillmentConfig;
 use crate::state::fulfillment_params::serum::SerumContext;
 use crate::state::fulfillment_params::serum::SerumV3FulfillmentConfig;
+use crate::state::insurance_fund_stake::InsuranceFundStake;
 use crate::state::oracle::{
     get_oracle_price, get_pyth_price, HistoricalIndexData, HistoricalOracleData, OraclePriceData,
     OracleSource,
 };
+use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
 use crate::state::perp_market::{
     ContractTier, ContractType, InsuranceClaim, MarketStatus, PerpMarket, PoolBalance, AMM,
 };
 use crate::state::spot_market::{
-    AssetTier, InsuranceFund, SpotBalanceType, SpotFulfillmentConfigStatus, SpotMarket,
+    AssetTier, InsuranceFund, ProtocolRevenue, ProtocolRevenueSource, SpotBalanceType,
+    SpotFulfillmentConfigStatus, SpotMarket,
 };
 use crate::state::state::{ExchangeStatus, FeeStructure, OracleGuardRails, State};
+use crate::state::tokenized_lp_pool::TokenizedLpPool;
 use crate::state::traits::Size;
+use crate::state::user::{User, UserStats};
 use crate::validate;
 use crate::validation::fee_structure::validate_fee_structure;
 use crate::validation::margin::{validate_margin, validate_margin_weights};
-use crate::validation::perp_market::validate_perp_market;
+use crate::validation::perp_market::{
+    validate_perp_market, validate_perp_market_funding_params, validate_perp_market_funding_period,
+};
 use crate::validation::spot_market::validate_borrow_rate;
 use crate::{controller, QUOTE_PRECISION_I64};
 use crate::{math, safe_decrement, safe_increment};
@@ -81,7 +94,11 @@ pub fn handle_initialize(ctx: Context<Initialize>) -> Result<()> {
         lp_cooldown_time: 0,
         liquidation_duration: 0,
         initial_pct_to_liquidate: 0,
-        padding: [0; 14],
+        liquidation_auction_duration: 0,
+        auto_deleverage_contract_tiers: 0,
+        health_bands: [0; 4],
+        auto_deleverage_score_threshold: 0,
+        padding: [0; 3],
     };
 
     Ok(())
@@ -262,11 +279,17 @@ pub fn handle_initialize_spot_market(
         spot_fee_pool: PoolBalance::default(), // in quote asset
         total_spot_fee: 0,
         orders_enabled: spot_market_index != 0,
-        padding1: [0; 6],
+        paused_operations: MarketOperation::active(),
+        padding1: [0; 5],
         flash_loan_amount: 0,
         flash_loan_initial_token_amount: 0,
         total_swap_fee: 0,
-        padding: [0; 56],
+        insurance_fund_revenue_per_share: 0,
+        padding: [0; 40],
+        junior_insurance_fund: InsuranceFund::default(),
+        junior_insurance_fund_revenue_per_share: 0,
+        protocol_revenue: ProtocolRevenue::default(),
+        padding2: [0; 24],
         insurance_fund: InsuranceFund {
             vault: *ctx.accounts.insurance_fund_vault.to_account_info().key,
             unstaking_period: THIRTEEN_DAY,
@@ -479,6 +502,167 @@ pub fn handle_update_phoenix_fulfillment_config_status(
     Ok(())
 }
 
+pub fn handle_initialize_constant_product_pool_fulfillment_config(
+    ctx: Context<InitializeConstantProductPoolFulfillmentConfig>,
+    market_index: u16,
+) -> Result<()> {
+    validate!(
+        market_index != QUOTE_SPOT_MARKET_INDEX,
+        ErrorCode::InvalidSpotMarketAccount,
+        "Cannot add constant product pool to quote asset"
+    )?;
+
+    let base_spot_market = load!(&ctx.accounts.base_spot_market)?;
+    let quote_spot_market = load!(&ctx.accounts.quote_spot_market)?;
+
+    let pool_program_id = ctx.accounts.pool_program.key();
+
+    validate!(
+        ctx.accounts.pool_program.executable,
+        ErrorCode::InvalidConstantProductPool,
+        "Pool program must be executable"
+    )?;
+
+    let pool = load_constant_product_pool(&ctx.accounts.pool, &pool_program_id)?;
+
+    validate!(
+        pool.base_mint == base_spot_market.mint,
+        ErrorCode::InvalidConstantProductPool,
+        "Invalid base mint"
+    )?;
+
+    validate!(
+        pool.quote_mint == quote_spot_market.mint,
+        ErrorCode::InvalidConstantProductPool,
+        "Invalid quote mint"
+    )?;
+
+    let pool_fulfillment_config_key = ctx.accounts.constant_product_pool_fulfillment_config.key();
+    let mut pool_fulfillment_config = ctx
+        .accounts
+        .constant_product_pool_fulfillment_config
+        .load_init()?;
+    *pool_fulfillment_config = pool.to_constant_product_pool_fulfillment_config(
+        &pool_fulfillment_config_key,
+        &pool_program_id,
+        ctx.accounts.pool.key,
+        market_index,
+    );
+
+    Ok(())
+}
+
+pub fn handle_update_constant_product_pool_fulfillment_config_status(
+    ctx: Context<UpdateConstantProductPoolFulfillmentConfig>,
+    status: SpotFulfillmentConfigStatus,
+) -> Result<()> {
+    let mut config = load_mut!(ctx.accounts.constant_product_pool_fulfillment_config)?;
+    config.status = status;
+    Ok(())
+}
+
+pub fn handle_initialize_amm_hedge_config(
+    ctx: Context<InitializeAmmHedgeConfig>,
+    perp_market_index: u16,
+    spot_market_index: u16,
+    hedge_ratio: u32,
+    max_slippage: u32,
+    min_hedge_base_asset_amount: u64,
+    max_hedge_base_asset_amount: u64,
+    min_hedge_interval: u32,
+) -> Result<()> {
+    validate!(
+        spot_market_index != QUOTE_SPOT_MARKET_INDEX,
+        ErrorCode::InvalidAmmHedgeConfig,
+        "Cannot hedge with quote asset"
+    )?;
+
+    let perp_market = load!(ctx.accounts.perp_market)?;
+    let spot_market = load!(ctx.accounts.spot_market)?;
+
+    validate!(
+        perp_market.amm.oracle == spot_market.oracle,
+        ErrorCode::InvalidAmmHedgeConfig,
+        "perp market oracle {} != spot market oracle {}",
+        perp_market.amm.oracle,
+        spot_market.oracle
+    )?;
+
+    validate!(
+        perp_market.quote_spot_market_index == QUOTE_SPOT_MARKET_INDEX,
+        ErrorCode::InvalidAmmHedgeConfig,
+        "perp market must be quoted in quote spot market"
+    )?;
+
+    let amm_hedge_config_key = ctx.accounts.amm_hedge_config.key();
+    let mut amm_hedge_config = ctx.accounts.amm_hedge_config.load_init()?;
+    *amm_hedge_config = AmmHedgeConfig {
+        pubkey: amm_hedge_config_key,
+        hedge_balance: AmmHedgeBalance {
+            market_index: spot_market_index,
+            ..AmmHedgeBalance::default()
+        },
+        hedge_ratio,
+        max_slippage,
+        min_hedge_interval,
+        min_hedge_base_asset_amount,
+        max_hedge_base_asset_amount,
+        perp_market_index,
+        status: SpotFulfillmentConfigStatus::Disabled,
+        ..AmmHedgeConfig::default()
+    };
+
+    amm_hedge_config.validate()?;
+
+    Ok(())
+}
+
+pub fn handle_update_amm_hedge_config_params(
+    ctx: Context<UpdateAmmHedgeConfig>,
+    hedge_ratio: u32,
+    max_slippage: u32,
+    min_hedge_base_asset_amount: u64,
+    max_hedge_base_asset_amount: u64,
+    min_hedge_interval: u32,
+) -> Result<()> {
+    let mut config = load_mut!(ctx.accounts.amm_hedge_config)?;
+
+    msg!(
+        "config.hedge_ratio {} -> {}",
+        config.hedge_ratio,
+        hedge_ratio
+    );
+    msg!(
+        "config.max_slippage {} -> {}",
+        config.max_slippage,
+        max_slippage
+    );
+    msg!(
+        "config.min_hedge_interval {} -> {}",
+        config.min_hedge_interval,
+        min_hedge_interval
+    );
+
+    config.hedge_ratio = hedge_ratio;
+    config.max_slippage = max_slippage;
+    config.min_hedge_base_asset_amount = min_hedge_base_asset_amount;
+    config.max_hedge_base_asset_amount = max_hedge_base_asset_amount;
+    config.min_hedge_interval = min_hedge_interval;
+
+    config.validate()?;
+
+    Ok(())
+}
+
+pub fn handle_update_amm_hedge_config_status(
+    ctx: Context<UpdateAmmHedgeConfig>,
+    status: SpotFulfillmentConfigStatus,
+) -> Result<()> {
+    let mut config = load_mut!(ctx.accounts.amm_hedge_config)?;
+    config.status = status;
+    Ok(())
+}
+
 pub fn handle_initialize_perp_market(
     ctx: Context<InitializePerpMarket>,
     market_index: u16,
@@ -627,9 +811,16 @@ pub fn handle_initialize_perp_market(
         unrealized_pnl_max_imbalance: 0,
         liquidator_fee,
         if_liquidation_fee: LIQUIDATION_FEE_PRECISION / 100, // 1%
-        padding1: false,
+        paused_operations: MarketOperation::active(),
         quote_spot_market_index: 0,
-        padding: [0; 48],
+        circuit_breaker_cooldown_end_ts: 0,
+        circuit_breaker_threshold: 0,
+        circuit_breaker_cooldown_period: 0,
+        amm_liquidation_grace_period: 0,
+        padding: [0; 28],
+        insurance_fund: InsuranceFund::default(),
+        insurance_fund_revenue_per_share: 0,
+        padding1: [0; 8],
         amm: AMM {
             oracle: *ctx.accounts.oracle.key,
             oracle_source,
@@ -717,7 +908,15 @@ pub fn handle_initialize_perp_market(
 
             last_oracle_valid: false,
             target_base_asset_amount_per_lp: 0,
-            padding: [0; 44],
+            padding1: [0; 4],
+            fee_per_lp: 0,
+            funding_max_price_spread: 0,
+            funding_interest_rate: 0,
+            funding_premium_dampener: 0,
+            continuous_funding: false,
+            padding2: [0; 3],
+            last_funding_accrual_ts: 0,
+            funding_imbalance_revenue_since_last_funding: 0,
         },
     };
 
@@ -953,6 +1152,10 @@ pub fn handle_settle_expired_market_pools_to_revenue_pool(
         &SpotBalanceType::Deposit,
         spot_market,
     )?;
+    spot_market.protocol_revenue.record_revenue(
+        ProtocolRevenueSource::AmmFee,
+        pnl_pool_token_amount.safe_add(fee_pool_token_amount)?,
+    )?;
 
     math::spot_withdraw::validate_spot_balances(spot_market)?;
 
@@ -1595,6 +1798,211 @@ pub fn handle_update_spot_market_revenue_settle_period(
     Ok(())
 }
 
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_update_protocol_revenue_withdraw_limit(
+    ctx: Context<AdminUpdateSpotMarket>,
+    max_withdraw_per_period: u64,
+    withdraw_period: i64,
+) -> Result<()> {
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    validate!(
+        withdraw_period > 0,
+        ErrorCode::DefaultError,
+        "withdraw_period must be > 0"
+    )?;
+
+    msg!(
+        "spot_market.protocol_revenue.max_withdraw_per_period: {:?} -> {:?}",
+        spot_market.protocol_revenue.max_withdraw_per_period,
+        max_withdraw_per_period
+    );
+    msg!(
+        "spot_market.protocol_revenue.withdraw_period: {:?} -> {:?}",
+        spot_market.protocol_revenue.withdraw_period,
+        withdraw_period
+    );
+
+    spot_market.protocol_revenue.max_withdraw_per_period = max_withdraw_per_period;
+    spot_market.protocol_revenue.withdraw_period = withdraw_period;
+    Ok(())
+}
+
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_withdraw_protocol_revenue(
+    ctx: Context<WithdrawProtocolRevenue>,
+    market_index: u16,
+    amount: u64,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        market_index == spot_market.market_index,
+        ErrorCode::DefaultError,
+        "market_index doesnt match spot_market"
+    )?;
+
+    controller::spot_balance::update_spot_market_cumulative_interest(spot_market, None, now)?;
+
+    controller::spot_balance::withdraw_protocol_revenue(
+        amount,
+        ctx.accounts.spot_market_vault.amount,
+        spot_market,
+        now,
+    )?;
+
+    controller::token::send_from_program_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.spot_market_vault,
+        &ctx.accounts.admin_token_account,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        amount,
+    )?;
+
+    ctx.accounts.spot_market_vault.reload()?;
+    math::spot_withdraw::validate_spot_market_vault_amount(
+        spot_market,
+        ctx.accounts.spot_market_vault.amount,
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_resize_spot_market(ctx: Context<ResizeSpotMarket>, market_index: u16) -> Result<()> {
+    // the account is reallocated by the account constraints, loading it checks it fits the current layout
+    let spot_market = load!(ctx.accounts.spot_market)?;
+
+    msg!(
+        "spot market {} resized to {} bytes",
+        spot_market.market_index,
+        SpotMarket::SIZE
+    );
+
+    validate!(
+        spot_market.market_index == market_index,
+        ErrorCode::DefaultError,
+        "market_index dne spot_market.index"
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_initialize_junior_insurance_fund(
+    ctx: Context<InitializeJuniorInsuranceFund>,
+    market_index: u16,
+) -> Result<()> {
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        spot_market.market_index == market_index,
+        ErrorCode::DefaultError,
+        "market_index dne spot_market.index"
+    )?;
+
+    validate!(
+        !spot_market.has_junior_insurance_fund(),
+        ErrorCode::DefaultError,
+        "spot market {} already has a junior insurance fund",
+        market_index
+    )?;
+
+    // protocol must be authority of insurance fund vault
+    if ctx.accounts.junior_insurance_fund_vault.owner != state.signer {
+        return Err(ErrorCode::InvalidInsuranceFundAuthority.into());
+    }
+
+    msg!(
+        "spot_market.junior_insurance_fund.vault {} -> {}",
+        spot_market.junior_insurance_fund.vault,
+        ctx.accounts.junior_insurance_fund_vault.key()
+    );
+
+    spot_market.junior_insurance_fund = InsuranceFund {
+        vault: ctx.accounts.junior_insurance_fund_vault.key(),
+        unstaking_period: THIRTEEN_DAY,
+        ..InsuranceFund::default()
+    };
+
+    Ok(())
+}
+
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_update_junior_insurance_fund(
+    ctx: Context<AdminUpdateSpotMarket>,
+    unstaking_period: i64,
+    revenue_settle_period: i64,
+    user_if_factor: u32,
+    total_if_factor: u32,
+) -> Result<()> {
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        spot_market.has_junior_insurance_fund(),
+        ErrorCode::JuniorInsuranceFundNotInitialized,
+        "spot market {} has no junior insurance fund",
+        spot_market.market_index
+    )?;
+
+    validate!(
+        unstaking_period >= 0 && revenue_settle_period >= 0,
+        ErrorCode::DefaultError,
+        "unstaking_period and revenue_settle_period must be non-negative"
+    )?;
+
+    validate!(
+        user_if_factor <= total_if_factor,
+        ErrorCode::DefaultError,
+        "user_if_factor must be <= total_if_factor"
+    )?;
+
+    validate!(
+        total_if_factor <= IF_FACTOR_PRECISION.cast()?,
+        ErrorCode::DefaultError,
+        "total_if_factor must be <= 100%"
+    )?;
+
+    msg!(
+        "spot_market.junior_insurance_fund.unstaking_period: {:?} -> {:?}",
+        spot_market.junior_insurance_fund.unstaking_period,
+        unstaking_period
+    );
+    msg!(
+        "spot_market.junior_insurance_fund.revenue_settle_period: {:?} -> {:?}",
+        spot_market.junior_insurance_fund.revenue_settle_period,
+        revenue_settle_period
+    );
+    msg!(
+        "spot_market.junior_insurance_fund.user_factor: {:?} -> {:?}",
+        spot_market.junior_insurance_fund.user_factor,
+        user_if_factor
+    );
+    msg!(
+        "spot_market.junior_insurance_fund.total_factor: {:?} -> {:?}",
+        spot_market.junior_insurance_fund.total_factor,
+        total_if_factor
+    );
+
+    spot_market.junior_insurance_fund.unstaking_period = unstaking_period;
+    spot_market.junior_insurance_fund.revenue_settle_period = revenue_settle_period;
+    spot_market.junior_insurance_fund.user_factor = user_if_factor;
+    spot_market.junior_insurance_fund.total_factor = total_if_factor;
+
+    Ok(())
+}
+
 #[access_control(
     spot_market_valid(&ctx.accounts.spot_market)
 )]
@@ -1607,6 +2015,23 @@ pub fn handle_update_spot_market_status(
     Ok(())
 }
 
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_update_spot_market_paused_operations(
+    ctx: Context<AdminUpdateSpotMarket>,
+    paused_operations: u8,
+) -> Result<()> {
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    msg!(
+        "spot_market.paused_operations {} -> {}",
+        spot_market.paused_operations,
+        paused_operations
+    );
+    spot_market.paused_operations = paused_operations;
+    Ok(())
+}
+
 #[access_control(
     spot_market_valid(&ctx.accounts.spot_market)
 )]
@@ -1721,43 +2146,341 @@ pub fn handle_update_perp_market_status(
 #[access_control(
     perp_market_valid(&ctx.accounts.perp_market)
 )]
-pub fn handle_update_perp_market_contract_tier(
+pub fn handle_update_perp_market_paused_operations(
     ctx: Context<AdminUpdatePerpMarket>,
-    contract_tier: ContractTier,
+    paused_operations: u8,
 ) -> Result<()> {
     let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
-    perp_market.contract_tier = contract_tier;
+    msg!(
+        "perp_market.paused_operations {} -> {}",
+        perp_market.paused_operations,
+        paused_operations
+    );
+    perp_market.paused_operations = paused_operations;
     Ok(())
 }
 
 #[access_control(
     perp_market_valid(&ctx.accounts.perp_market)
 )]
-pub fn handle_update_perp_market_imf_factor(
+pub fn handle_update_perp_market_contract_tier(
     ctx: Context<AdminUpdatePerpMarket>,
-    imf_factor: u32,
-    unrealized_pnl_imf_factor: u32,
+    contract_tier: ContractTier,
 ) -> Result<()> {
-    validate!(
-        imf_factor <= SPOT_IMF_PRECISION,
-        ErrorCode::DefaultError,
-        "invalid imf factor",
-    )?;
-    validate!(
-        unrealized_pnl_imf_factor <= SPOT_IMF_PRECISION,
-        ErrorCode::DefaultError,
-        "invalid unrealized pnl imf factor",
-    )?;
     let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
-    perp_market.imf_factor = imf_factor;
-    perp_market.unrealized_pnl_imf_factor = unrealized_pnl_imf_factor;
+    perp_market.contract_tier = contract_tier;
     Ok(())
 }
 
 #[access_control(
     perp_market_valid(&ctx.accounts.perp_market)
 )]
-pub fn handle_update_perp_market_unrealized_asset_weight(
+pub fn handle_update_perp_market_funding_params(
+    ctx: Context<AdminUpdatePerpMarket>,
+    funding_period: i64,
+    funding_max_price_spread: u32,
+    funding_interest_rate: i32,
+    funding_premium_dampener: u32,
+) -> Result<()> {
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    msg!(
+        "perp_market.amm.funding_period {} -> {}",
+        perp_market.amm.funding_period,
+        funding_period
+    );
+
+    msg!(
+        "perp_market.amm.funding_max_price_spread {} -> {}",
+        perp_market.amm.funding_max_price_spread,
+        funding_max_price_spread
+    );
+
+    msg!(
+        "perp_market.amm.funding_interest_rate {} -> {}",
+        perp_market.amm.funding_interest_rate,
+        funding_interest_rate
+    );
+
+    msg!(
+        "perp_market.amm.funding_premium_dampener {} -> {}",
+        perp_market.amm.funding_premium_dampener,
+        funding_premium_dampener
+    );
+
+    perp_market.amm.funding_period = funding_period;
+    perp_market.amm.funding_max_price_spread = funding_max_price_spread;
+    perp_market.amm.funding_interest_rate = funding_interest_rate;
+    perp_market.amm.funding_premium_dampener = funding_premium_dampener;
+
+    validate_perp_market_funding_period(perp_market)?;
+    validate_perp_market_funding_params(perp_market)?;
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+    valid_oracle_for_perp_market(&ctx.accounts.oracle, &ctx.accounts.perp_market)
+)]
+pub fn handle_update_perp_market_continuous_funding(
+    ctx: Context<RepegCurve>,
+    continuous_funding: bool,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let state = &ctx.accounts.state;
+
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    let mut oracle_map = OracleMap::load_one(
+        &ctx.accounts.oracle,
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    msg!(
+        "perp_market.amm.continuous_funding {} -> {}",
+        perp_market.amm.continuous_funding,
+        continuous_funding
+    );
+
+    // accrue up to now under the current mode before switching
+    let funding_paused = controller::funding::is_continuous_funding_paused(
+        perp_market,
+        &mut oracle_map,
+        state.funding_paused()?,
+    )?;
+    controller::funding::accrue_continuous_funding(perp_market, now, funding_paused)?;
+
+    if continuous_funding != perp_market.amm.continuous_funding {
+        // restart the funding period so funding accrued in one mode isn't charged again in the other
+        perp_market.amm.last_funding_rate_ts = now;
+        perp_market.amm.last_funding_accrual_ts = now;
+        perp_market.amm.funding_imbalance_revenue_since_last_funding = 0;
+    }
+
+    perp_market.amm.continuous_funding = continuous_funding;
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_update_perp_market_amm_liquidation_grace_period(
+    ctx: Context<AdminUpdatePerpMarket>,
+    amm_liquidation_grace_period: u32,
+) -> Result<()> {
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    msg!(
+        "perp_market.amm_liquidation_grace_period {} -> {}",
+        perp_market.amm_liquidation_grace_period,
+        amm_liquidation_grace_period
+    );
+
+    perp_market.amm_liquidation_grace_period = amm_liquidation_grace_period;
+    Ok(())
+}
+
+pub fn handle_resize_perp_market(ctx: Context<ResizePerpMarket>, market_index: u16) -> Result<()> {
+    // the account is reallocated by the account constraints, loading it checks it fits the current layout
+    let perp_market = load!(ctx.accounts.perp_market)?;
+
+    msg!(
+        "perp market {} resized to {} bytes",
+        perp_market.market_index,
+        PerpMarket::SIZE
+    );
+
+    validate!(
+        perp_market.market_index == market_index,
+        ErrorCode::DefaultError,
+        "market_index dne perp_market.index"
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_initialize_perp_market_insurance_fund(
+    ctx: Context<InitializePerpMarketInsuranceFund>,
+    market_index: u16,
+) -> Result<()> {
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        perp_market.market_index == market_index,
+        ErrorCode::DefaultError,
+        "market_index dne perp_market.index"
+    )?;
+
+    validate!(
+        !perp_market.has_insurance_fund(),
+        ErrorCode::DefaultError,
+        "perp market {} already has an insurance fund",
+        market_index
+    )?;
+
+    // protocol must be authority of insurance fund vault
+    if ctx.accounts.insurance_fund_vault.owner != state.signer {
+        return Err(ErrorCode::InvalidInsuranceFundAuthority.into());
+    }
+
+    msg!(
+        "perp_market.insurance_fund.vault {} -> {}",
+        perp_market.insurance_fund.vault,
+        ctx.accounts.insurance_fund_vault.key()
+    );
+
+    perp_market.insurance_fund = InsuranceFund {
+        vault: ctx.accounts.insurance_fund_vault.key(),
+        unstaking_period: THIRTEEN_DAY,
+        ..InsuranceFund::default()
+    };
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_update_perp_market_insurance_fund(
+    ctx: Context<AdminUpdatePerpMarket>,
+    unstaking_period: i64,
+    revenue_settle_period: i64,
+    user_if_factor: u32,
+    total_if_factor: u32,
+) -> Result<()> {
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    validate!(
+        perp_market.has_insurance_fund(),
+        ErrorCode::PerpMarketInsuranceFundNotInitialized,
+        "perp market {} has no insurance fund",
+        perp_market.market_index
+    )?;
+
+    validate!(
+        unstaking_period >= 0 && revenue_settle_period >= 0,
+        ErrorCode::DefaultError,
+        "unstaking_period and revenue_settle_period must be non-negative"
+    )?;
+
+    validate!(
+        user_if_factor <= total_if_factor,
+        ErrorCode::DefaultError,
+        "user_if_factor must be <= total_if_factor"
+    )?;
+
+    validate!(
+        total_if_factor <= IF_FACTOR_PRECISION.cast()?,
+        ErrorCode::DefaultError,
+        "total_if_factor must be <= 100%"
+    )?;
+
+    msg!(
+        "perp_market.insurance_fund.unstaking_period: {:?} -> {:?}",
+        perp_market.insurance_fund.unstaking_period,
+        unstaking_period
+    );
+    msg!(
+        "perp_market.insurance_fund.revenue_settle_period: {:?} -> {:?}",
+        perp_market.insurance_fund.revenue_settle_period,
+        revenue_settle_period
+    );
+    msg!(
+        "perp_market.user_if_factor: {:?} -> {:?}",
+        perp_market.insurance_fund.user_factor,
+        user_if_factor
+    );
+    msg!(
+        "perp_market.total_if_factor: {:?} -> {:?}",
+        perp_market.insurance_fund.total_factor,
+        total_if_factor
+    );
+
+    perp_market.insurance_fund.unstaking_period = unstaking_period;
+    perp_market.insurance_fund.revenue_settle_period = revenue_settle_period;
+    perp_market.insurance_fund.user_factor = user_if_factor;
+    perp_market.insurance_fund.total_factor = total_if_factor;
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_update_perp_market_circuit_breaker(
+    ctx: Context<AdminUpdatePerpMarket>,
+    circuit_breaker_threshold: u32,
+    circuit_breaker_cooldown_period: u32,
+) -> Result<()> {
+    validate!(
+        circuit_breaker_threshold.cast::<u128>()? <= PERCENTAGE_PRECISION,
+        ErrorCode::DefaultError,
+        "invalid circuit breaker threshold",
+    )?;
+    validate!(
+        circuit_breaker_threshold == 0 || circuit_breaker_cooldown_period > 0,
+        ErrorCode::DefaultError,
+        "circuit breaker cooldown period must be positive",
+    )?;
+
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    msg!(
+        "perp_market.circuit_breaker_threshold {} -> {}",
+        perp_market.circuit_breaker_threshold,
+        circuit_breaker_threshold
+    );
+    msg!(
+        "perp_market.circuit_breaker_cooldown_period {} -> {}",
+        perp_market.circuit_breaker_cooldown_period,
+        circuit_breaker_cooldown_period
+    );
+
+    perp_market.circuit_breaker_threshold = circuit_breaker_threshold;
+    perp_market.circuit_breaker_cooldown_period = circuit_breaker_cooldown_period;
+
+    if circuit_breaker_threshold == 0 {
+        perp_market.circuit_breaker_cooldown_end_ts = 0;
+    }
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_update_perp_market_imf_factor(
+    ctx: Context<AdminUpdatePerpMarket>,
+    imf_factor: u32,
+    unrealized_pnl_imf_factor: u32,
+) -> Result<()> {
+    validate!(
+        imf_factor <= SPOT_IMF_PRECISION,
+        ErrorCode::DefaultError,
+        "invalid imf factor",
+    )?;
+    validate!(
+        unrealized_pnl_imf_factor <= SPOT_IMF_PRECISION,
+        ErrorCode::DefaultError,
+        "invalid unrealized pnl imf factor",
+    )?;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    perp_market.imf_factor = imf_factor;
+    perp_market.unrealized_pnl_imf_factor = unrealized_pnl_imf_factor;
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_update_perp_market_unrealized_asset_weight(
     ctx: Context<AdminUpdatePerpMarket>,
     unrealized_initial_asset_weight: u32,
     unrealized_maintenance_asset_weight: u32,
@@ -1882,6 +2605,89 @@ pub fn handle_update_liquidation_duration(
     Ok(())
 }
 
+pub fn handle_update_liquidation_auction_duration(
+    ctx: Context<AdminUpdateState>,
+    liquidation_auction_duration: u16,
+) -> Result<()> {
+    msg!(
+        "liquidation_auction_duration {} -> {}",
+        ctx.accounts.state.liquidation_auction_duration,
+        liquidation_auction_duration
+    );
+
+    ctx.accounts.state.liquidation_auction_duration = liquidation_auction_duration;
+    Ok(())
+}
+
+pub fn handle_update_auto_deleverage_contract_tier(
+    ctx: Context<AdminUpdateState>,
+    contract_tier: ContractTier,
+    auto_deleverage: bool,
+) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+
+    msg!(
+        "auto deleverage for contract tier {:?}: {} -> {}",
+        contract_tier,
+        state.is_auto_deleverage_enabled(contract_tier),
+        auto_deleverage
+    );
+
+    let contract_tier_flag = 1_u8 << contract_tier as u8;
+    if auto_deleverage {
+        state.auto_deleverage_contract_tiers |= contract_tier_flag;
+    } else {
+        state.auto_deleverage_contract_tiers &= !contract_tier_flag;
+    }
+
+    Ok(())
+}
+
+pub fn handle_update_auto_deleverage_score_threshold(
+    ctx: Context<AdminUpdateState>,
+    auto_deleverage_score_threshold: u32,
+) -> Result<()> {
+    msg!(
+        "auto_deleverage_score_threshold {} -> {}",
+        ctx.accounts.state.auto_deleverage_score_threshold,
+        auto_deleverage_score_threshold
+    );
+
+    ctx.accounts.state.auto_deleverage_score_threshold = auto_deleverage_score_threshold;
+    Ok(())
+}
+
+pub fn handle_update_health_bands(
+    ctx: Context<AdminUpdateState>,
+    health_bands: [u8; 4],
+) -> Result<()> {
+    for i in 0..health_bands.len() {
+        validate!(
+            health_bands[i] <= 100,
+            ErrorCode::InvalidHealthBands,
+            "health band {} must be <= 100",
+            health_bands[i]
+        )?;
+
+        if i > 0 {
+            validate!(
+                health_bands[i] == 0 || health_bands[i] < health_bands[i - 1],
+                ErrorCode::InvalidHealthBands,
+                "health bands must be descending"
+            )?;
+        }
+    }
+
+    msg!(
+        "health_bands {:?} -> {:?}",
+        ctx.accounts.state.health_bands,
+        health_bands
+    );
+
+    ctx.accounts.state.health_bands = health_bands;
+    Ok(())
+}
+
 pub fn handle_update_oracle_guard_rails(
     ctx: Context<AdminUpdateState>,
     oracle_guard_rails: OracleGuardRails,
@@ -2185,14 +2991,138 @@ pub fn handle_admin_remove_insurance_fund_stake(
     Ok(())
 }
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(
-        init,
-        seeds = [b"drift_state".as_ref()],
-        space = State::SIZE,
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_initialize_insurance_fund_share_mint(
+    ctx: Context<InitializeInsuranceFundShareMint>,
+    market_index: u16,
+) -> Result<()> {
+    let spot_market = load!(ctx.accounts.spot_market)?;
+
+    validate!(
+        market_index == spot_market.market_index,
+        ErrorCode::DefaultError,
+        "market_index doesnt match spot_market"
+    )?;
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    let mut tokenized_insurance_fund_stake = ctx
+        .accounts
+        .tokenized_insurance_fund_stake
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    // the share mint owns the pooled stake backing every outstanding share token
+    *tokenized_insurance_fund_stake = InsuranceFundStake::new(
+        ctx.accounts.insurance_fund_share_mint.key(),
+        market_index,
+        now,
+    );
+
+    msg!(
+        "spot market {} insurance fund share mint {}",
+        market_index,
+        ctx.accounts.insurance_fund_share_mint.key()
+    );
+
+    Ok(())
+}
+
+#[access_control(
+    perp_market_valid(&ctx.accounts.perp_market)
+)]
+pub fn handle_initialize_tokenized_lp_pool(
+    ctx: Context<InitializeTokenizedLpPool>,
+    market_index: u16,
+) -> Result<()> {
+    let perp_market = load!(ctx.accounts.perp_market)?;
+
+    validate!(
+        market_index == perp_market.market_index,
+        ErrorCode::DefaultError,
+        "market_index doesnt match perp_market"
+    )?;
+
+    let tokenized_lp_pool_key = ctx.accounts.tokenized_lp_pool.key();
+    let pool_user_key = ctx.accounts.pool_user.key();
+    let now = Clock::get()?.unix_timestamp;
+
+    let mut tokenized_lp_pool = ctx
+        .accounts
+        .tokenized_lp_pool
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    *tokenized_lp_pool = TokenizedLpPool {
+        pubkey: tokenized_lp_pool_key,
+        mint: ctx.accounts.lp_share_mint.key(),
+        user: pool_user_key,
+        token_supply: 0,
+        market_index,
+        padding: [0; 6],
+    };
+
+    // the pool owns its user, so only the program can move the pool's shares and collateral
+    let mut pool_user_stats = ctx
+        .accounts
+        .pool_user_stats
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    *pool_user_stats = UserStats {
+        authority: tokenized_lp_pool_key,
+        number_of_sub_accounts: 1,
+        number_of_sub_accounts_created: 1,
+        last_taker_volume_30d_ts: now,
+        last_maker_volume_30d_ts: now,
+        last_filler_volume_30d_ts: now,
+        ..UserStats::default()
+    };
+
+    let mut pool_user = ctx
+        .accounts
+        .pool_user
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    pool_user.authority = tokenized_lp_pool_key;
+    pool_user.sub_account_id = 0;
+    pool_user.next_order_id = 1;
+    pool_user.next_liquidation_id = 1;
+
+    let state = &mut ctx.accounts.state;
+    safe_increment!(state.number_of_authorities, 1);
+    safe_increment!(state.number_of_sub_accounts, 1);
+
+    emit!(NewUserRecord {
+        ts: now,
+        user_authority: tokenized_lp_pool_key,
+        user: pool_user_key,
+        sub_account_id: 0,
+        name: pool_user.name,
+        referrer: Pubkey::default(),
+    });
+
+    msg!(
+        "perp market {} lp share mint {}",
+        market_index,
+        ctx.accounts.lp_share_mint.key()
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        seeds = [b"drift_state".as_ref()],
+        space = State::SIZE,
         bump,
         payer = admin
     )]
@@ -2366,6 +3296,98 @@ pub struct UpdatePhoenixFulfillmentConfig<'info> {
     pub admin: Signer<'info>,
 }
 
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct InitializeConstantProductPoolFulfillmentConfig<'info> {
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub base_spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        seeds = [b"spot_market", 0_u16.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub quote_spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    /// CHECK: checked in ix
+    pub pool_program: AccountInfo<'info>,
+    /// CHECK: checked in ix
+    pub pool: AccountInfo<'info>,
+    #[account(
+        init,
+        seeds = [b"constant_product_pool_fulfillment_config".as_ref(), pool.key.as_ref()],
+        space = ConstantProductPoolFulfillmentConfig::SIZE,
+        bump,
+        payer = admin,
+    )]
+    pub constant_product_pool_fulfillment_config:
+        AccountLoader<'info, ConstantProductPoolFulfillmentConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConstantProductPoolFulfillmentConfig<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(mut)]
+    pub constant_product_pool_fulfillment_config:
+        AccountLoader<'info, ConstantProductPoolFulfillmentConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(perp_market_index: u16, spot_market_index: u16)]
+pub struct InitializeAmmHedgeConfig<'info> {
+    #[account(
+        seeds = [b"perp_market", perp_market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        seeds = [b"spot_market", spot_market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        init,
+        seeds = [b"amm_hedge_config".as_ref(), perp_market_index.to_le_bytes().as_ref()],
+        space = AmmHedgeConfig::SIZE,
+        bump,
+        payer = admin,
+    )]
+    pub amm_hedge_config: AccountLoader<'info, AmmHedgeConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAmmHedgeConfig<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(mut)]
+    pub amm_hedge_config: AccountLoader<'info, AmmHedgeConfig>,
+    pub admin: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateSerumVault<'info> {
     #[account(
@@ -2425,6 +3447,70 @@ pub struct AdminUpdatePerpMarket<'info> {
     pub perp_market: AccountLoader<'info, PerpMarket>,
 }
 
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct ResizePerpMarket<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump,
+        realloc = PerpMarket::SIZE,
+        realloc::payer = admin,
+        realloc::zero = false
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct InitializePerpMarketInsuranceFund<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        seeds = [b"spot_market", QUOTE_SPOT_MARKET_INDEX.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub quote_spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        constraint = quote_spot_market.load()?.mint.eq(&quote_spot_market_mint.key())
+    )]
+    pub quote_spot_market_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        seeds = [b"perp_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        payer = admin,
+        token::mint = quote_spot_market_mint,
+        token::authority = drift_signer
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: program signer
+    pub drift_signer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct SettleExpiredMarketPoolsToRevenuePool<'info> {
     #[account(
@@ -2524,6 +3610,156 @@ pub struct AdminUpdateSpotMarket<'info> {
     pub spot_market: AccountLoader<'info, SpotMarket>,
 }
 
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct InitializeTokenizedLpPool<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        init,
+        seeds = [b"lp_share_mint".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        payer = admin,
+        mint::decimals = 9, // lp shares use AMM_RESERVE_PRECISION
+        mint::authority = drift_signer
+    )]
+    pub lp_share_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        seeds = [b"tokenized_lp_pool".as_ref(), market_index.to_le_bytes().as_ref()],
+        space = TokenizedLpPool::SIZE,
+        bump,
+        payer = admin
+    )]
+    pub tokenized_lp_pool: AccountLoader<'info, TokenizedLpPool>,
+    #[account(
+        init,
+        seeds = [b"user", tokenized_lp_pool.key().as_ref(), 0u16.to_le_bytes().as_ref()],
+        space = User::SIZE,
+        bump,
+        payer = admin
+    )]
+    pub pool_user: AccountLoader<'info, User>,
+    #[account(
+        init,
+        seeds = [b"user_stats", tokenized_lp_pool.key().as_ref()],
+        space = UserStats::SIZE,
+        bump,
+        payer = admin
+    )]
+    pub pool_user_stats: AccountLoader<'info, UserStats>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: program signer
+    pub drift_signer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct WithdrawProtocolRevenue<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = spot_market_vault.mint
+    )]
+    pub admin_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct ResizeSpotMarket<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump,
+        realloc = SpotMarket::SIZE,
+        realloc::payer = admin,
+        realloc::zero = false
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct InitializeJuniorInsuranceFund<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        constraint = spot_market.load()?.mint.eq(&spot_market_mint.key())
+    )]
+    pub spot_market_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        seeds = [b"junior_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        payer = admin,
+        token::mint = spot_market_mint,
+        token::authority = drift_signer
+    )]
+    pub junior_insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: program signer
+    pub drift_signer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct AdminUpdateSpotMarketOracle<'info> {
     pub admin: Signer<'info>,
@@ -2569,3 +3805,44 @@ pub struct AdminRemoveInsuranceFundStake<'info> {
     pub admin_token_account: Box<Account<'info, TokenAccount>>,
     pub token_program: Program<'info, Token>,
 }
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct InitializeInsuranceFundShareMint<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        init,
+        seeds = [b"insurance_fund_share_mint".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        payer = admin,
+        mint::decimals = spot_market.load()?.decimals as u8,
+        mint::authority = drift_signer
+    )]
+    pub insurance_fund_share_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        seeds = [b"insurance_fund_stake", insurance_fund_share_mint.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        space = InsuranceFundStake::SIZE,
+        bump,
+        payer = admin
+    )]
+    pub tokenized_insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: program signer
+    pub drift_signer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
@@ -1,16 +1,21 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount};
+use anchor_spl::token::{Mint, Token, TokenAccount};
 
 use crate::error::ErrorCode;
+use crate::get_then_update_id;
 use crate::instructions::constraints::*;
-use crate::load_mut;
-use crate::state::insurance_fund_stake::InsuranceFundStake;
-use crate::state::spot_market::SpotMarket;
+use crate::math::casting::Cast;
+use crate::state::events::{DepositDirection, DepositExplanation, DepositRecord};
+use crate::state::insurance_fund_stake::{InsuranceFundStake, InsuranceFundTranche};
+use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_market::{MarketStatus, PerpMarket};
+use crate::state::spot_market::{SpotBalanceType, SpotMarket};
 use crate::state::state::State;
 use crate::state::traits::Size;
-use crate::state::user::UserStats;
+use crate::state::user::{MarketType, User, UserStats};
 use crate::validate;
-use crate::{controller, math};
+use crate::{controller, load, load_mut, math};
 
 pub fn handle_initialize_insurance_fund_stake(
     ctx: Context<InitializeInsuranceFundStake>,
@@ -30,6 +35,30 @@ pub fn handle_initialize_insurance_fund_stake(
     Ok(())
 }
 
+pub fn handle_resize_insurance_fund_stake(
+    ctx: Context<ResizeInsuranceFundStake>,
+    market_index: u16,
+) -> Result<()> {
+    // the account is reallocated by the account constraints, loading it checks it fits the current layout.
+    // a stake can't change its shares until it's resized, so starting from a zero revenue_per_share
+    // credits it with exactly the revenue settled to its shares since the upgrade
+    let insurance_fund_stake = load!(ctx.accounts.insurance_fund_stake)?;
+
+    msg!(
+        "insurance fund stake for market {} resized to {} bytes",
+        insurance_fund_stake.market_index,
+        InsuranceFundStake::SIZE
+    );
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    Ok(())
+}
+
 pub fn handle_add_insurance_fund_stake(
     ctx: Context<AddInsuranceFundStake>,
     market_index: u16,
@@ -133,7 +162,7 @@ pub fn handle_request_remove_insurance_fund_stake(
         "Requested lp_shares = 0"
     )?;
 
-    let user_if_shares = insurance_fund_stake.checked_if_shares(spot_market)?;
+    let user_if_shares = insurance_fund_stake.checked_if_shares(&spot_market.insurance_fund)?;
     validate!(user_if_shares >= n_shares, ErrorCode::InsufficientIFShares)?;
 
     controller::insurance::request_remove_insurance_fund_stake(
@@ -238,147 +267,1246 @@ pub fn handle_remove_insurance_fund_stake(
     Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(
+pub fn handle_initialize_perp_insurance_fund_stake(
+    ctx: Context<InitializePerpInsuranceFundStake>,
     market_index: u16,
-)]
-pub struct InitializeInsuranceFundStake<'info> {
-    #[account(
-        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub spot_market: AccountLoader<'info, SpotMarket>,
-    #[account(
-        init,
-        seeds = [b"insurance_fund_stake", authority.key.as_ref(), market_index.to_le_bytes().as_ref()],
-        space = InsuranceFundStake::SIZE,
-        bump,
-        payer = payer
-    )]
-    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
-    #[account(
-        mut,
-        has_one = authority
-    )]
-    pub user_stats: AccountLoader<'info, UserStats>,
-    pub state: Box<Account<'info, State>>,
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub rent: Sysvar<'info, Rent>,
-    pub system_program: Program<'info, System>,
+) -> Result<()> {
+    let perp_market = load!(ctx.accounts.perp_market)?;
+
+    validate!(
+        perp_market.has_insurance_fund(),
+        ErrorCode::PerpMarketInsuranceFundNotInitialized,
+        "perp market {} has no insurance fund",
+        market_index
+    )?;
+
+    let mut if_stake = ctx
+        .accounts
+        .insurance_fund_stake
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    *if_stake = InsuranceFundStake::new(*ctx.accounts.authority.key, market_index, now);
+    if_stake.market_type = MarketType::Perp;
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(market_index: u16)]
-pub struct AddInsuranceFundStake<'info> {
-    pub state: Box<Account<'info, State>>,
-    #[account(
-        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub spot_market: AccountLoader<'info, SpotMarket>,
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub user_stats: AccountLoader<'info, UserStats>,
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
-    #[account(
-        mut,
-        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+pub fn handle_add_perp_insurance_fund_stake(
+    ctx: Context<AddPerpInsuranceFundStake>,
+    market_index: u16,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Err(ErrorCode::InsufficientDeposit.into());
+    }
 
-    #[account(
-        constraint = state.signer.eq(&drift_signer.key())
-    )]
-    /// CHECK: forced drift_signer
-    pub drift_signer: AccountInfo<'info>,
-    #[account(
-        mut,
-        token::mint = insurance_fund_vault.mint,
-        token::authority = authority
-    )]
-    pub user_token_account: Box<Account<'info, TokenAccount>>,
-    pub token_program: Program<'info, Token>,
+    let clock = Clock::get()?;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::InvalidMarketAccount,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0
+            && insurance_fund_stake.last_withdraw_request_value == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "withdraw request in progress"
+    )?;
+
+    controller::insurance::add_perp_insurance_fund_stake(
+        amount,
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        perp_market,
+        clock.unix_timestamp,
+    )?;
+
+    controller::token::receive(
+        &ctx.accounts.token_program,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.insurance_fund_vault,
+        &ctx.accounts.authority,
+        amount,
+    )?;
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(market_index: u16,)]
-pub struct RequestRemoveInsuranceFundStake<'info> {
-    #[account(
-        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub spot_market: AccountLoader<'info, SpotMarket>,
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub user_stats: AccountLoader<'info, UserStats>,
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+pub fn handle_request_remove_perp_insurance_fund_stake(
+    ctx: Context<RequestRemovePerpInsuranceFundStake>,
+    market_index: u16,
+    amount: u64,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::InvalidMarketAccount,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "Withdraw request is already in progress"
+    )?;
+
+    let n_shares = math::insurance::vault_amount_to_if_shares(
+        amount,
+        perp_market.insurance_fund.total_shares,
+        ctx.accounts.insurance_fund_vault.amount,
+    )?;
+
+    validate!(
+        n_shares > 0,
+        ErrorCode::IFWithdrawRequestTooSmall,
+        "Requested lp_shares = 0"
+    )?;
+
+    let user_if_shares = insurance_fund_stake.checked_if_shares(&perp_market.insurance_fund)?;
+    validate!(user_if_shares >= n_shares, ErrorCode::InsufficientIFShares)?;
+
+    controller::insurance::request_remove_perp_insurance_fund_stake(
+        n_shares,
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        perp_market,
+        clock.unix_timestamp,
+    )?;
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(market_index: u16,)]
-pub struct RemoveInsuranceFundStake<'info> {
-    pub state: Box<Account<'info, State>>,
-    #[account(
-        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub spot_market: AccountLoader<'info, SpotMarket>,
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub user_stats: AccountLoader<'info, UserStats>,
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
-    #[account(
-        constraint = state.signer.eq(&drift_signer.key())
-    )]
-    /// CHECK: forced drift_signer
-    pub drift_signer: AccountInfo<'info>,
-    #[account(
-        mut,
-        token::mint = insurance_fund_vault.mint,
-        token::authority = authority
-    )]
-    pub user_token_account: Box<Account<'info, TokenAccount>>,
+pub fn handle_cancel_request_remove_perp_insurance_fund_stake(
+    ctx: Context<RequestRemovePerpInsuranceFundStake>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::InvalidMarketAccount,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares != 0,
+        ErrorCode::NoIFWithdrawRequestInProgress,
+        "No withdraw request in progress"
+    )?;
+
+    controller::insurance::cancel_request_remove_perp_insurance_fund_stake(
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        perp_market,
+        now,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    withdraw_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_remove_perp_insurance_fund_stake(
+    ctx: Context<RemovePerpInsuranceFundStake>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::InvalidMarketAccount,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    let amount = controller::insurance::remove_perp_insurance_fund_stake(
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        perp_market,
+        now,
+    )?;
+
+    controller::token::send_from_program_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.insurance_fund_vault,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        amount,
+    )?;
+
+    ctx.accounts.insurance_fund_vault.reload()?;
+    validate!(
+        ctx.accounts.insurance_fund_vault.amount > 0,
+        ErrorCode::InvalidIFDetected,
+        "insurance_fund_vault.amount must remain > 0"
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_initialize_junior_insurance_fund_stake(
+    ctx: Context<InitializeJuniorInsuranceFundStake>,
+    market_index: u16,
+) -> Result<()> {
+    let spot_market = load!(ctx.accounts.spot_market)?;
+
+    validate!(
+        spot_market.has_junior_insurance_fund(),
+        ErrorCode::JuniorInsuranceFundNotInitialized,
+        "spot market {} has no junior insurance fund",
+        market_index
+    )?;
+
+    let mut if_stake = ctx
+        .accounts
+        .insurance_fund_stake
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    *if_stake = InsuranceFundStake::new(*ctx.accounts.authority.key, market_index, now);
+    if_stake.tranche = InsuranceFundTranche::Junior;
+
+    Ok(())
+}
+
+pub fn handle_add_junior_insurance_fund_stake(
+    ctx: Context<AddJuniorInsuranceFundStake>,
+    market_index: u16,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Err(ErrorCode::InsufficientDeposit.into());
+    }
+
+    let clock = Clock::get()?;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0
+            && insurance_fund_stake.last_withdraw_request_value == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "withdraw request in progress"
+    )?;
+
+    controller::insurance::add_junior_insurance_fund_stake(
+        amount,
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        spot_market,
+        clock.unix_timestamp,
+    )?;
+
+    controller::token::receive(
+        &ctx.accounts.token_program,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.insurance_fund_vault,
+        &ctx.accounts.authority,
+        amount,
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_request_remove_junior_insurance_fund_stake(
+    ctx: Context<RequestRemoveJuniorInsuranceFundStake>,
+    market_index: u16,
+    amount: u64,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares == 0,
+        ErrorCode::IFWithdrawRequestInProgress,
+        "Withdraw request is already in progress"
+    )?;
+
+    let n_shares = math::insurance::vault_amount_to_if_shares(
+        amount,
+        spot_market.junior_insurance_fund.total_shares,
+        ctx.accounts.insurance_fund_vault.amount,
+    )?;
+
+    validate!(
+        n_shares > 0,
+        ErrorCode::IFWithdrawRequestTooSmall,
+        "Requested lp_shares = 0"
+    )?;
+
+    let user_if_shares =
+        insurance_fund_stake.checked_if_shares(&spot_market.junior_insurance_fund)?;
+    validate!(user_if_shares >= n_shares, ErrorCode::InsufficientIFShares)?;
+
+    controller::insurance::request_remove_junior_insurance_fund_stake(
+        n_shares,
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        spot_market,
+        clock.unix_timestamp,
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_cancel_request_remove_junior_insurance_fund_stake(
+    ctx: Context<RequestRemoveJuniorInsuranceFundStake>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(
+        insurance_fund_stake.last_withdraw_request_shares != 0,
+        ErrorCode::NoIFWithdrawRequestInProgress,
+        "No withdraw request in progress"
+    )?;
+
+    controller::insurance::cancel_request_remove_junior_insurance_fund_stake(
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        spot_market,
+        now,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    withdraw_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_remove_junior_insurance_fund_stake(
+    ctx: Context<RemoveJuniorInsuranceFundStake>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    let amount = controller::insurance::remove_junior_insurance_fund_stake(
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        spot_market,
+        now,
+    )?;
+
+    controller::token::send_from_program_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.insurance_fund_vault,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        amount,
+    )?;
+
+    ctx.accounts.insurance_fund_vault.reload()?;
+    validate!(
+        ctx.accounts.insurance_fund_vault.amount > 0,
+        ErrorCode::InvalidIFDetected,
+        "insurance_fund_vault.amount must remain > 0"
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_update_insurance_fund_stake_claim_revenue(
+    ctx: Context<UpdateInsuranceFundStakeClaimRevenue>,
+    market_index: u16,
+    claim_revenue: bool,
+) -> Result<()> {
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    controller::insurance::update_insurance_fund_stake_claim_revenue(
+        claim_revenue,
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        spot_market,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    withdraw_not_paused(&ctx.accounts.state)
+)]
+#[access_control(
+    deposit_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_claim_insurance_fund_revenue(
+    ctx: Context<ClaimInsuranceFundRevenue>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let user_key = ctx.accounts.user.key();
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    let state = &ctx.accounts.state;
+
+    let mut oracle_map = OracleMap::load(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+
+    // revenue is paid into the user's spot balance rather than their wallet, so it's a deposit
+    validate!(
+        !matches!(spot_market.status, MarketStatus::Initialized),
+        ErrorCode::MarketBeingInitialized,
+        "Market is being initialized"
+    )?;
+
+    validate!(
+        !spot_market.is_operation_paused(MarketOperation::Deposit)?,
+        ErrorCode::MarketActionPaused,
+        "Spot Market {} deposits are currently paused",
+        spot_market.market_index
+    )?;
+
+    let amount = controller::insurance::claim_insurance_fund_revenue(
+        ctx.accounts.insurance_fund_vault.amount,
+        insurance_fund_stake,
+        user_stats,
+        spot_market,
+        now,
+    )?;
+
+    let oracle_price_data = oracle_map.get_price_data(&spot_market.oracle)?.clone();
+    controller::spot_balance::update_spot_market_cumulative_interest(
+        spot_market,
+        Some(&oracle_price_data),
+        now,
+    )?;
+
+    user.increment_total_deposits(
+        amount,
+        oracle_price_data.price,
+        spot_market.get_precision().cast()?,
+    )?;
+
+    let position_index = user.force_get_spot_position_index(market_index)?;
+    controller::spot_position::update_spot_balances_and_cumulative_deposits(
+        amount.cast()?,
+        &SpotBalanceType::Deposit,
+        spot_market,
+        &mut user.spot_positions[position_index],
+        false,
+        None,
+    )?;
+
+    validate!(
+        spot_market.status.is_active_or_legacy_paused(),
+        ErrorCode::MarketActionPaused,
+        "spot_market in reduce only mode",
+    )?;
+
+    controller::token::send_from_program_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.insurance_fund_vault,
+        &ctx.accounts.spot_market_vault,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        amount,
+    )?;
+
+    ctx.accounts.insurance_fund_vault.reload()?;
+    validate!(
+        ctx.accounts.insurance_fund_vault.amount > 0,
+        ErrorCode::InvalidIFDetected,
+        "insurance_fund_vault.amount must remain > 0"
+    )?;
+
+    ctx.accounts.spot_market_vault.reload()?;
+    math::spot_withdraw::validate_spot_market_vault_amount(
+        spot_market,
+        ctx.accounts.spot_market_vault.amount,
+    )?;
+
+    let deposit_record_id = get_then_update_id!(spot_market, next_deposit_record_id);
+    emit!(DepositRecord {
+        ts: now,
+        deposit_record_id,
+        user_authority: user.authority,
+        user: user_key,
+        direction: DepositDirection::Deposit,
+        amount,
+        oracle_price: oracle_price_data.price,
+        market_deposit_balance: spot_market.deposit_balance,
+        market_withdraw_balance: spot_market.borrow_balance,
+        market_cumulative_deposit_interest: spot_market.cumulative_deposit_interest,
+        market_cumulative_borrow_interest: spot_market.cumulative_borrow_interest,
+        total_deposits_after: user.total_deposits,
+        total_withdraws_after: user.total_withdraws,
+        market_index,
+        explanation: DepositExplanation::InsuranceFundRevenue,
+        transfer_user: None,
+    });
+
+    spot_market.validate_max_token_deposits()?;
+
+    Ok(())
+}
+
+pub fn handle_tokenize_insurance_fund_stake(
+    ctx: Context<TokenizeInsuranceFundStake>,
+    market_index: u16,
+    n_shares: u128,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let tokenized_insurance_fund_stake =
+        &mut load_mut!(ctx.accounts.tokenized_insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    let token_amount = controller::insurance::tokenize_insurance_fund_stake(
+        n_shares,
+        ctx.accounts.insurance_fund_vault.amount,
+        ctx.accounts.insurance_fund_share_mint.supply,
+        insurance_fund_stake,
+        tokenized_insurance_fund_stake,
+        user_stats,
+        spot_market,
+        clock.unix_timestamp,
+    )?;
+
+    controller::token::mint_to_user(
+        &ctx.accounts.token_program,
+        &ctx.accounts.insurance_fund_share_mint,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        token_amount,
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_redeem_insurance_fund_stake_tokens(
+    ctx: Context<RedeemInsuranceFundStakeTokens>,
+    market_index: u16,
+    amount: u64,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let insurance_fund_stake = &mut load_mut!(ctx.accounts.insurance_fund_stake)?;
+    let tokenized_insurance_fund_stake =
+        &mut load_mut!(ctx.accounts.tokenized_insurance_fund_stake)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        insurance_fund_stake.market_index == market_index,
+        ErrorCode::IncorrectSpotMarketAccountPassed,
+        "insurance_fund_stake does not match market_index"
+    )?;
+
+    controller::insurance::redeem_insurance_fund_stake_tokens(
+        amount,
+        ctx.accounts.insurance_fund_vault.amount,
+        ctx.accounts.insurance_fund_share_mint.supply,
+        insurance_fund_stake,
+        tokenized_insurance_fund_stake,
+        user_stats,
+        spot_market,
+        clock.unix_timestamp,
+    )?;
+
+    controller::token::burn_from_user(
+        &ctx.accounts.token_program,
+        &ctx.accounts.insurance_fund_share_mint,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.authority,
+        amount,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(
+    market_index: u16,
+)]
+pub struct InitializeInsuranceFundStake<'info> {
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        init,
+        seeds = [b"insurance_fund_stake", authority.key.as_ref(), market_index.to_le_bytes().as_ref()],
+        space = InsuranceFundStake::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct ResizeInsuranceFundStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_stake", authority.key.as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        realloc = InsuranceFundStake::SIZE,
+        realloc::payer = payer,
+        realloc::zero = false
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    /// CHECK: the stake's authority, checked by the stake's seeds
+    pub authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct AddInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_vault.mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct RequestRemoveInsuranceFundStake<'info> {
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct RemoveInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_vault.mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    market_index: u16,
+)]
+pub struct InitializePerpInsuranceFundStake<'info> {
+    #[account(
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        init,
+        seeds = [b"perp_insurance_fund_stake", authority.key.as_ref(), market_index.to_le_bytes().as_ref()],
+        space = InsuranceFundStake::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct AddPerpInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"perp_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_vault.mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct RequestRemovePerpInsuranceFundStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"perp_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct RemovePerpInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"perp_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"perp_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_vault.mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct TokenizeInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_stake", insurance_fund_share_mint.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub tokenized_insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_share_mint".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_share_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_share_mint,
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct RedeemInsuranceFundStakeTokens<'info> {
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_stake", insurance_fund_share_mint.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub tokenized_insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_share_mint".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_share_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_share_mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    market_index: u16,
+)]
+pub struct InitializeJuniorInsuranceFundStake<'info> {
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        init,
+        seeds = [b"junior_insurance_fund_stake", authority.key.as_ref(), market_index.to_le_bytes().as_ref()],
+        space = InsuranceFundStake::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct AddJuniorInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"junior_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_vault.mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct RequestRemoveJuniorInsuranceFundStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"junior_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct RemoveJuniorInsuranceFundStake<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"junior_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = insurance_fund_vault.mint,
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct UpdateInsuranceFundStakeClaimRevenue<'info> {
+    #[account(
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct ClaimInsuranceFundRevenue<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub insurance_fund_stake: AccountLoader<'info, InsuranceFundStake>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
     pub token_program: Program<'info, Token>,
 }
@@ -1,20 +1,32 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
 
+use crate::controller::position::PositionDelta;
 use crate::error::ErrorCode;
 use crate::instructions::constraints::*;
 use crate::instructions::optional_accounts::{
-    get_maker_and_maker_stats, get_referrer_and_referrer_stats, load_maps, AccountMaps,
+    get_junior_insurance_fund_vault, get_maker_and_maker_stats, get_perp_insurance_fund_vault,
+    get_perp_lp_stats, get_referrer_and_referrer_stats, load_maps, load_shared_collateral_group,
+    AccountMaps,
 };
 use crate::load_mut;
+use crate::math::casting::Cast;
 use crate::math::constants::QUOTE_SPOT_MARKET_INDEX;
 use crate::math::insurance::if_shares_to_vault_amount;
+use crate::math::margin::meets_initial_margin_requirement;
+use crate::math::oracle::{is_oracle_valid_for_action, DriftAction};
 use crate::math::spot_withdraw::validate_spot_market_vault_amount;
+use crate::state::amm_hedge::AmmHedgeConfig;
+use crate::state::events::{LPAction, LPRecord};
+use crate::state::fulfillment_params::constant_product_pool::ConstantProductPoolFulfillmentParams;
 use crate::state::fulfillment_params::drift::MatchFulfillmentParams;
 use crate::state::fulfillment_params::phoenix::PhoenixFulfillmentParams;
 use crate::state::fulfillment_params::serum::SerumFulfillmentParams;
 use crate::state::insurance_fund_stake::InsuranceFundStake;
 use crate::state::oracle_map::OracleMap;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_lp_hedge::PerpLpHedge;
+use crate::state::perp_lp_range::PerpLpRange;
 use crate::state::perp_market::{MarketStatus, PerpMarket};
 use crate::state::perp_market_map::{
     get_market_set_for_user_positions, get_market_set_from_list, get_writable_perp_market_set,
@@ -27,7 +39,7 @@ use crate::state::spot_market_map::{
 };
 use crate::state::state::State;
 use crate::state::user::{MarketType, OrderStatus, User, UserStats};
-use crate::state::user_map::load_user_maps;
+use crate::state::user_map::{load_user_map, load_user_maps, UserMap};
 use crate::validate;
 use crate::validation::user::validate_user_is_idle;
 use crate::{controller, load, math};
@@ -133,6 +145,7 @@ pub enum SpotFulfillmentType {
     SerumV3,
     Match,
     PhoenixV1,
+    ConstantProductPool,
 }
 
 impl Default for SpotFulfillmentType {
@@ -167,7 +180,44 @@ pub fn handle_fill_spot_order<'a, 'b, 'c, 'info>(
         ctx,
         order_id,
         market_index,
-        fulfillment_type.unwrap_or(SpotFulfillmentType::Match),
+        &[fulfillment_type.unwrap_or(SpotFulfillmentType::Match)],
+        maker_order_id,
+    )
+    .map_err(|e| {
+        msg!("Err filling order id {} for user {}", order_id, user_key);
+        e
+    })?;
+
+    Ok(())
+}
+
+#[access_control(
+    fill_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_fill_spot_order_with_multiple_venues<'a, 'b, 'c, 'info>(
+    ctx: Context<'a, 'b, 'c, 'info, FillOrder<'info>>,
+    order_id: Option<u32>,
+    fulfillment_types: Vec<SpotFulfillmentType>,
+    maker_order_id: Option<u32>,
+) -> Result<()> {
+    let (order_id, market_index) = {
+        let user = &load!(ctx.accounts.user)?;
+        // if there is no order id, use the users last order id
+        let order_id = order_id.unwrap_or_else(|| user.get_last_order_id());
+        let market_index = user
+            .get_order(order_id)
+            .map(|order| order.market_index)
+            .ok_or(ErrorCode::OrderDoesNotExist)?;
+
+        (order_id, market_index)
+    };
+
+    let user_key = &ctx.accounts.user.key();
+    fill_spot_order(
+        ctx,
+        order_id,
+        market_index,
+        &fulfillment_types,
         maker_order_id,
     )
     .map_err(|e| {
@@ -182,11 +232,32 @@ fn fill_spot_order<'info>(
     ctx: Context<'_, '_, '_, 'info, FillOrder<'info>>,
     order_id: u32,
     market_index: u16,
-    fulfillment_type: SpotFulfillmentType,
+    fulfillment_types: &[SpotFulfillmentType],
     maker_order_id: Option<u32>,
 ) -> Result<()> {
     let clock = Clock::get()?;
 
+    validate!(
+        !fulfillment_types.is_empty(),
+        ErrorCode::InvalidSpotFulfillmentParams,
+        "must specify at least one fulfillment type"
+    )?;
+
+    validate!(
+        fulfillment_types.len() == 1 || !fulfillment_types.contains(&SpotFulfillmentType::Match),
+        ErrorCode::InvalidSpotFulfillmentParams,
+        "match fulfillment type cant be combined with external markets"
+    )?;
+
+    for (i, fulfillment_type) in fulfillment_types.iter().enumerate() {
+        validate!(
+            !fulfillment_types[i + 1..].contains(fulfillment_type),
+            ErrorCode::InvalidSpotFulfillmentParams,
+            "duplicate fulfillment type {:?}",
+            fulfillment_type
+        )?;
+    }
+
     let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
@@ -210,38 +281,59 @@ fn fill_spot_order<'info>(
 
     let (_referrer, _referrer_stats) = get_referrer_and_referrer_stats(remaining_accounts_iter)?;
 
-    let mut fulfillment_params: Box<dyn SpotFulfillmentParams> = match fulfillment_type {
-        SpotFulfillmentType::SerumV3 => {
-            let base_market = spot_market_map.get_ref(&market_index)?;
-            let quote_market = spot_market_map.get_quote_spot_market()?;
-            Box::new(SerumFulfillmentParams::new(
-                remaining_accounts_iter,
-                &ctx.accounts.state,
-                &base_market,
-                &quote_market,
-                clock.unix_timestamp,
-            )?)
-        }
-        SpotFulfillmentType::PhoenixV1 => {
-            let base_market = spot_market_map.get_ref(&market_index)?;
-            let quote_market = spot_market_map.get_quote_spot_market()?;
-            Box::new(PhoenixFulfillmentParams::new(
-                remaining_accounts_iter,
-                &ctx.accounts.state,
-                &base_market,
-                &quote_market,
-            )?)
-        }
-        SpotFulfillmentType::Match => {
-            let base_market = spot_market_map.get_ref(&market_index)?;
-            let quote_market = spot_market_map.get_quote_spot_market()?;
-            Box::new(MatchFulfillmentParams::new(
-                remaining_accounts_iter,
-                &base_market,
-                &quote_market,
-            )?)
-        }
-    };
+    let mut fulfillment_params: Vec<Box<dyn SpotFulfillmentParams>> =
+        Vec::with_capacity(fulfillment_types.len());
+    for fulfillment_type in fulfillment_types.iter() {
+        let params: Box<dyn SpotFulfillmentParams> = match fulfillment_type {
+            SpotFulfillmentType::SerumV3 => {
+                let base_market = spot_market_map.get_ref(&market_index)?;
+                let quote_market = spot_market_map.get_quote_spot_market()?;
+                Box::new(SerumFulfillmentParams::new(
+                    remaining_accounts_iter,
+                    &ctx.accounts.state,
+                    &base_market,
+                    &quote_market,
+                    clock.unix_timestamp,
+                )?)
+            }
+            SpotFulfillmentType::PhoenixV1 => {
+                let base_market = spot_market_map.get_ref(&market_index)?;
+                let quote_market = spot_market_map.get_quote_spot_market()?;
+                Box::new(PhoenixFulfillmentParams::new(
+                    remaining_accounts_iter,
+                    &ctx.accounts.state,
+                    &base_market,
+                    &quote_market,
+                )?)
+            }
+            SpotFulfillmentType::ConstantProductPool => {
+                let base_market = spot_market_map.get_ref(&market_index)?;
+                let quote_market = spot_market_map.get_quote_spot_market()?;
+                Box::new(ConstantProductPoolFulfillmentParams::new(
+                    remaining_accounts_iter,
+                    &ctx.accounts.state,
+                    &base_market,
+                    &quote_market,
+                )?)
+            }
+            SpotFulfillmentType::Match => {
+                let base_market = spot_market_map.get_ref(&market_index)?;
+                let quote_market = spot_market_map.get_quote_spot_market()?;
+                Box::new(MatchFulfillmentParams::new(
+                    remaining_accounts_iter,
+                    &base_market,
+                    &quote_market,
+                )?)
+            }
+        };
+        fulfillment_params.push(params);
+    }
+
+    let mut fulfillment_params_refs: Vec<&mut dyn SpotFulfillmentParams> =
+        Vec::with_capacity(fulfillment_params.len());
+    for params in fulfillment_params.iter_mut() {
+        fulfillment_params_refs.push(params.as_mut());
+    }
 
     controller::orders::fill_spot_order(
         order_id,
@@ -257,12 +349,14 @@ fn fill_spot_order<'info>(
         maker_stats.as_ref(),
         maker_order_id,
         &clock,
-        fulfillment_params.as_mut(),
+        &mut fulfillment_params_refs,
     )?;
 
     let base_market = spot_market_map.get_ref(&market_index)?;
     let quote_market = spot_market_map.get_quote_spot_market()?;
-    fulfillment_params.validate_vault_amounts(&base_market, &quote_market)?;
+    for params in fulfillment_params.iter_mut() {
+        params.validate_vault_amounts(&base_market, &quote_market)?;
+    }
 
     Ok(())
 }
@@ -346,6 +440,77 @@ pub fn handle_force_cancel_orders<'info>(ctx: Context<ForceCancelOrder>) -> Resu
     Ok(())
 }
 
+#[access_control(
+    exchange_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_force_place_deleverage_order<'info>(
+    ctx: Context<ForceCancelOrder>,
+    market_index: u16,
+) -> Result<()> {
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &MarketSet::new(),
+        &MarketSet::new(),
+        Clock::get()?.slot,
+        None,
+    )?;
+
+    controller::orders::force_place_deleverage_order(
+        &ctx.accounts.state,
+        &ctx.accounts.user,
+        &spot_market_map,
+        &perp_market_map,
+        &mut oracle_map,
+        &ctx.accounts.filler,
+        &Clock::get()?,
+        market_index,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    exchange_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_place_perp_lp_hedge_order(
+    ctx: Context<PlacePerpLpHedgeOrder>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &get_writable_perp_market_set(market_index),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    let perp_lp_hedge = &mut load_mut!(ctx.accounts.perp_lp_hedge)?;
+
+    controller::orders::place_perp_lp_hedge_order(
+        state,
+        &ctx.accounts.user,
+        perp_lp_hedge,
+        &spot_market_map,
+        &perp_market_map,
+        &mut oracle_map,
+        &clock,
+        market_index,
+    )?;
+
+    Ok(())
+}
+
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
 )]
@@ -454,6 +619,16 @@ pub fn handle_settle_pnl(ctx: Context<SettlePNL>, market_index: u16) -> Result<(
         user.update_last_active_slot(clock.slot);
     }
 
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        clock.unix_timestamp,
+    )?;
+
     let spot_market = spot_market_map.get_quote_spot_market()?;
     validate_spot_market_vault_amount(&spot_market, ctx.accounts.spot_market_vault.amount)?;
 
@@ -496,20 +671,163 @@ pub fn handle_settle_lp<'info>(ctx: Context<SettleLP>, market_index: u16) -> Res
     let clock = Clock::get()?;
     let now = clock.unix_timestamp;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
-        perp_market_map, ..
+        perp_market_map,
+        mut oracle_map,
+        ..
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &get_writable_perp_market_set(market_index),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
+    let perp_lp_stats = get_perp_lp_stats(remaining_accounts_iter, &user_key, market_index)?;
 
     let market = &mut perp_market_map.get_ref_mut(&market_index)?;
-    controller::lp::settle_funding_payment_then_lp(user, &user_key, market, now)?;
+    let funding_paused = controller::funding::is_continuous_funding_paused(
+        market,
+        &mut oracle_map,
+        state.funding_paused()?,
+    )?;
+
+    if let Some(perp_lp_stats) = perp_lp_stats {
+        controller::funding::settle_funding_payment(user, &user_key, market, now, funding_paused)?;
+
+        let oracle_price = oracle_map.get_price_data(&market.amm.oracle)?.price;
+        let position = user.force_get_perp_position_mut(market_index)?;
+
+        let (position_delta, pnl) = if position.lp_shares > 0 {
+            controller::lp::settle_lp_position(position, market)?
+        } else {
+            (PositionDelta::default(), 0)
+        };
+
+        let attribution = controller::lp::update_perp_lp_stats(
+            position,
+            market,
+            &mut load_mut!(perp_lp_stats)?,
+            oracle_price,
+            now,
+        )?;
+
+        emit!(LPRecord {
+            ts: now,
+            action: LPAction::SettleLiquidity,
+            user: user_key,
+            n_shares: 0,
+            market_index,
+            delta_base_asset_amount: position_delta.base_asset_amount,
+            delta_quote_asset_amount: position_delta.quote_asset_amount,
+            pnl,
+            fee_revenue: attribution.fee_revenue.cast()?,
+            funding_payment: attribution.funding_payment.cast()?,
+            inventory_pnl: attribution.inventory_pnl.cast()?,
+        });
+    } else {
+        controller::lp::settle_funding_payment_then_lp(
+            user,
+            &user_key,
+            market,
+            now,
+            funding_paused,
+        )?;
+    }
+
+    user.update_last_active_slot(clock.slot);
+
+    Ok(())
+}
+
+#[access_control(
+    amm_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_update_perp_lp_range_status(
+    ctx: Context<UpdatePerpLpRangeStatus>,
+    market_index: u16,
+) -> Result<()> {
+    let user_key = ctx.accounts.user.key();
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let perp_lp_range = &mut load_mut!(ctx.accounts.perp_lp_range)?;
+
+    let state = &ctx.accounts.state;
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &get_writable_perp_market_set(market_index),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+
+    let (action, n_shares, position_delta, pnl) = {
+        let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+        let funding_paused = controller::funding::is_continuous_funding_paused(
+            &market,
+            &mut oracle_map,
+            state.funding_paused()?,
+        )?;
+        controller::funding::settle_funding_payment(
+            user,
+            &user_key,
+            &mut market,
+            now,
+            funding_paused,
+        )?;
+
+        let oracle_price = oracle_map.get_price_data(&market.amm.oracle)?.price;
+        let last_add_perp_lp_shares_ts = user.last_add_perp_lp_shares_ts;
+
+        controller::lp::update_perp_lp_range_status(
+            user.force_get_perp_position_mut(market_index)?,
+            &mut market,
+            perp_lp_range,
+            oracle_price,
+            last_add_perp_lp_shares_ts,
+            state.lp_cooldown_time.cast()?,
+            now,
+        )?
+    };
+
+    if action == LPAction::ActivateLiquidity {
+        user.last_add_perp_lp_shares_ts = now;
+
+        validate!(
+            meets_initial_margin_requirement(
+                user,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map
+            )?,
+            ErrorCode::InsufficientCollateral,
+            "User does not meet initial margin requirement to activate ranged liquidity"
+        )?;
+    }
+
     user.update_last_active_slot(clock.slot);
 
+    emit!(LPRecord {
+        ts: now,
+        action,
+        user: user_key,
+        n_shares,
+        market_index,
+        delta_base_asset_amount: position_delta.base_asset_amount,
+        delta_quote_asset_amount: position_delta.quote_asset_amount,
+        pnl,
+        ..LPRecord::default()
+    });
+
     Ok(())
 }
 
@@ -580,18 +898,56 @@ pub fn handle_liquidate_perp(
     let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
     let liquidator_stats = &mut load_mut!(ctx.accounts.liquidator_stats)?;
 
+    let mut writable_spot_markets = MarketSet::new();
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &get_writable_perp_market_set(market_index),
-        &MarketSet::new(),
+        &writable_spot_markets,
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(&user_key, user, user_stats, remaining_accounts_iter)?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the user's shortage before it can be liquidated
+    let covered_by_shared_collateral_group =
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+    if covered_by_shared_collateral_group && !user.is_being_liquidated() {
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            state,
+            now,
+        )?;
+
+        return Ok(());
+    }
+
     controller::liquidation::liquidate_perp(
         market_index,
         liquidator_max_base_asset_amount,
@@ -610,8 +966,233 @@ pub fn handle_liquidate_perp(
         state,
     )?;
 
-    Ok(())
-}
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    controller::user_health::update_user_health(
+        liquidator,
+        &liquidator_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    liq_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_liquidate_perp_lp(ctx: Context<LiquidatePerp>, market_index: u16) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let slot = clock.slot;
+    let state = &ctx.accounts.state;
+
+    let user_key = ctx.accounts.user.key();
+    let liquidator_key = ctx.accounts.liquidator.key();
+
+    validate!(
+        user_key != liquidator_key,
+        ErrorCode::UserCantLiquidateThemself
+    )?;
+
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
+
+    let mut writable_spot_markets = MarketSet::new();
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &get_writable_perp_market_set(market_index),
+        &writable_spot_markets,
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the user's shortage before it can be liquidated
+    let covered_by_shared_collateral_group =
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+    if covered_by_shared_collateral_group && !user.is_being_liquidated() {
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            state,
+            now,
+        )?;
+
+        return Ok(());
+    }
+
+    controller::liquidation::liquidate_perp_lp(
+        market_index,
+        user,
+        &user_key,
+        liquidator,
+        &liquidator_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        slot,
+        now,
+        state,
+    )?;
+
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    controller::user_health::update_user_health(
+        liquidator,
+        &liquidator_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    liq_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_liquidate_perp_with_amm(
+    ctx: Context<LiquidatePerpWithAmm>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let slot = clock.slot;
+    let state = &ctx.accounts.state;
+
+    let user_key = ctx.accounts.user.key();
+
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+
+    let mut writable_spot_markets = MarketSet::new();
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &get_writable_perp_market_set(market_index),
+        &writable_spot_markets,
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(&user_key, user, user_stats, remaining_accounts_iter)?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the user's shortage before it can be liquidated
+    let covered_by_shared_collateral_group =
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+    if covered_by_shared_collateral_group && !user.is_being_liquidated() {
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            state,
+            now,
+        )?;
+
+        return Ok(());
+    }
+
+    controller::liquidation::liquidate_perp_with_amm(
+        market_index,
+        user,
+        &user_key,
+        user_stats,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        slot,
+        now,
+        state,
+    )?;
+
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    Ok(())
+}
 
 #[access_control(
     liq_not_paused(&ctx.accounts.state)
@@ -638,18 +1219,62 @@ pub fn handle_liquidate_spot(
     let user = &mut load_mut!(ctx.accounts.user)?;
     let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
 
+    let mut writable_spot_markets =
+        get_writable_spot_market_set_from_many(vec![asset_market_index, liability_market_index]);
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
-        &get_writable_spot_market_set_from_many(vec![asset_market_index, liability_market_index]),
+        &writable_spot_markets,
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the user's shortage before it can be liquidated
+    let covered_by_shared_collateral_group =
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+    if covered_by_shared_collateral_group && !user.is_being_liquidated() {
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            state,
+            now,
+        )?;
+
+        return Ok(());
+    }
+
     controller::liquidation::liquidate_spot(
         asset_market_index,
         liability_market_index,
@@ -669,6 +1294,26 @@ pub fn handle_liquidate_spot(
         state.liquidation_duration as u128,
     )?;
 
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    controller::user_health::update_user_health(
+        liquidator,
+        &liquidator_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
     Ok(())
 }
 
@@ -697,18 +1342,61 @@ pub fn handle_liquidate_borrow_for_perp_pnl(
     let user = &mut load_mut!(ctx.accounts.user)?;
     let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
 
+    let mut writable_spot_markets = get_writable_spot_market_set(spot_market_index);
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
-        &get_writable_spot_market_set(spot_market_index),
+        &writable_spot_markets,
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the user's shortage before it can be liquidated
+    let covered_by_shared_collateral_group =
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+    if covered_by_shared_collateral_group && !user.is_being_liquidated() {
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            state,
+            now,
+        )?;
+
+        return Ok(());
+    }
+
     controller::liquidation::liquidate_borrow_for_perp_pnl(
         perp_market_index,
         spot_market_index,
@@ -726,6 +1414,27 @@ pub fn handle_liquidate_borrow_for_perp_pnl(
         state.liquidation_margin_buffer_ratio,
         state.initial_pct_to_liquidate as u128,
         state.liquidation_duration as u128,
+        state.funding_paused()?,
+    )?;
+
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    controller::user_health::update_user_health(
+        liquidator,
+        &liquidator_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
     )?;
 
     Ok(())
@@ -756,35 +1465,99 @@ pub fn handle_liquidate_perp_pnl_for_deposit(
     let user = &mut load_mut!(ctx.accounts.user)?;
     let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
 
+    let mut writable_spot_markets = get_writable_spot_market_set(spot_market_index);
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
-        &get_writable_spot_market_set(spot_market_index),
+        &writable_spot_markets,
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
-    controller::liquidation::liquidate_perp_pnl_for_deposit(
-        perp_market_index,
-        spot_market_index,
-        liquidator_max_pnl_transfer,
-        limit_price,
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the user's shortage before it can be liquidated
+    let covered_by_shared_collateral_group =
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+    if covered_by_shared_collateral_group && !user.is_being_liquidated() {
+        controller::user_health::update_user_health(
+            user,
+            &user_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            state,
+            now,
+        )?;
+
+        return Ok(());
+    }
+
+    controller::liquidation::liquidate_perp_pnl_for_deposit(
+        perp_market_index,
+        spot_market_index,
+        liquidator_max_pnl_transfer,
+        limit_price,
+        user,
+        &user_key,
+        liquidator,
+        &liquidator_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        now,
+        clock.slot,
+        state.liquidation_margin_buffer_ratio,
+        state.initial_pct_to_liquidate as u128,
+        state.liquidation_duration as u128,
+        state.funding_paused()?,
+    )?;
+
+    controller::user_health::update_user_health(
         user,
         &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
+    controller::user_health::update_user_health(
         liquidator,
         &liquidator_key,
         &perp_market_map,
         &spot_market_map,
         &mut oracle_map,
+        state,
         now,
-        clock.slot,
-        state.liquidation_margin_buffer_ratio,
-        state.initial_pct_to_liquidate as u128,
-        state.liquidation_duration as u128,
     )?;
 
     Ok(())
@@ -941,18 +1714,76 @@ pub fn handle_resolve_perp_bankruptcy(
     let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &get_writable_perp_market_set(market_index),
         &get_writable_spot_market_set(quote_spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the deficit before it's socialized
+    if user.is_bankrupt() {
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+        if !user.is_bankrupt() {
+            return Ok(());
+        }
+    }
+
+    // the perp market's dedicated insurance fund vault, if it has one, precedes the counterparties
+    let mut perp_insurance_fund_vault = {
+        let perp_market = perp_market_map.get_ref(&market_index)?;
+        if perp_market.has_insurance_fund() {
+            Some(get_perp_insurance_fund_vault(
+                remaining_accounts_iter,
+                &perp_market.insurance_fund.vault,
+            )?)
+        } else {
+            None
+        }
+    };
+
+    // the quote spot market's junior insurance fund vault, if it has one, follows
+    let mut junior_insurance_fund_vault = {
+        let quote_spot_market = spot_market_map.get_ref(&quote_spot_market_index)?;
+        if quote_spot_market.has_junior_insurance_fund() {
+            Some(get_junior_insurance_fund_vault(
+                remaining_accounts_iter,
+                &quote_spot_market.junior_insurance_fund.vault,
+            )?)
+        } else {
+            None
+        }
+    };
+
+    // profitable users to rank for auto-deleveraging, if enabled for the market's contract tier
+    let counterparties = load_user_map(remaining_accounts_iter)?;
+
     {
         let spot_market = &mut spot_market_map.get_ref_mut(&quote_spot_market_index)?;
         controller::insurance::attempt_settle_revenue_to_insurance_fund(
@@ -974,18 +1805,85 @@ pub fn handle_resolve_perp_bankruptcy(
         )?;
     }
 
-    let pay_from_insurance = controller::liquidation::resolve_perp_bankruptcy(
-        market_index,
-        user,
-        &user_key,
-        liquidator,
-        &liquidator_key,
-        &perp_market_map,
-        &spot_market_map,
-        &mut oracle_map,
-        now,
-        ctx.accounts.insurance_fund_vault.amount,
-    )?;
+    let junior_insurance_fund_vault_amount = junior_insurance_fund_vault
+        .as_ref()
+        .map_or(0, |vault| vault.amount);
+
+    let (pay_from_perp_insurance, pay_from_insurance) =
+        controller::liquidation::resolve_perp_bankruptcy(
+            market_index,
+            user,
+            &user_key,
+            liquidator,
+            &liquidator_key,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            math::insurance::calculate_tranched_insurance_fund_vault_amount(
+                ctx.accounts.insurance_fund_vault.amount,
+                junior_insurance_fund_vault_amount,
+            )?,
+            perp_insurance_fund_vault
+                .as_ref()
+                .map_or(0, |vault| vault.amount),
+            &counterparties,
+            state,
+        )?;
+
+    let (pay_from_junior_insurance, pay_from_insurance) =
+        math::insurance::calculate_insurance_fund_tranche_payments(
+            pay_from_insurance,
+            junior_insurance_fund_vault_amount,
+        )?;
+
+    if let Some(junior_insurance_fund_vault) = junior_insurance_fund_vault.as_mut() {
+        if pay_from_junior_insurance > 0 {
+            controller::token::send_from_program_vault(
+                &ctx.accounts.token_program,
+                junior_insurance_fund_vault,
+                &ctx.accounts.spot_market_vault,
+                &ctx.accounts.drift_signer,
+                state.signer_nonce,
+                pay_from_junior_insurance,
+            )?;
+
+            junior_insurance_fund_vault.reload()?;
+            validate!(
+                junior_insurance_fund_vault.amount > 0,
+                ErrorCode::InvalidIFDetected,
+                "junior_insurance_fund_vault.amount must remain > 0"
+            )?;
+        }
+    }
+
+    if let Some(perp_insurance_fund_vault) = perp_insurance_fund_vault.as_mut() {
+        if pay_from_perp_insurance > 0 {
+            validate!(
+                pay_from_perp_insurance < perp_insurance_fund_vault.amount,
+                ErrorCode::InsufficientCollateral,
+                "Perp Insurance Fund balance InsufficientCollateral for payment: !{} < {}",
+                pay_from_perp_insurance,
+                perp_insurance_fund_vault.amount
+            )?;
+
+            controller::token::send_from_program_vault(
+                &ctx.accounts.token_program,
+                perp_insurance_fund_vault,
+                &ctx.accounts.spot_market_vault,
+                &ctx.accounts.drift_signer,
+                state.signer_nonce,
+                pay_from_perp_insurance,
+            )?;
+
+            perp_insurance_fund_vault.reload()?;
+            validate!(
+                perp_insurance_fund_vault.amount > 0,
+                ErrorCode::InvalidIFDetected,
+                "perp_insurance_fund_vault.amount must remain > 0"
+            )?;
+        }
+    }
 
     if pay_from_insurance > 0 {
         validate!(
@@ -1047,18 +1945,64 @@ pub fn handle_resolve_spot_bankruptcy(
     let user = &mut load_mut!(ctx.accounts.user)?;
     let liquidator = &mut load_mut!(ctx.accounts.liquidator)?;
 
+    let mut writable_spot_markets = get_writable_spot_market_set(market_index);
+    if user.is_in_shared_collateral_group {
+        writable_spot_markets.insert(QUOTE_SPOT_MARKET_INDEX);
+    }
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
-        &get_writable_spot_market_set(market_index),
+        &writable_spot_markets,
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
+    // the rest of the group covers the deficit before it's socialized
+    if user.is_bankrupt() {
+        controller::liquidation::transfer_shared_collateral_to_user(
+            user,
+            &user_key,
+            &shared_collateral_group,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+        )?;
+
+        if !user.is_bankrupt() {
+            return Ok(());
+        }
+    }
+
+    let mut junior_insurance_fund_vault = {
+        let spot_market = spot_market_map.get_ref(&market_index)?;
+        if spot_market.has_junior_insurance_fund() {
+            Some(get_junior_insurance_fund_vault(
+                remaining_accounts_iter,
+                &spot_market.junior_insurance_fund.vault,
+            )?)
+        } else {
+            None
+        }
+    };
+
     {
         let spot_market = &mut spot_market_map.get_ref_mut(&market_index)?;
         controller::insurance::attempt_settle_revenue_to_insurance_fund(
@@ -1080,6 +2024,10 @@ pub fn handle_resolve_spot_bankruptcy(
         )?;
     }
 
+    let junior_insurance_fund_vault_amount = junior_insurance_fund_vault
+        .as_ref()
+        .map_or(0, |vault| vault.amount);
+
     let pay_from_insurance = controller::liquidation::resolve_spot_bankruptcy(
         market_index,
         user,
@@ -1090,9 +2038,38 @@ pub fn handle_resolve_spot_bankruptcy(
         &spot_market_map,
         &mut oracle_map,
         now,
-        ctx.accounts.insurance_fund_vault.amount,
+        math::insurance::calculate_tranched_insurance_fund_vault_amount(
+            ctx.accounts.insurance_fund_vault.amount,
+            junior_insurance_fund_vault_amount,
+        )?,
     )?;
 
+    let (pay_from_junior_insurance, pay_from_insurance) =
+        math::insurance::calculate_insurance_fund_tranche_payments(
+            pay_from_insurance,
+            junior_insurance_fund_vault_amount,
+        )?;
+
+    if let Some(junior_insurance_fund_vault) = junior_insurance_fund_vault.as_mut() {
+        if pay_from_junior_insurance > 0 {
+            controller::token::send_from_program_vault(
+                &ctx.accounts.token_program,
+                junior_insurance_fund_vault,
+                &ctx.accounts.spot_market_vault,
+                &ctx.accounts.drift_signer,
+                ctx.accounts.state.signer_nonce,
+                pay_from_junior_insurance,
+            )?;
+
+            junior_insurance_fund_vault.reload()?;
+            validate!(
+                junior_insurance_fund_vault.amount > 0,
+                ErrorCode::InvalidIFDetected,
+                "junior_insurance_fund_vault.amount must remain > 0"
+            )?;
+        }
+    }
+
     if pay_from_insurance > 0 {
         controller::token::send_from_program_vault(
             &ctx.accounts.token_program,
@@ -1132,55 +2109,193 @@ pub fn handle_update_funding_rate(
     ctx: Context<UpdateFundingRate>,
     perp_market_index: u16,
 ) -> Result<()> {
-    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
-    let clock = Clock::get()?;
-    let now = clock.unix_timestamp;
-    let clock_slot = clock.slot;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let clock_slot = clock.slot;
+    let state = &ctx.accounts.state;
+    let mut oracle_map = OracleMap::load_one(
+        &ctx.accounts.oracle,
+        clock_slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    let oracle_price_data = &oracle_map.get_price_data(&perp_market.amm.oracle)?;
+    controller::repeg::_update_amm(perp_market, oracle_price_data, state, now, clock_slot)?;
+
+    validate!(
+        matches!(perp_market.status, MarketStatus::Active)
+            && !perp_market.is_operation_paused(MarketOperation::Funding)?,
+        ErrorCode::MarketActionPaused,
+        "Market funding is paused",
+    )?;
+
+    validate!(
+        ((clock_slot == perp_market.amm.last_update_slot && perp_market.amm.last_oracle_valid)
+            || perp_market.amm.curve_update_intensity == 0),
+        ErrorCode::AMMNotUpdatedInSameSlot,
+        "AMM must be updated in a prior instruction within same slot"
+    )?;
+
+    let is_updated = controller::funding::update_funding_rate(
+        perp_market_index,
+        perp_market,
+        &mut oracle_map,
+        now,
+        &state.oracle_guard_rails,
+        state.funding_paused()?,
+        None,
+    )?;
+
+    if !is_updated {
+        let time_until_next_update = crate::math::helpers::on_the_hour_update(
+            now,
+            perp_market.amm.last_funding_rate_ts,
+            perp_market.amm.funding_period,
+        )?;
+        msg!(
+            "time_until_next_update = {:?} seconds",
+            time_until_next_update
+        );
+        return Err(ErrorCode::FundingWasNotUpdated.into());
+    }
+
+    Ok(())
+}
+
+#[access_control(
+    withdraw_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_settle_revenue_to_insurance_fund(
+    ctx: Context<SettleRevenueToInsuranceFund>,
+    spot_market_index: u16,
+) -> Result<()> {
+    let state = &ctx.accounts.state;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+
+    validate!(
+        spot_market_index == spot_market.market_index,
+        ErrorCode::InvalidSpotMarketAccount,
+        "invalid spot_market passed"
+    )?;
+
+    validate!(
+        spot_market.insurance_fund.revenue_settle_period > 0,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "invalid revenue_settle_period settings on spot market"
+    )?;
+
+    let spot_vault_amount = ctx.accounts.spot_market_vault.amount;
+    let insurance_vault_amount = ctx.accounts.insurance_fund_vault.amount;
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    let time_until_next_update = math::helpers::on_the_hour_update(
+        now,
+        spot_market.insurance_fund.last_revenue_settle_ts,
+        spot_market.insurance_fund.revenue_settle_period,
+    )?;
+
+    validate!(
+        time_until_next_update == 0,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "Must wait {} seconds until next available settlement time",
+        time_until_next_update
+    )?;
+
+    // uses proportion of revenue pool allocated to insurance fund
+    let token_amount = controller::insurance::settle_revenue_to_insurance_fund(
+        spot_vault_amount,
+        insurance_vault_amount,
+        spot_market,
+        now,
+    )?;
+
+    spot_market.insurance_fund.last_revenue_settle_ts = now;
+
+    controller::token::send_from_program_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.spot_market_vault,
+        &ctx.accounts.insurance_fund_vault,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        token_amount,
+    )?;
+
+    // reload the spot market vault balance so it's up-to-date
+    ctx.accounts.spot_market_vault.reload()?;
+    math::spot_withdraw::validate_spot_market_vault_amount(
+        spot_market,
+        ctx.accounts.spot_market_vault.amount,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    withdraw_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_settle_revenue_to_junior_insurance_fund(
+    ctx: Context<SettleRevenueToJuniorInsuranceFund>,
+    spot_market_index: u16,
+) -> Result<()> {
     let state = &ctx.accounts.state;
-    let mut oracle_map = OracleMap::load_one(
-        &ctx.accounts.oracle,
-        clock_slot,
-        Some(state.oracle_guard_rails),
-    )?;
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
 
-    let oracle_price_data = &oracle_map.get_price_data(&perp_market.amm.oracle)?;
-    controller::repeg::_update_amm(perp_market, oracle_price_data, state, now, clock_slot)?;
+    validate!(
+        spot_market_index == spot_market.market_index,
+        ErrorCode::InvalidSpotMarketAccount,
+        "invalid spot_market passed"
+    )?;
 
     validate!(
-        matches!(perp_market.status, MarketStatus::Active),
-        ErrorCode::MarketActionPaused,
-        "Market funding is paused",
+        spot_market.junior_insurance_fund.revenue_settle_period > 0,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "invalid revenue_settle_period settings on junior insurance fund"
+    )?;
+
+    let spot_vault_amount = ctx.accounts.spot_market_vault.amount;
+    let insurance_vault_amount = ctx.accounts.junior_insurance_fund_vault.amount;
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    let time_until_next_update = math::helpers::on_the_hour_update(
+        now,
+        spot_market.junior_insurance_fund.last_revenue_settle_ts,
+        spot_market.junior_insurance_fund.revenue_settle_period,
     )?;
 
     validate!(
-        ((clock_slot == perp_market.amm.last_update_slot && perp_market.amm.last_oracle_valid)
-            || perp_market.amm.curve_update_intensity == 0),
-        ErrorCode::AMMNotUpdatedInSameSlot,
-        "AMM must be updated in a prior instruction within same slot"
+        time_until_next_update == 0,
+        ErrorCode::RevenueSettingsCannotSettleToIF,
+        "Must wait {} seconds until next available settlement time",
+        time_until_next_update
     )?;
 
-    let is_updated = controller::funding::update_funding_rate(
-        perp_market_index,
-        perp_market,
-        &mut oracle_map,
+    let token_amount = controller::insurance::settle_revenue_to_junior_insurance_fund(
+        spot_vault_amount,
+        insurance_vault_amount,
+        spot_market,
         now,
-        &state.oracle_guard_rails,
-        state.funding_paused()?,
-        None,
     )?;
 
-    if !is_updated {
-        let time_until_next_update = crate::math::helpers::on_the_hour_update(
-            now,
-            perp_market.amm.last_funding_rate_ts,
-            perp_market.amm.funding_period,
-        )?;
-        msg!(
-            "time_until_next_update = {:?} seconds",
-            time_until_next_update
-        );
-        return Err(ErrorCode::FundingWasNotUpdated.into());
-    }
+    controller::token::send_from_program_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.spot_market_vault,
+        &ctx.accounts.junior_insurance_fund_vault,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        token_amount,
+    )?;
+
+    // reload the spot market vault balance so it's up-to-date
+    ctx.accounts.spot_market_vault.reload()?;
+    math::spot_withdraw::validate_spot_market_vault_amount(
+        spot_market,
+        ctx.accounts.spot_market_vault.amount,
+    )?;
 
     Ok(())
 }
@@ -1188,23 +2303,24 @@ pub fn handle_update_funding_rate(
 #[access_control(
     withdraw_not_paused(&ctx.accounts.state)
 )]
-pub fn handle_settle_revenue_to_insurance_fund(
-    ctx: Context<SettleRevenueToInsuranceFund>,
-    spot_market_index: u16,
+pub fn handle_settle_revenue_to_perp_insurance_fund(
+    ctx: Context<SettleRevenueToPerpInsuranceFund>,
+    perp_market_index: u16,
 ) -> Result<()> {
     let state = &ctx.accounts.state;
-    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    let quote_spot_market = &mut load_mut!(ctx.accounts.quote_spot_market)?;
 
     validate!(
-        spot_market_index == spot_market.market_index,
-        ErrorCode::InvalidSpotMarketAccount,
-        "invalid spot_market passed"
+        perp_market_index == perp_market.market_index,
+        ErrorCode::InvalidMarketAccount,
+        "invalid perp_market passed"
     )?;
 
     validate!(
-        spot_market.insurance_fund.revenue_settle_period > 0,
+        perp_market.insurance_fund.revenue_settle_period > 0,
         ErrorCode::RevenueSettingsCannotSettleToIF,
-        "invalid revenue_settle_period settings on spot market"
+        "invalid revenue_settle_period settings on perp market"
     )?;
 
     let spot_vault_amount = ctx.accounts.spot_market_vault.amount;
@@ -1215,8 +2331,8 @@ pub fn handle_settle_revenue_to_insurance_fund(
 
     let time_until_next_update = math::helpers::on_the_hour_update(
         now,
-        spot_market.insurance_fund.last_revenue_settle_ts,
-        spot_market.insurance_fund.revenue_settle_period,
+        perp_market.insurance_fund.last_revenue_settle_ts,
+        perp_market.insurance_fund.revenue_settle_period,
     )?;
 
     validate!(
@@ -1226,16 +2342,15 @@ pub fn handle_settle_revenue_to_insurance_fund(
         time_until_next_update
     )?;
 
-    // uses proportion of revenue pool allocated to insurance fund
-    let token_amount = controller::insurance::settle_revenue_to_insurance_fund(
+    // uses the perp market's fees above what the amm retains
+    let token_amount = controller::insurance::settle_revenue_to_perp_insurance_fund(
         spot_vault_amount,
         insurance_vault_amount,
-        spot_market,
+        quote_spot_market,
+        perp_market,
         now,
     )?;
 
-    spot_market.insurance_fund.last_revenue_settle_ts = now;
-
     controller::token::send_from_program_vault(
         &ctx.accounts.token_program,
         &ctx.accounts.spot_market_vault,
@@ -1248,7 +2363,7 @@ pub fn handle_settle_revenue_to_insurance_fund(
     // reload the spot market vault balance so it's up-to-date
     ctx.accounts.spot_market_vault.reload()?;
     math::spot_withdraw::validate_spot_market_vault_amount(
-        spot_market,
+        quote_spot_market,
         ctx.accounts.spot_market_vault.amount,
     )?;
 
@@ -1317,6 +2432,113 @@ pub fn handle_update_amms(ctx: Context<UpdateAMM>, market_indexes: [u16; 5]) ->
     Ok(())
 }
 
+#[access_control(
+    amm_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_hedge_amm_inventory<'info>(
+    ctx: Context<'_, '_, '_, 'info, HedgeAmmInventory<'info>>,
+    perp_market_index: u16,
+    fulfillment_type: SpotFulfillmentType,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let state = &ctx.accounts.state;
+
+    let amm_hedge_config = &mut load_mut!(ctx.accounts.amm_hedge_config)?;
+    let spot_market_index = amm_hedge_config.spot_market_index();
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &get_writable_perp_market_set(perp_market_index),
+        &get_writable_spot_market_set_from_many(vec![QUOTE_SPOT_MARKET_INDEX, spot_market_index]),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    let mut fulfillment_params: Box<dyn SpotFulfillmentParams> = {
+        let base_market = spot_market_map.get_ref(&spot_market_index)?;
+        let quote_market = spot_market_map.get_quote_spot_market()?;
+        match fulfillment_type {
+            SpotFulfillmentType::SerumV3 => Box::new(SerumFulfillmentParams::new(
+                remaining_accounts_iter,
+                state,
+                &base_market,
+                &quote_market,
+                now,
+            )?),
+            SpotFulfillmentType::PhoenixV1 => Box::new(PhoenixFulfillmentParams::new(
+                remaining_accounts_iter,
+                state,
+                &base_market,
+                &quote_market,
+            )?),
+            SpotFulfillmentType::ConstantProductPool => {
+                Box::new(ConstantProductPoolFulfillmentParams::new(
+                    remaining_accounts_iter,
+                    state,
+                    &base_market,
+                    &quote_market,
+                )?)
+            }
+            SpotFulfillmentType::Match => {
+                msg!("amm can only hedge on external markets");
+                return Err(ErrorCode::InvalidSpotFulfillmentParams.into());
+            }
+        }
+    };
+
+    {
+        let perp_market = &mut perp_market_map.get_ref_mut(&perp_market_index)?;
+        let base_market = &mut spot_market_map.get_ref_mut(&spot_market_index)?;
+        let quote_market = &mut spot_market_map.get_quote_spot_market_mut()?;
+
+        let oracle_price_data = oracle_map.get_price_data(&perp_market.amm.oracle)?;
+        let oracle_validity = math::oracle::oracle_validity(
+            perp_market
+                .amm
+                .historical_oracle_data
+                .last_oracle_price_twap,
+            oracle_price_data,
+            &state.oracle_guard_rails.validity,
+        )?;
+
+        validate!(
+            is_oracle_valid_for_action(oracle_validity, Some(DriftAction::FillOrderAmm))?,
+            ErrorCode::InvalidOracle,
+            "oracle invalid for amm hedge. oracle validity = {:?}",
+            oracle_validity
+        )?;
+
+        controller::spot_balance::update_spot_market_cumulative_interest(
+            base_market,
+            Some(oracle_price_data),
+            now,
+        )?;
+        controller::spot_balance::update_spot_market_cumulative_interest(quote_market, None, now)?;
+
+        controller::amm_hedge::hedge_amm_inventory(
+            amm_hedge_config,
+            perp_market,
+            base_market,
+            quote_market,
+            oracle_price_data.price,
+            now,
+            fulfillment_params.as_mut(),
+        )?;
+    }
+
+    let base_market = spot_market_map.get_ref(&spot_market_index)?;
+    let quote_market = spot_market_map.get_quote_spot_market()?;
+    fulfillment_params.validate_vault_amounts(&base_market, &quote_market)?;
+
+    Ok(())
+}
+
 pub fn handle_update_user_quote_asset_insurance_stake(
     ctx: Context<UpdateUserQuoteAssetInsuranceStake>,
 ) -> Result<()> {
@@ -1325,13 +2547,14 @@ pub fn handle_update_user_quote_asset_insurance_stake(
     let quote_spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
 
     validate!(
-        insurance_fund_stake.market_index == 0,
+        insurance_fund_stake.market_index == 0
+            && insurance_fund_stake.market_type == MarketType::Spot,
         ErrorCode::IncorrectSpotMarketAccountPassed,
         "insurance_fund_stake is not for quote market"
     )?;
 
     user_stats.if_staked_quote_asset_amount = if_shares_to_vault_amount(
-        insurance_fund_stake.checked_if_shares(quote_spot_market)?,
+        insurance_fund_stake.checked_if_shares(&quote_spot_market.insurance_fund)?,
         quote_spot_market.insurance_fund.total_shares,
         ctx.accounts.insurance_fund_vault.amount,
     )?;
@@ -1404,6 +2627,27 @@ pub struct ForceCancelOrder<'info> {
     pub user: AccountLoader<'info, User>,
 }
 
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct PlacePerpLpHedgeOrder<'info> {
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = can_sign_for_user(&filler, &authority)?
+    )]
+    pub filler: AccountLoader<'info, User>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"perp_lp_hedge", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        has_one = user,
+    )]
+    pub perp_lp_hedge: AccountLoader<'info, PerpLpHedge>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateUserIdle<'info> {
     pub state: Box<Account<'info, State>>,
@@ -1444,6 +2688,21 @@ pub struct SettleLP<'info> {
     pub user: AccountLoader<'info, User>,
 }
 
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct UpdatePerpLpRangeStatus<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"perp_lp_range", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        has_one = user,
+    )]
+    pub perp_lp_range: AccountLoader<'info, PerpLpRange>,
+}
+
 #[derive(Accounts)]
 pub struct LiquidatePerp<'info> {
     pub state: Box<Account<'info, State>>,
@@ -1467,6 +2726,19 @@ pub struct LiquidatePerp<'info> {
     pub user_stats: AccountLoader<'info, UserStats>,
 }
 
+#[derive(Accounts)]
+pub struct LiquidatePerpWithAmm<'info> {
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        constraint = is_stats_for_user(&user, &user_stats)?
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+}
+
 #[derive(Accounts)]
 pub struct LiquidateSpot<'info> {
     pub state: Box<Account<'info, State>>,
@@ -1633,6 +2905,72 @@ pub struct SettleRevenueToInsuranceFund<'info> {
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct SettleRevenueToJuniorInsuranceFund<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"junior_insurance_fund_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub junior_insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(perp_market_index: u16,)]
+pub struct SettleRevenueToPerpInsuranceFund<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"perp_market", perp_market_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub perp_market: AccountLoader<'info, PerpMarket>,
+    #[account(
+        mut,
+        seeds = [b"spot_market", QUOTE_SPOT_MARKET_INDEX.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub quote_spot_market: AccountLoader<'info, SpotMarket>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), QUOTE_SPOT_MARKET_INDEX.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"perp_insurance_fund_vault".as_ref(), perp_market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateSpotMarketCumulativeInterest<'info> {
     pub state: Box<Account<'info, State>>,
@@ -1648,6 +2986,22 @@ pub struct UpdateAMM<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+#[instruction(perp_market_index: u16)]
+pub struct HedgeAmmInventory<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"amm_hedge_config".as_ref(), perp_market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub amm_hedge_config: AccountLoader<'info, AmmHedgeConfig>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateFundingRate<'info> {
     pub state: Box<Account<'info, State>>,
@@ -1,17 +1,25 @@
 use crate::error::{DriftResult, ErrorCode};
 
+use crate::load;
+use crate::math::safe_math::SafeMath;
 use crate::math::safe_unwrap::SafeUnwrap;
 use crate::state::oracle_map::OracleMap;
+use crate::state::perp_lp_stats::PerpLpStats;
 use crate::state::perp_market_map::{MarketSet, PerpMarketMap};
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::state::OracleGuardRails;
 use crate::state::traits::Size;
 use crate::state::user::{User, UserStats};
+use crate::state::user_delegate::UserDelegate;
+use crate::state::user_map::UserMap;
+use crate::state::withdraw_policy::WithdrawPolicy;
 use crate::validate;
 use anchor_lang::accounts::account::Account;
 use anchor_lang::prelude::AccountInfo;
 use anchor_lang::prelude::AccountLoader;
+use anchor_lang::prelude::Pubkey;
 use anchor_lang::Discriminator;
+use anchor_lang::Key;
 use anchor_spl::token::TokenAccount;
 use arrayref::array_ref;
 use solana_program::account_info::next_account_info;
@@ -146,6 +154,221 @@ pub fn get_referrer_and_referrer_stats<'a>(
     Ok((Some(referrer), Some(referrer_stats)))
 }
 
+/// Returns None if the signer is the user's authority or delegate. Otherwise the signer must be a
+/// scoped delegate and pass its UserDelegate account as the first remaining account
+pub fn get_user_delegate<'a>(
+    user_loader: &AccountLoader<User>,
+    signer: &Pubkey,
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+) -> DriftResult<Option<UserDelegate>> {
+    {
+        let user = load!(user_loader)?;
+        if user.authority == *signer
+            || (user.delegate != Pubkey::default() && user.delegate == *signer)
+        {
+            return Ok(None);
+        }
+    }
+
+    let user_delegate_account_info = account_info_iter.peek().ok_or_else(|| {
+        msg!("signer {} cant sign for user", signer);
+        ErrorCode::DelegatePermissionDenied
+    })?;
+
+    {
+        let data = user_delegate_account_info.try_borrow_data().map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::InvalidUserDelegate
+        })?;
+
+        let user_delegate_discriminator: [u8; 8] = UserDelegate::discriminator();
+        if data.len() < UserDelegate::SIZE || array_ref![data, 0, 8] != &user_delegate_discriminator
+        {
+            msg!("signer {} cant sign for user", signer);
+            return Err(ErrorCode::DelegatePermissionDenied);
+        }
+    }
+
+    let user_delegate_account_info = next_account_info(account_info_iter).safe_unwrap()?;
+
+    let user_delegate_loader: AccountLoader<UserDelegate> =
+        AccountLoader::try_from(user_delegate_account_info)
+            .or(Err(ErrorCode::InvalidUserDelegate))?;
+    let user_delegate = *load!(user_delegate_loader)?;
+
+    validate!(
+        user_delegate.user == user_loader.key() && user_delegate.delegate == *signer,
+        ErrorCode::DelegatePermissionDenied,
+        "user delegate {} is not for signer {} and user {}",
+        user_delegate_loader.key(),
+        signer,
+        user_loader.key()
+    )?;
+
+    Ok(Some(user_delegate))
+}
+
+/// Returns the authority's withdraw policy, which must be the first remaining account if the
+/// authority has one
+pub fn get_withdraw_policy<'a>(
+    user_stats: &UserStats,
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+) -> DriftResult<Option<AccountLoader<'a, WithdrawPolicy>>> {
+    if !user_stats.has_withdraw_policy {
+        return Ok(None);
+    }
+
+    let withdraw_policy_account_info = next_account_info(account_info_iter).map_err(|_| {
+        msg!("Could not find withdraw policy");
+        ErrorCode::InvalidWithdrawPolicy
+    })?;
+
+    validate!(
+        withdraw_policy_account_info.is_writable,
+        ErrorCode::InvalidWithdrawPolicy,
+        "withdraw policy must be writable"
+    )?;
+
+    let withdraw_policy: AccountLoader<WithdrawPolicy> =
+        AccountLoader::try_from(withdraw_policy_account_info)
+            .or(Err(ErrorCode::InvalidWithdrawPolicy))?;
+
+    validate!(
+        load!(withdraw_policy)?.authority == user_stats.authority,
+        ErrorCode::InvalidWithdrawPolicy,
+        "withdraw policy authority doesnt match user stats authority {}",
+        user_stats.authority
+    )?;
+
+    Ok(Some(withdraw_policy))
+}
+
+/// Returns the user's lp stats for the market if they're the next remaining account. Lp stats are
+/// opt-in, so the remaining accounts are left untouched if the next one isn't lp stats
+pub fn get_perp_lp_stats<'a>(
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+    user_key: &Pubkey,
+    market_index: u16,
+) -> DriftResult<Option<AccountLoader<'a, PerpLpStats>>> {
+    let perp_lp_stats_account_info = account_info_iter.peek();
+
+    if perp_lp_stats_account_info.is_none() {
+        return Ok(None);
+    }
+
+    let perp_lp_stats_account_info = perp_lp_stats_account_info.safe_unwrap()?;
+
+    {
+        let data = perp_lp_stats_account_info.try_borrow_data().map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::InvalidPerpLpStats
+        })?;
+
+        if data.len() < PerpLpStats::SIZE {
+            return Ok(None);
+        }
+
+        let perp_lp_stats_discriminator: [u8; 8] = PerpLpStats::discriminator();
+        let account_discriminator = array_ref![data, 0, 8];
+        if account_discriminator != &perp_lp_stats_discriminator {
+            return Ok(None);
+        }
+    }
+
+    let perp_lp_stats_account_info = next_account_info(account_info_iter).safe_unwrap()?;
+
+    validate!(
+        perp_lp_stats_account_info.is_writable,
+        ErrorCode::InvalidPerpLpStats,
+        "perp lp stats must be writable"
+    )?;
+
+    let perp_lp_stats: AccountLoader<PerpLpStats> =
+        AccountLoader::try_from(perp_lp_stats_account_info)
+            .or(Err(ErrorCode::InvalidPerpLpStats))?;
+
+    {
+        let perp_lp_stats = load!(perp_lp_stats)?;
+        validate!(
+            perp_lp_stats.user == *user_key && perp_lp_stats.market_index == market_index,
+            ErrorCode::InvalidPerpLpStats,
+            "perp lp stats are for user {} market {}",
+            perp_lp_stats.user,
+            perp_lp_stats.market_index
+        )?;
+    }
+
+    Ok(Some(perp_lp_stats))
+}
+
+/// Loads the other members of the user's shared collateral group. Every member must be passed so
+/// the group's combined margin can't be overstated by leaving out members with a deficit. Members
+/// are writable when the group's collateral is moved, e.g. during liquidation and bankruptcy
+pub fn load_shared_collateral_group<'a>(
+    user_key: &Pubkey,
+    user: &User,
+    user_stats: &UserStats,
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+) -> DriftResult<UserMap<'a>> {
+    let mut shared_collateral_group = UserMap::empty();
+
+    let expected_number_of_users = user_stats
+        .number_of_sub_accounts_in_shared_collateral_group
+        .safe_sub(user.is_in_shared_collateral_group as u16)?;
+
+    let user_discriminator: [u8; 8] = User::discriminator();
+    while shared_collateral_group.0.len() < expected_number_of_users as usize {
+        // stop at the expected count so any user accounts that follow are left for the caller
+        let user_account_info = match account_info_iter.peek() {
+            Some(user_account_info) => user_account_info,
+            None => break,
+        };
+
+        {
+            let data = user_account_info
+                .try_borrow_data()
+                .or(Err(ErrorCode::CouldNotLoadUserData))?;
+
+            if data.len() < User::SIZE || array_ref![data, 0, 8] != &user_discriminator {
+                break;
+            }
+        }
+
+        let user_account_info = next_account_info(account_info_iter).safe_unwrap()?;
+
+        validate!(
+            user_account_info.key != user_key,
+            ErrorCode::InvalidUserAccount,
+            "user cant be in its own shared collateral group"
+        )?;
+
+        let member: AccountLoader<User> =
+            AccountLoader::try_from(user_account_info).or(Err(ErrorCode::InvalidUserAccount))?;
+
+        {
+            let member = load!(member)?;
+            validate!(
+                member.authority == user.authority && member.is_in_shared_collateral_group,
+                ErrorCode::InvalidUserAccount,
+                "user {} not in shared collateral group",
+                user_account_info.key
+            )?;
+        }
+
+        shared_collateral_group.insert(*user_account_info.key, member)?;
+    }
+
+    validate!(
+        shared_collateral_group.0.len() == expected_number_of_users as usize,
+        ErrorCode::InvalidUserAccount,
+        "expected {} users in shared collateral group, found {}",
+        expected_number_of_users,
+        shared_collateral_group.0.len()
+    )?;
+
+    Ok(shared_collateral_group)
+}
+
 pub fn get_whitelist_token<'a>(
     account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
 ) -> DriftResult<Account<'a, TokenAccount>> {
@@ -165,3 +388,65 @@ pub fn get_whitelist_token<'a>(
 
     Ok(whitelist_token)
 }
+
+pub fn get_perp_insurance_fund_vault<'a>(
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+    expected_vault: &Pubkey,
+) -> DriftResult<Account<'a, TokenAccount>> {
+    get_insurance_fund_vault(
+        account_info_iter,
+        expected_vault,
+        "perp market insurance fund vault",
+        ErrorCode::PerpMarketInsuranceFundNotInitialized,
+    )
+}
+
+pub fn get_junior_insurance_fund_vault<'a>(
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+    expected_vault: &Pubkey,
+) -> DriftResult<Account<'a, TokenAccount>> {
+    get_insurance_fund_vault(
+        account_info_iter,
+        expected_vault,
+        "junior insurance fund vault",
+        ErrorCode::JuniorInsuranceFundNotInitialized,
+    )
+}
+
+fn get_insurance_fund_vault<'a>(
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+    expected_vault: &Pubkey,
+    vault_name: &str,
+    error_code: ErrorCode,
+) -> DriftResult<Account<'a, TokenAccount>> {
+    let vault_account_info = account_info_iter.next();
+    if vault_account_info.is_none() {
+        msg!("Could not find {}", vault_name);
+        return Err(error_code);
+    }
+
+    let vault_account_info = vault_account_info.safe_unwrap()?;
+    validate!(
+        vault_account_info.key == expected_vault,
+        error_code,
+        "{} {} != {}",
+        vault_name,
+        vault_account_info.key,
+        expected_vault
+    )?;
+
+    validate!(
+        vault_account_info.is_writable,
+        error_code,
+        "{} must be writable",
+        vault_name
+    )?;
+
+    let vault: Account<TokenAccount> = Account::try_from(vault_account_info).map_err(|e| {
+        msg!("Unable to deserialize {}", vault_name);
+        msg!("{:?}", e);
+        error_code
+    })?;
+
+    Ok(vault)
+}
@@ -1,6 +1,6 @@
 use anchor_lang::Discriminator;
 use anchor_lang::{prelude::*, AnchorDeserialize, AnchorSerialize};
-use anchor_spl::token::{Token, TokenAccount};
+use anchor_spl::token::{Mint, Token, TokenAccount};
 
 use crate::controller::orders::{cancel_orders, ModifyOrderId};
 use crate::controller::position::PositionDirection;
@@ -14,46 +14,61 @@ use crate::get_then_update_id;
 use crate::ids::{jupiter_mainnet_3, jupiter_mainnet_4, marinade_mainnet, serum_program};
 use crate::instructions::constraints::*;
 use crate::instructions::optional_accounts::{
-    get_maker_and_maker_stats, get_referrer_and_referrer_stats, get_whitelist_token, load_maps,
-    AccountMaps,
+    get_maker_and_maker_stats, get_perp_lp_stats, get_referrer_and_referrer_stats,
+    get_user_delegate, get_whitelist_token, get_withdraw_policy, load_maps,
+    load_shared_collateral_group, AccountMaps,
 };
 use crate::instructions::SpotFulfillmentType;
 use crate::load;
 use crate::load_mut;
 use crate::math::casting::Cast;
+use crate::math::constants::QUOTE_SPOT_MARKET_INDEX;
+use crate::math::helpers::get_proportion_i128;
 use crate::math::liquidation::is_user_being_liquidated;
+use crate::math::lp::LPAttribution;
 use crate::math::margin::{
-    calculate_max_withdrawable_amount, meets_initial_margin_requirement,
+    calculate_margin_requirement_and_total_collateral, calculate_max_withdrawable_amount,
+    calculate_shared_collateral_group_margin_requirement_and_total_collateral,
+    meets_initial_margin_requirement, meets_shared_collateral_group_margin_requirement,
     meets_withdraw_margin_requirement, validate_spot_margin_trading, MarginRequirementType,
 };
 use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::get_token_value;
-use crate::math::spot_swap::calculate_swap_price;
+use crate::math::spot_swap::{calculate_swap_net_outflow, calculate_swap_price};
 use crate::math_error;
 use crate::print_error;
 use crate::safe_decrement;
 use crate::safe_increment;
 use crate::state::events::{
     DepositDirection, DepositExplanation, DepositRecord, LPAction, LPRecord, NewUserRecord,
-    OrderActionExplanation, SwapRecord,
+    OrderActionExplanation, SwapRecord, WithdrawPolicyAction, WithdrawPolicyRecord,
 };
+use crate::state::fulfillment_params::constant_product_pool::ConstantProductPoolFulfillmentParams;
 use crate::state::fulfillment_params::drift::MatchFulfillmentParams;
 use crate::state::fulfillment_params::phoenix::PhoenixFulfillmentParams;
 use crate::state::fulfillment_params::serum::SerumFulfillmentParams;
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_lp_hedge::PerpLpHedge;
+use crate::state::perp_lp_range::PerpLpRange;
+use crate::state::perp_lp_stats::PerpLpStats;
 use crate::state::perp_market::MarketStatus;
 use crate::state::perp_market_map::{get_writable_perp_market_set, MarketSet};
 use crate::state::spot_fulfillment_params::SpotFulfillmentParams;
-use crate::state::spot_market::SpotBalanceType;
+use crate::state::spot_market::{ProtocolRevenueSource, SpotBalanceType};
 use crate::state::spot_market_map::{
     get_writable_spot_market_set, get_writable_spot_market_set_from_many,
 };
 use crate::state::state::State;
+use crate::state::tokenized_lp_pool::TokenizedLpPool;
 use crate::state::traits::Size;
 use crate::state::user::{
     MarketType, OrderTriggerCondition, OrderType, ReferrerName, User, UserStats, UserStatus,
 };
-use crate::state::user_map::load_user_maps;
+use crate::state::user_delegate::{DelegatePermission, UserDelegate};
+use crate::state::user_map::{load_user_maps, UserMap};
+use crate::state::withdraw_policy::WithdrawPolicy;
 use crate::validate;
+use crate::validation::delegate::validate_delegate_order_params;
 use crate::validation::user::validate_user_deletion;
 use crate::validation::whitelist::validate_whitelist_token;
 use crate::{controller, math};
@@ -243,6 +258,13 @@ pub fn handle_deposit(
         "Market is being initialized"
     )?;
 
+    validate!(
+        !spot_market.is_operation_paused(MarketOperation::Deposit)?,
+        ErrorCode::MarketActionPaused,
+        "Spot Market {} deposits are currently paused",
+        spot_market.market_index
+    )?;
+
     controller::spot_balance::update_spot_market_cumulative_interest(
         &mut spot_market,
         Some(oracle_price_data),
@@ -297,14 +319,7 @@ pub fn handle_deposit(
 
     if spot_position.balance_type == SpotBalanceType::Deposit && spot_position.scaled_balance > 0 {
         validate!(
-            matches!(
-                spot_market.status,
-                MarketStatus::Active
-                    | MarketStatus::FundingPaused
-                    | MarketStatus::AmmPaused
-                    | MarketStatus::FillPaused
-                    | MarketStatus::WithdrawPaused
-            ),
+            spot_market.status.is_active_or_legacy_paused(),
             ErrorCode::MarketActionPaused,
             "spot_market in reduce only mode",
         )?;
@@ -384,18 +399,33 @@ pub fn handle_withdraw(
     let slot = clock.slot;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let withdraw_policy =
+        get_withdraw_policy(&*load!(ctx.accounts.user_stats)?, remaining_accounts_iter)?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &get_writable_spot_market_set(market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    let shared_collateral_group = if user.is_in_shared_collateral_group {
+        load_shared_collateral_group(
+            &user_key,
+            user,
+            &*load!(ctx.accounts.user_stats)?,
+            remaining_accounts_iter,
+        )?
+    } else {
+        UserMap::empty()
+    };
+
     validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
 
     let spot_market_is_reduce_only = {
@@ -464,6 +494,7 @@ pub fn handle_withdraw(
 
     meets_withdraw_margin_requirement(
         user,
+        &shared_collateral_group,
         &perp_market_map,
         &spot_market_map,
         &mut oracle_map,
@@ -472,8 +503,27 @@ pub fn handle_withdraw(
 
     validate_spot_margin_trading(user, &spot_market_map, &mut oracle_map)?;
 
+    if let Some(withdraw_policy) = withdraw_policy {
+        load_mut!(withdraw_policy)?.validate_withdrawal(
+            &ctx.accounts.user_token_account.owner,
+            market_index,
+            amount,
+            now,
+        )?;
+    }
+
     user.status = UserStatus::Active;
 
+    controller::user_health::update_user_health(
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state,
+        now,
+    )?;
+
     user.update_last_active_slot(slot);
 
     let mut spot_market = spot_market_map.get_ref_mut(&market_index)?;
@@ -556,12 +606,26 @@ pub fn handle_transfer_deposit(
         "cant transfer between the same user account"
     )?;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let withdraw_policy =
+        get_withdraw_policy(&*load!(ctx.accounts.user_stats)?, remaining_accounts_iter)?;
+
+    // transfers must stay within the user accounts covered by the withdraw policy
+    if let Some(withdraw_policy) = withdraw_policy {
+        validate!(
+            to_user.authority == load!(withdraw_policy)?.authority,
+            ErrorCode::WithdrawDestinationNotAllowed,
+            "to_user authority {} not covered by withdraw policy",
+            to_user.authority
+        )?;
+    }
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &get_writable_spot_market_set(market_index),
         clock.slot,
@@ -587,15 +651,12 @@ pub fn handle_transfer_deposit(
         let spot_market = &mut spot_market_map.get_ref_mut(&market_index)?;
 
         validate!(
-            matches!(
-                spot_market.status,
-                MarketStatus::Active
-                    | MarketStatus::AmmPaused
-                    | MarketStatus::FundingPaused
-                    | MarketStatus::FillPaused
-                    | MarketStatus::ReduceOnly
-                    | MarketStatus::Settlement
-            ),
+            (spot_market.status.is_active_or_legacy_paused()
+                || matches!(
+                    spot_market.status,
+                    MarketStatus::ReduceOnly | MarketStatus::Settlement
+                ))
+                && !spot_market.is_operation_paused(MarketOperation::Withdraw)?,
             ErrorCode::MarketWithdrawPaused,
             "Spot Market {} withdraws are currently paused",
             spot_market.market_index
@@ -618,6 +679,7 @@ pub fn handle_transfer_deposit(
 
     meets_withdraw_margin_requirement(
         from_user,
+        &UserMap::empty(),
         &perp_market_map,
         &spot_market_map,
         &mut oracle_map,
@@ -759,6 +821,7 @@ impl Default for PostOnlyParam {
 pub struct PlaceOrderOptions {
     pub try_expire_orders: bool,
     pub enforce_margin_check: bool,
+    pub explanation: OrderActionExplanation,
 }
 
 impl Default for PlaceOrderOptions {
@@ -766,6 +829,7 @@ impl Default for PlaceOrderOptions {
         Self {
             try_expire_orders: true,
             enforce_margin_check: true,
+            explanation: OrderActionExplanation::None,
         }
     }
 }
@@ -777,18 +841,35 @@ pub fn handle_place_perp_order(ctx: Context<PlaceOrder>, params: OrderParams) ->
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            &user_delegate,
+            &params,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+        )?;
+    }
+
     if params.immediate_or_cancel {
         msg!("immediate_or_cancel order must be in place_and_make or place_and_take");
         return Err(print_error!(ErrorCode::InvalidOrderIOC)().into());
@@ -815,12 +896,19 @@ pub fn handle_cancel_order(ctx: Context<CancelOrder>, order_id: Option<u32>) ->
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
@@ -832,6 +920,15 @@ pub fn handle_cancel_order(ctx: Context<CancelOrder>, order_id: Option<u32>) ->
         None => load!(ctx.accounts.user)?.get_last_order_id(),
     };
 
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::CancelOrder)?;
+
+        let user = load!(ctx.accounts.user)?;
+        if let Some(order) = user.get_order(order_id) {
+            user_delegate.validate_market(order.market_type, order.market_index)?;
+        }
+    }
+
     controller::orders::cancel_order_by_order_id(
         order_id,
         &ctx.accounts.user,
@@ -851,18 +948,34 @@ pub fn handle_cancel_order_by_user_id(ctx: Context<CancelOrder>, user_order_id:
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::CancelOrder)?;
+
+        let user = load!(ctx.accounts.user)?;
+        let order_index = user.get_order_index_by_user_order_id(user_order_id)?;
+        let order = &user.orders[order_index];
+        user_delegate.validate_market(order.market_type, order.market_index)?;
+    }
+
     controller::orders::cancel_order_by_user_order_id(
         user_order_id,
         &ctx.accounts.user,
@@ -887,18 +1000,41 @@ pub fn handle_cancel_orders(
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::CancelOrder)?;
+
+        if !user_delegate.has_permission(DelegatePermission::AllMarkets)? {
+            match (market_type, market_index) {
+                (Some(market_type), Some(market_index)) => {
+                    user_delegate.validate_market(market_type, market_index)?
+                }
+                _ => {
+                    msg!("delegate scoped to markets must cancel orders by market");
+                    return Err(ErrorCode::DelegatePermissionDenied.into());
+                }
+            }
+        }
+    }
+
     let user_key = ctx.accounts.user.key();
     let mut user = load_mut!(ctx.accounts.user)?;
 
@@ -961,12 +1097,19 @@ pub fn handle_modify_order(
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
@@ -987,6 +1130,7 @@ pub fn handle_modify_order(
         &spot_market_map,
         &mut oracle_map,
         clock,
+        user_delegate.as_ref(),
     )?;
 
     Ok(())
@@ -1003,12 +1147,19 @@ pub fn handle_modify_order_by_user_order_id(
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
@@ -1024,6 +1175,7 @@ pub fn handle_modify_order_by_user_order_id(
         &spot_market_map,
         &mut oracle_map,
         clock,
+        user_delegate.as_ref(),
     )?;
 
     Ok(())
@@ -1036,12 +1188,19 @@ pub fn handle_place_orders(ctx: Context<PlaceOrder>, params: Vec<OrderParams>) -
     let clock = &Clock::get()?;
     let state = &ctx.accounts.state;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         clock.slot,
@@ -1062,10 +1221,21 @@ pub fn handle_place_orders(ctx: Context<PlaceOrder>, params: Vec<OrderParams>) -
             "immediate_or_cancel order must be in place_and_make or place_and_take"
         )?;
 
+        if let Some(user_delegate) = user_delegate {
+            validate_delegate_order_params(
+                &user_delegate,
+                params,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+            )?;
+        }
+
         // only enforce margin on last order and only try to expire on first order
         let options = PlaceOrderOptions {
             enforce_margin_check: i == num_orders - 1,
             try_expire_orders: i == 0,
+            ..PlaceOrderOptions::default()
         };
 
         if params.market_type == MarketType::Perp {
@@ -1108,6 +1278,11 @@ pub fn handle_place_and_take_perp_order<'info>(
     let state = &ctx.accounts.state;
 
     let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
     let AccountMaps {
         perp_market_map,
         spot_market_map,
@@ -1120,6 +1295,16 @@ pub fn handle_place_and_take_perp_order<'info>(
         Some(state.oracle_guard_rails),
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            &user_delegate,
+            &params,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+        )?;
+    }
+
     if params.post_only != PostOnlyParam::None {
         msg!("post_only cant be used in place_and_take");
         return Err(print_error!(ErrorCode::InvalidOrderPostOnly)().into());
@@ -1198,6 +1383,11 @@ pub fn handle_place_and_make_perp_order<'a, 'b, 'c, 'info>(
     let state = &ctx.accounts.state;
 
     let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
     let AccountMaps {
         perp_market_map,
         spot_market_map,
@@ -1210,6 +1400,16 @@ pub fn handle_place_and_make_perp_order<'a, 'b, 'c, 'info>(
         Some(state.oracle_guard_rails),
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            &user_delegate,
+            &params,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+        )?;
+    }
+
     if !params.immediate_or_cancel
         || params.post_only == PostOnlyParam::None
         || params.order_type != OrderType::Limit
@@ -1284,18 +1484,35 @@ pub fn handle_place_and_make_perp_order<'a, 'b, 'c, 'info>(
 }
 
 pub fn handle_place_spot_order(ctx: Context<PlaceOrder>, params: OrderParams) -> Result<()> {
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            &user_delegate,
+            &params,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+        )?;
+    }
+
     if params.immediate_or_cancel {
         msg!("immediate_or_cancel order must be in place_and_make or place_and_take");
         return Err(print_error!(ErrorCode::InvalidOrderIOC)().into());
@@ -1328,6 +1545,11 @@ pub fn handle_place_and_take_spot_order<'info>(
     let market_index = params.market_index;
 
     let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
     let AccountMaps {
         perp_market_map,
         spot_market_map,
@@ -1340,6 +1562,16 @@ pub fn handle_place_and_take_spot_order<'info>(
         None,
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            &user_delegate,
+            &params,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+        )?;
+    }
+
     if params.post_only != PostOnlyParam::None {
         msg!("post_only cant be used in place_and_take");
         return Err(print_error!(ErrorCode::InvalidOrderPostOnly)().into());
@@ -1379,6 +1611,16 @@ pub fn handle_place_and_take_spot_order<'info>(
                 &quote_market,
             )?)
         }
+        SpotFulfillmentType::ConstantProductPool => {
+            let base_market = spot_market_map.get_ref(&market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Box::new(ConstantProductPoolFulfillmentParams::new(
+                remaining_accounts_iter,
+                &ctx.accounts.state,
+                &base_market,
+                &quote_market,
+            )?)
+        }
         SpotFulfillmentType::Match => {
             let base_market = spot_market_map.get_ref(&market_index)?;
             let quote_market = spot_market_map.get_quote_spot_market()?;
@@ -1418,7 +1660,7 @@ pub fn handle_place_and_take_spot_order<'info>(
         maker_stats.as_ref(),
         maker_order_id,
         &clock,
-        fulfillment_params.as_mut(),
+        &mut [fulfillment_params.as_mut()],
     )?;
 
     let order_exists = load!(ctx.accounts.user)?
@@ -1457,6 +1699,11 @@ pub fn handle_place_and_make_spot_order<'info>(
     let state = &ctx.accounts.state;
 
     let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
     let AccountMaps {
         perp_market_map,
         spot_market_map,
@@ -1469,6 +1716,16 @@ pub fn handle_place_and_make_spot_order<'info>(
         None,
     )?;
 
+    if let Some(user_delegate) = user_delegate {
+        validate_delegate_order_params(
+            &user_delegate,
+            &params,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+        )?;
+    }
+
     let (_referrer, _referrer_stats) = get_referrer_and_referrer_stats(remaining_accounts_iter)?;
 
     if !params.immediate_or_cancel
@@ -1503,6 +1760,16 @@ pub fn handle_place_and_make_spot_order<'info>(
                 &quote_market,
             )?)
         }
+        SpotFulfillmentType::ConstantProductPool => {
+            let base_market = spot_market_map.get_ref(&market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Box::new(ConstantProductPoolFulfillmentParams::new(
+                remaining_accounts_iter,
+                &ctx.accounts.state,
+                &base_market,
+                &quote_market,
+            )?)
+        }
         SpotFulfillmentType::Match => {
             let base_market = spot_market_map.get_ref(&market_index)?;
             let quote_market = spot_market_map.get_quote_spot_market()?;
@@ -1541,7 +1808,7 @@ pub fn handle_place_and_make_spot_order<'info>(
         Some(&ctx.accounts.user_stats),
         Some(order_id),
         clock,
-        fulfillment_params.as_mut(),
+        &mut [fulfillment_params.as_mut()],
     )?;
 
     let order_exists = load!(ctx.accounts.user)?
@@ -1575,6 +1842,18 @@ pub fn handle_add_perp_lp_shares<'info>(
     n_shares: u64,
     market_index: u16,
 ) -> Result<()> {
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::Lp)?;
+        user_delegate.validate_market(MarketType::Perp, market_index)?;
+    }
+
     let user_key = ctx.accounts.user.key();
     let user = &mut load_mut!(ctx.accounts.user)?;
     let state = &ctx.accounts.state;
@@ -1586,12 +1865,13 @@ pub fn handle_add_perp_lp_shares<'info>(
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &get_writable_perp_market_set(market_index),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
+    let perp_lp_stats = get_perp_lp_stats(remaining_accounts_iter, &user_key, market_index)?;
 
     validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
     math::liquidation::validate_user_not_being_liquidated(
@@ -1602,17 +1882,12 @@ pub fn handle_add_perp_lp_shares<'info>(
         state.liquidation_margin_buffer_ratio,
     )?;
 
-    {
+    let attribution = {
         let mut market = perp_market_map.get_ref_mut(&market_index)?;
 
         validate!(
-            matches!(
-                market.status,
-                MarketStatus::Active
-                    | MarketStatus::FundingPaused
-                    | MarketStatus::FillPaused
-                    | MarketStatus::WithdrawPaused
-            ),
+            market.status.is_active_or_legacy_paused()
+                && !market.is_operation_paused(MarketOperation::Lp)?,
             ErrorCode::MarketStatusInvalidForNewLP,
             "Market Status doesn't allow for new LP liquidity"
         )?;
@@ -1625,7 +1900,18 @@ pub fn handle_add_perp_lp_shares<'info>(
             market.amm.order_step_size,
         )?;
 
-        controller::funding::settle_funding_payment(user, &user_key, &mut market, now)?;
+        let funding_paused = controller::funding::is_continuous_funding_paused(
+            &market,
+            &mut oracle_map,
+            state.funding_paused()?,
+        )?;
+        controller::funding::settle_funding_payment(
+            user,
+            &user_key,
+            &mut market,
+            now,
+            funding_paused,
+        )?;
 
         // standardize n shares to mint
         let n_shares = crate::math::orders::standardize_base_asset_amount(
@@ -1634,14 +1920,37 @@ pub fn handle_add_perp_lp_shares<'info>(
         )?
         .cast::<u64>()?;
 
-        controller::lp::mint_lp_shares(
-            user.force_get_perp_position_mut(market_index)?,
-            &mut market,
-            n_shares,
-        )?;
+        let oracle_price = oracle_map.get_price_data(&market.amm.oracle)?.price;
+        let position = user.force_get_perp_position_mut(market_index)?;
+
+        // settle before minting so the stats attribute with the shares held until now
+        let attribution = match &perp_lp_stats {
+            Some(perp_lp_stats) => {
+                if position.lp_shares > 0 {
+                    controller::lp::settle_lp_position(position, &mut market)?;
+                }
+
+                controller::lp::update_perp_lp_stats(
+                    position,
+                    &market,
+                    &mut load_mut!(perp_lp_stats)?,
+                    oracle_price,
+                    now,
+                )?
+            }
+            None => LPAttribution::default(),
+        };
+
+        controller::lp::mint_lp_shares(position, &mut market, n_shares)?;
+
+        if let Some(perp_lp_stats) = &perp_lp_stats {
+            load_mut!(perp_lp_stats)?.lp_shares = position.lp_shares;
+        }
 
         user.last_add_perp_lp_shares_ts = now;
-    }
+
+        attribution
+    };
 
     // check margin requirements
     validate!(
@@ -1663,6 +1972,9 @@ pub fn handle_add_perp_lp_shares<'info>(
         user: user_key,
         n_shares,
         market_index,
+        fee_revenue: attribution.fee_revenue.cast()?,
+        funding_payment: attribution.funding_payment.cast()?,
+        inventory_pnl: attribution.inventory_pnl.cast()?,
         ..LPRecord::default()
     });
 
@@ -1681,17 +1993,23 @@ pub fn handle_remove_perp_lp_shares_in_expiring_market(
     let clock = Clock::get()?;
     let now = clock.unix_timestamp;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
         mut oracle_map,
         ..
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &get_writable_perp_market_set(market_index),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
+    let perp_lp_stats = get_perp_lp_stats(remaining_accounts_iter, &user_key, market_index)?;
+    let mut perp_lp_stats = match &perp_lp_stats {
+        Some(perp_lp_stats) => Some(load_mut!(perp_lp_stats)?),
+        None => None,
+    };
 
     // additional validate
     {
@@ -1711,6 +2029,7 @@ pub fn handle_remove_perp_lp_shares_in_expiring_market(
         user_key,
         shares_to_burn,
         market_index,
+        perp_lp_stats.as_deref_mut(),
         now,
     )?;
 
@@ -1727,6 +2046,18 @@ pub fn handle_remove_perp_lp_shares(
     shares_to_burn: u64,
     market_index: u16,
 ) -> Result<()> {
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::Lp)?;
+        user_delegate.validate_market(MarketType::Perp, market_index)?;
+    }
+
     let user_key = ctx.accounts.user.key();
     let user = &mut load_mut!(ctx.accounts.user)?;
 
@@ -1739,12 +2070,17 @@ pub fn handle_remove_perp_lp_shares(
         mut oracle_map,
         ..
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &get_writable_perp_market_set(market_index),
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
     )?;
+    let perp_lp_stats = get_perp_lp_stats(remaining_accounts_iter, &user_key, market_index)?;
+    let mut perp_lp_stats = match &perp_lp_stats {
+        Some(perp_lp_stats) => Some(load_mut!(perp_lp_stats)?),
+        None => None,
+    };
 
     controller::lp::remove_perp_lp_shares(
         perp_market_map,
@@ -1754,6 +2090,7 @@ pub fn handle_remove_perp_lp_shares(
         user_key,
         shares_to_burn,
         market_index,
+        perp_lp_stats.as_deref_mut(),
         now,
     )?;
 
@@ -1762,97 +2099,1317 @@ pub fn handle_remove_perp_lp_shares(
     Ok(())
 }
 
-pub fn handle_update_user_name(
-    ctx: Context<UpdateUser>,
-    _sub_account_id: u16,
-    name: [u8; 32],
+#[access_control(
+    amm_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_tokenize_perp_lp_shares(
+    ctx: Context<TokenizePerpLpShares>,
+    market_index: u16,
+    n_shares: u64,
 ) -> Result<()> {
-    let mut user = load_mut!(ctx.accounts.user)?;
-    user.name = name;
-    Ok(())
-}
+    let user_key = ctx.accounts.user.key();
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let pool_user_key = ctx.accounts.pool_user.key();
+    let pool_user = &mut load_mut!(ctx.accounts.pool_user)?;
+    let tokenized_lp_pool = &mut load_mut!(ctx.accounts.tokenized_lp_pool)?;
+    let state = &ctx.accounts.state;
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
 
-pub fn handle_update_user_custom_margin_ratio(
-    ctx: Context<UpdateUser>,
-    _sub_account_id: u16,
-    margin_ratio: u32,
-) -> Result<()> {
-    let mut user = load_mut!(ctx.accounts.user)?;
-    user.max_margin_ratio = margin_ratio;
-    Ok(())
-}
+    validate!(
+        tokenized_lp_pool.user == pool_user_key,
+        ErrorCode::InvalidUserAccount,
+        "pool_user isn't the tokenized lp pool's user"
+    )?;
 
-pub fn handle_update_user_margin_trading_enabled(
-    ctx: Context<UpdateUser>,
-    _sub_account_id: u16,
-    margin_trading_enabled: bool,
-) -> Result<()> {
-    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
+        perp_market_map,
         spot_market_map,
         mut oracle_map,
-        ..
     } = load_maps(
-        remaining_accounts_iter,
-        &MarketSet::new(),
-        &MarketSet::new(),
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &get_writable_perp_market_set(market_index),
+        &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+    math::liquidation::validate_user_not_being_liquidated(
+        user,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state.liquidation_margin_buffer_ratio,
+    )?;
+
+    validate!(!pool_user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+    math::liquidation::validate_user_not_being_liquidated(
+        pool_user,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state.liquidation_margin_buffer_ratio,
+    )?;
+
+    // tokenizing takes the shares out of the user's account, so it is subject to the same cooldown as removing them
+    let time_since_last_add_liquidity = now.safe_sub(user.last_add_perp_lp_shares_ts)?;
+    validate!(
+        time_since_last_add_liquidity >= state.lp_cooldown_time.cast()?,
+        ErrorCode::TryingToRemoveLiquidityTooFast
+    )?;
+
+    let pool_lp_shares = pool_user
+        .get_perp_position(market_index)
+        .map_or(0, |position| position.lp_shares);
+
+    let (n_shares, n_tokens, position_delta, pnl) = {
+        let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+        // standardize n shares to tokenize
+        let n_shares = crate::math::orders::standardize_base_asset_amount(
+            n_shares.cast()?,
+            market.amm.order_step_size,
+        )?
+        .cast::<u64>()?;
+
+        let funding_paused = controller::funding::is_continuous_funding_paused(
+            &market,
+            &mut oracle_map,
+            state.funding_paused()?,
+        )?;
+        controller::funding::settle_funding_payment(
+            user,
+            &user_key,
+            &mut market,
+            now,
+            funding_paused,
+        )?;
+        controller::funding::settle_funding_payment(
+            pool_user,
+            &pool_user_key,
+            &mut market,
+            now,
+            funding_paused,
+        )?;
+
+        let (n_tokens, position_delta, pnl) = controller::lp::tokenize_lp_shares(
+            user.get_perp_position_mut(market_index)?,
+            pool_user.force_get_perp_position_mut(market_index)?,
+            &mut market,
+            tokenized_lp_pool,
+            n_shares,
+        )?;
+
+        (n_shares, n_tokens, position_delta, pnl)
+    };
+
+    {
+        let quote_spot_market = &mut spot_market_map.get_ref_mut(&QUOTE_SPOT_MARKET_INDEX)?;
+        let quote_oracle_price = oracle_map.get_price_data(&quote_spot_market.oracle)?.price;
+
+        controller::spot_balance::update_spot_market_cumulative_interest(
+            quote_spot_market,
+            Some(oracle_map.get_price_data(&quote_spot_market.oracle)?),
+            now,
+        )?;
+
+        if pool_lp_shares > 0 {
+            // the user brings the slice of the pool's collateral that comes with each share
+            let pool_token_amount = pool_user
+                .get_quote_spot_position()
+                .get_signed_token_amount(quote_spot_market)?;
+
+            let token_amount =
+                get_proportion_i128(pool_token_amount, n_shares.cast()?, pool_lp_shares.cast()?)?;
+
+            if token_amount > 0 {
+                controller::lp::transfer_tokenized_lp_pool_collateral(
+                    user,
+                    &user_key,
+                    pool_user,
+                    &pool_user_key,
+                    quote_spot_market,
+                    token_amount.unsigned_abs(),
+                    quote_oracle_price,
+                    now,
+                )?;
+            } else {
+                controller::lp::transfer_tokenized_lp_pool_collateral(
+                    pool_user,
+                    &pool_user_key,
+                    user,
+                    &user_key,
+                    quote_spot_market,
+                    token_amount.unsigned_abs(),
+                    quote_oracle_price,
+                    now,
+                )?;
+            }
+        }
+    }
+
+    if pool_lp_shares == 0 {
+        // the first shares in the pool bring the collateral it needs to meet its initial margin requirement
+        let (margin_requirement, total_collateral, _, _) =
+            calculate_margin_requirement_and_total_collateral(
+                pool_user,
+                &perp_market_map,
+                MarginRequirementType::Initial,
+                &spot_market_map,
+                &mut oracle_map,
+                None,
+            )?;
+
+        if total_collateral < margin_requirement.cast()? {
+            let margin_shortage = margin_requirement
+                .cast::<i128>()?
+                .safe_sub(total_collateral)?
+                .unsigned_abs();
+
+            let quote_spot_market = &mut spot_market_map.get_ref_mut(&QUOTE_SPOT_MARKET_INDEX)?;
+            let quote_oracle_price = oracle_map.get_price_data(&quote_spot_market.oracle)?.price;
+
+            let token_amount = margin_shortage
+                .safe_mul(quote_spot_market.get_precision().cast()?)?
+                .safe_div_ceil(quote_oracle_price.cast()?)?;
+
+            controller::lp::transfer_tokenized_lp_pool_collateral(
+                user,
+                &user_key,
+                pool_user,
+                &pool_user_key,
+                quote_spot_market,
+                token_amount,
+                quote_oracle_price,
+                now,
+            )?;
+        }
+    }
+
+    // check margin requirements
+    validate!(
+        meets_initial_margin_requirement(
+            user,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map
+        )?,
+        ErrorCode::InsufficientCollateral,
+        "User does not meet initial margin requirement"
+    )?;
+
+    validate!(
+        meets_initial_margin_requirement(
+            pool_user,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map
+        )?,
+        ErrorCode::InsufficientCollateral,
+        "Tokenized lp pool user does not meet initial margin requirement"
+    )?;
+
+    user.update_last_active_slot(clock.slot);
+    pool_user.update_last_active_slot(clock.slot);
+
+    controller::token::mint_to_user(
+        &ctx.accounts.token_program,
+        &ctx.accounts.lp_share_mint,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.drift_signer,
+        state.signer_nonce,
+        n_tokens,
+    )?;
+
+    emit!(LPRecord {
+        ts: now,
+        action: LPAction::TokenizeLiquidity,
+        user: user_key,
+        n_shares,
+        market_index,
+        delta_base_asset_amount: position_delta.base_asset_amount,
+        delta_quote_asset_amount: position_delta.quote_asset_amount,
+        pnl,
+        ..LPRecord::default()
+    });
+
+    Ok(())
+}
+
+#[access_control(
+    amm_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_redeem_perp_lp_shares(
+    ctx: Context<RedeemPerpLpShares>,
+    market_index: u16,
+    n_tokens: u64,
+) -> Result<()> {
+    let user_key = ctx.accounts.user.key();
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let pool_user_key = ctx.accounts.pool_user.key();
+    let pool_user = &mut load_mut!(ctx.accounts.pool_user)?;
+    let tokenized_lp_pool = &mut load_mut!(ctx.accounts.tokenized_lp_pool)?;
+    let state = &ctx.accounts.state;
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+
+    validate!(
+        tokenized_lp_pool.user == pool_user_key,
+        ErrorCode::InvalidUserAccount,
+        "pool_user isn't the tokenized lp pool's user"
+    )?;
+
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &get_writable_perp_market_set(market_index),
+        &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+    math::liquidation::validate_user_not_being_liquidated(
+        user,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        state.liquidation_margin_buffer_ratio,
+    )?;
+
+    validate!(!pool_user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+
+    let token_supply = tokenized_lp_pool.token_supply;
+
+    let (n_shares, position_delta, pnl) = {
+        let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+        let funding_paused = controller::funding::is_continuous_funding_paused(
+            &market,
+            &mut oracle_map,
+            state.funding_paused()?,
+        )?;
+        controller::funding::settle_funding_payment(
+            user,
+            &user_key,
+            &mut market,
+            now,
+            funding_paused,
+        )?;
+        controller::funding::settle_funding_payment(
+            pool_user,
+            &pool_user_key,
+            &mut market,
+            now,
+            funding_paused,
+        )?;
+
+        controller::lp::redeem_lp_shares(
+            user.force_get_perp_position_mut(market_index)?,
+            pool_user.force_get_perp_position_mut(market_index)?,
+            &mut market,
+            tokenized_lp_pool,
+            n_tokens,
+        )?
+    };
+
+    {
+        let quote_spot_market = &mut spot_market_map.get_ref_mut(&QUOTE_SPOT_MARKET_INDEX)?;
+        let quote_oracle_price = oracle_map.get_price_data(&quote_spot_market.oracle)?.price;
+
+        controller::spot_balance::update_spot_market_cumulative_interest(
+            quote_spot_market,
+            Some(oracle_map.get_price_data(&quote_spot_market.oracle)?),
+            now,
+        )?;
+
+        // the tokens are a claim on the same slice of the pool's collateral
+        let pool_token_amount = pool_user
+            .get_quote_spot_position()
+            .get_signed_token_amount(quote_spot_market)?;
+
+        let token_amount =
+            get_proportion_i128(pool_token_amount, n_tokens.cast()?, token_supply.cast()?)?;
+
+        if token_amount > 0 {
+            controller::lp::transfer_tokenized_lp_pool_collateral(
+                pool_user,
+                &pool_user_key,
+                user,
+                &user_key,
+                quote_spot_market,
+                token_amount.unsigned_abs(),
+                quote_oracle_price,
+                now,
+            )?;
+        } else {
+            controller::lp::transfer_tokenized_lp_pool_collateral(
+                user,
+                &user_key,
+                pool_user,
+                &pool_user_key,
+                quote_spot_market,
+                token_amount.unsigned_abs(),
+                quote_oracle_price,
+                now,
+            )?;
+        }
+    }
+
+    // redeemed shares are subject to the same cooldown as newly added ones
+    user.last_add_perp_lp_shares_ts = now;
+
+    // check margin requirements
+    validate!(
+        meets_initial_margin_requirement(
+            user,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map
+        )?,
+        ErrorCode::InsufficientCollateral,
+        "User does not meet initial margin requirement"
+    )?;
+
+    user.update_last_active_slot(clock.slot);
+    pool_user.update_last_active_slot(clock.slot);
+
+    controller::token::burn_from_user(
+        &ctx.accounts.token_program,
+        &ctx.accounts.lp_share_mint,
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.authority.to_account_info(),
+        n_tokens,
+    )?;
+
+    emit!(LPRecord {
+        ts: now,
+        action: LPAction::RedeemLiquidity,
+        user: user_key,
+        n_shares,
+        market_index,
+        delta_base_asset_amount: position_delta.base_asset_amount,
+        delta_quote_asset_amount: position_delta.quote_asset_amount,
+        pnl,
+        ..LPRecord::default()
+    });
+
+    Ok(())
+}
+
+pub fn handle_update_user_name(
+    ctx: Context<UpdateUser>,
+    _sub_account_id: u16,
+    name: [u8; 32],
+) -> Result<()> {
+    let mut user = load_mut!(ctx.accounts.user)?;
+    user.name = name;
+    Ok(())
+}
+
+pub fn handle_update_user_custom_margin_ratio(
+    ctx: Context<UpdateUser>,
+    _sub_account_id: u16,
+    margin_ratio: u32,
+) -> Result<()> {
+    let mut user = load_mut!(ctx.accounts.user)?;
+    user.max_margin_ratio = margin_ratio;
+    Ok(())
+}
+
+pub fn handle_update_user_margin_trading_enabled(
+    ctx: Context<UpdateUser>,
+    _sub_account_id: u16,
+    margin_trading_enabled: bool,
+) -> Result<()> {
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        spot_market_map,
+        mut oracle_map,
+        ..
+    } = load_maps(
+        remaining_accounts_iter,
+        &MarketSet::new(),
+        &MarketSet::new(),
         Clock::get()?.slot,
         None,
     )?;
 
-    let mut user = load_mut!(ctx.accounts.user)?;
-    user.is_margin_trading_enabled = margin_trading_enabled;
+    let mut user = load_mut!(ctx.accounts.user)?;
+    user.is_margin_trading_enabled = margin_trading_enabled;
+
+    validate_spot_margin_trading(&user, &spot_market_map, &mut oracle_map)
+        .map_err(|_| ErrorCode::MarginOrdersOpen)?;
+
+    Ok(())
+}
+
+pub fn handle_update_user_health_tracking(
+    ctx: Context<UpdateUser>,
+    _sub_account_id: u16,
+    health_tracking_enabled: bool,
+) -> Result<()> {
+    let mut user = load_mut!(ctx.accounts.user)?;
+    user.is_health_tracking_enabled = health_tracking_enabled;
+    user.last_health = 100;
+    user.last_health_slot = Clock::get()?.slot;
+    user.slots_below_health_threshold = 0;
+    Ok(())
+}
+
+pub fn handle_update_user_delegate(
+    ctx: Context<UpdateUser>,
+    _sub_account_id: u16,
+    delegate: Pubkey,
+) -> Result<()> {
+    let mut user = load_mut!(ctx.accounts.user)?;
+    user.delegate = delegate;
+    Ok(())
+}
+
+pub fn handle_initialize_user_delegate(
+    ctx: Context<InitializeUserDelegate>,
+    _sub_account_id: u16,
+    delegate: Pubkey,
+    permissions: u8,
+    max_order_notional: u64,
+    perp_market_indexes: Vec<u16>,
+    spot_market_indexes: Vec<u16>,
+) -> Result<()> {
+    let mut user_delegate = ctx
+        .accounts
+        .user_delegate
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    validate!(
+        delegate != Pubkey::default() && delegate != ctx.accounts.authority.key(),
+        ErrorCode::InvalidUserDelegate,
+        "invalid delegate {}",
+        delegate
+    )?;
+
+    user_delegate.user = ctx.accounts.user.key();
+    user_delegate.delegate = delegate;
+    user_delegate.update(
+        permissions,
+        max_order_notional,
+        &perp_market_indexes,
+        &spot_market_indexes,
+    )?;
+
+    let mut user = load_mut!(ctx.accounts.user)?;
+    safe_increment!(user.number_of_user_delegates, 1);
+
+    Ok(())
+}
+
+pub fn handle_update_user_delegate_permissions(
+    ctx: Context<UpdateUserDelegate>,
+    _sub_account_id: u16,
+    permissions: u8,
+    max_order_notional: u64,
+    perp_market_indexes: Vec<u16>,
+    spot_market_indexes: Vec<u16>,
+) -> Result<()> {
+    let mut user_delegate = load_mut!(ctx.accounts.user_delegate)?;
+
+    msg!(
+        "user_delegate.permissions {} -> {}",
+        user_delegate.permissions,
+        permissions
+    );
+
+    msg!(
+        "user_delegate.max_order_notional {} -> {}",
+        user_delegate.max_order_notional,
+        max_order_notional
+    );
+
+    user_delegate.update(
+        permissions,
+        max_order_notional,
+        &perp_market_indexes,
+        &spot_market_indexes,
+    )?;
+
+    Ok(())
+}
+
+pub fn handle_delete_user_delegate(
+    ctx: Context<DeleteUserDelegate>,
+    _sub_account_id: u16,
+) -> Result<()> {
+    let mut user = load_mut!(ctx.accounts.user)?;
+    safe_decrement!(user.number_of_user_delegates, 1);
+    Ok(())
+}
+
+pub fn handle_initialize_perp_lp_range(
+    ctx: Context<InitializePerpLpRange>,
+    _sub_account_id: u16,
+    market_index: u16,
+    lower_reserve_price: u64,
+    upper_reserve_price: u64,
+) -> Result<()> {
+    let mut perp_lp_range = ctx
+        .accounts
+        .perp_lp_range
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    perp_lp_range.user = ctx.accounts.user.key();
+    perp_lp_range.market_index = market_index;
+    perp_lp_range.update_range(lower_reserve_price, upper_reserve_price)?;
+
+    Ok(())
+}
+
+pub fn handle_update_perp_lp_range(
+    ctx: Context<UpdatePerpLpRange>,
+    _sub_account_id: u16,
+    _market_index: u16,
+    lower_reserve_price: u64,
+    upper_reserve_price: u64,
+) -> Result<()> {
+    let mut perp_lp_range = load_mut!(ctx.accounts.perp_lp_range)?;
+
+    perp_lp_range.update_range(lower_reserve_price, upper_reserve_price)?;
+
+    Ok(())
+}
+
+pub fn handle_delete_perp_lp_range(
+    _ctx: Context<DeletePerpLpRange>,
+    _sub_account_id: u16,
+    _market_index: u16,
+) -> Result<()> {
+    // parked shares are already out of the amm, so they are simply dropped
+    Ok(())
+}
+
+pub fn handle_initialize_perp_lp_hedge(
+    ctx: Context<InitializePerpLpHedge>,
+    _sub_account_id: u16,
+    market_index: u16,
+    base_asset_amount_threshold: u64,
+) -> Result<()> {
+    let mut perp_lp_hedge = ctx
+        .accounts
+        .perp_lp_hedge
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    perp_lp_hedge.user = ctx.accounts.user.key();
+    perp_lp_hedge.market_index = market_index;
+    perp_lp_hedge.update_base_asset_amount_threshold(base_asset_amount_threshold)?;
+
+    Ok(())
+}
+
+pub fn handle_update_perp_lp_hedge(
+    ctx: Context<UpdatePerpLpHedge>,
+    _sub_account_id: u16,
+    _market_index: u16,
+    base_asset_amount_threshold: u64,
+) -> Result<()> {
+    let mut perp_lp_hedge = load_mut!(ctx.accounts.perp_lp_hedge)?;
+
+    perp_lp_hedge.update_base_asset_amount_threshold(base_asset_amount_threshold)?;
+
+    Ok(())
+}
+
+pub fn handle_delete_perp_lp_hedge(
+    _ctx: Context<DeletePerpLpHedge>,
+    _sub_account_id: u16,
+    _market_index: u16,
+) -> Result<()> {
+    Ok(())
+}
+
+pub fn handle_initialize_perp_lp_stats(
+    ctx: Context<InitializePerpLpStats>,
+    _sub_account_id: u16,
+    market_index: u16,
+) -> Result<()> {
+    let mut perp_lp_stats = ctx
+        .accounts
+        .perp_lp_stats
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    // lp_shares start at 0 so nothing is attributed until the first update snapshots the amm
+    perp_lp_stats.user = ctx.accounts.user.key();
+    perp_lp_stats.market_index = market_index;
+
+    Ok(())
+}
+
+pub fn handle_delete_perp_lp_stats(
+    _ctx: Context<DeletePerpLpStats>,
+    _sub_account_id: u16,
+    _market_index: u16,
+) -> Result<()> {
+    Ok(())
+}
+
+pub fn handle_initialize_withdraw_policy(
+    ctx: Context<InitializeWithdrawPolicy>,
+    time_lock_period: i64,
+    allowed_destinations: Vec<Pubkey>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    let mut withdraw_policy = ctx
+        .accounts
+        .withdraw_policy
+        .load_init()
+        .or(Err(ErrorCode::UnableToLoadAccountLoader))?;
+
+    withdraw_policy.initialize(
+        ctx.accounts.authority.key(),
+        time_lock_period,
+        &allowed_destinations,
+    )?;
+
+    let mut user_stats = load_mut!(ctx.accounts.user_stats)?;
+    user_stats.has_withdraw_policy = true;
+
+    emit!(get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::Initialize,
+        now
+    ));
+
+    Ok(())
+}
+
+pub fn handle_update_withdraw_policy(
+    ctx: Context<UpdateWithdrawPolicy>,
+    time_lock_period: i64,
+    allowed_destinations: Vec<Pubkey>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    msg!(
+        "withdraw_policy.time_lock_period {} -> {}",
+        withdraw_policy.time_lock_period,
+        time_lock_period
+    );
+
+    withdraw_policy.update(time_lock_period, &allowed_destinations)?;
+
+    emit!(get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::Update,
+        now
+    ));
+
+    Ok(())
+}
+
+pub fn handle_request_withdrawal(
+    ctx: Context<UpdateWithdrawPolicy>,
+    market_index: u16,
+    amount: u64,
+    destination: Pubkey,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    withdraw_policy.request_withdrawal(destination, market_index, amount, now)?;
+
+    emit!(get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::RequestWithdrawal,
+        now
+    ));
+
+    Ok(())
+}
+
+pub fn handle_cancel_withdrawal_request(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    let pending_withdrawal = withdraw_policy.pending_withdrawal;
+
+    withdraw_policy.cancel_withdrawal()?;
+
+    let mut withdraw_policy_record = get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::CancelWithdrawal,
+        now,
+    );
+    withdraw_policy_record.pending_destination = Some(pending_withdrawal.destination);
+    withdraw_policy_record.pending_market_index = pending_withdrawal.market_index;
+    withdraw_policy_record.pending_amount = pending_withdrawal.amount;
+    withdraw_policy_record.pending_unlock_ts = pending_withdrawal.unlock_ts;
+    emit!(withdraw_policy_record);
+
+    Ok(())
+}
+
+pub fn handle_request_withdraw_policy_removal(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    withdraw_policy.request_removal(now)?;
+
+    emit!(get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::RequestRemoval,
+        now
+    ));
+
+    Ok(())
+}
+
+pub fn handle_cancel_withdraw_policy_removal(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    withdraw_policy.cancel_removal()?;
+
+    emit!(get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::CancelRemoval,
+        now
+    ));
+
+    Ok(())
+}
+
+pub fn handle_delete_withdraw_policy(ctx: Context<DeleteWithdrawPolicy>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let withdraw_policy = load!(ctx.accounts.withdraw_policy)?;
+
+    withdraw_policy.validate_removal(now)?;
+
+    let mut user_stats = load_mut!(ctx.accounts.user_stats)?;
+    user_stats.has_withdraw_policy = false;
+
+    emit!(get_withdraw_policy_record(
+        &withdraw_policy,
+        WithdrawPolicyAction::Delete,
+        now
+    ));
+
+    Ok(())
+}
+
+fn get_withdraw_policy_record(
+    withdraw_policy: &WithdrawPolicy,
+    action: WithdrawPolicyAction,
+    now: i64,
+) -> WithdrawPolicyRecord {
+    let pending_withdrawal = &withdraw_policy.pending_withdrawal;
+    WithdrawPolicyRecord {
+        ts: now,
+        authority: withdraw_policy.authority,
+        action,
+        time_lock_period: withdraw_policy.time_lock_period,
+        allowed_destinations: withdraw_policy.get_allowed_destinations().to_vec(),
+        pending_destination: if pending_withdrawal.is_empty() {
+            None
+        } else {
+            Some(pending_withdrawal.destination)
+        },
+        pending_market_index: pending_withdrawal.market_index,
+        pending_amount: pending_withdrawal.amount,
+        pending_unlock_ts: pending_withdrawal.unlock_ts,
+        removal_unlock_ts: withdraw_policy.removal_unlock_ts,
+    }
+}
+
+pub fn handle_update_user_shared_collateral_group(
+    ctx: Context<UpdateUserSharedCollateralGroup>,
+    _sub_account_id: u16,
+    in_shared_collateral_group: bool,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+    let user_key = ctx.accounts.user.key();
+    let mut user = load_mut!(ctx.accounts.user)?;
+    let mut user_stats = load_mut!(ctx.accounts.user_stats)?;
+
+    validate!(
+        user.is_in_shared_collateral_group != in_shared_collateral_group,
+        ErrorCode::DefaultError,
+        "user.is_in_shared_collateral_group already {}",
+        in_shared_collateral_group
+    )?;
+
+    validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
+    validate!(
+        !user.is_being_liquidated(),
+        ErrorCode::UserIsBeingLiquidated
+    )?;
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &MarketSet::new(),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+    )?;
+
+    let shared_collateral_group =
+        load_shared_collateral_group(&user_key, &user, &user_stats, remaining_accounts_iter)?;
+
+    if in_shared_collateral_group {
+        // joining cant leave the group below the initial margin requirement
+        validate!(
+            meets_shared_collateral_group_margin_requirement(
+                &user,
+                &shared_collateral_group,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+                MarginRequirementType::Initial,
+            )?,
+            ErrorCode::InsufficientCollateral,
+            "shared collateral group would be below initial margin requirement"
+        )?;
+
+        user_stats.number_of_sub_accounts_in_shared_collateral_group = user_stats
+            .number_of_sub_accounts_in_shared_collateral_group
+            .safe_add(1)?;
+    } else {
+        // leaving cant leave the user or the rest of the group below the initial margin requirement
+        validate!(
+            meets_initial_margin_requirement(
+                &user,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+            )?,
+            ErrorCode::InsufficientCollateral,
+            "user would be below initial margin requirement"
+        )?;
+
+        let (group_margin_requirement, group_total_collateral) =
+            calculate_shared_collateral_group_margin_requirement_and_total_collateral(
+                &shared_collateral_group,
+                &perp_market_map,
+                MarginRequirementType::Initial,
+                &spot_market_map,
+                &mut oracle_map,
+                false,
+            )?;
+
+        validate!(
+            group_total_collateral >= group_margin_requirement.cast()?,
+            ErrorCode::InsufficientCollateral,
+            "shared collateral group would be below initial margin requirement. total_collateral {} margin_requirement {}",
+            group_total_collateral,
+            group_margin_requirement
+        )?;
+
+        user_stats.number_of_sub_accounts_in_shared_collateral_group = user_stats
+            .number_of_sub_accounts_in_shared_collateral_group
+            .safe_sub(1)?;
+    }
+
+    msg!(
+        "user.is_in_shared_collateral_group {} -> {}",
+        user.is_in_shared_collateral_group,
+        in_shared_collateral_group
+    );
+
+    user.is_in_shared_collateral_group = in_shared_collateral_group;
+
+    Ok(())
+}
+
+pub fn handle_delete_user<'info>(ctx: Context<'_, '_, '_, 'info, DeleteUser<'info>>) -> Result<()> {
+    let user_key = ctx.accounts.user.key();
+    let user = &mut load_mut!(ctx.accounts.user)?;
+    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+
+    // the user's UserDelegate accounts are passed as remaining accounts and closed with the user
+    for account_info in ctx.remaining_accounts.iter() {
+        let user_delegate_loader: AccountLoader<UserDelegate> =
+            AccountLoader::try_from(account_info).or(Err(ErrorCode::InvalidUserDelegate))?;
+
+        {
+            let user_delegate = load!(user_delegate_loader)?;
+            validate!(
+                user_delegate.user == user_key,
+                ErrorCode::InvalidUserDelegate,
+                "user delegate {} is not for user {}",
+                account_info.key(),
+                user_key
+            )?;
+        }
+
+        user_delegate_loader.close(ctx.accounts.authority.to_account_info())?;
+        safe_decrement!(user.number_of_user_delegates, 1);
+    }
+
+    validate_user_deletion(user, user_stats)?;
+
+    safe_decrement!(user_stats.number_of_sub_accounts, 1);
+
+    let state = &mut ctx.accounts.state;
+    safe_decrement!(state.number_of_sub_accounts, 1);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(
+    sub_account_id: u16,
+)]
+pub struct InitializeUser<'info> {
+    #[account(
+        init,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        space = User::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    #[account(mut)]
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserStats<'info> {
+    #[account(
+        init,
+        seeds = [b"user_stats", authority.key.as_ref()],
+        space = UserStats::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    #[account(mut)]
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    name: [u8; 32],
+)]
+pub struct InitializeReferrerName<'info> {
+    #[account(
+        init,
+        seeds = [b"referrer_name", name.as_ref()],
+        space = ReferrerName::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub referrer_name: AccountLoader<'info, ReferrerName>,
+    #[account(
+        mut,
+        constraint = can_sign_for_user(&user, &authority)?
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        constraint = is_stats_for_user(&user, &user_stats)?
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct Deposit<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        constraint = can_sign_for_user(&user, &authority)?
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        constraint = is_stats_for_user(&user, &user_stats)?
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = &spot_market_vault.mint.eq(&user_token_account.mint),
+        token::authority = authority
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct Withdraw<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = &spot_market_vault.mint.eq(&user_token_account.mint)
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
 
-    validate_spot_margin_trading(&user, &spot_market_map, &mut oracle_map)
-        .map_err(|_| ErrorCode::MarginOrdersOpen)?;
+#[derive(Accounts)]
+#[instruction(market_index: u16,)]
+pub struct TransferDeposit<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub from_user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub to_user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+}
 
-    Ok(())
+#[derive(Accounts)]
+pub struct PlaceOrder<'info> {
+    pub state: Box<Account<'info, State>>,
+    /// authority can be a scoped delegate, checked in the handler with get_user_delegate
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
 }
 
-pub fn handle_update_user_delegate(
-    ctx: Context<UpdateUser>,
-    _sub_account_id: u16,
-    delegate: Pubkey,
-) -> Result<()> {
-    let mut user = load_mut!(ctx.accounts.user)?;
-    user.delegate = delegate;
-    Ok(())
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    pub state: Box<Account<'info, State>>,
+    /// authority can be a scoped delegate, checked in the handler with get_user_delegate
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
 }
 
-pub fn handle_delete_user(ctx: Context<DeleteUser>) -> Result<()> {
-    let user = &load!(ctx.accounts.user)?;
-    let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
+#[derive(Accounts)]
+pub struct PlaceAndTake<'info> {
+    pub state: Box<Account<'info, State>>,
+    /// authority can be a scoped delegate, checked in the handler with get_user_delegate
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        constraint = is_stats_for_user(&user, &user_stats)?
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+}
 
-    validate_user_deletion(user, user_stats)?;
+#[derive(Accounts)]
+pub struct PlaceAndMake<'info> {
+    pub state: Box<Account<'info, State>>,
+    /// authority can be a scoped delegate, checked in the handler with get_user_delegate
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        constraint = is_stats_for_user(&user, &user_stats)?
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    #[account(mut)]
+    pub taker: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        constraint = is_stats_for_user(&taker, &taker_stats)?
+    )]
+    pub taker_stats: AccountLoader<'info, UserStats>,
+    pub authority: Signer<'info>,
+}
 
-    safe_decrement!(user_stats.number_of_sub_accounts, 1);
+#[derive(Accounts)]
+pub struct AddRemoveLiquidity<'info> {
+    pub state: Box<Account<'info, State>>,
+    /// authority can be a scoped delegate, checked in the handler with get_user_delegate
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+}
 
-    let state = &mut ctx.accounts.state;
-    safe_decrement!(state.number_of_sub_accounts, 1);
+#[derive(Accounts)]
+pub struct RemoveLiquidityInExpiredMarket<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+}
 
-    Ok(())
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct TokenizePerpLpShares<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"tokenized_lp_pool".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub tokenized_lp_pool: AccountLoader<'info, TokenizedLpPool>,
+    /// checked in the handler against tokenized_lp_pool.user
+    #[account(mut)]
+    pub pool_user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"lp_share_mint".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub lp_share_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = lp_share_mint,
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = state.signer.eq(&drift_signer.key())
+    )]
+    /// CHECK: forced drift_signer
+    pub drift_signer: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(market_index: u16)]
+pub struct RedeemPerpLpShares<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"tokenized_lp_pool".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub tokenized_lp_pool: AccountLoader<'info, TokenizedLpPool>,
+    /// checked in the handler against tokenized_lp_pool.user
+    #[account(mut)]
+    pub pool_user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"lp_share_mint".as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub lp_share_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = lp_share_mint,
+        token::authority = authority,
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
 #[instruction(
     sub_account_id: u16,
 )]
-pub struct InitializeUser<'info> {
+pub struct UpdateUser<'info> {
     #[account(
-        init,
+        mut,
         seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
-        space = User::SIZE,
         bump,
-        payer = payer
     )]
     pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    sub_account_id: u16,
+    delegate: Pubkey,
+)]
+pub struct InitializeUserDelegate<'info> {
     #[account(
         mut,
-        has_one = authority
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
     )]
-    pub user_stats: AccountLoader<'info, UserStats>,
-    #[account(mut)]
-    pub state: Box<Account<'info, State>>,
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        init,
+        seeds = [b"user_delegate", user.key().as_ref(), delegate.as_ref()],
+        space = UserDelegate::SIZE,
+        bump,
+        payer = payer
+    )]
+    pub user_delegate: AccountLoader<'info, UserDelegate>,
     pub authority: Signer<'info>,
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -1861,47 +3418,63 @@ pub struct InitializeUser<'info> {
 }
 
 #[derive(Accounts)]
-pub struct InitializeUserStats<'info> {
+#[instruction(
+    sub_account_id: u16,
+)]
+pub struct UpdateUserDelegate<'info> {
     #[account(
-        init,
-        seeds = [b"user_stats", authority.key.as_ref()],
-        space = UserStats::SIZE,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
         bump,
-        payer = payer
     )]
-    pub user_stats: AccountLoader<'info, UserStats>,
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = user
+    )]
+    pub user_delegate: AccountLoader<'info, UserDelegate>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    sub_account_id: u16,
+)]
+pub struct DeleteUserDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = user,
+        close = authority
+    )]
+    pub user_delegate: AccountLoader<'info, UserDelegate>,
     #[account(mut)]
-    pub state: Box<Account<'info, State>>,
     pub authority: Signer<'info>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub rent: Sysvar<'info, Rent>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(
-    name: [u8; 32],
+    sub_account_id: u16,
+    market_index: u16,
 )]
-pub struct InitializeReferrerName<'info> {
+pub struct InitializePerpLpRange<'info> {
     #[account(
-        init,
-        seeds = [b"referrer_name", name.as_ref()],
-        space = ReferrerName::SIZE,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
         bump,
-        payer = payer
-    )]
-    pub referrer_name: AccountLoader<'info, ReferrerName>,
-    #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
     )]
     pub user: AccountLoader<'info, User>,
     #[account(
-        mut,
-        constraint = is_stats_for_user(&user, &user_stats)?
+        init,
+        seeds = [b"perp_lp_range", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        space = PerpLpRange::SIZE,
+        bump,
+        payer = payer
     )]
-    pub user_stats: AccountLoader<'info, UserStats>,
+    pub perp_lp_range: AccountLoader<'info, PerpLpRange>,
     pub authority: Signer<'info>,
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -1910,186 +3483,233 @@ pub struct InitializeReferrerName<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(market_index: u16,)]
-pub struct Deposit<'info> {
-    pub state: Box<Account<'info, State>>,
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct UpdatePerpLpRange<'info> {
     #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
     )]
     pub user: AccountLoader<'info, User>,
     #[account(
         mut,
-        constraint = is_stats_for_user(&user, &user_stats)?
+        seeds = [b"perp_lp_range", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
     )]
-    pub user_stats: AccountLoader<'info, UserStats>,
+    pub perp_lp_range: AccountLoader<'info, PerpLpRange>,
     pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct DeletePerpLpRange<'info> {
     #[account(
-        mut,
-        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
         bump,
     )]
-    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    pub user: AccountLoader<'info, User>,
     #[account(
         mut,
-        constraint = &spot_market_vault.mint.eq(&user_token_account.mint),
-        token::authority = authority
+        seeds = [b"perp_lp_range", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        close = authority
     )]
-    pub user_token_account: Box<Account<'info, TokenAccount>>,
-    pub token_program: Program<'info, Token>,
+    pub perp_lp_range: AccountLoader<'info, PerpLpRange>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-#[instruction(market_index: u16,)]
-pub struct Withdraw<'info> {
-    pub state: Box<Account<'info, State>>,
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct InitializePerpLpHedge<'info> {
     #[account(
-        mut,
-        has_one = authority,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
     )]
     pub user: AccountLoader<'info, User>,
     #[account(
-        mut,
-        has_one = authority
+        init,
+        seeds = [b"perp_lp_hedge", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        space = PerpLpHedge::SIZE,
+        bump,
+        payer = payer
     )]
-    pub user_stats: AccountLoader<'info, UserStats>,
+    pub perp_lp_hedge: AccountLoader<'info, PerpLpHedge>,
     pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct UpdatePerpLpHedge<'info> {
     #[account(
-        mut,
-        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
         bump,
     )]
-    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
-    #[account(
-        constraint = state.signer.eq(&drift_signer.key())
-    )]
-    /// CHECK: forced drift_signer
-    pub drift_signer: AccountInfo<'info>,
+    pub user: AccountLoader<'info, User>,
     #[account(
         mut,
-        constraint = &spot_market_vault.mint.eq(&user_token_account.mint)
+        seeds = [b"perp_lp_hedge", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
     )]
-    pub user_token_account: Box<Account<'info, TokenAccount>>,
-    pub token_program: Program<'info, Token>,
+    pub perp_lp_hedge: AccountLoader<'info, PerpLpHedge>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-#[instruction(market_index: u16,)]
-pub struct TransferDeposit<'info> {
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub from_user: AccountLoader<'info, User>,
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct DeletePerpLpHedge<'info> {
     #[account(
-        mut,
-        has_one = authority,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
     )]
-    pub to_user: AccountLoader<'info, User>,
+    pub user: AccountLoader<'info, User>,
     #[account(
         mut,
-        has_one = authority
-    )]
-    pub user_stats: AccountLoader<'info, UserStats>,
-    pub authority: Signer<'info>,
-    pub state: Box<Account<'info, State>>,
-    #[account(
-        seeds = [b"spot_market_vault".as_ref(), market_index.to_le_bytes().as_ref()],
+        seeds = [b"perp_lp_hedge", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
         bump,
+        close = authority
     )]
-    pub spot_market_vault: Box<Account<'info, TokenAccount>>,
+    pub perp_lp_hedge: AccountLoader<'info, PerpLpHedge>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct PlaceOrder<'info> {
-    pub state: Box<Account<'info, State>>,
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct InitializePerpLpStats<'info> {
     #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
     )]
     pub user: AccountLoader<'info, User>,
-    pub authority: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct CancelOrder<'info> {
-    pub state: Box<Account<'info, State>>,
     #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
+        init,
+        seeds = [b"perp_lp_stats", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        space = PerpLpStats::SIZE,
+        bump,
+        payer = payer
     )]
-    pub user: AccountLoader<'info, User>,
+    pub perp_lp_stats: AccountLoader<'info, PerpLpStats>,
     pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct PlaceAndTake<'info> {
-    pub state: Box<Account<'info, State>>,
+#[instruction(
+    sub_account_id: u16,
+    market_index: u16,
+)]
+pub struct DeletePerpLpStats<'info> {
     #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
     )]
     pub user: AccountLoader<'info, User>,
     #[account(
         mut,
-        constraint = is_stats_for_user(&user, &user_stats)?
+        seeds = [b"perp_lp_stats", user.key().as_ref(), market_index.to_le_bytes().as_ref()],
+        bump,
+        close = authority
     )]
-    pub user_stats: AccountLoader<'info, UserStats>,
+    pub perp_lp_stats: AccountLoader<'info, PerpLpStats>,
+    #[account(mut)]
     pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct PlaceAndMake<'info> {
-    pub state: Box<Account<'info, State>>,
+pub struct InitializeWithdrawPolicy<'info> {
     #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
+        init,
+        seeds = [b"withdraw_policy", authority.key.as_ref()],
+        space = WithdrawPolicy::SIZE,
+        bump,
+        payer = payer
     )]
-    pub user: AccountLoader<'info, User>,
+    pub withdraw_policy: AccountLoader<'info, WithdrawPolicy>,
     #[account(
         mut,
-        constraint = is_stats_for_user(&user, &user_stats)?
+        has_one = authority
     )]
     pub user_stats: AccountLoader<'info, UserStats>,
-    #[account(mut)]
-    pub taker: AccountLoader<'info, User>,
-    #[account(
-        mut,
-        constraint = is_stats_for_user(&taker, &taker_stats)?
-    )]
-    pub taker_stats: AccountLoader<'info, UserStats>,
     pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct AddRemoveLiquidity<'info> {
-    pub state: Box<Account<'info, State>>,
+pub struct UpdateWithdrawPolicy<'info> {
     #[account(
         mut,
-        constraint = can_sign_for_user(&user, &authority)?,
+        seeds = [b"withdraw_policy", authority.key.as_ref()],
+        bump,
+        has_one = authority
     )]
-    pub user: AccountLoader<'info, User>,
+    pub withdraw_policy: AccountLoader<'info, WithdrawPolicy>,
     pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct RemoveLiquidityInExpiredMarket<'info> {
-    pub state: Box<Account<'info, State>>,
+pub struct DeleteWithdrawPolicy<'info> {
+    #[account(
+        mut,
+        seeds = [b"withdraw_policy", authority.key.as_ref()],
+        bump,
+        has_one = authority,
+        close = authority
+    )]
+    pub withdraw_policy: AccountLoader<'info, WithdrawPolicy>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
     #[account(mut)]
-    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
 #[instruction(
     sub_account_id: u16,
 )]
-pub struct UpdateUser<'info> {
+pub struct UpdateUserSharedCollateralGroup<'info> {
+    pub state: Box<Account<'info, State>>,
     #[account(
         mut,
         seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
         bump,
     )]
     pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
     pub authority: Signer<'info>,
 }
 
@@ -2108,6 +3728,7 @@ pub struct DeleteUser<'info> {
     pub user_stats: AccountLoader<'info, UserStats>,
     #[account(mut)]
     pub state: Box<Account<'info, State>>,
+    #[account(mut)]
     pub authority: Signer<'info>,
 }
 
@@ -2115,10 +3736,8 @@ pub struct DeleteUser<'info> {
 #[instruction(in_market_index: u16, out_market_index: u16, )]
 pub struct Swap<'info> {
     pub state: Box<Account<'info, State>>,
-    #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
-    )]
+    /// authority can be a scoped delegate, checked in the handler with get_user_delegate
+    #[account(mut)]
     pub user: AccountLoader<'info, User>,
     #[account(
         mut,
@@ -2175,12 +3794,25 @@ pub fn handle_begin_swap(
     let clock = Clock::get()?;
     let now = clock.unix_timestamp;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::Swap)?;
+        user_delegate.validate_market(MarketType::Spot, in_market_index)?;
+        user_delegate.validate_market(MarketType::Spot, out_market_index)?;
+    }
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &get_writable_spot_market_set_from_many(vec![in_market_index, out_market_index]),
         clock.slot,
@@ -2188,7 +3820,7 @@ pub fn handle_begin_swap(
     )?;
 
     let mut user = load_mut!(&ctx.accounts.user)?;
-    let delegate_is_signer = user.delegate == ctx.accounts.authority.key();
+    let delegate_is_signer = user.authority != ctx.accounts.authority.key();
 
     validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
 
@@ -2203,7 +3835,7 @@ pub fn handle_begin_swap(
     let mut in_spot_market = spot_market_map.get_ref_mut(&in_market_index)?;
 
     validate!(
-        in_spot_market.fills_enabled(),
+        in_spot_market.fills_enabled()?,
         ErrorCode::MarketFillOrderPaused,
         "Swaps disabled for {}",
         in_market_index
@@ -2226,7 +3858,7 @@ pub fn handle_begin_swap(
     let mut out_spot_market = spot_market_map.get_ref_mut(&out_market_index)?;
 
     validate!(
-        out_spot_market.fills_enabled(),
+        out_spot_market.fills_enabled()?,
         ErrorCode::MarketFillOrderPaused,
         "Swaps disabled for {}",
         out_market_index
@@ -2395,12 +4027,28 @@ pub fn handle_end_swap(
     let slot = clock.slot;
     let now = clock.unix_timestamp;
 
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let user_delegate = get_user_delegate(
+        &ctx.accounts.user,
+        ctx.accounts.authority.key,
+        remaining_accounts_iter,
+    )?;
+
+    if let Some(user_delegate) = user_delegate {
+        user_delegate.validate_permission(DelegatePermission::Swap)?;
+        user_delegate.validate_market(MarketType::Spot, in_market_index)?;
+        user_delegate.validate_market(MarketType::Spot, out_market_index)?;
+    }
+
+    let withdraw_policy =
+        get_withdraw_policy(&*load!(ctx.accounts.user_stats)?, remaining_accounts_iter)?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
         mut oracle_map,
     } = load_maps(
-        &mut ctx.remaining_accounts.iter().peekable(),
+        remaining_accounts_iter,
         &MarketSet::new(),
         &get_writable_spot_market_set_from_many(vec![in_market_index, out_market_index]),
         clock.slot,
@@ -2531,6 +4179,26 @@ pub fn handle_end_swap(
         )?;
     }
 
+    if let Some(withdraw_policy) = withdraw_policy {
+        let net_outflow = calculate_swap_net_outflow(
+            amount_in,
+            in_spot_market.decimals,
+            in_oracle_price,
+            amount_out,
+            out_spot_market.decimals,
+            out_oracle_price,
+        )?;
+
+        if net_outflow > 0 {
+            load_mut!(withdraw_policy)?.validate_withdrawal(
+                &ctx.accounts.in_token_account.owner,
+                in_market_index,
+                net_outflow,
+                now,
+            )?;
+        }
+    }
+
     let fee = 0_u64; // no fee
     let amount_out_after_fee = amount_out.safe_sub(fee)?;
 
@@ -2571,6 +4239,9 @@ pub fn handle_end_swap(
 
     // update fees
     update_revenue_pool_balances(fee.cast()?, &SpotBalanceType::Deposit, &mut out_spot_market)?;
+    out_spot_market
+        .protocol_revenue
+        .record_revenue(ProtocolRevenueSource::TakerFee, fee.cast()?)?;
 
     let out_position_is_reduced = out_token_amount_before < 0
         && out_token_amount_before.unsigned_abs() >= amount_out.cast()?;
@@ -2614,6 +4285,7 @@ pub fn handle_end_swap(
 
     meets_withdraw_margin_requirement(
         &user,
+        &UserMap::empty(),
         &perp_market_map,
         &spot_market_map,
         &mut oracle_map,
@@ -217,6 +217,22 @@ pub mod drift {
         handle_remove_perp_lp_shares(ctx, shares_to_burn, market_index)
     }
 
+    pub fn tokenize_perp_lp_shares(
+        ctx: Context<TokenizePerpLpShares>,
+        market_index: u16,
+        n_shares: u64,
+    ) -> Result<()> {
+        handle_tokenize_perp_lp_shares(ctx, market_index, n_shares)
+    }
+
+    pub fn redeem_perp_lp_shares(
+        ctx: Context<RedeemPerpLpShares>,
+        market_index: u16,
+        n_tokens: u64,
+    ) -> Result<()> {
+        handle_redeem_perp_lp_shares(ctx, market_index, n_tokens)
+    }
+
     pub fn remove_perp_lp_shares_in_expiring_market(
         ctx: Context<RemoveLiquidityInExpiredMarket>,
         shares_to_burn: u64,
@@ -249,6 +265,14 @@ pub mod drift {
         handle_update_user_margin_trading_enabled(ctx, _sub_account_id, margin_trading_enabled)
     }
 
+    pub fn update_user_health_tracking(
+        ctx: Context<UpdateUser>,
+        _sub_account_id: u16,
+        health_tracking_enabled: bool,
+    ) -> Result<()> {
+        handle_update_user_health_tracking(ctx, _sub_account_id, health_tracking_enabled)
+    }
+
     pub fn update_user_delegate(
         ctx: Context<UpdateUser>,
         _sub_account_id: u16,
@@ -257,7 +281,193 @@ pub mod drift {
         handle_update_user_delegate(ctx, _sub_account_id, delegate)
     }
 
-    pub fn delete_user(ctx: Context<DeleteUser>) -> Result<()> {
+    pub fn initialize_user_delegate(
+        ctx: Context<InitializeUserDelegate>,
+        sub_account_id: u16,
+        delegate: Pubkey,
+        permissions: u8,
+        max_order_notional: u64,
+        perp_market_indexes: Vec<u16>,
+        spot_market_indexes: Vec<u16>,
+    ) -> Result<()> {
+        handle_initialize_user_delegate(
+            ctx,
+            sub_account_id,
+            delegate,
+            permissions,
+            max_order_notional,
+            perp_market_indexes,
+            spot_market_indexes,
+        )
+    }
+
+    pub fn update_user_delegate_permissions(
+        ctx: Context<UpdateUserDelegate>,
+        sub_account_id: u16,
+        permissions: u8,
+        max_order_notional: u64,
+        perp_market_indexes: Vec<u16>,
+        spot_market_indexes: Vec<u16>,
+    ) -> Result<()> {
+        handle_update_user_delegate_permissions(
+            ctx,
+            sub_account_id,
+            permissions,
+            max_order_notional,
+            perp_market_indexes,
+            spot_market_indexes,
+        )
+    }
+
+    pub fn delete_user_delegate(
+        ctx: Context<DeleteUserDelegate>,
+        sub_account_id: u16,
+    ) -> Result<()> {
+        handle_delete_user_delegate(ctx, sub_account_id)
+    }
+
+    pub fn initialize_perp_lp_range(
+        ctx: Context<InitializePerpLpRange>,
+        sub_account_id: u16,
+        market_index: u16,
+        lower_reserve_price: u64,
+        upper_reserve_price: u64,
+    ) -> Result<()> {
+        handle_initialize_perp_lp_range(
+            ctx,
+            sub_account_id,
+            market_index,
+            lower_reserve_price,
+            upper_reserve_price,
+        )
+    }
+
+    pub fn update_perp_lp_range(
+        ctx: Context<UpdatePerpLpRange>,
+        sub_account_id: u16,
+        market_index: u16,
+        lower_reserve_price: u64,
+        upper_reserve_price: u64,
+    ) -> Result<()> {
+        handle_update_perp_lp_range(
+            ctx,
+            sub_account_id,
+            market_index,
+            lower_reserve_price,
+            upper_reserve_price,
+        )
+    }
+
+    pub fn delete_perp_lp_range(
+        ctx: Context<DeletePerpLpRange>,
+        sub_account_id: u16,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_delete_perp_lp_range(ctx, sub_account_id, market_index)
+    }
+
+    pub fn initialize_perp_lp_hedge(
+        ctx: Context<InitializePerpLpHedge>,
+        sub_account_id: u16,
+        market_index: u16,
+        base_asset_amount_threshold: u64,
+    ) -> Result<()> {
+        handle_initialize_perp_lp_hedge(
+            ctx,
+            sub_account_id,
+            market_index,
+            base_asset_amount_threshold,
+        )
+    }
+
+    pub fn update_perp_lp_hedge(
+        ctx: Context<UpdatePerpLpHedge>,
+        sub_account_id: u16,
+        market_index: u16,
+        base_asset_amount_threshold: u64,
+    ) -> Result<()> {
+        handle_update_perp_lp_hedge(
+            ctx,
+            sub_account_id,
+            market_index,
+            base_asset_amount_threshold,
+        )
+    }
+
+    pub fn delete_perp_lp_hedge(
+        ctx: Context<DeletePerpLpHedge>,
+        sub_account_id: u16,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_delete_perp_lp_hedge(ctx, sub_account_id, market_index)
+    }
+
+    pub fn initialize_perp_lp_stats(
+        ctx: Context<InitializePerpLpStats>,
+        sub_account_id: u16,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_perp_lp_stats(ctx, sub_account_id, market_index)
+    }
+
+    pub fn delete_perp_lp_stats(
+        ctx: Context<DeletePerpLpStats>,
+        sub_account_id: u16,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_delete_perp_lp_stats(ctx, sub_account_id, market_index)
+    }
+
+    pub fn initialize_withdraw_policy(
+        ctx: Context<InitializeWithdrawPolicy>,
+        time_lock_period: i64,
+        allowed_destinations: Vec<Pubkey>,
+    ) -> Result<()> {
+        handle_initialize_withdraw_policy(ctx, time_lock_period, allowed_destinations)
+    }
+
+    pub fn update_withdraw_policy(
+        ctx: Context<UpdateWithdrawPolicy>,
+        time_lock_period: i64,
+        allowed_destinations: Vec<Pubkey>,
+    ) -> Result<()> {
+        handle_update_withdraw_policy(ctx, time_lock_period, allowed_destinations)
+    }
+
+    pub fn request_withdrawal(
+        ctx: Context<UpdateWithdrawPolicy>,
+        market_index: u16,
+        amount: u64,
+        destination: Pubkey,
+    ) -> Result<()> {
+        handle_request_withdrawal(ctx, market_index, amount, destination)
+    }
+
+    pub fn cancel_withdrawal_request(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+        handle_cancel_withdrawal_request(ctx)
+    }
+
+    pub fn request_withdraw_policy_removal(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+        handle_request_withdraw_policy_removal(ctx)
+    }
+
+    pub fn cancel_withdraw_policy_removal(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+        handle_cancel_withdraw_policy_removal(ctx)
+    }
+
+    pub fn delete_withdraw_policy(ctx: Context<DeleteWithdrawPolicy>) -> Result<()> {
+        handle_delete_withdraw_policy(ctx)
+    }
+
+    pub fn update_user_shared_collateral_group(
+        ctx: Context<UpdateUserSharedCollateralGroup>,
+        sub_account_id: u16,
+        in_shared_collateral_group: bool,
+    ) -> Result<()> {
+        handle_update_user_shared_collateral_group(ctx, sub_account_id, in_shared_collateral_group)
+    }
+
+    pub fn delete_user<'info>(ctx: Context<'_, '_, '_, 'info, DeleteUser<'info>>) -> Result<()> {
         handle_delete_user(ctx)
     }
 
@@ -284,6 +494,20 @@ pub mod drift {
         handle_fill_spot_order(ctx, order_id, fulfillment_type, maker_order_id)
     }
 
+    pub fn fill_spot_order_with_multiple_venues<'info>(
+        ctx: Context<'_, '_, '_, 'info, FillOrder<'info>>,
+        order_id: Option<u32>,
+        fulfillment_types: Vec<SpotFulfillmentType>,
+        maker_order_id: Option<u32>,
+    ) -> Result<()> {
+        handle_fill_spot_order_with_multiple_venues(
+            ctx,
+            order_id,
+            fulfillment_types,
+            maker_order_id,
+        )
+    }
+
     pub fn trigger_order(ctx: Context<TriggerOrder>, order_id: u32) -> Result<()> {
         handle_trigger_order(ctx, order_id)
     }
@@ -292,6 +516,20 @@ pub mod drift {
         handle_force_cancel_orders(ctx)
     }
 
+    pub fn force_place_deleverage_order(
+        ctx: Context<ForceCancelOrder>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_force_place_deleverage_order(ctx, market_index)
+    }
+
+    pub fn place_perp_lp_hedge_order(
+        ctx: Context<PlacePerpLpHedgeOrder>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_place_perp_lp_hedge_order(ctx, market_index)
+    }
+
     pub fn update_user_idle(ctx: Context<UpdateUserIdle>) -> Result<()> {
         handle_update_user_idle(ctx)
     }
@@ -312,6 +550,13 @@ pub mod drift {
         handle_settle_lp(ctx, market_index)
     }
 
+    pub fn update_perp_lp_range_status(
+        ctx: Context<UpdatePerpLpRangeStatus>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_update_perp_lp_range_status(ctx, market_index)
+    }
+
     pub fn settle_expired_market(ctx: Context<UpdateAMM>, market_index: u16) -> Result<()> {
         handle_settle_expired_market(ctx, market_index)
     }
@@ -330,6 +575,17 @@ pub mod drift {
         )
     }
 
+    pub fn liquidate_perp_lp(ctx: Context<LiquidatePerp>, market_index: u16) -> Result<()> {
+        handle_liquidate_perp_lp(ctx, market_index)
+    }
+
+    pub fn liquidate_perp_with_amm(
+        ctx: Context<LiquidatePerpWithAmm>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_liquidate_perp_with_amm(ctx, market_index)
+    }
+
     pub fn liquidate_spot(
         ctx: Context<LiquidateSpot>,
         asset_market_index: u16,
@@ -408,6 +664,20 @@ pub mod drift {
         handle_settle_revenue_to_insurance_fund(ctx, spot_market_index)
     }
 
+    pub fn settle_revenue_to_junior_insurance_fund(
+        ctx: Context<SettleRevenueToJuniorInsuranceFund>,
+        spot_market_index: u16,
+    ) -> Result<()> {
+        handle_settle_revenue_to_junior_insurance_fund(ctx, spot_market_index)
+    }
+
+    pub fn settle_revenue_to_perp_insurance_fund(
+        ctx: Context<SettleRevenueToPerpInsuranceFund>,
+        perp_market_index: u16,
+    ) -> Result<()> {
+        handle_settle_revenue_to_perp_insurance_fund(ctx, perp_market_index)
+    }
+
     pub fn update_funding_rate(ctx: Context<UpdateFundingRate>, market_index: u16) -> Result<()> {
         handle_update_funding_rate(ctx, market_index)
     }
@@ -422,6 +692,14 @@ pub mod drift {
         handle_update_amms(ctx, market_indexes)
     }
 
+    pub fn hedge_amm_inventory<'info>(
+        ctx: Context<'_, '_, '_, 'info, HedgeAmmInventory<'info>>,
+        perp_market_index: u16,
+        fulfillment_type: SpotFulfillmentType,
+    ) -> Result<()> {
+        handle_hedge_amm_inventory(ctx, perp_market_index, fulfillment_type)
+    }
+
     pub fn update_spot_market_expiry(
         ctx: Context<AdminUpdateSpotMarket>,
         expiry_ts: i64,
@@ -444,6 +722,13 @@ pub mod drift {
         handle_initialize_insurance_fund_stake(ctx, market_index)
     }
 
+    pub fn resize_insurance_fund_stake(
+        ctx: Context<ResizeInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_resize_insurance_fund_stake(ctx, market_index)
+    }
+
     pub fn add_insurance_fund_stake(
         ctx: Context<AddInsuranceFundStake>,
         market_index: u16,
@@ -474,6 +759,111 @@ pub mod drift {
         handle_remove_insurance_fund_stake(ctx, market_index)
     }
 
+    pub fn initialize_perp_insurance_fund_stake(
+        ctx: Context<InitializePerpInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_perp_insurance_fund_stake(ctx, market_index)
+    }
+
+    pub fn add_perp_insurance_fund_stake(
+        ctx: Context<AddPerpInsuranceFundStake>,
+        market_index: u16,
+        amount: u64,
+    ) -> Result<()> {
+        handle_add_perp_insurance_fund_stake(ctx, market_index, amount)
+    }
+
+    pub fn request_remove_perp_insurance_fund_stake(
+        ctx: Context<RequestRemovePerpInsuranceFundStake>,
+        market_index: u16,
+        amount: u64,
+    ) -> Result<()> {
+        handle_request_remove_perp_insurance_fund_stake(ctx, market_index, amount)
+    }
+
+    pub fn cancel_request_remove_perp_insurance_fund_stake(
+        ctx: Context<RequestRemovePerpInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_cancel_request_remove_perp_insurance_fund_stake(ctx, market_index)
+    }
+
+    pub fn remove_perp_insurance_fund_stake(
+        ctx: Context<RemovePerpInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_remove_perp_insurance_fund_stake(ctx, market_index)
+    }
+
+    pub fn initialize_junior_insurance_fund_stake(
+        ctx: Context<InitializeJuniorInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_junior_insurance_fund_stake(ctx, market_index)
+    }
+
+    pub fn add_junior_insurance_fund_stake(
+        ctx: Context<AddJuniorInsuranceFundStake>,
+        market_index: u16,
+        amount: u64,
+    ) -> Result<()> {
+        handle_add_junior_insurance_fund_stake(ctx, market_index, amount)
+    }
+
+    pub fn request_remove_junior_insurance_fund_stake(
+        ctx: Context<RequestRemoveJuniorInsuranceFundStake>,
+        market_index: u16,
+        amount: u64,
+    ) -> Result<()> {
+        handle_request_remove_junior_insurance_fund_stake(ctx, market_index, amount)
+    }
+
+    pub fn cancel_request_remove_junior_insurance_fund_stake(
+        ctx: Context<RequestRemoveJuniorInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_cancel_request_remove_junior_insurance_fund_stake(ctx, market_index)
+    }
+
+    pub fn remove_junior_insurance_fund_stake(
+        ctx: Context<RemoveJuniorInsuranceFundStake>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_remove_junior_insurance_fund_stake(ctx, market_index)
+    }
+
+    pub fn update_insurance_fund_stake_claim_revenue(
+        ctx: Context<UpdateInsuranceFundStakeClaimRevenue>,
+        market_index: u16,
+        claim_revenue: bool,
+    ) -> Result<()> {
+        handle_update_insurance_fund_stake_claim_revenue(ctx, market_index, claim_revenue)
+    }
+
+    pub fn claim_insurance_fund_revenue(
+        ctx: Context<ClaimInsuranceFundRevenue>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_claim_insurance_fund_revenue(ctx, market_index)
+    }
+
+    pub fn tokenize_insurance_fund_stake(
+        ctx: Context<TokenizeInsuranceFundStake>,
+        market_index: u16,
+        n_shares: u128,
+    ) -> Result<()> {
+        handle_tokenize_insurance_fund_stake(ctx, market_index, n_shares)
+    }
+
+    pub fn redeem_insurance_fund_stake_tokens(
+        ctx: Context<RedeemInsuranceFundStakeTokens>,
+        market_index: u16,
+        amount: u64,
+    ) -> Result<()> {
+        handle_redeem_insurance_fund_stake_tokens(ctx, market_index, amount)
+    }
+
     // Admin Instructions
 
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
@@ -540,6 +930,67 @@ pub mod drift {
         handle_update_phoenix_fulfillment_config_status(ctx, status)
     }
 
+    pub fn initialize_constant_product_pool_fulfillment_config(
+        ctx: Context<InitializeConstantProductPoolFulfillmentConfig>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_constant_product_pool_fulfillment_config(ctx, market_index)
+    }
+
+    pub fn update_constant_product_pool_fulfillment_config_status(
+        ctx: Context<UpdateConstantProductPoolFulfillmentConfig>,
+        status: SpotFulfillmentConfigStatus,
+    ) -> Result<()> {
+        handle_update_constant_product_pool_fulfillment_config_status(ctx, status)
+    }
+
+    pub fn initialize_amm_hedge_config(
+        ctx: Context<InitializeAmmHedgeConfig>,
+        perp_market_index: u16,
+        spot_market_index: u16,
+        hedge_ratio: u32,
+        max_slippage: u32,
+        min_hedge_base_asset_amount: u64,
+        max_hedge_base_asset_amount: u64,
+        min_hedge_interval: u32,
+    ) -> Result<()> {
+        handle_initialize_amm_hedge_config(
+            ctx,
+            perp_market_index,
+            spot_market_index,
+            hedge_ratio,
+            max_slippage,
+            min_hedge_base_asset_amount,
+            max_hedge_base_asset_amount,
+            min_hedge_interval,
+        )
+    }
+
+    pub fn update_amm_hedge_config_params(
+        ctx: Context<UpdateAmmHedgeConfig>,
+        hedge_ratio: u32,
+        max_slippage: u32,
+        min_hedge_base_asset_amount: u64,
+        max_hedge_base_asset_amount: u64,
+        min_hedge_interval: u32,
+    ) -> Result<()> {
+        handle_update_amm_hedge_config_params(
+            ctx,
+            hedge_ratio,
+            max_slippage,
+            min_hedge_base_asset_amount,
+            max_hedge_base_asset_amount,
+            min_hedge_interval,
+        )
+    }
+
+    pub fn update_amm_hedge_config_status(
+        ctx: Context<UpdateAmmHedgeConfig>,
+        status: SpotFulfillmentConfigStatus,
+    ) -> Result<()> {
+        handle_update_amm_hedge_config_status(ctx, status)
+    }
+
     pub fn update_serum_vault(ctx: Context<UpdateSerumVault>) -> Result<()> {
         handle_update_serum_vault(ctx)
     }
@@ -687,6 +1138,33 @@ pub mod drift {
         handle_update_spot_market_if_factor(ctx, spot_market_index, user_if_factor, total_if_factor)
     }
 
+    pub fn resize_spot_market(ctx: Context<ResizeSpotMarket>, market_index: u16) -> Result<()> {
+        handle_resize_spot_market(ctx, market_index)
+    }
+
+    pub fn initialize_junior_insurance_fund(
+        ctx: Context<InitializeJuniorInsuranceFund>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_junior_insurance_fund(ctx, market_index)
+    }
+
+    pub fn update_junior_insurance_fund(
+        ctx: Context<AdminUpdateSpotMarket>,
+        unstaking_period: i64,
+        revenue_settle_period: i64,
+        user_if_factor: u32,
+        total_if_factor: u32,
+    ) -> Result<()> {
+        handle_update_junior_insurance_fund(
+            ctx,
+            unstaking_period,
+            revenue_settle_period,
+            user_if_factor,
+            total_if_factor,
+        )
+    }
+
     pub fn update_spot_market_revenue_settle_period(
         ctx: Context<AdminUpdateSpotMarket>,
         revenue_settle_period: i64,
@@ -694,6 +1172,22 @@ pub mod drift {
         handle_update_spot_market_revenue_settle_period(ctx, revenue_settle_period)
     }
 
+    pub fn update_protocol_revenue_withdraw_limit(
+        ctx: Context<AdminUpdateSpotMarket>,
+        max_withdraw_per_period: u64,
+        withdraw_period: i64,
+    ) -> Result<()> {
+        handle_update_protocol_revenue_withdraw_limit(ctx, max_withdraw_per_period, withdraw_period)
+    }
+
+    pub fn withdraw_protocol_revenue(
+        ctx: Context<WithdrawProtocolRevenue>,
+        market_index: u16,
+        amount: u64,
+    ) -> Result<()> {
+        handle_withdraw_protocol_revenue(ctx, market_index, amount)
+    }
+
     pub fn update_spot_market_status(
         ctx: Context<AdminUpdateSpotMarket>,
         status: MarketStatus,
@@ -701,6 +1195,13 @@ pub mod drift {
         handle_update_spot_market_status(ctx, status)
     }
 
+    pub fn update_spot_market_paused_operations(
+        ctx: Context<AdminUpdateSpotMarket>,
+        paused_operations: u8,
+    ) -> Result<()> {
+        handle_update_spot_market_paused_operations(ctx, paused_operations)
+    }
+
     pub fn update_spot_market_asset_tier(
         ctx: Context<AdminUpdateSpotMarket>,
         asset_tier: AssetTier,
@@ -791,6 +1292,13 @@ pub mod drift {
         handle_update_perp_market_status(ctx, status)
     }
 
+    pub fn update_perp_market_paused_operations(
+        ctx: Context<AdminUpdatePerpMarket>,
+        paused_operations: u8,
+    ) -> Result<()> {
+        handle_update_perp_market_paused_operations(ctx, paused_operations)
+    }
+
     pub fn update_perp_market_contract_tier(
         ctx: Context<AdminUpdatePerpMarket>,
         contract_tier: ContractTier,
@@ -798,6 +1306,75 @@ pub mod drift {
         handle_update_perp_market_contract_tier(ctx, contract_tier)
     }
 
+    pub fn update_perp_market_funding_params(
+        ctx: Context<AdminUpdatePerpMarket>,
+        funding_period: i64,
+        funding_max_price_spread: u32,
+        funding_interest_rate: i32,
+        funding_premium_dampener: u32,
+    ) -> Result<()> {
+        handle_update_perp_market_funding_params(
+            ctx,
+            funding_period,
+            funding_max_price_spread,
+            funding_interest_rate,
+            funding_premium_dampener,
+        )
+    }
+
+    pub fn update_perp_market_continuous_funding(
+        ctx: Context<RepegCurve>,
+        continuous_funding: bool,
+    ) -> Result<()> {
+        handle_update_perp_market_continuous_funding(ctx, continuous_funding)
+    }
+
+    pub fn update_perp_market_amm_liquidation_grace_period(
+        ctx: Context<AdminUpdatePerpMarket>,
+        amm_liquidation_grace_period: u32,
+    ) -> Result<()> {
+        handle_update_perp_market_amm_liquidation_grace_period(ctx, amm_liquidation_grace_period)
+    }
+
+    pub fn resize_perp_market(ctx: Context<ResizePerpMarket>, market_index: u16) -> Result<()> {
+        handle_resize_perp_market(ctx, market_index)
+    }
+
+    pub fn initialize_perp_market_insurance_fund(
+        ctx: Context<InitializePerpMarketInsuranceFund>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_perp_market_insurance_fund(ctx, market_index)
+    }
+
+    pub fn update_perp_market_insurance_fund(
+        ctx: Context<AdminUpdatePerpMarket>,
+        unstaking_period: i64,
+        revenue_settle_period: i64,
+        user_if_factor: u32,
+        total_if_factor: u32,
+    ) -> Result<()> {
+        handle_update_perp_market_insurance_fund(
+            ctx,
+            unstaking_period,
+            revenue_settle_period,
+            user_if_factor,
+            total_if_factor,
+        )
+    }
+
+    pub fn update_perp_market_circuit_breaker(
+        ctx: Context<AdminUpdatePerpMarket>,
+        circuit_breaker_threshold: u32,
+        circuit_breaker_cooldown_period: u32,
+    ) -> Result<()> {
+        handle_update_perp_market_circuit_breaker(
+            ctx,
+            circuit_breaker_threshold,
+            circuit_breaker_cooldown_period,
+        )
+    }
+
     pub fn update_perp_market_imf_factor(
         ctx: Context<AdminUpdatePerpMarket>,
         imf_factor: u32,
@@ -877,6 +1454,35 @@ pub mod drift {
         handle_update_liquidation_duration(ctx, liquidation_duration)
     }
 
+    pub fn update_liquidation_auction_duration(
+        ctx: Context<AdminUpdateState>,
+        liquidation_auction_duration: u16,
+    ) -> Result<()> {
+        handle_update_liquidation_auction_duration(ctx, liquidation_auction_duration)
+    }
+
+    pub fn update_auto_deleverage_contract_tier(
+        ctx: Context<AdminUpdateState>,
+        contract_tier: ContractTier,
+        auto_deleverage: bool,
+    ) -> Result<()> {
+        handle_update_auto_deleverage_contract_tier(ctx, contract_tier, auto_deleverage)
+    }
+
+    pub fn update_auto_deleverage_score_threshold(
+        ctx: Context<AdminUpdateState>,
+        auto_deleverage_score_threshold: u32,
+    ) -> Result<()> {
+        handle_update_auto_deleverage_score_threshold(ctx, auto_deleverage_score_threshold)
+    }
+
+    pub fn update_health_bands(
+        ctx: Context<AdminUpdateState>,
+        health_bands: [u8; 4],
+    ) -> Result<()> {
+        handle_update_health_bands(ctx, health_bands)
+    }
+
     pub fn update_oracle_guard_rails(
         ctx: Context<AdminUpdateState>,
         oracle_guard_rails: OracleGuardRails,
@@ -1009,6 +1615,20 @@ pub mod drift {
     ) -> Result<()> {
         handle_admin_remove_insurance_fund_stake(ctx, market_index, amount)
     }
+
+    pub fn initialize_tokenized_lp_pool(
+        ctx: Context<InitializeTokenizedLpPool>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_tokenized_lp_pool(ctx, market_index)
+    }
+
+    pub fn initialize_insurance_fund_share_mint(
+        ctx: Context<InitializeInsuranceFundShareMint>,
+        market_index: u16,
+    ) -> Result<()> {
+        handle_initialize_insurance_fund_share_mint(ctx, market_index)
+    }
 }
 
 #[cfg(not(feature = "no-entrypoint"))]
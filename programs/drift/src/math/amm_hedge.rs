@@ -0,0 +1,131 @@
+use crate::controller::position::PositionDirection;
+use crate::error::DriftResult;
+use crate::math::casting::Cast;
+use crate::math::constants::{BASE_PRECISION, PERCENTAGE_PRECISION, PERCENTAGE_PRECISION_U64};
+use crate::math::orders::standardize_base_asset_amount;
+use crate::math::safe_math::SafeMath;
+
+#[cfg(test)]
+mod tests;
+
+/// Calculates the amount of spot tokens the amm should hold or borrow to offset its perp inventory
+///
+/// When users are net long, the amm is short and buys spot to offset it. When users are net short,
+/// the amm is long and borrows spot to sell.
+///
+/// Returns the target, positive to hold and negative to borrow, precision: token mint precision
+pub fn calculate_target_hedge_base_asset_amount(
+    base_asset_amount_with_amm: i128,
+    hedge_ratio: u32,
+    max_hedge_base_asset_amount: u64,
+    spot_precision: u64,
+) -> DriftResult<i64> {
+    let target = base_asset_amount_with_amm
+        .unsigned_abs()
+        .safe_mul(hedge_ratio.cast()?)?
+        .safe_div(PERCENTAGE_PRECISION)?
+        .safe_mul(spot_precision.cast()?)?
+        .safe_div(BASE_PRECISION)?
+        .min(max_hedge_base_asset_amount.cast()?)
+        .cast::<i64>()?;
+
+    if base_asset_amount_with_amm < 0 {
+        Ok(-target)
+    } else {
+        Ok(target)
+    }
+}
+
+/// Calculates the trade needed to move the hedge from its current amount to the target
+///
+/// A hedge that has to flip sides is closed first and reopened on the next call
+///
+/// Returns None if the change is smaller than min_hedge_base_asset_amount or the order step size
+pub fn calculate_hedge_order(
+    target_hedge_base_asset_amount: i64,
+    hedge_base_asset_amount: i64,
+    min_hedge_base_asset_amount: u64,
+    order_step_size: u64,
+) -> DriftResult<Option<(PositionDirection, u64)>> {
+    let target_hedge_base_asset_amount = if hedge_base_asset_amount > 0 {
+        target_hedge_base_asset_amount.max(0)
+    } else if hedge_base_asset_amount < 0 {
+        target_hedge_base_asset_amount.min(0)
+    } else {
+        target_hedge_base_asset_amount
+    };
+
+    let delta = target_hedge_base_asset_amount.safe_sub(hedge_base_asset_amount)?;
+
+    let direction = if delta > 0 {
+        PositionDirection::Long
+    } else {
+        PositionDirection::Short
+    };
+    let base_asset_amount = delta.unsigned_abs();
+
+    if base_asset_amount == 0 || base_asset_amount < min_hedge_base_asset_amount {
+        return Ok(None);
+    }
+
+    let base_asset_amount = standardize_base_asset_amount(base_asset_amount, order_step_size)?;
+
+    if base_asset_amount == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some((direction, base_asset_amount)))
+}
+
+/// Calculates the worst price the hedge can trade at, in PRICE_PRECISION
+pub fn calculate_hedge_limit_price(
+    direction: PositionDirection,
+    oracle_price: i64,
+    max_slippage: u32,
+) -> DriftResult<u64> {
+    let oracle_price = oracle_price.unsigned_abs();
+    let slippage = oracle_price
+        .safe_mul(max_slippage.cast()?)?
+        .safe_div(PERCENTAGE_PRECISION_U64)?;
+
+    match direction {
+        PositionDirection::Long => oracle_price.safe_add(slippage),
+        PositionDirection::Short => oracle_price.safe_sub(slippage),
+    }
+}
+
+/// Calculates the entry amount for the part of the hedge being closed and the pnl from closing it
+///
+/// For a hedge holding tokens, the quote is received from selling them. For a hedge borrowing tokens,
+/// the quote is paid to buy them back.
+///
+/// Returns (entry amount removed, realized pnl), precision: QUOTE_PRECISION
+pub fn calculate_hedge_pnl(
+    quote_entry_amount: u64,
+    hedge_base_asset_amount: i64,
+    base_asset_amount_closed: u64,
+    quote_asset_amount: u64,
+) -> DriftResult<(u64, i64)> {
+    let hedge_base_asset_amount_abs = hedge_base_asset_amount.unsigned_abs();
+    let quote_entry_amount_removed = if base_asset_amount_closed >= hedge_base_asset_amount_abs {
+        quote_entry_amount
+    } else {
+        quote_entry_amount
+            .cast::<u128>()?
+            .safe_mul(base_asset_amount_closed.cast()?)?
+            .safe_div(hedge_base_asset_amount_abs.cast()?)?
+            .cast::<u64>()?
+    };
+
+    let pnl = if hedge_base_asset_amount > 0 {
+        quote_asset_amount
+            .cast::<i64>()?
+            .safe_sub(quote_entry_amount_removed.cast()?)?
+    } else {
+        quote_entry_amount_removed
+            .cast::<i64>()?
+            .safe_sub(quote_asset_amount.cast()?)?
+    };
+
+    Ok((quote_entry_amount_removed, pnl))
+}
@@ -0,0 +1,238 @@
+mod calculate_target_hedge_base_asset_amount {
+    use crate::math::amm_hedge::calculate_target_hedge_base_asset_amount;
+    use crate::math::constants::{
+        BASE_PRECISION_I128, LAMPORTS_PER_SOL_I64, LAMPORTS_PER_SOL_U64, PERCENTAGE_PRECISION,
+    };
+
+    #[test]
+    fn users_net_short() {
+        let target = calculate_target_hedge_base_asset_amount(
+            -100 * BASE_PRECISION_I128,
+            PERCENTAGE_PRECISION as u32,
+            u64::MAX,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+
+        assert_eq!(target, -100 * LAMPORTS_PER_SOL_I64);
+
+        let target = calculate_target_hedge_base_asset_amount(
+            -100 * BASE_PRECISION_I128,
+            PERCENTAGE_PRECISION as u32,
+            10 * LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+
+        assert_eq!(target, -10 * LAMPORTS_PER_SOL_I64);
+    }
+
+    #[test]
+    fn users_net_long() {
+        let target = calculate_target_hedge_base_asset_amount(
+            100 * BASE_PRECISION_I128,
+            PERCENTAGE_PRECISION as u32 / 2,
+            u64::MAX,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+
+        assert_eq!(target, 50 * LAMPORTS_PER_SOL_I64);
+
+        // 6 decimal mint
+        let target = calculate_target_hedge_base_asset_amount(
+            100 * BASE_PRECISION_I128,
+            PERCENTAGE_PRECISION as u32 / 2,
+            u64::MAX,
+            1_000_000,
+        )
+        .unwrap();
+
+        assert_eq!(target, 50 * 1_000_000);
+    }
+
+    #[test]
+    fn max_hedge() {
+        let target = calculate_target_hedge_base_asset_amount(
+            100 * BASE_PRECISION_I128,
+            PERCENTAGE_PRECISION as u32,
+            10 * LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+
+        assert_eq!(target, 10 * LAMPORTS_PER_SOL_I64);
+    }
+}
+
+mod calculate_hedge_order {
+    use crate::controller::position::PositionDirection;
+    use crate::math::amm_hedge::calculate_hedge_order;
+    use crate::math::constants::{LAMPORTS_PER_SOL_I64, LAMPORTS_PER_SOL_U64};
+
+    #[test]
+    fn below_min_hedge() {
+        let order = calculate_hedge_order(
+            10 * LAMPORTS_PER_SOL_I64,
+            9 * LAMPORTS_PER_SOL_I64,
+            2 * LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64 / 10,
+        )
+        .unwrap();
+
+        assert_eq!(order, None);
+    }
+
+    #[test]
+    fn increase_hedge() {
+        let order = calculate_hedge_order(
+            10 * LAMPORTS_PER_SOL_I64 + 1,
+            5 * LAMPORTS_PER_SOL_I64,
+            LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64 / 10,
+        )
+        .unwrap();
+
+        assert_eq!(
+            order,
+            Some((PositionDirection::Long, 5 * LAMPORTS_PER_SOL_U64))
+        );
+    }
+
+    #[test]
+    fn decrease_hedge() {
+        let order = calculate_hedge_order(
+            0,
+            5 * LAMPORTS_PER_SOL_I64,
+            LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64 / 10,
+        )
+        .unwrap();
+
+        assert_eq!(
+            order,
+            Some((PositionDirection::Short, 5 * LAMPORTS_PER_SOL_U64))
+        );
+    }
+
+    #[test]
+    fn increase_short_hedge() {
+        let order = calculate_hedge_order(
+            -10 * LAMPORTS_PER_SOL_I64,
+            -5 * LAMPORTS_PER_SOL_I64,
+            LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64 / 10,
+        )
+        .unwrap();
+
+        assert_eq!(
+            order,
+            Some((PositionDirection::Short, 5 * LAMPORTS_PER_SOL_U64))
+        );
+    }
+
+    #[test]
+    fn flip_hedge_closes_first() {
+        let order = calculate_hedge_order(
+            -10 * LAMPORTS_PER_SOL_I64,
+            5 * LAMPORTS_PER_SOL_I64,
+            LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64 / 10,
+        )
+        .unwrap();
+
+        assert_eq!(
+            order,
+            Some((PositionDirection::Short, 5 * LAMPORTS_PER_SOL_U64))
+        );
+
+        let order = calculate_hedge_order(
+            10 * LAMPORTS_PER_SOL_I64,
+            -5 * LAMPORTS_PER_SOL_I64,
+            LAMPORTS_PER_SOL_U64,
+            LAMPORTS_PER_SOL_U64 / 10,
+        )
+        .unwrap();
+
+        assert_eq!(
+            order,
+            Some((PositionDirection::Long, 5 * LAMPORTS_PER_SOL_U64))
+        );
+    }
+}
+
+mod calculate_hedge_limit_price {
+    use crate::controller::position::PositionDirection;
+    use crate::math::amm_hedge::calculate_hedge_limit_price;
+    use crate::math::constants::{PERCENTAGE_PRECISION, PRICE_PRECISION_I64, PRICE_PRECISION_U64};
+
+    #[test]
+    fn one_percent_slippage() {
+        let max_slippage = PERCENTAGE_PRECISION as u32 / 100;
+
+        let long_price = calculate_hedge_limit_price(
+            PositionDirection::Long,
+            100 * PRICE_PRECISION_I64,
+            max_slippage,
+        )
+        .unwrap();
+        assert_eq!(long_price, 101 * PRICE_PRECISION_U64);
+
+        let short_price = calculate_hedge_limit_price(
+            PositionDirection::Short,
+            100 * PRICE_PRECISION_I64,
+            max_slippage,
+        )
+        .unwrap();
+        assert_eq!(short_price, 99 * PRICE_PRECISION_U64);
+    }
+}
+
+mod calculate_hedge_pnl {
+    use crate::math::amm_hedge::calculate_hedge_pnl;
+    use crate::math::constants::{
+        LAMPORTS_PER_SOL_I64, LAMPORTS_PER_SOL_U64, QUOTE_PRECISION_I64, QUOTE_PRECISION_U64,
+    };
+
+    #[test]
+    fn partial_close_with_profit() {
+        let (entry_removed, pnl) = calculate_hedge_pnl(
+            1000 * QUOTE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_I64,
+            5 * LAMPORTS_PER_SOL_U64,
+            600 * QUOTE_PRECISION_U64,
+        )
+        .unwrap();
+
+        assert_eq!(entry_removed, 500 * QUOTE_PRECISION_U64);
+        assert_eq!(pnl, 100 * QUOTE_PRECISION_I64);
+    }
+
+    #[test]
+    fn full_close_with_loss() {
+        let (entry_removed, pnl) = calculate_hedge_pnl(
+            1000 * QUOTE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_I64,
+            10 * LAMPORTS_PER_SOL_U64,
+            900 * QUOTE_PRECISION_U64,
+        )
+        .unwrap();
+
+        assert_eq!(entry_removed, 1000 * QUOTE_PRECISION_U64);
+        assert_eq!(pnl, -100 * QUOTE_PRECISION_I64);
+    }
+
+    #[test]
+    fn short_hedge_close_with_profit() {
+        let (entry_removed, pnl) = calculate_hedge_pnl(
+            1000 * QUOTE_PRECISION_U64,
+            -10 * LAMPORTS_PER_SOL_I64,
+            5 * LAMPORTS_PER_SOL_U64,
+            450 * QUOTE_PRECISION_U64,
+        )
+        .unwrap();
+
+        assert_eq!(entry_removed, 500 * QUOTE_PRECISION_U64);
+        assert_eq!(pnl, 50 * QUOTE_PRECISION_I64);
+    }
+}
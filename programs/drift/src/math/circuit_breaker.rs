@@ -0,0 +1,28 @@
+use crate::error::DriftResult;
+use crate::math::casting::Cast;
+use crate::math::constants::PERCENTAGE_PRECISION_U64;
+use crate::math::safe_math::SafeMath;
+
+#[cfg(test)]
+mod tests;
+
+/// Checks whether a price has moved more than the threshold away from its 5min twap
+///
+/// Returns false if there is no twap yet
+pub fn is_price_move_breaching_circuit_breaker(
+    price: i64,
+    twap_5min: i64,
+    threshold: u32,
+) -> DriftResult<bool> {
+    if twap_5min <= 0 {
+        return Ok(false);
+    }
+
+    let percent_diff = price
+        .safe_sub(twap_5min)?
+        .unsigned_abs()
+        .safe_mul(PERCENTAGE_PRECISION_U64)?
+        .safe_div(twap_5min.unsigned_abs())?;
+
+    Ok(percent_diff > threshold.cast()?)
+}
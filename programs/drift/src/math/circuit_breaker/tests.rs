@@ -0,0 +1,62 @@
+mod is_price_move_breaching_circuit_breaker {
+    use crate::math::circuit_breaker::is_price_move_breaching_circuit_breaker;
+    use crate::math::constants::{PERCENTAGE_PRECISION, PRICE_PRECISION_I64};
+
+    #[test]
+    fn within_threshold() {
+        let threshold = PERCENTAGE_PRECISION as u32 / 10; // 10%
+
+        let breached = is_price_move_breaching_circuit_breaker(
+            109 * PRICE_PRECISION_I64,
+            100 * PRICE_PRECISION_I64,
+            threshold,
+        )
+        .unwrap();
+        assert!(!breached);
+
+        let breached = is_price_move_breaching_circuit_breaker(
+            91 * PRICE_PRECISION_I64,
+            100 * PRICE_PRECISION_I64,
+            threshold,
+        )
+        .unwrap();
+        assert!(!breached);
+
+        // exactly at threshold
+        let breached = is_price_move_breaching_circuit_breaker(
+            110 * PRICE_PRECISION_I64,
+            100 * PRICE_PRECISION_I64,
+            threshold,
+        )
+        .unwrap();
+        assert!(!breached);
+    }
+
+    #[test]
+    fn breaches_threshold() {
+        let threshold = PERCENTAGE_PRECISION as u32 / 10; // 10%
+
+        let breached = is_price_move_breaching_circuit_breaker(
+            111 * PRICE_PRECISION_I64,
+            100 * PRICE_PRECISION_I64,
+            threshold,
+        )
+        .unwrap();
+        assert!(breached);
+
+        let breached = is_price_move_breaching_circuit_breaker(
+            89 * PRICE_PRECISION_I64,
+            100 * PRICE_PRECISION_I64,
+            threshold,
+        )
+        .unwrap();
+        assert!(breached);
+    }
+
+    #[test]
+    fn no_twap() {
+        let breached =
+            is_price_move_breaching_circuit_breaker(100 * PRICE_PRECISION_I64, 0, 1).unwrap();
+        assert!(!breached);
+    }
+}
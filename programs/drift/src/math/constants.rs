@@ -60,6 +60,7 @@ pub const BID_ASK_SPREAD_PRECISION_I128: i128 = BID_ASK_SPREAD_PRECISION as i128
 
 pub const CONCENTRATION_PRECISION: u128 = PERCENTAGE_PRECISION; // expo 6
 pub const IF_FACTOR_PRECISION: u128 = PERCENTAGE_PRECISION; // expo 6
+pub const IF_REVENUE_PER_SHARE_PRECISION: u128 = 1_000_000_000_000; // expo = -12
 
 pub const SPOT_UTILIZATION_PRECISION: u128 = PERCENTAGE_PRECISION; // expo = -6
 pub const SPOT_UTILIZATION_PRECISION_U32: u32 = PERCENTAGE_PRECISION as u32; // expo = -6
@@ -121,6 +122,7 @@ pub const THIRTEEN_DAY: i64 = TWENTY_FOUR_HOUR * 13; // IF unstake default
 pub const EPOCH_DURATION: i64 = TWENTY_FOUR_HOUR * 28;
 pub const THIRTY_DAY: i64 = TWENTY_FOUR_HOUR * 30;
 pub const THIRTY_DAY_I128: i128 = (TWENTY_FOUR_HOUR * 30) as i128;
+pub const LP_HEDGE_MIN_INTERVAL: i64 = 60; // min time between keeper lp hedge orders
 pub const ONE_YEAR: u128 = 31536000;
 
 // QUOTE AMOUNTS
@@ -173,6 +175,7 @@ pub const MAX_CONCENTRATION_COEFFICIENT: u128 = 1_414_200;
 pub const MAX_LIQUIDATION_SLIPPAGE: i128 = 10_000; // expo = -2
 pub const MAX_LIQUIDATION_SLIPPAGE_U128: u128 = 10_000; // expo = -2
 pub const MAX_MARK_TWAP_DIVERGENCE: u128 = 500_000; // expo = -3
+pub const MAX_AMM_HEDGE_SLIPPAGE: u32 = PERCENTAGE_PRECISION as u32 / 200; // 50 bps
 
 pub const MAX_MARGIN_RATIO: u32 = MARGIN_PRECISION as u32; // 1x leverage
 pub const MIN_MARGIN_RATIO: u32 = MARGIN_PRECISION as u32 / 50; // 50x leverage
@@ -88,19 +88,71 @@ pub fn determine_perp_fulfillment_methods(
     Ok(fulfillment_methods)
 }
 
+/// Walks the maker and external market prices from best to worst for the taker.
+///
+/// Each external market is first filled up to the price of the next best venue, so that liquidity is taken
+/// where it is cheapest. Afterwards the external markets are swept again up to the taker's limit price.
+///
+/// `external_market_prices` holds the best price (ask for longs, bid for shorts) of each fulfillment params,
+/// None if the fulfillment params is not external or has no liquidity on that side
 pub fn determine_spot_fulfillment_methods(
     taker_order: &Order,
-    maker_available: bool,
-    external_fulfillment_params_available: bool,
+    taker_price: Option<u64>,
+    maker_price: Option<u64>,
+    external_market_prices: &[Option<u64>],
 ) -> DriftResult<Vec<SpotFulfillmentMethod>> {
-    let mut fulfillment_methods = vec![];
+    let maker_direction = taker_order.direction.opposite();
+
+    let taker_crosses = |price: u64| match taker_price {
+        Some(taker_price) => do_orders_cross(maker_direction, price, taker_price),
+        None => true,
+    };
+
+    // venue index (None for the maker) and its best price
+    let mut venues: Vec<(Option<usize>, u64)> =
+        Vec::with_capacity(external_market_prices.len() + 1);
+
+    if let Some(maker_price) = maker_price {
+        if taker_crosses(maker_price) {
+            venues.push((None, maker_price));
+        }
+    }
+
+    if !taker_order.post_only {
+        for (index, price) in external_market_prices.iter().enumerate() {
+            if let Some(price) = price {
+                if taker_crosses(*price) {
+                    venues.push((Some(index), *price));
+                }
+            }
+        }
+    }
 
-    if maker_available {
-        fulfillment_methods.push(SpotFulfillmentMethod::Match)
+    // stable sort so the maker is preferred when prices are equal
+    match taker_order.direction {
+        PositionDirection::Long => venues.sort_by(|a, b| a.1.cmp(&b.1)),
+        PositionDirection::Short => venues.sort_by(|a, b| b.1.cmp(&a.1)),
+    }
+
+    let mut fulfillment_methods = Vec::with_capacity(venues.len() * 2);
+    let mut external_markets_to_sweep = vec![];
+    for (i, (venue, _)) in venues.iter().enumerate() {
+        match venue {
+            None => fulfillment_methods.push(SpotFulfillmentMethod::Match),
+            Some(index) => {
+                let price_limit = venues.get(i + 1).map(|(_, price)| *price);
+                fulfillment_methods
+                    .push(SpotFulfillmentMethod::ExternalMarket(*index, price_limit));
+
+                if price_limit.is_some() {
+                    external_markets_to_sweep.push(*index);
+                }
+            }
+        }
     }
 
-    if !taker_order.post_only && external_fulfillment_params_available {
-        fulfillment_methods.push(SpotFulfillmentMethod::ExternalMarket)
+    for index in external_markets_to_sweep {
+        fulfillment_methods.push(SpotFulfillmentMethod::ExternalMarket(index, None));
     }
 
     Ok(fulfillment_methods)
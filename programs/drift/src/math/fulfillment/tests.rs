@@ -670,7 +670,7 @@ mod determine_perp_fulfillment_methods {
         )
         .unwrap();
 
-        assert_eq!(fulfillment_methods, []);
+        assert!(fulfillment_methods.is_empty());
     }
 
     #[test]
@@ -730,6 +730,154 @@ mod determine_perp_fulfillment_methods {
         )
         .unwrap();
 
-        assert_eq!(fulfillment_methods, []);
+        assert!(fulfillment_methods.is_empty());
+    }
+}
+
+mod determine_spot_fulfillment_methods {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::PRICE_PRECISION_U64;
+    use crate::math::fulfillment::determine_spot_fulfillment_methods;
+    use crate::state::fulfillment::SpotFulfillmentMethod;
+    use crate::state::user::Order;
+
+    #[test]
+    fn maker_only() {
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            ..Order::default()
+        };
+
+        let fulfillment_methods = determine_spot_fulfillment_methods(
+            &taker_order,
+            Some(102 * PRICE_PRECISION_U64),
+            Some(101 * PRICE_PRECISION_U64),
+            &[None],
+        )
+        .unwrap();
+
+        assert_eq!(fulfillment_methods, [SpotFulfillmentMethod::Match]);
+    }
+
+    #[test]
+    fn taker_doesnt_cross_external_markets() {
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            ..Order::default()
+        };
+
+        let fulfillment_methods = determine_spot_fulfillment_methods(
+            &taker_order,
+            Some(100 * PRICE_PRECISION_U64),
+            None,
+            &[
+                Some(101 * PRICE_PRECISION_U64),
+                Some(102 * PRICE_PRECISION_U64),
+            ],
+        )
+        .unwrap();
+
+        assert!(fulfillment_methods.is_empty());
+    }
+
+    #[test]
+    fn long_walks_external_markets_by_price() {
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            ..Order::default()
+        };
+
+        let fulfillment_methods = determine_spot_fulfillment_methods(
+            &taker_order,
+            Some(105 * PRICE_PRECISION_U64),
+            Some(102 * PRICE_PRECISION_U64),
+            &[
+                Some(103 * PRICE_PRECISION_U64),
+                Some(101 * PRICE_PRECISION_U64),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [
+                SpotFulfillmentMethod::ExternalMarket(1, Some(102 * PRICE_PRECISION_U64)),
+                SpotFulfillmentMethod::Match,
+                SpotFulfillmentMethod::ExternalMarket(0, None),
+                SpotFulfillmentMethod::ExternalMarket(1, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn short_walks_external_markets_by_price() {
+        let taker_order = Order {
+            direction: PositionDirection::Short,
+            ..Order::default()
+        };
+
+        let fulfillment_methods = determine_spot_fulfillment_methods(
+            &taker_order,
+            Some(95 * PRICE_PRECISION_U64),
+            Some(99 * PRICE_PRECISION_U64),
+            &[
+                Some(98 * PRICE_PRECISION_U64),
+                Some(100 * PRICE_PRECISION_U64),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [
+                SpotFulfillmentMethod::ExternalMarket(1, Some(99 * PRICE_PRECISION_U64)),
+                SpotFulfillmentMethod::Match,
+                SpotFulfillmentMethod::ExternalMarket(0, None),
+                SpotFulfillmentMethod::ExternalMarket(1, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn maker_preferred_when_prices_equal() {
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            ..Order::default()
+        };
+
+        let fulfillment_methods = determine_spot_fulfillment_methods(
+            &taker_order,
+            None,
+            Some(101 * PRICE_PRECISION_U64),
+            &[Some(101 * PRICE_PRECISION_U64)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [
+                SpotFulfillmentMethod::Match,
+                SpotFulfillmentMethod::ExternalMarket(0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn post_only_taker_doesnt_use_external_markets() {
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            post_only: true,
+            ..Order::default()
+        };
+
+        let fulfillment_methods = determine_spot_fulfillment_methods(
+            &taker_order,
+            Some(102 * PRICE_PRECISION_U64),
+            Some(101 * PRICE_PRECISION_U64),
+            &[Some(100 * PRICE_PRECISION_U64)],
+        )
+        .unwrap();
+
+        assert_eq!(fulfillment_methods, [SpotFulfillmentMethod::Match]);
     }
 }
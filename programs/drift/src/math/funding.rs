@@ -7,12 +7,12 @@ use crate::math::bn;
 use crate::math::casting::Cast;
 use crate::math::constants::{
     AMM_TO_QUOTE_PRECISION_RATIO, AMM_TO_QUOTE_PRECISION_RATIO_I128, FUNDING_RATE_BUFFER,
-    ONE_HOUR_I128, PRICE_PRECISION, QUOTE_TO_BASE_AMT_FUNDING_PRECISION,
+    ONE_HOUR_I128, PERCENTAGE_PRECISION_I128, PRICE_PRECISION, QUOTE_TO_BASE_AMT_FUNDING_PRECISION,
 };
 use crate::math::repeg::{calculate_fee_pool, get_total_fee_lower_bound};
 use crate::math::safe_math::SafeMath;
 
-use crate::state::perp_market::PerpMarket;
+use crate::state::perp_market::{PerpMarket, AMM};
 use crate::state::user::PerpPosition;
 
 #[cfg(test)]
@@ -22,6 +22,32 @@ pub fn calculate_funding_rate(
     mid_price_twap: u128,
     oracle_price_twap: i128,
     funding_period: i64,
+) -> DriftResult<i128> {
+    _calculate_funding_rate(mid_price_twap, oracle_price_twap, funding_period, 0, 0, 0)
+}
+
+pub fn calculate_funding_rate_for_amm(
+    amm: &AMM,
+    mid_price_twap: u128,
+    oracle_price_twap: i128,
+) -> DriftResult<i128> {
+    _calculate_funding_rate(
+        mid_price_twap,
+        oracle_price_twap,
+        amm.funding_period,
+        amm.funding_max_price_spread,
+        amm.funding_interest_rate,
+        amm.funding_premium_dampener,
+    )
+}
+
+fn _calculate_funding_rate(
+    mid_price_twap: u128,
+    oracle_price_twap: i128,
+    funding_period: i64,
+    funding_max_price_spread: u32,
+    funding_interest_rate: i32,
+    funding_premium_dampener: u32,
 ) -> DriftResult<i128> {
     // funding period = 1 hour, window = 1 day
     // low periodicity => quickly updating/settled funding rates
@@ -30,10 +56,25 @@ pub fn calculate_funding_rate(
         .safe_mul(ONE_HOUR_I128)?
         .safe_div(max(ONE_HOUR_I128, funding_period as i128))?;
 
-    let price_spread = mid_price_twap.cast::<i128>()?.safe_sub(oracle_price_twap)?;
+    let premium = mid_price_twap
+        .cast::<i128>()?
+        .safe_sub(oracle_price_twap)?
+        .safe_div(max(funding_premium_dampener, 1).cast()?)?;
+
+    let interest = oracle_price_twap
+        .safe_mul(funding_interest_rate.cast()?)?
+        .safe_div(PERCENTAGE_PRECISION_I128)?;
 
-    // clamp price divergence to 3% for funding rate calculation
-    let max_price_spread = oracle_price_twap.safe_div(33)?; // 3%
+    let price_spread = premium.safe_add(interest)?;
+
+    // clamp price divergence for funding rate calculation, 3% by default
+    let max_price_spread = if funding_max_price_spread == 0 {
+        oracle_price_twap.safe_div(33)?
+    } else {
+        oracle_price_twap
+            .safe_mul(funding_max_price_spread.cast()?)?
+            .safe_div(PERCENTAGE_PRECISION_I128)?
+    };
     let clamped_price_spread = max(-max_price_spread, min(price_spread, max_price_spread));
 
     let funding_rate = clamped_price_spread
@@ -446,3 +446,38 @@ fn funding_unsettled_lps_amm_lose_test() {
     let new_fees = market.amm.total_fee_minus_distributions;
     assert_eq!(new_fees, 416667); // lost
 }
+
+#[test]
+fn funding_rate_with_market_params_test() {
+    let mut amm = AMM {
+        funding_period: 3600,
+        ..AMM::default()
+    };
+
+    // mark 10% above oracle
+    let mid_price_twap = 110 * PRICE_PRECISION;
+    let oracle_price_twap = 100 * PRICE_PRECISION as i128;
+
+    // default params clamp the spread to 1/33
+    let funding_rate =
+        calculate_funding_rate_for_amm(&amm, mid_price_twap, oracle_price_twap).unwrap();
+    assert_eq!(funding_rate, 126_262_625);
+    assert_eq!(
+        funding_rate,
+        calculate_funding_rate(mid_price_twap, oracle_price_twap, amm.funding_period).unwrap()
+    );
+
+    // 5% clamp, premium dampened by 4 plus 0.1% interest
+    amm.funding_max_price_spread = 50_000;
+    amm.funding_premium_dampener = 4;
+    amm.funding_interest_rate = 1_000;
+    let funding_rate =
+        calculate_funding_rate_for_amm(&amm, mid_price_twap, oracle_price_twap).unwrap();
+    assert_eq!(funding_rate, 108_333_333);
+
+    // undampened premium plus interest is clamped to 5%
+    amm.funding_premium_dampener = 1;
+    let funding_rate =
+        calculate_funding_rate_for_amm(&amm, mid_price_twap, oracle_price_twap).unwrap();
+    assert_eq!(funding_rate, 208_333_333);
+}
@@ -6,7 +6,7 @@ use crate::math::helpers::{get_proportion_u128, log10_iter};
 use crate::math::safe_math::SafeMath;
 
 use crate::state::insurance_fund_stake::InsuranceFundStake;
-use crate::state::spot_market::SpotMarket;
+use crate::state::spot_market::InsuranceFund;
 use crate::validate;
 
 #[cfg(test)]
@@ -83,21 +83,21 @@ pub fn calculate_rebase_info(
 
 pub fn calculate_if_shares_lost(
     insurance_fund_stake: &InsuranceFundStake,
-    spot_market: &SpotMarket,
+    insurance_fund: &InsuranceFund,
     insurance_fund_vault_balance: u64,
 ) -> DriftResult<u128> {
     let n_shares = insurance_fund_stake.last_withdraw_request_shares;
 
     let amount = if_shares_to_vault_amount(
         n_shares,
-        spot_market.insurance_fund.total_shares,
+        insurance_fund.total_shares,
         insurance_fund_vault_balance,
     )?;
 
     let if_shares_lost = if amount > insurance_fund_stake.last_withdraw_request_value {
         let new_n_shares = vault_amount_to_if_shares(
             insurance_fund_stake.last_withdraw_request_value,
-            spot_market.insurance_fund.total_shares.safe_sub(n_shares)?,
+            insurance_fund.total_shares.safe_sub(n_shares)?,
             insurance_fund_vault_balance
                 .safe_sub(insurance_fund_stake.last_withdraw_request_value)?,
         )?;
@@ -117,3 +117,24 @@ pub fn calculate_if_shares_lost(
 
     Ok(if_shares_lost)
 }
+
+/// Available balance across both tranches. The junior vault always keeps 1 token, the senior vault's
+/// minimum balance is enforced by the bankruptcy resolution itself
+pub fn calculate_tranched_insurance_fund_vault_amount(
+    senior_insurance_fund_vault_amount: u64,
+    junior_insurance_fund_vault_amount: u64,
+) -> DriftResult<u64> {
+    senior_insurance_fund_vault_amount
+        .safe_add(junior_insurance_fund_vault_amount.saturating_sub(1))
+}
+
+/// Splits an insurance fund payment into (junior payment, senior payment). The junior tranche pays first
+pub fn calculate_insurance_fund_tranche_payments(
+    if_payment: u64,
+    junior_insurance_fund_vault_amount: u64,
+) -> DriftResult<(u64, u64)> {
+    let junior_payment = if_payment.min(junior_insurance_fund_vault_amount.saturating_sub(1));
+    let senior_payment = if_payment.safe_sub(junior_payment)?;
+
+    Ok((junior_payment, senior_payment))
+}
@@ -3,7 +3,7 @@ use anchor_lang::prelude::Pubkey;
 use crate::math::constants::{QUOTE_PRECISION, SPOT_CUMULATIVE_INTEREST_PRECISION};
 use crate::math::helpers::log10;
 use crate::math::insurance::*;
-use crate::state::spot_market::InsuranceFund;
+use crate::state::spot_market::{InsuranceFund, SpotMarket};
 
 #[test]
 pub fn basic_stake_if_test() {
@@ -114,7 +114,7 @@ pub fn if_shares_lost_test() {
 
     let mut if_stake = InsuranceFundStake::new(Pubkey::default(), 0, 0);
     if_stake
-        .update_if_shares(100 * QUOTE_PRECISION, &spot_market)
+        .update_if_shares(100 * QUOTE_PRECISION, &spot_market.insurance_fund)
         .unwrap();
     if_stake.last_withdraw_request_shares = 100 * QUOTE_PRECISION;
     if_stake.last_withdraw_request_value = ((100 * QUOTE_PRECISION) - 1) as u64;
@@ -122,43 +122,50 @@ pub fn if_shares_lost_test() {
     let if_balance = (1000 * QUOTE_PRECISION) as u64;
 
     // unchanged balance
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 2);
 
     let if_balance = if_balance + (100 * QUOTE_PRECISION) as u64;
     spot_market.insurance_fund.total_shares += 100 * QUOTE_PRECISION;
     spot_market.insurance_fund.user_shares += 100 * QUOTE_PRECISION;
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 2); // giving up $5 of gains
 
     let if_balance = if_balance - (100 * QUOTE_PRECISION) as u64;
     spot_market.insurance_fund.total_shares -= 100 * QUOTE_PRECISION;
     spot_market.insurance_fund.user_shares -= 100 * QUOTE_PRECISION;
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 2); // giving up $5 of gains
 
     // take back gain
     let if_balance = (1100 * QUOTE_PRECISION) as u64;
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 10_000_001); // giving up $10 of gains
 
     // doesnt matter if theres a loss
     if_stake.last_withdraw_request_value = (200 * QUOTE_PRECISION) as u64;
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 0);
     if_stake.last_withdraw_request_value = (100 * QUOTE_PRECISION - 1) as u64;
 
     // take back gain and total_if_shares alter w/o user alter
     let if_balance = (2100 * QUOTE_PRECISION) as u64;
     spot_market.insurance_fund.total_shares *= 2;
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 5_000_001); // giving up $5 of gains
 
     let if_balance = (2100 * QUOTE_PRECISION * 10) as u64;
 
     let expected_gain_if_no_loss = if_balance * 100 / 2000;
     assert_eq!(expected_gain_if_no_loss, 1_050_000_000);
-    let lost_shares = calculate_if_shares_lost(&if_stake, &spot_market, if_balance).unwrap();
+    let lost_shares =
+        calculate_if_shares_lost(&if_stake, &spot_market.insurance_fund, if_balance).unwrap();
     assert_eq!(lost_shares, 90_909_092); // giving up $5 of gains
     assert_eq!(
         (9090908 * if_balance / ((spot_market.insurance_fund.total_shares - lost_shares) as u64))
@@ -166,3 +173,34 @@ pub fn if_shares_lost_test() {
         true
     );
 }
+
+#[test]
+pub fn junior_tranche_pays_first_test() {
+    let junior_vault_amount = 50 * QUOTE_PRECISION as u64;
+    let senior_vault_amount = 1000 * QUOTE_PRECISION as u64;
+
+    let vault_amount =
+        calculate_tranched_insurance_fund_vault_amount(senior_vault_amount, junior_vault_amount)
+            .unwrap();
+    assert_eq!(vault_amount, 1050 * QUOTE_PRECISION as u64 - 1);
+
+    // junior tranche covers the entire loss
+    let (junior_payment, senior_payment) =
+        calculate_insurance_fund_tranche_payments(10 * QUOTE_PRECISION as u64, junior_vault_amount)
+            .unwrap();
+    assert_eq!(junior_payment, 10 * QUOTE_PRECISION as u64);
+    assert_eq!(senior_payment, 0);
+
+    // junior tranche is wiped out (leaving 1) before the senior tranche pays
+    let (junior_payment, senior_payment) =
+        calculate_insurance_fund_tranche_payments(80 * QUOTE_PRECISION as u64, junior_vault_amount)
+            .unwrap();
+    assert_eq!(junior_payment, 50 * QUOTE_PRECISION as u64 - 1);
+    assert_eq!(senior_payment, 30 * QUOTE_PRECISION as u64 + 1);
+
+    // no junior tranche
+    let (junior_payment, senior_payment) =
+        calculate_insurance_fund_tranche_payments(80 * QUOTE_PRECISION as u64, 0).unwrap();
+    assert_eq!(junior_payment, 0);
+    assert_eq!(senior_payment, 80 * QUOTE_PRECISION as u64);
+}
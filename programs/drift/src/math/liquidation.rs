@@ -1,10 +1,12 @@
+use crate::controller::position::PositionDirection;
 use crate::error::{DriftResult, ErrorCode};
 use crate::math::casting::Cast;
 use crate::math::constants::{
-    AMM_RESERVE_PRECISION_I128, FUNDING_RATE_TO_QUOTE_PRECISION_PRECISION_RATIO,
+    AMM_RESERVE_PRECISION_I128, BASE_PRECISION, FUNDING_RATE_TO_QUOTE_PRECISION_PRECISION_RATIO,
     LIQUIDATION_FEE_PRECISION, LIQUIDATION_FEE_PRECISION_U128,
-    LIQUIDATION_FEE_TO_MARGIN_PRECISION_RATIO, LIQUIDATION_PCT_PRECISION, PRICE_PRECISION,
-    PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO, QUOTE_PRECISION, SPOT_WEIGHT_PRECISION_U128,
+    LIQUIDATION_FEE_TO_MARGIN_PRECISION_RATIO, LIQUIDATION_PCT_PRECISION, PERCENTAGE_PRECISION,
+    PRICE_PRECISION, PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO, QUOTE_PRECISION,
+    SPOT_WEIGHT_PRECISION_U128,
 };
 use crate::math::margin::{
     calculate_margin_requirement_and_total_collateral, MarginRequirementType,
@@ -246,6 +248,39 @@ pub fn validate_user_not_being_liquidated(
     Ok(())
 }
 
+/// The token amount of a shared collateral group member's deposit to move into a user to cover the
+/// user's margin shortage. Bounded by the member's free collateral so the member isn't left below
+/// its own maintenance margin requirement
+pub fn calculate_shared_collateral_transfer(
+    margin_shortage: u128,
+    member_free_collateral: u128,
+    member_token_amount: u128,
+    spot_decimals: u32,
+    oracle_price: i64,
+) -> DriftResult<u128> {
+    validate!(
+        oracle_price > 0,
+        ErrorCode::InvalidOracle,
+        "oracle_price={} (<= 0)",
+        oracle_price
+    )?;
+
+    let precision = 10_u128.pow(spot_decimals);
+    let oracle_price = oracle_price.cast::<u128>()?;
+
+    let shortage_token_amount = margin_shortage
+        .safe_mul(precision)?
+        .safe_div_ceil(oracle_price)?;
+
+    let free_token_amount = member_free_collateral
+        .safe_mul(precision)?
+        .safe_div(oracle_price)?;
+
+    Ok(shortage_token_amount
+        .min(free_token_amount)
+        .min(member_token_amount))
+}
+
 pub enum LiquidationMultiplierType {
     Discount,
     Premium,
@@ -365,3 +400,82 @@ pub fn calculate_max_pct_to_liquidate(
         .safe_mul(LIQUIDATION_PCT_PRECISION)?
         .safe_div(margin_shortage)
 }
+
+/// Calculates the liquidator fee for a dutch auction liquidation
+///
+/// The fee ramps linearly from zero to the market's max liquidator fee over the auction duration,
+/// starting from the slot the user entered liquidation. An auction duration of zero uses the max fee
+pub fn calculate_liquidation_auction_fee(
+    max_liquidator_fee: u32,
+    user: &User,
+    slot: u64,
+    liquidation_auction_duration: u16,
+) -> DriftResult<u32> {
+    if liquidation_auction_duration == 0 {
+        return Ok(max_liquidator_fee);
+    }
+
+    let slots_elapsed = slot
+        .safe_sub(user.last_active_slot)?
+        .min(liquidation_auction_duration.cast()?);
+
+    max_liquidator_fee
+        .cast::<u64>()?
+        .safe_mul(slots_elapsed)?
+        .safe_div(liquidation_auction_duration.cast()?)?
+        .cast()
+}
+
+/// Ranks a position for auto-deleveraging by its pnl percentage times its leverage
+///
+/// Unprofitable positions score zero. Profitable positions without collateral backing them score highest
+pub fn calculate_auto_deleverage_score(
+    unrealized_pnl: i128,
+    quote_entry_amount: i64,
+    base_asset_value: u128,
+    total_collateral: i128,
+) -> DriftResult<u128> {
+    if unrealized_pnl <= 0 {
+        return Ok(0);
+    }
+
+    if total_collateral <= 0 || quote_entry_amount == 0 {
+        return Ok(u128::MAX);
+    }
+
+    let pnl_pct = unrealized_pnl
+        .unsigned_abs()
+        .safe_mul(PERCENTAGE_PRECISION)?
+        .safe_div(quote_entry_amount.unsigned_abs().cast()?)?;
+
+    let leverage = base_asset_value
+        .safe_mul(PERCENTAGE_PRECISION)?
+        .safe_div(total_collateral.unsigned_abs())?;
+
+    pnl_pct.safe_mul(leverage)?.safe_div(PERCENTAGE_PRECISION)
+}
+
+/// The price a deleveraged position effectively exits at once the loss it covered is taken from
+/// the oracle price it was closed at, rounded against the deleveraged user
+pub fn calculate_auto_deleverage_bankruptcy_price(
+    oracle_price: i64,
+    base_asset_amount: u64,
+    loss_covered: u128,
+    direction_to_close: PositionDirection,
+) -> DriftResult<u64> {
+    let oracle_price = oracle_price.cast::<u64>()?;
+
+    if base_asset_amount == 0 {
+        return Ok(oracle_price);
+    }
+
+    let loss_per_base = loss_covered
+        .safe_mul(BASE_PRECISION)?
+        .safe_div_ceil(base_asset_amount.cast()?)?
+        .cast::<u64>()?;
+
+    match direction_to_close {
+        PositionDirection::Short => Ok(oracle_price.saturating_sub(loss_per_base)),
+        PositionDirection::Long => oracle_price.safe_add(loss_per_base),
+    }
+}
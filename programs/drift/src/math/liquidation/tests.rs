@@ -531,3 +531,206 @@ mod validate_transfer_satisfies_limit_price {
         .is_err());
     }
 }
+
+mod calculate_liquidation_auction_fee {
+    use crate::math::constants::LIQUIDATION_FEE_PRECISION;
+    use crate::math::liquidation::calculate_liquidation_auction_fee;
+    use crate::state::user::User;
+
+    #[test]
+    pub fn zero_duration() {
+        let user = User {
+            last_active_slot: 100,
+            ..User::default()
+        };
+        let max_liquidator_fee = LIQUIDATION_FEE_PRECISION / 100;
+
+        let fee = calculate_liquidation_auction_fee(max_liquidator_fee, &user, 100, 0).unwrap();
+        assert_eq!(fee, max_liquidator_fee);
+    }
+
+    #[test]
+    pub fn ramp() {
+        let user = User {
+            last_active_slot: 100,
+            ..User::default()
+        };
+        let max_liquidator_fee = LIQUIDATION_FEE_PRECISION / 100;
+
+        let fee = calculate_liquidation_auction_fee(max_liquidator_fee, &user, 100, 50).unwrap();
+        assert_eq!(fee, 0);
+
+        let fee = calculate_liquidation_auction_fee(max_liquidator_fee, &user, 125, 50).unwrap();
+        assert_eq!(fee, max_liquidator_fee / 2);
+
+        let fee = calculate_liquidation_auction_fee(max_liquidator_fee, &user, 150, 50).unwrap();
+        assert_eq!(fee, max_liquidator_fee);
+
+        let fee = calculate_liquidation_auction_fee(max_liquidator_fee, &user, 1000, 50).unwrap();
+        assert_eq!(fee, max_liquidator_fee);
+    }
+}
+
+mod calculate_auto_deleverage_score {
+    use crate::math::constants::{PERCENTAGE_PRECISION, QUOTE_PRECISION, QUOTE_PRECISION_I128};
+    use crate::math::liquidation::calculate_auto_deleverage_score;
+
+    #[test]
+    pub fn unprofitable() {
+        let score = calculate_auto_deleverage_score(
+            -10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I128 as i64,
+            90 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    pub fn no_collateral() {
+        let score = calculate_auto_deleverage_score(
+            10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I128 as i64,
+            110 * QUOTE_PRECISION,
+            0,
+        )
+        .unwrap();
+        assert_eq!(score, u128::MAX);
+    }
+
+    #[test]
+    pub fn pnl_pct_times_leverage() {
+        // 10% pnl at 1.1x leverage
+        let score = calculate_auto_deleverage_score(
+            10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I128 as i64,
+            110 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(score, 11 * PERCENTAGE_PRECISION / 100);
+
+        // same pnl at 5.5x leverage ranks higher
+        let higher_leverage_score = calculate_auto_deleverage_score(
+            10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I128 as i64,
+            110 * QUOTE_PRECISION,
+            20 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(higher_leverage_score, 55 * PERCENTAGE_PRECISION / 100);
+        assert!(higher_leverage_score > score);
+    }
+}
+
+mod calculate_auto_deleverage_bankruptcy_price {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::{BASE_PRECISION_U64, PRICE_PRECISION_I64, QUOTE_PRECISION};
+    use crate::math::liquidation::calculate_auto_deleverage_bankruptcy_price;
+
+    #[test]
+    pub fn long() {
+        // giving up $25 on 1.25 base closed at $100 exits at $80
+        let price = calculate_auto_deleverage_bankruptcy_price(
+            100 * PRICE_PRECISION_I64,
+            125 * BASE_PRECISION_U64 / 100,
+            25 * QUOTE_PRECISION,
+            PositionDirection::Short,
+        )
+        .unwrap();
+        assert_eq!(price, 80 * PRICE_PRECISION_I64 as u64);
+    }
+
+    #[test]
+    pub fn short() {
+        let price = calculate_auto_deleverage_bankruptcy_price(
+            100 * PRICE_PRECISION_I64,
+            125 * BASE_PRECISION_U64 / 100,
+            25 * QUOTE_PRECISION,
+            PositionDirection::Long,
+        )
+        .unwrap();
+        assert_eq!(price, 120 * PRICE_PRECISION_I64 as u64);
+    }
+
+    #[test]
+    pub fn rounds_against_user() {
+        // $10 over 3 base is $3.333..., rounded up
+        let price = calculate_auto_deleverage_bankruptcy_price(
+            100 * PRICE_PRECISION_I64,
+            3 * BASE_PRECISION_U64,
+            10 * QUOTE_PRECISION,
+            PositionDirection::Short,
+        )
+        .unwrap();
+        assert_eq!(price, 96_666_666);
+
+        let price = calculate_auto_deleverage_bankruptcy_price(
+            100 * PRICE_PRECISION_I64,
+            3 * BASE_PRECISION_U64,
+            10 * QUOTE_PRECISION,
+            PositionDirection::Long,
+        )
+        .unwrap();
+        assert_eq!(price, 103_333_334);
+    }
+}
+
+mod calculate_shared_collateral_transfer {
+    use crate::math::constants::{PRICE_PRECISION_I64, QUOTE_PRECISION};
+    use crate::math::liquidation::calculate_shared_collateral_transfer;
+
+    #[test]
+    pub fn covers_shortage() {
+        let transfer = calculate_shared_collateral_transfer(
+            10 * QUOTE_PRECISION,
+            50 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION,
+            6,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+        assert_eq!(transfer, 10 * QUOTE_PRECISION);
+    }
+
+    #[test]
+    pub fn bounded_by_free_collateral() {
+        let transfer = calculate_shared_collateral_transfer(
+            10 * QUOTE_PRECISION,
+            4 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION,
+            6,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+        assert_eq!(transfer, 4 * QUOTE_PRECISION);
+    }
+
+    #[test]
+    pub fn bounded_by_deposit() {
+        let transfer = calculate_shared_collateral_transfer(
+            10 * QUOTE_PRECISION,
+            50 * QUOTE_PRECISION,
+            3 * QUOTE_PRECISION,
+            6,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+        assert_eq!(transfer, 3 * QUOTE_PRECISION);
+    }
+
+    #[test]
+    pub fn rounds_shortage_up() {
+        // quote trading below peg needs more tokens to cover the shortage
+        let transfer = calculate_shared_collateral_transfer(
+            10 * QUOTE_PRECISION,
+            50 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION,
+            6,
+            PRICE_PRECISION_I64 * 3 / 4,
+        )
+        .unwrap();
+        assert_eq!(transfer, 13333334);
+    }
+}
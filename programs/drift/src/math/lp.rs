@@ -1,11 +1,20 @@
-use crate::error::DriftResult;
-use crate::math::amm::calculate_market_open_bids_asks;
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::amm::{_calculate_market_open_bids_asks, calculate_market_open_bids_asks};
+use crate::math::bn::U256;
 use crate::math::casting::Cast;
-use crate::math::constants::AMM_RESERVE_PRECISION_I128;
+use crate::math::constants::{
+    AMM_RESERVE_PRECISION_I128, PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO_I128,
+    PRICE_TO_PEG_PRECISION_RATIO,
+};
+use crate::math::funding::calculate_funding_payment_in_quote_precision;
 use crate::math::helpers;
 use crate::math::orders::standardize_base_asset_amount_with_remainder_i128;
+use crate::math::quote_asset::reserve_to_asset_amount;
 use crate::math::safe_math::SafeMath;
+use crate::validate;
 
+use crate::state::perp_lp_range::PerpLpRange;
+use crate::state::perp_lp_stats::PerpLpStats;
 use crate::state::perp_market::PerpMarket;
 use crate::state::perp_market::AMM;
 use crate::state::user::PerpPosition;
@@ -18,11 +27,32 @@ pub struct LPMetrics {
     pub base_asset_amount: i128,
     pub quote_asset_amount: i128,
     pub remainder_base_asset_amount: i128,
+    /// base the lp shares took on past their range's bound, left with the amm
+    pub out_of_range_base_asset_amount: i128,
 }
 
-pub fn calculate_settle_lp_metrics(amm: &AMM, position: &PerpPosition) -> DriftResult<LPMetrics> {
+/// Calculates what the lp shares are owed since the position was last settled. For ranged
+/// lp shares, what they took on while the reserve price moved past the range's bound is excluded
+pub fn calculate_settle_lp_metrics(
+    amm: &AMM,
+    position: &PerpPosition,
+    perp_lp_range: Option<&PerpLpRange>,
+) -> DriftResult<LPMetrics> {
     let (base_asset_amount, quote_asset_amount) = calculate_settled_lp_base_quote(amm, position)?;
 
+    let (out_of_range_base_asset_amount, out_of_range_quote_asset_amount) = match perp_lp_range {
+        Some(perp_lp_range) => calculate_lp_out_of_range_base_quote(
+            amm,
+            position.lp_shares,
+            perp_lp_range,
+            base_asset_amount,
+        )?,
+        None => (0, 0),
+    };
+
+    let base_asset_amount = base_asset_amount.safe_sub(out_of_range_base_asset_amount)?;
+    let quote_asset_amount = quote_asset_amount.safe_sub(out_of_range_quote_asset_amount)?;
+
     // stepsize it
     let (standardized_base_asset_amount, remainder_base_asset_amount) =
         standardize_base_asset_amount_with_remainder_i128(
@@ -34,11 +64,107 @@ pub fn calculate_settle_lp_metrics(amm: &AMM, position: &PerpPosition) -> DriftR
         base_asset_amount: standardized_base_asset_amount,
         quote_asset_amount,
         remainder_base_asset_amount: remainder_base_asset_amount.cast()?,
+        out_of_range_base_asset_amount,
     };
 
     Ok(lp_metrics)
 }
 
+/// The lp shares' slice of the amm's reserve change between the range's bound and the current
+/// reserve price, i.e. what the shares took on after the reserve price left the range. Capped at
+/// the base the shares took on since they were last settled
+pub fn calculate_lp_out_of_range_base_quote(
+    amm: &AMM,
+    lp_shares: u64,
+    perp_lp_range: &PerpLpRange,
+    settled_base_asset_amount: i128,
+) -> DriftResult<(i128, i128)> {
+    let reserve_price = amm.reserve_price()?;
+
+    let bound_reserve_price = if reserve_price > perp_lp_range.upper_reserve_price {
+        perp_lp_range.upper_reserve_price
+    } else if reserve_price < perp_lp_range.lower_reserve_price {
+        perp_lp_range.lower_reserve_price
+    } else {
+        return Ok((0, 0));
+    };
+
+    let (bound_base_asset_reserve, bound_quote_asset_reserve) =
+        calculate_reserves_at_reserve_price(amm, bound_reserve_price)?;
+
+    // lps take the other side of the takers that moved the reserves
+    let base_asset_amount = helpers::get_proportion_i128(
+        amm.base_asset_reserve
+            .cast::<i128>()?
+            .safe_sub(bound_base_asset_reserve.cast()?)?,
+        lp_shares.cast()?,
+        amm.sqrt_k,
+    )?;
+
+    let quote_asset_reserve_change = amm
+        .quote_asset_reserve
+        .cast::<i128>()?
+        .safe_sub(bound_quote_asset_reserve.cast()?)?;
+
+    let quote_asset_amount = helpers::get_proportion_i128(
+        reserve_to_asset_amount(
+            quote_asset_reserve_change.unsigned_abs(),
+            amm.peg_multiplier,
+        )?
+        .cast::<i128>()?
+        .safe_mul(quote_asset_reserve_change.signum())?,
+        lp_shares.cast()?,
+        amm.sqrt_k,
+    )?;
+
+    if base_asset_amount == 0
+        || settled_base_asset_amount == 0
+        || base_asset_amount.signum() != settled_base_asset_amount.signum()
+    {
+        return Ok((0, 0));
+    }
+
+    if base_asset_amount.unsigned_abs() > settled_base_asset_amount.unsigned_abs() {
+        let quote_asset_amount = helpers::get_proportion_i128(
+            quote_asset_amount,
+            settled_base_asset_amount.unsigned_abs(),
+            base_asset_amount.unsigned_abs(),
+        )?;
+
+        return Ok((settled_base_asset_amount, quote_asset_amount));
+    }
+
+    Ok((base_asset_amount, quote_asset_amount))
+}
+
+/// The amm's base and quote asset reserves if its reserve price were `reserve_price`, with k and the peg unchanged
+pub fn calculate_reserves_at_reserve_price(
+    amm: &AMM,
+    reserve_price: u64,
+) -> DriftResult<(u128, u128)> {
+    validate!(
+        reserve_price > 0,
+        ErrorCode::InvalidPerpLpRange,
+        "reserve_price must be > 0"
+    )?;
+
+    let sqrt_k = U256::from(amm.sqrt_k);
+    let k = sqrt_k.safe_mul(sqrt_k)?;
+
+    let base_asset_reserve = k
+        .safe_mul(U256::from(amm.peg_multiplier))?
+        .safe_mul(U256::from(PRICE_TO_PEG_PRECISION_RATIO))?
+        .safe_div(U256::from(reserve_price))?
+        .integer_sqrt();
+
+    let quote_asset_reserve = k.safe_div(base_asset_reserve)?;
+
+    Ok((
+        base_asset_reserve.try_to_u128()?,
+        quote_asset_reserve.try_to_u128()?,
+    ))
+}
+
 pub fn calculate_settled_lp_base_quote(
     amm: &AMM,
     position: &PerpPosition,
@@ -68,16 +194,131 @@ pub fn calculate_settled_lp_base_quote(
     Ok((base_asset_amount, quote_asset_amount))
 }
 
+/// The lp shares' slice of the amm's open bids and asks. Ranged lp shares only fill until the
+/// reserve price reaches their range's bounds, so the amm's reserve bounds are narrowed to the range's
 pub fn calculate_lp_open_bids_asks(
     market_position: &PerpPosition,
     market: &PerpMarket,
+    perp_lp_range: Option<&PerpLpRange>,
 ) -> DriftResult<(i64, i64)> {
     let total_lp_shares = market.amm.sqrt_k;
     let lp_shares = market_position.lp_shares;
 
-    let (max_bids, max_asks) = calculate_market_open_bids_asks(&market.amm)?;
+    let (max_bids, max_asks) = match perp_lp_range {
+        Some(perp_lp_range) => {
+            let (upper_base_asset_reserve, _) = calculate_reserves_at_reserve_price(
+                &market.amm,
+                perp_lp_range.upper_reserve_price,
+            )?;
+            let (lower_base_asset_reserve, _) = calculate_reserves_at_reserve_price(
+                &market.amm,
+                perp_lp_range.lower_reserve_price,
+            )?;
+
+            _calculate_market_open_bids_asks(
+                market.amm.base_asset_reserve,
+                market
+                    .amm
+                    .min_base_asset_reserve
+                    .max(upper_base_asset_reserve),
+                market
+                    .amm
+                    .max_base_asset_reserve
+                    .min(lower_base_asset_reserve),
+            )?
+        }
+        None => calculate_market_open_bids_asks(&market.amm)?,
+    };
     let open_asks = helpers::get_proportion_i128(max_asks, lp_shares.cast()?, total_lp_shares)?;
     let open_bids = helpers::get_proportion_i128(max_bids, lp_shares.cast()?, total_lp_shares)?;
 
     Ok((open_bids.cast()?, open_asks.cast()?))
 }
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct LPAttribution {
+    /// base asset amount taken on by the lp shares since the last update
+    pub base_asset_amount: i128,
+    pub fee_revenue: i128,
+    pub funding_payment: i128,
+    pub inventory_pnl: i128,
+}
+
+/// Splits what the lp earned since the stats were last updated into fees, funding on the
+/// lp inventory and the pnl of the inventory marked to the oracle price
+pub fn calculate_lp_attribution(
+    amm: &AMM,
+    stats: &PerpLpStats,
+    oracle_price: i64,
+) -> DriftResult<LPAttribution> {
+    let n_shares_i128 = stats.lp_shares.cast::<i128>()?;
+
+    let base_asset_amount = amm
+        .base_asset_amount_per_lp
+        .safe_sub(stats.last_base_asset_amount_per_lp.cast()?)?
+        .safe_mul(n_shares_i128)?
+        .safe_div(AMM_RESERVE_PRECISION_I128)?;
+
+    let quote_asset_amount = amm
+        .quote_asset_amount_per_lp
+        .safe_sub(stats.last_quote_asset_amount_per_lp.cast()?)?
+        .safe_mul(n_shares_i128)?
+        .safe_div(AMM_RESERVE_PRECISION_I128)?;
+
+    let fee_revenue = amm
+        .fee_per_lp
+        .safe_sub(stats.last_fee_per_lp)?
+        .cast::<i128>()?
+        .safe_mul(n_shares_i128)?
+        .safe_div(AMM_RESERVE_PRECISION_I128)?;
+
+    let funding_payment = if stats.base_asset_amount != 0 {
+        let amm_cumulative_funding_rate = if stats.base_asset_amount > 0 {
+            amm.cumulative_funding_rate_long
+        } else {
+            amm.cumulative_funding_rate_short
+        };
+
+        calculate_funding_payment_in_quote_precision(
+            amm_cumulative_funding_rate.safe_sub(stats.last_cumulative_funding_rate.cast()?)?,
+            stats.base_asset_amount.cast()?,
+        )?
+    } else {
+        0
+    };
+
+    let last_inventory_value = calculate_signed_base_asset_value(
+        stats.base_asset_amount.cast()?,
+        stats.last_oracle_price,
+    )?;
+
+    let inventory_value = calculate_signed_base_asset_value(
+        stats
+            .base_asset_amount
+            .cast::<i128>()?
+            .safe_add(base_asset_amount)?,
+        oracle_price,
+    )?;
+
+    // the quote the lp paid/received for new inventory, less the fees that are also in quote_asset_amount_per_lp
+    let inventory_pnl = quote_asset_amount
+        .safe_sub(fee_revenue)?
+        .safe_add(inventory_value)?
+        .safe_sub(last_inventory_value)?;
+
+    Ok(LPAttribution {
+        base_asset_amount,
+        fee_revenue,
+        funding_payment,
+        inventory_pnl,
+    })
+}
+
+fn calculate_signed_base_asset_value(
+    base_asset_amount: i128,
+    oracle_price: i64,
+) -> DriftResult<i128> {
+    base_asset_amount
+        .safe_mul(oracle_price.cast()?)?
+        .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO_I128)
+}
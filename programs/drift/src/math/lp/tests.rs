@@ -79,7 +79,7 @@ mod calculate_lp_open_bids_asks {
             ..PerpMarket::default_test()
         };
 
-        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market).unwrap();
+        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market, None).unwrap();
 
         assert_eq!(open_bids, 10 * 100 / 200);
         assert_eq!(open_asks, -90 * 100 / 200);
@@ -104,7 +104,7 @@ mod calculate_lp_open_bids_asks {
             ..PerpMarket::default_test()
         };
 
-        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market).unwrap();
+        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market, None).unwrap();
 
         assert_eq!(open_bids, 0); // wont go anymore short
         assert_eq!(open_asks, -100 * 100 / 200);
@@ -129,11 +129,42 @@ mod calculate_lp_open_bids_asks {
             ..PerpMarket::default_test()
         };
 
-        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market).unwrap();
+        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market, None).unwrap();
 
         assert_eq!(open_bids, 10 * 100 / 200);
         assert_eq!(open_asks, 0); // no more long
     }
+
+    #[test]
+    fn test_ranged_lp_bid_ask() {
+        let position = PerpPosition {
+            lp_shares: 10 * AMM_RESERVE_PRECISION as u64,
+            ..PerpPosition::default()
+        };
+
+        let market = PerpMarket {
+            amm: AMM::default_test(),
+            ..PerpMarket::default_test()
+        };
+
+        let perp_lp_range = PerpLpRange {
+            lower_reserve_price: 800_000,
+            upper_reserve_price: 1_250_000,
+            ..PerpLpRange::default()
+        };
+
+        let (open_bids, open_asks) =
+            calculate_lp_open_bids_asks(&position, &market, Some(&perp_lp_range)).unwrap();
+
+        // the reserves at the range's bounds cap what the shares fill
+        assert_eq!(open_bids, 1055728090);
+        assert_eq!(open_asks, -1180339887);
+
+        let (open_bids, open_asks) = calculate_lp_open_bids_asks(&position, &market, None).unwrap();
+
+        assert_eq!(open_bids, 10 * AMM_RESERVE_PRECISION as i64);
+        assert!(open_asks < -1180339887);
+    }
 }
 
 mod calculate_settled_lp_base_quote {
@@ -199,7 +230,7 @@ mod calculate_settle_lp_metrics {
             ..AMM::default_test()
         };
 
-        let lp_metrics = calculate_settle_lp_metrics(&amm, &position).unwrap();
+        let lp_metrics = calculate_settle_lp_metrics(&amm, &position, None).unwrap();
 
         assert_eq!(lp_metrics.base_asset_amount, 10 * 100);
         assert_eq!(lp_metrics.quote_asset_amount, -10 * 100);
@@ -220,7 +251,7 @@ mod calculate_settle_lp_metrics {
             ..AMM::default_test()
         };
 
-        let lp_metrics = calculate_settle_lp_metrics(&amm, &position).unwrap();
+        let lp_metrics = calculate_settle_lp_metrics(&amm, &position, None).unwrap();
 
         assert_eq!(lp_metrics.base_asset_amount, 0);
         assert_eq!(lp_metrics.quote_asset_amount, -10 * 100);
@@ -241,10 +272,130 @@ mod calculate_settle_lp_metrics {
             ..AMM::default_test()
         };
 
-        let lp_metrics = calculate_settle_lp_metrics(&amm, &position).unwrap();
+        let lp_metrics = calculate_settle_lp_metrics(&amm, &position, None).unwrap();
 
         assert_eq!(lp_metrics.base_asset_amount, 9);
         assert_eq!(lp_metrics.quote_asset_amount, -10);
         assert_eq!(lp_metrics.remainder_base_asset_amount, 1);
     }
+
+    #[test]
+    fn test_ranged_settle_excludes_out_of_range() {
+        let position = PerpPosition {
+            lp_shares: 10 * BASE_PRECISION_U64,
+            ..PerpPosition::default()
+        };
+
+        // reserve price moved to $1.5625, past the range's $1.25 upper bound
+        let amm = AMM {
+            base_asset_reserve: 80 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 125 * AMM_RESERVE_PRECISION,
+            base_asset_amount_per_lp: -200_000_000,
+            quote_asset_amount_per_lp: 300_000,
+            order_step_size: 1,
+            ..AMM::default_test()
+        };
+
+        let perp_lp_range = PerpLpRange {
+            lower_reserve_price: 800_000,
+            upper_reserve_price: 1_250_000,
+            ..PerpLpRange::default()
+        };
+
+        let lp_metrics = calculate_settle_lp_metrics(&amm, &position, None).unwrap();
+        assert_eq!(lp_metrics.base_asset_amount, -2_000_000_000);
+        assert_eq!(lp_metrics.quote_asset_amount, 3_000_000);
+        assert_eq!(lp_metrics.out_of_range_base_asset_amount, 0);
+
+        let lp_metrics =
+            calculate_settle_lp_metrics(&amm, &position, Some(&perp_lp_range)).unwrap();
+        assert_eq!(lp_metrics.base_asset_amount, -1055728091);
+        assert_eq!(lp_metrics.quote_asset_amount, 1680340);
+        assert_eq!(lp_metrics.remainder_base_asset_amount, 0);
+        assert_eq!(lp_metrics.out_of_range_base_asset_amount, -944271909);
+
+        // in range, nothing is excluded
+        let perp_lp_range = PerpLpRange {
+            lower_reserve_price: 800_000,
+            upper_reserve_price: 2_000_000,
+            ..PerpLpRange::default()
+        };
+        let lp_metrics =
+            calculate_settle_lp_metrics(&amm, &position, Some(&perp_lp_range)).unwrap();
+        assert_eq!(lp_metrics.base_asset_amount, -2_000_000_000);
+        assert_eq!(lp_metrics.out_of_range_base_asset_amount, 0);
+    }
+
+    #[test]
+    fn test_ranged_settle_exclusion_capped_at_settled() {
+        let position = PerpPosition {
+            lp_shares: 10 * BASE_PRECISION_U64,
+            ..PerpPosition::default()
+        };
+
+        let amm = AMM {
+            base_asset_reserve: 80 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 125 * AMM_RESERVE_PRECISION,
+            base_asset_amount_per_lp: -50_000_000,
+            quote_asset_amount_per_lp: 300_000,
+            order_step_size: 1,
+            ..AMM::default_test()
+        };
+
+        let perp_lp_range = PerpLpRange {
+            lower_reserve_price: 800_000,
+            upper_reserve_price: 1_250_000,
+            ..PerpLpRange::default()
+        };
+
+        let lp_metrics =
+            calculate_settle_lp_metrics(&amm, &position, Some(&perp_lp_range)).unwrap();
+        assert_eq!(lp_metrics.base_asset_amount, 0);
+        assert_eq!(lp_metrics.quote_asset_amount, 2301229);
+        assert_eq!(lp_metrics.out_of_range_base_asset_amount, -500_000_000);
+    }
+}
+
+mod calculate_lp_attribution {
+    use crate::math::constants::{
+        BASE_PRECISION_I64, BASE_PRECISION_U64, FUNDING_RATE_PRECISION_I128, PRICE_PRECISION_I64,
+        QUOTE_PRECISION_I128,
+    };
+    use crate::state::perp_lp_stats::PerpLpStats;
+
+    use super::*;
+
+    #[test]
+    fn test_long_inventory() {
+        let stats = PerpLpStats {
+            lp_shares: 100 * BASE_PRECISION_U64,
+            base_asset_amount: BASE_PRECISION_I64,
+            last_oracle_price: 100 * PRICE_PRECISION_I64,
+            ..PerpLpStats::default()
+        };
+
+        // lp shares bought 1 base for 100 and earned 1 in fees
+        let amm = AMM {
+            base_asset_amount_per_lp: 10_000_000,
+            quote_asset_amount_per_lp: -990_000,
+            fee_per_lp: 10_000,
+            cumulative_funding_rate_long: FUNDING_RATE_PRECISION_I128,
+            ..AMM::default_test()
+        };
+
+        let attribution =
+            calculate_lp_attribution(&amm, &stats, 101 * PRICE_PRECISION_I64).unwrap();
+
+        assert_eq!(
+            attribution,
+            LPAttribution {
+                base_asset_amount: BASE_PRECISION_I64 as i128,
+                fee_revenue: QUOTE_PRECISION_I128,
+                // longs pay 1 per base
+                funding_payment: -QUOTE_PRECISION_I128,
+                // old and new inventory both marked up 1
+                inventory_pnl: 2 * QUOTE_PRECISION_I128,
+            }
+        );
+    }
 }
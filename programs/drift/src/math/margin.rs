@@ -2,7 +2,8 @@ use crate::error::DriftResult;
 use crate::error::ErrorCode;
 use crate::math::constants::{
     MARGIN_PRECISION_U128, MAX_POSITIVE_UPNL_FOR_INITIAL_MARGIN, PRICE_PRECISION,
-    SPOT_IMF_PRECISION_U128, SPOT_WEIGHT_PRECISION, SPOT_WEIGHT_PRECISION_U128,
+    QUOTE_SPOT_MARKET_INDEX, SPOT_IMF_PRECISION_U128, SPOT_WEIGHT_PRECISION,
+    SPOT_WEIGHT_PRECISION_U128,
 };
 use crate::math::position::{
     calculate_base_asset_value_and_pnl_with_oracle_price,
@@ -28,6 +29,7 @@ use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_market::{AssetTier, SpotBalanceType, SpotMarket};
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::user::{PerpPosition, SpotPosition, User};
+use crate::state::user_map::UserMap;
 use num_integer::Roots;
 use solana_program::msg;
 use std::cmp::{max, min, Ordering};
@@ -630,8 +632,106 @@ pub fn calculate_margin_requirement_and_total_collateral(
     ))
 }
 
+/// Calculates the combined margin requirement and total collateral of the users in a shared
+/// collateral group. Only a member's quote deposits can be moved to cover another member, so its
+/// free collateral counts toward the group up to the value of those deposits
+pub fn calculate_shared_collateral_group_margin_requirement_and_total_collateral(
+    shared_collateral_group: &UserMap,
+    perp_market_map: &PerpMarketMap,
+    margin_requirement_type: MarginRequirementType,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    strict: bool,
+) -> DriftResult<(u128, i128)> {
+    let mut margin_requirement: u128 = 0;
+    let mut total_collateral: i128 = 0;
+
+    if shared_collateral_group.0.is_empty() {
+        return Ok((margin_requirement, total_collateral));
+    }
+
+    let quote_spot_market = spot_market_map.get_quote_spot_market()?;
+    let quote_oracle_price = oracle_map.get_price_data(&quote_spot_market.oracle)?.price;
+
+    for user_key in shared_collateral_group.0.keys() {
+        let user = shared_collateral_group.get_ref(user_key)?;
+        let (user_margin_requirement, user_total_collateral, _, _, _, _) =
+            calculate_margin_requirement_and_total_collateral_and_liability_info(
+                &user,
+                perp_market_map,
+                margin_requirement_type,
+                spot_market_map,
+                oracle_map,
+                None,
+                strict,
+            )?;
+
+        let quote_deposit_value = match user.get_spot_position(QUOTE_SPOT_MARKET_INDEX) {
+            Ok(spot_position) if spot_position.balance_type == SpotBalanceType::Deposit => {
+                get_token_value(
+                    spot_position.get_token_amount(&quote_spot_market)?.cast()?,
+                    quote_spot_market.decimals,
+                    quote_oracle_price,
+                )?
+            }
+            _ => 0,
+        };
+
+        let user_total_collateral = user_total_collateral.min(
+            user_margin_requirement
+                .cast::<i128>()?
+                .safe_add(quote_deposit_value)?,
+        );
+
+        margin_requirement = margin_requirement.safe_add(user_margin_requirement)?;
+        total_collateral = total_collateral.safe_add(user_total_collateral)?;
+    }
+
+    Ok((margin_requirement, total_collateral))
+}
+
+/// Whether a user and the other members of its shared collateral group meet the margin requirement
+/// as one unit
+pub fn meets_shared_collateral_group_margin_requirement(
+    user: &User,
+    shared_collateral_group: &UserMap,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    margin_requirement_type: MarginRequirementType,
+) -> DriftResult<bool> {
+    let (margin_requirement, total_collateral, _, _) =
+        calculate_margin_requirement_and_total_collateral(
+            user,
+            perp_market_map,
+            margin_requirement_type,
+            spot_market_map,
+            oracle_map,
+            None,
+        )?;
+
+    let (group_margin_requirement, group_total_collateral) =
+        calculate_shared_collateral_group_margin_requirement_and_total_collateral(
+            shared_collateral_group,
+            perp_market_map,
+            margin_requirement_type,
+            spot_market_map,
+            oracle_map,
+            false,
+        )?;
+
+    Ok(total_collateral.safe_add(group_total_collateral)?
+        >= margin_requirement
+            .safe_add(group_margin_requirement)?
+            .cast::<i128>()?)
+}
+
+/// shared_collateral_group is the other members of the user's shared collateral group. The user
+/// must meet its own margin requirement and the group must meet the combined requirement, so a
+/// member can't withdraw the collateral backing its own orders
 pub fn meets_withdraw_margin_requirement(
     user: &User,
+    shared_collateral_group: &UserMap,
     perp_market_map: &PerpMarketMap,
     spot_market_map: &SpotMarketMap,
     oracle_map: &mut OracleMap,
@@ -656,7 +756,17 @@ pub fn meets_withdraw_margin_requirement(
         strict,
     )?;
 
-    if initial_margin_requirement > 0 {
+    let (group_margin_requirement, group_total_collateral) =
+        calculate_shared_collateral_group_margin_requirement_and_total_collateral(
+            shared_collateral_group,
+            perp_market_map,
+            margin_requirement_type,
+            spot_market_map,
+            oracle_map,
+            strict,
+        )?;
+
+    if initial_margin_requirement.safe_add(group_margin_requirement)? > 0 {
         validate!(
             oracles_valid,
             ErrorCode::InvalidOracle,
@@ -680,6 +790,18 @@ pub fn meets_withdraw_margin_requirement(
         initial_margin_requirement
     )?;
 
+    let group_initial_margin_requirement =
+        initial_margin_requirement.safe_add(group_margin_requirement)?;
+    let group_total_collateral = total_collateral.safe_add(group_total_collateral)?;
+
+    validate!(
+        group_total_collateral >= group_initial_margin_requirement.cast::<i128>()?,
+        ErrorCode::InsufficientCollateral,
+        "User attempting to withdraw where shared collateral group total_collateral {} is below initial_margin_requirement {}",
+        group_total_collateral,
+        group_initial_margin_requirement
+    )?;
+
     Ok(true)
 }
 
@@ -769,6 +891,25 @@ pub fn meets_maintenance_margin_requirement(
     Ok(total_collateral >= margin_requirement.cast::<i128>()?)
 }
 
+/// The user's health from 0 to 100, where 0 means the user can be liquidated and 100 means the user
+/// has no maintenance margin requirement
+pub fn calculate_user_health(margin_requirement: u128, total_collateral: i128) -> DriftResult<u8> {
+    if margin_requirement == 0 {
+        return Ok(100);
+    }
+
+    if total_collateral <= 0 {
+        return Ok(0);
+    }
+
+    let margin_requirement_pct = margin_requirement
+        .safe_mul(100)?
+        .safe_div(total_collateral.unsigned_abs())?
+        .min(100);
+
+    100_u128.safe_sub(margin_requirement_pct)?.cast()
+}
+
 pub fn calculate_free_collateral(
     user: &User,
     perp_market_map: &PerpMarketMap,
@@ -3102,3 +3102,252 @@ mod calculate_user_equity {
         assert_eq!(net_usd_value, 1000000000);
     }
 }
+
+mod meets_shared_collateral_group_margin_requirement {
+    use std::str::FromStr;
+
+    use anchor_lang::Owner;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::math::constants::{
+        SPOT_BALANCE_PRECISION_U64, SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::math::margin::{
+        meets_maintenance_margin_requirement, meets_shared_collateral_group_margin_requirement,
+        meets_withdraw_margin_requirement, MarginRequirementType,
+    };
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::user::{Order, PerpPosition, SpotPosition, User};
+    use crate::state::user_map::UserMap;
+    use crate::test_utils::get_pyth_price;
+    use crate::test_utils::*;
+    use crate::{
+        create_account_info, BASE_PRECISION_I64, PRICE_PRECISION_I64, QUOTE_PRECISION_I64,
+    };
+    use crate::{
+        create_anchor_account_info, MarketStatus, AMM_RESERVE_PRECISION, PEG_PRECISION,
+        PRICE_PRECISION,
+    };
+
+    #[test]
+    pub fn sibling_deposit_covers_perp_position() {
+        let slot = 0_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                base_spread: 0,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 2000,
+            margin_ratio_maintenance: 1000,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut usdc_spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(usdc_spot_market, SpotMarket, usdc_spot_market_account_info);
+        let spot_market_account_infos = Vec::from([&usdc_spot_market_account_info]);
+        let spot_market_map =
+            SpotMarketMap::load_multiple(spot_market_account_infos, true).unwrap();
+
+        // $100 long with no collateral
+        let user = User {
+            orders: [Order::default(); 32],
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+
+        assert!(!meets_maintenance_margin_requirement(
+            &user,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map
+        )
+        .unwrap());
+
+        // $15 deposit in sibling
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 15 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        let mut sibling = User {
+            orders: [Order::default(); 32],
+            spot_positions,
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+        let sibling_key = Pubkey::new_unique();
+        create_anchor_account_info!(sibling, &sibling_key, User, sibling_account_info);
+        let shared_collateral_group = UserMap::load_one(&sibling_account_info).unwrap();
+
+        // maintenance requirement is $10
+        assert!(meets_shared_collateral_group_margin_requirement(
+            &user,
+            &shared_collateral_group,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            MarginRequirementType::Maintenance,
+        )
+        .unwrap());
+
+        // initial requirement is $20
+        assert!(!meets_shared_collateral_group_margin_requirement(
+            &user,
+            &shared_collateral_group,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            MarginRequirementType::Initial,
+        )
+        .unwrap());
+
+        // empty group is the same as the user alone
+        assert!(!meets_shared_collateral_group_margin_requirement(
+            &user,
+            &UserMap::empty(),
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            MarginRequirementType::Maintenance,
+        )
+        .unwrap());
+
+        // $100 deposit in sibling
+        let mut spot_positions = [SpotPosition::default(); 8];
+        spot_positions[0] = SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        };
+        let mut rich_sibling = User {
+            orders: [Order::default(); 32],
+            spot_positions,
+            is_in_shared_collateral_group: true,
+            ..User::default()
+        };
+        let rich_sibling_key = Pubkey::new_unique();
+        create_anchor_account_info!(
+            rich_sibling,
+            &rich_sibling_key,
+            User,
+            rich_sibling_account_info
+        );
+        let shared_collateral_group = UserMap::load_one(&rich_sibling_account_info).unwrap();
+
+        assert!(meets_shared_collateral_group_margin_requirement(
+            &user,
+            &shared_collateral_group,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            MarginRequirementType::Initial,
+        )
+        .unwrap());
+
+        // user still has to meet its own initial margin requirement to withdraw
+        assert!(meets_withdraw_margin_requirement(
+            &user,
+            &shared_collateral_group,
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            MarginRequirementType::Initial,
+        )
+        .is_err());
+    }
+}
+
+mod calculate_user_health {
+    use crate::math::constants::{QUOTE_PRECISION, QUOTE_PRECISION_I128};
+    use crate::math::margin::calculate_user_health;
+
+    #[test]
+    fn no_margin_requirement() {
+        let health = calculate_user_health(0, 0).unwrap();
+        assert_eq!(health, 100);
+    }
+
+    #[test]
+    fn no_collateral() {
+        let health = calculate_user_health(10 * QUOTE_PRECISION, -QUOTE_PRECISION_I128).unwrap();
+        assert_eq!(health, 0);
+    }
+
+    #[test]
+    fn partial() {
+        let health =
+            calculate_user_health(25 * QUOTE_PRECISION, 100 * QUOTE_PRECISION_I128).unwrap();
+        assert_eq!(health, 75);
+
+        // below maintenance margin requirement
+        let health =
+            calculate_user_health(150 * QUOTE_PRECISION, 100 * QUOTE_PRECISION_I128).unwrap();
+        assert_eq!(health, 0);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod amm;
+pub mod amm_hedge;
 pub mod amm_jit;
 pub mod amm_spread;
 pub mod auction;
@@ -6,6 +7,7 @@ pub mod bankruptcy;
 pub mod bn;
 pub mod casting;
 pub mod ceil_div;
+pub mod circuit_breaker;
 pub mod constants;
 pub mod cp_curve;
 pub mod fees;
@@ -10,7 +10,8 @@ use crate::math::constants::BID_ASK_SPREAD_PRECISION;
 use crate::math::safe_math::SafeMath;
 
 use crate::state::oracle::OraclePriceData;
-use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+use crate::state::paused_operations::MarketOperation;
+use crate::state::perp_market::{PerpMarket, AMM};
 use crate::state::state::{OracleGuardRails, ValidityGuardRails};
 
 #[cfg(test)]
@@ -120,7 +121,7 @@ pub fn block_operation(
     let is_oracle_valid =
         is_oracle_valid_for_action(oracle_validity, Some(DriftAction::UpdateFunding))?;
 
-    let funding_paused_on_market = market.status == MarketStatus::FundingPaused;
+    let funding_paused_on_market = market.is_operation_paused(MarketOperation::Funding)?;
 
     let block = !is_oracle_valid || is_oracle_mark_too_divergent || funding_paused_on_market;
     Ok(block)
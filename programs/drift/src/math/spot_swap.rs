@@ -1,7 +1,12 @@
 use crate::error::DriftResult;
+use crate::math::casting::Cast;
 use crate::math::safe_math::SafeMath;
+use crate::math::spot_balance::get_token_value;
 use crate::PRICE_PRECISION;
 
+#[cfg(test)]
+mod tests;
+
 pub fn calculate_swap_price(
     asset_amount: u128,
     liability_amount: u128,
@@ -14,3 +19,30 @@ pub fn calculate_swap_price(
         .safe_mul(10_u128.pow(liability_decimals))?
         .safe_div(liability_amount)
 }
+
+/// Calculates the in tokens that left the user's account without being replaced by out tokens of
+/// equal oracle value
+///
+/// Returns the net outflow, precision: in token mint precision
+pub fn calculate_swap_net_outflow(
+    amount_in: u64,
+    in_decimals: u32,
+    in_oracle_price: i64,
+    amount_out: u64,
+    out_decimals: u32,
+    out_oracle_price: i64,
+) -> DriftResult<u64> {
+    let in_value = get_token_value(amount_in.cast()?, in_decimals, in_oracle_price)?;
+    let out_value = get_token_value(amount_out.cast()?, out_decimals, out_oracle_price)?;
+
+    if out_value >= in_value || in_oracle_price <= 0 {
+        return Ok(0);
+    }
+
+    in_value
+        .safe_sub(out_value)?
+        .safe_mul(10_i128.pow(in_decimals))?
+        .safe_div(in_oracle_price.cast()?)?
+        .min(amount_in.cast()?)
+        .cast()
+}
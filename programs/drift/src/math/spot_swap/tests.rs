@@ -0,0 +1,49 @@
+mod calculate_swap_net_outflow {
+    use crate::math::constants::{LAMPORTS_PER_SOL_U64, PRICE_PRECISION_I64, QUOTE_PRECISION_U64};
+    use crate::math::spot_swap::calculate_swap_net_outflow;
+
+    #[test]
+    fn fair_swap() {
+        let net_outflow = calculate_swap_net_outflow(
+            100 * QUOTE_PRECISION_U64,
+            6,
+            PRICE_PRECISION_I64,
+            LAMPORTS_PER_SOL_U64,
+            9,
+            100 * PRICE_PRECISION_I64,
+        )
+        .unwrap();
+
+        assert_eq!(net_outflow, 0);
+    }
+
+    #[test]
+    fn swap_below_oracle() {
+        let net_outflow = calculate_swap_net_outflow(
+            100 * QUOTE_PRECISION_U64,
+            6,
+            PRICE_PRECISION_I64,
+            LAMPORTS_PER_SOL_U64 / 2,
+            9,
+            100 * PRICE_PRECISION_I64,
+        )
+        .unwrap();
+
+        assert_eq!(net_outflow, 50 * QUOTE_PRECISION_U64);
+    }
+
+    #[test]
+    fn nothing_received() {
+        let net_outflow = calculate_swap_net_outflow(
+            LAMPORTS_PER_SOL_U64,
+            9,
+            100 * PRICE_PRECISION_I64,
+            0,
+            6,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+
+        assert_eq!(net_outflow, LAMPORTS_PER_SOL_U64);
+    }
+}
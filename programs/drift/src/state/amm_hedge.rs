@@ -0,0 +1,157 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::casting::Cast;
+use crate::math::constants::{MAX_AMM_HEDGE_SLIPPAGE, PERCENTAGE_PRECISION};
+use crate::math::safe_math::SafeMath;
+use crate::math::spot_balance::{get_signed_token_amount, get_token_amount};
+use crate::state::spot_market::{
+    SpotBalance, SpotBalanceType, SpotFulfillmentConfigStatus, SpotMarket,
+};
+use crate::state::traits::Size;
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct AmmHedgeConfig {
+    pub pubkey: Pubkey,
+    /// The spot tokens held (deposit) or borrowed (borrow) by the amm to offset its perp inventory
+    /// market_index is the linked spot market
+    pub hedge_balance: AmmHedgeBalance,
+    /// The quote paid for the tokens held or received for the tokens borrowed by the hedge
+    /// precision: QUOTE_PRECISION
+    pub quote_entry_amount: u64,
+    /// The cumulative pnl from closing hedges, booked into the perp market's fee pool
+    /// precision: QUOTE_PRECISION
+    pub total_realized_pnl: i64,
+    /// The cumulative fees paid to external markets while hedging
+    /// precision: QUOTE_PRECISION
+    pub total_fee_paid: u64,
+    /// The minimum change in the hedge before a trade is executed
+    /// precision: token mint precision
+    pub min_hedge_base_asset_amount: u64,
+    /// The maximum amount of tokens the hedge can hold or borrow
+    /// precision: token mint precision
+    pub max_hedge_base_asset_amount: u64,
+    pub last_hedge_ts: i64,
+    /// The fraction of the amm's inventory to offset
+    /// precision: PERCENTAGE_PRECISION
+    pub hedge_ratio: u32,
+    /// The max difference between the hedge's limit price and the oracle price
+    /// precision: PERCENTAGE_PRECISION
+    pub max_slippage: u32,
+    pub perp_market_index: u16,
+    pub status: SpotFulfillmentConfigStatus,
+    pub padding1: u8,
+    /// The minimum number of seconds between hedges
+    pub min_hedge_interval: u32,
+    pub padding: [u8; 8],
+}
+
+impl Size for AmmHedgeConfig {
+    const SIZE: usize = 136;
+}
+
+impl AmmHedgeConfig {
+    pub fn spot_market_index(&self) -> u16 {
+        self.hedge_balance.market_index
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status == SpotFulfillmentConfigStatus::Enabled
+    }
+
+    /// Returns the tokens held (positive) or borrowed (negative) by the hedge, precision: token mint precision
+    pub fn get_hedge_base_asset_amount(&self, spot_market: &SpotMarket) -> DriftResult<i64> {
+        get_signed_token_amount(
+            self.hedge_balance.get_token_amount(spot_market)?,
+            &self.hedge_balance.balance_type,
+        )?
+        .cast()
+    }
+
+    pub fn can_hedge(&self, now: i64) -> DriftResult<bool> {
+        Ok(now.safe_sub(self.last_hedge_ts)? >= self.min_hedge_interval.cast()?)
+    }
+
+    pub fn validate(&self) -> DriftResult {
+        validate!(
+            self.hedge_ratio.cast::<u128>()? <= PERCENTAGE_PRECISION,
+            ErrorCode::InvalidAmmHedgeConfig,
+            "hedge ratio {} must be <= PERCENTAGE_PRECISION",
+            self.hedge_ratio
+        )?;
+
+        validate!(
+            self.max_slippage <= MAX_AMM_HEDGE_SLIPPAGE,
+            ErrorCode::InvalidAmmHedgeConfig,
+            "max slippage {} must be <= {}",
+            self.max_slippage,
+            MAX_AMM_HEDGE_SLIPPAGE
+        )?;
+
+        validate!(
+            self.min_hedge_interval > 0,
+            ErrorCode::InvalidAmmHedgeConfig,
+            "min hedge interval must be > 0"
+        )?;
+
+        validate!(
+            self.min_hedge_base_asset_amount <= self.max_hedge_base_asset_amount,
+            ErrorCode::InvalidAmmHedgeConfig,
+            "min hedge base asset amount {} > max hedge base asset amount {}",
+            self.min_hedge_base_asset_amount,
+            self.max_hedge_base_asset_amount
+        )?;
+
+        Ok(())
+    }
+}
+
+#[zero_copy]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct AmmHedgeBalance {
+    /// To get the token amount, multiply by the market's cumulative deposit or borrow interest
+    /// precision: SPOT_BALANCE_PRECISION
+    pub scaled_balance: u128,
+    /// The spot market the hedge is for
+    pub market_index: u16,
+    pub balance_type: SpotBalanceType,
+    pub padding: [u8; 5],
+}
+
+impl SpotBalance for AmmHedgeBalance {
+    fn market_index(&self) -> u16 {
+        self.market_index
+    }
+
+    fn balance_type(&self) -> &SpotBalanceType {
+        &self.balance_type
+    }
+
+    fn balance(&self) -> u128 {
+        self.scaled_balance
+    }
+
+    fn increase_balance(&mut self, delta: u128) -> DriftResult {
+        self.scaled_balance = self.scaled_balance.safe_add(delta)?;
+        Ok(())
+    }
+
+    fn decrease_balance(&mut self, delta: u128) -> DriftResult {
+        self.scaled_balance = self.scaled_balance.safe_sub(delta)?;
+        Ok(())
+    }
+
+    fn update_balance_type(&mut self, balance_type: SpotBalanceType) -> DriftResult {
+        self.balance_type = balance_type;
+        Ok(())
+    }
+}
+
+impl AmmHedgeBalance {
+    pub fn get_token_amount(&self, spot_market: &SpotMarket) -> DriftResult<u128> {
+        get_token_amount(self.scaled_balance, spot_market, &self.balance_type)
+    }
+}
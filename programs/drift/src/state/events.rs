@@ -5,6 +5,7 @@ use crate::controller::position::PositionDirection;
 use crate::error::{DriftResult, ErrorCode::InvalidOrder};
 use crate::math::casting::Cast;
 use crate::math::safe_unwrap::SafeUnwrap;
+use crate::state::insurance_fund_stake::InsuranceFundTranche;
 use crate::state::traits::Size;
 use crate::state::user::{MarketType, Order};
 use anchor_lang::Discriminator;
@@ -44,6 +45,7 @@ pub struct DepositRecord {
 pub enum DepositExplanation {
     None,
     Transfer,
+    InsuranceFundRevenue,
 }
 
 impl Default for DepositExplanation {
@@ -280,6 +282,9 @@ pub enum OrderActionExplanation {
     OrderFillWithPhoenix,
     OrderFilledWithAMMJitLPSplit,
     OrderFilledWithLPJit,
+    OrderFillWithConstantProductPool,
+    DeleverageOrder,
+    LpHedgeOrder,
 }
 
 impl Default for OrderAction {
@@ -300,6 +305,13 @@ pub struct LPRecord {
     pub delta_base_asset_amount: i64,
     pub delta_quote_asset_amount: i64,
     pub pnl: i64,
+    /// only set when the perp lp stats are passed
+    /// precision: QUOTE_PRECISION
+    pub fee_revenue: i64,
+    /// precision: QUOTE_PRECISION
+    pub funding_payment: i64,
+    /// precision: QUOTE_PRECISION
+    pub inventory_pnl: i64,
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
@@ -307,10 +319,18 @@ pub enum LPAction {
     AddLiquidity,
     RemoveLiquidity,
     SettleLiquidity,
+    /// Lp shares moved from a position into the tokenized lp pool
+    TokenizeLiquidity,
+    /// Lp tokens burned for lp shares in a position
+    RedeemLiquidity,
+    /// Ranged lp shares taken out of the amm when the reserve price left the range
+    DeactivateLiquidity,
+    /// Ranged lp shares added back to the amm when the reserve price re-entered the range
+    ActivateLiquidity,
 }
 
 impl Size for LPRecord {
-    const SIZE: usize = 112;
+    const SIZE: usize = 136;
 }
 
 impl Default for LPAction {
@@ -338,6 +358,7 @@ pub struct LiquidationRecord {
     pub liquidate_perp_pnl_for_deposit: LiquidatePerpPnlForDepositRecord,
     pub perp_bankruptcy: PerpBankruptcyRecord,
     pub spot_bankruptcy: SpotBankruptcyRecord,
+    pub liquidate_perp_lp: LiquidatePerpLpRecord,
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
@@ -348,6 +369,8 @@ pub enum LiquidationType {
     LiquidatePerpPnlForDeposit,
     PerpBankruptcy,
     SpotBankruptcy,
+    LiquidatePerpWithAmm,
+    LiquidatePerpLp,
 }
 
 impl Default for LiquidationType {
@@ -371,6 +394,21 @@ pub struct LiquidatePerpRecord {
     pub if_fee: u64,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct LiquidatePerpLpRecord {
+    pub market_index: u16,
+    pub oracle_price: i64,
+    /// The lp shares burned
+    pub lp_shares: u64,
+    /// The base and quote realized by the user from settling and burning the lp shares
+    pub base_asset_amount: i64,
+    pub quote_asset_amount: i64,
+    pub pnl: i64,
+    /// The fee paid to the liquidator, a fraction of the margin freed
+    /// precision: QUOTE_PRECISION
+    pub liquidator_fee: u64,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
 pub struct LiquidateSpotRecord {
     pub asset_market_index: u16,
@@ -410,6 +448,12 @@ pub struct PerpBankruptcyRecord {
     pub clawback_user: Option<Pubkey>,
     pub clawback_user_payment: Option<u128>,
     pub cumulative_funding_rate_delta: i128,
+    /// The loss covered by auto-deleveraging profitable positions
+    /// precision: QUOTE_PRECISION
+    pub auto_deleverage_payment: u128,
+    /// The loss covered by the perp market's dedicated insurance fund
+    /// precision: QUOTE_PRECISION
+    pub perp_if_payment: u128,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
@@ -462,6 +506,27 @@ pub struct InsuranceFundRecord {
     pub amount: i64,
 }
 
+#[event]
+#[derive(Default)]
+pub struct ProtocolRevenueRecord {
+    pub ts: i64,
+    pub spot_market_index: u16,
+    /// precision: token mint precision
+    pub amount: u64,
+    /// precision: token mint precision
+    pub revenue_pool_amount_before: u64,
+    pub vault_amount_before: u64,
+    /// Cumulative ledger after the withdraw. settled_to_insurance_fund reconciles against
+    /// the sum of InsuranceFundRecord amounts for the market
+    pub taker_fees: u64,
+    pub liquidation_fees: u64,
+    pub borrow_interest: u64,
+    pub amm_fees: u64,
+    pub amm_fee_pool_top_ups: u64,
+    pub settled_to_insurance_fund: u64,
+    pub withdrawn: u64,
+}
+
 #[event]
 #[derive(Default)]
 pub struct InsuranceFundStakeRecord {
@@ -470,6 +535,9 @@ pub struct InsuranceFundStakeRecord {
     pub action: StakeAction,
     pub amount: u64,
     pub market_index: u16,
+    /// Whether the stake is in a spot market's insurance fund or a perp market's dedicated pool
+    pub market_type: MarketType,
+    pub tranche: InsuranceFundTranche,
 
     pub insurance_vault_amount_before: u64,
     pub if_shares_before: u128,
@@ -486,6 +554,9 @@ pub enum StakeAction {
     UnstakeRequest,
     UnstakeCancelRequest,
     Unstake,
+    Tokenize,
+    RedeemTokens,
+    ClaimRevenue,
 }
 
 impl Default for StakeAction {
@@ -508,6 +579,143 @@ pub struct SwapRecord {
     pub fee: u64,
 }
 
+#[event]
+#[derive(Default)]
+pub struct AmmHedgeRecord {
+    pub ts: i64,
+    pub perp_market_index: u16,
+    pub spot_market_index: u16,
+    pub direction: PositionDirection,
+    /// precision: token mint precision
+    pub base_asset_amount_filled: u64,
+    /// quote paid from or received by the fee pool, including fees
+    /// precision: QUOTE_PRECISION
+    pub quote_asset_amount: u64,
+    /// precision: QUOTE_PRECISION
+    pub external_market_fee: u64,
+    /// positive when holding tokens, negative when borrowing
+    /// precision: token mint precision
+    pub hedge_base_asset_amount_after: i64,
+    /// precision: QUOTE_PRECISION
+    pub quote_entry_amount_after: u64,
+    /// precision: QUOTE_PRECISION
+    pub realized_pnl: i64,
+    /// precision: BASE_PRECISION
+    pub base_asset_amount_with_amm: i128,
+    /// precision: PRICE_PRECISION
+    pub oracle_price: i64,
+}
+
+#[event]
+#[derive(Default)]
+pub struct CircuitBreakerRecord {
+    pub ts: i64,
+    pub market_index: u16,
+    pub action: CircuitBreakerAction,
+    /// precision: PRICE_PRECISION
+    pub oracle_price: i64,
+    /// precision: PRICE_PRECISION
+    pub oracle_price_twap_5min: i64,
+    /// precision: PRICE_PRECISION
+    pub mark_price: u64,
+    /// precision: PRICE_PRECISION
+    pub mark_price_twap_5min: u64,
+    pub cooldown_end_ts: i64,
+}
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug)]
+pub enum CircuitBreakerAction {
+    Trip,
+    Reset,
+}
+
+impl Default for CircuitBreakerAction {
+    fn default() -> Self {
+        CircuitBreakerAction::Trip
+    }
+}
+
+#[event]
+#[derive(Default)]
+pub struct WithdrawPolicyRecord {
+    pub ts: i64,
+    pub authority: Pubkey,
+    pub action: WithdrawPolicyAction,
+    pub time_lock_period: i64,
+    pub allowed_destinations: Vec<Pubkey>,
+    /// the destination of the pending withdrawal, if any
+    pub pending_destination: Option<Pubkey>,
+    pub pending_market_index: u16,
+    /// precision: token mint precision
+    pub pending_amount: u64,
+    pub pending_unlock_ts: i64,
+    /// the time the policy can be deleted, 0 if removal wasn't requested
+    pub removal_unlock_ts: i64,
+}
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug)]
+pub enum WithdrawPolicyAction {
+    Initialize,
+    Update,
+    RequestWithdrawal,
+    CancelWithdrawal,
+    RequestRemoval,
+    CancelRemoval,
+    Delete,
+}
+
+impl Default for WithdrawPolicyAction {
+    fn default() -> Self {
+        WithdrawPolicyAction::Initialize
+    }
+}
+
+#[event]
+#[derive(Default)]
+pub struct AutoDeleverageRecord {
+    pub ts: i64,
+    /// The user whose position was deleveraged
+    pub user: Pubkey,
+    /// The bankrupt user whose loss was covered
+    pub bankrupt_user: Pubkey,
+    pub market_index: u16,
+    /// The price the liquidator took over the base closed at
+    /// precision: PRICE_PRECISION
+    pub oracle_price: i64,
+    /// The price the position effectively exited at after giving up the loss covered
+    /// precision: PRICE_PRECISION
+    pub bankruptcy_price: u64,
+    /// The base closed
+    /// precision: BASE_PRECISION
+    pub base_asset_amount: i64,
+    /// The quote the position was closed for at the oracle price
+    /// precision: QUOTE_PRECISION
+    pub quote_asset_amount: u64,
+    /// The pnl realized closing the position, before covering the loss
+    /// precision: QUOTE_PRECISION
+    pub pnl: i64,
+    /// The pnl given up to cover the bankrupt user's loss
+    /// precision: QUOTE_PRECISION
+    pub loss_covered: u64,
+}
+
+#[event]
+#[derive(Default)]
+pub struct HealthRecord {
+    pub ts: i64,
+    pub user: Pubkey,
+    pub health_before: u8,
+    pub health: u8,
+    /// The number of health bands the user's health is below
+    pub health_band_before: u8,
+    pub health_band: u8,
+    /// precision: QUOTE_PRECISION
+    pub margin_requirement: u128,
+    /// precision: QUOTE_PRECISION
+    pub total_collateral: i128,
+    pub slots_below_health_threshold: u64,
+}
+
 pub fn emit_stack<T: AnchorSerialize + Discriminator, const N: usize>(event: T) -> DriftResult {
     let mut data_buf = [0u8; N];
     let mut out_buf = [0u8; N];
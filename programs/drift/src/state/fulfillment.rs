@@ -6,8 +6,9 @@ pub enum PerpFulfillmentMethod {
     Match(Pubkey, u16),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum SpotFulfillmentMethod {
-    ExternalMarket,
+    /// Index of the external market's fulfillment params and the price the fill is limited to
+    ExternalMarket(usize, Option<u64>),
     Match,
 }
@@ -0,0 +1,937 @@
+use anchor_lang::{prelude::*, ToAccountInfo};
+use anchor_spl::token::{Token, TokenAccount};
+use arrayref::array_ref;
+use solana_program::instruction::Instruction;
+use solana_program::{msg, program::invoke_signed_unchecked};
+use std::cell::Ref;
+use std::mem::size_of;
+
+use crate::{
+    controller::position::PositionDirection,
+    error::{DriftResult, ErrorCode},
+    instructions::SpotFulfillmentType,
+    load,
+    math::{
+        bn::U192, casting::Cast, orders::standardize_base_asset_amount, safe_math::SafeMath,
+        spot_withdraw::validate_spot_market_vault_amount,
+    },
+    signer::get_signer_seeds,
+    state::{
+        events::OrderActionExplanation,
+        spot_fulfillment_params::{ExternalSpotFill, SpotFulfillmentParams},
+        spot_market::{SpotBalanceType, SpotFulfillmentConfigStatus, SpotMarket},
+        state::State,
+        traits::Size,
+    },
+    validate,
+};
+
+/// Anchor account discriminator of the ConstantProductPool account
+pub const CONSTANT_PRODUCT_POOL_DISCRIMINATOR: [u8; 8] = [38, 75, 99, 240, 193, 144, 20, 169];
+/// Anchor instruction discriminator of swap(amount_in, minimum_amount_out)
+pub const CONSTANT_PRODUCT_POOL_SWAP_DISCRIMINATOR: [u8; 8] =
+    [248, 198, 158, 145, 225, 117, 135, 200];
+/// Anchor instruction discriminator of swap_exact_out(amount_out, maximum_amount_in)
+pub const CONSTANT_PRODUCT_POOL_SWAP_EXACT_OUT_DISCRIMINATOR: [u8; 8] =
+    [250, 73, 101, 33, 38, 207, 75, 184];
+
+/// Layout of the pool account any constant product pool program must expose to be used for fulfillment
+#[zero_copy]
+#[derive(Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct ConstantProductPool {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub pool_signer: Pubkey,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    pub pool_signer_nonce: u8,
+    pub padding: [u8; 7],
+}
+
+#[account(zero_copy)]
+#[derive(Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct ConstantProductPoolFulfillmentConfig {
+    pub pubkey: Pubkey,
+    pub pool_program_id: Pubkey,
+    pub pool: Pubkey,
+    pub pool_signer: Pubkey,
+    pub pool_base_vault: Pubkey,
+    pub pool_quote_vault: Pubkey,
+    pub market_index: u16,
+    pub fulfillment_type: SpotFulfillmentType,
+    pub status: SpotFulfillmentConfigStatus,
+    pub padding: [u8; 4],
+}
+
+impl Size for ConstantProductPoolFulfillmentConfig {
+    const SIZE: usize = 208;
+}
+
+pub fn load_constant_product_pool(
+    pool: &AccountInfo,
+    pool_program_id: &Pubkey,
+) -> DriftResult<ConstantProductPool> {
+    validate!(
+        pool.owner == pool_program_id,
+        ErrorCode::InvalidConstantProductPool,
+        "Pool must be owned by the pool program",
+    )?;
+
+    let data = pool
+        .try_borrow_data()
+        .map_err(|_| ErrorCode::InvalidConstantProductPool)?;
+
+    validate!(
+        data.len() >= 8 + size_of::<ConstantProductPool>(),
+        ErrorCode::InvalidConstantProductPool,
+        "Invalid pool data length"
+    )?;
+
+    validate!(
+        data[..8] == CONSTANT_PRODUCT_POOL_DISCRIMINATOR,
+        ErrorCode::InvalidConstantProductPool,
+        "Invalid pool discriminator"
+    )?;
+
+    let pool = bytemuck::try_from_bytes::<ConstantProductPool>(
+        &data[8..8 + size_of::<ConstantProductPool>()],
+    )
+    .map_err(|_| {
+        msg!("Failed to parse constant product pool");
+        ErrorCode::InvalidConstantProductPool
+    })?;
+
+    validate!(
+        pool.fee_denominator > 0 && pool.fee_numerator < pool.fee_denominator,
+        ErrorCode::InvalidConstantProductPool,
+        "Invalid pool fee"
+    )?;
+
+    Ok(*pool)
+}
+
+impl ConstantProductPool {
+    pub fn to_constant_product_pool_fulfillment_config(
+        &self,
+        config_key: &Pubkey,
+        pool_program_id: &Pubkey,
+        pool_key: &Pubkey,
+        market_index: u16,
+    ) -> ConstantProductPoolFulfillmentConfig {
+        ConstantProductPoolFulfillmentConfig {
+            pubkey: *config_key,
+            pool_program_id: *pool_program_id,
+            pool: *pool_key,
+            pool_signer: self.pool_signer,
+            pool_base_vault: self.base_vault,
+            pool_quote_vault: self.quote_vault,
+            market_index,
+            fulfillment_type: SpotFulfillmentType::ConstantProductPool,
+            status: SpotFulfillmentConfigStatus::Enabled,
+            padding: [0; 4],
+        }
+    }
+}
+
+/// Returns the pool's best bid and ask, in PRICE_PRECISION, after accounting for the pool fee
+pub fn calculate_constant_product_pool_bid_ask(
+    base_reserve: u64,
+    quote_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    base_precision: u64,
+) -> DriftResult<(Option<u64>, Option<u64>)> {
+    if base_reserve == 0 || quote_reserve == 0 {
+        return Ok((None, None));
+    }
+
+    let reserve_price = quote_reserve
+        .cast::<u128>()?
+        .safe_mul(base_precision.cast()?)?
+        .safe_div(base_reserve.cast()?)?;
+
+    let fee_complement = fee_denominator.safe_sub(fee_numerator)?.cast::<u128>()?;
+
+    let bid = reserve_price
+        .safe_mul(fee_complement)?
+        .safe_div(fee_denominator.cast()?)?
+        .cast::<u64>()?;
+
+    let ask = reserve_price
+        .safe_mul(fee_denominator.cast()?)?
+        .safe_div_ceil(fee_complement)?
+        .cast::<u64>()?;
+
+    Ok((Some(bid).filter(|bid| *bid > 0), Some(ask)))
+}
+
+/// Amount received for swapping exactly amount_in into the pool. Mirrors the pool's swap instruction
+pub fn calculate_constant_product_pool_amount_out(
+    amount_in: u64,
+    source_reserve: u64,
+    destination_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> DriftResult<u64> {
+    let amount_in_after_fee = amount_in
+        .cast::<u128>()?
+        .safe_mul(fee_denominator.safe_sub(fee_numerator)?.cast()?)?
+        .safe_div(fee_denominator.cast()?)?;
+
+    destination_reserve
+        .cast::<u128>()?
+        .safe_mul(amount_in_after_fee)?
+        .safe_div(
+            source_reserve
+                .cast::<u128>()?
+                .safe_add(amount_in_after_fee)?,
+        )?
+        .cast()
+}
+
+/// Amount that must be paid to receive exactly amount_out from the pool. Mirrors the pool's swap_exact_out instruction
+pub fn calculate_constant_product_pool_amount_in(
+    amount_out: u64,
+    source_reserve: u64,
+    destination_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> DriftResult<u64> {
+    let amount_in_after_fee = source_reserve
+        .cast::<u128>()?
+        .safe_mul(amount_out.cast()?)?
+        .safe_div_ceil(
+            destination_reserve
+                .cast::<u128>()?
+                .safe_sub(amount_out.cast()?)?,
+        )?;
+
+    amount_in_after_fee
+        .safe_mul(fee_denominator.cast()?)?
+        .safe_div_ceil(fee_denominator.safe_sub(fee_numerator)?.cast()?)?
+        .cast()
+}
+
+/// Calculates the base asset amount to swap with the pool so that the pool price after the swap (including fees)
+/// does not cross the taker limit price.
+///
+/// Returns (base_asset_amount, quote_asset_amount). For longs, the quote asset amount is the amount paid into the pool.
+/// For shorts, it is the amount received from the pool.
+pub fn calculate_constant_product_pool_fill_amounts(
+    taker_direction: PositionDirection,
+    taker_price: u64,
+    taker_base_asset_amount: u64,
+    taker_max_quote_asset_amount: u64,
+    base_reserve: u64,
+    quote_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    base_precision: u64,
+    base_step_size: u64,
+) -> DriftResult<(u64, u64)> {
+    if taker_price == 0 || base_reserve == 0 || quote_reserve == 0 {
+        return Ok((0, 0));
+    }
+
+    let invariant = U192::from(base_reserve).safe_mul(U192::from(quote_reserve))?;
+    let fee_complement = fee_denominator.safe_sub(fee_numerator)?;
+
+    match taker_direction {
+        PositionDirection::Long => {
+            // ask after swap = k / base_reserve_after^2 * fee_denominator / fee_complement <= taker_price
+            let min_base_reserve_squared = invariant
+                .safe_mul(U192::from(fee_denominator))?
+                .safe_mul(U192::from(base_precision))?
+                .safe_div_ceil(U192::from(fee_complement).safe_mul(U192::from(taker_price))?)?;
+
+            let mut min_base_reserve = min_base_reserve_squared.integer_sqrt();
+            if min_base_reserve.safe_mul(min_base_reserve)? < min_base_reserve_squared {
+                min_base_reserve = min_base_reserve.safe_add(U192::one())?;
+            }
+            let min_base_reserve = min_base_reserve.try_to_u64()?;
+
+            if min_base_reserve >= base_reserve {
+                return Ok((0, 0));
+            }
+
+            let mut base_asset_amount = standardize_base_asset_amount(
+                base_reserve
+                    .safe_sub(min_base_reserve)?
+                    .min(taker_base_asset_amount),
+                base_step_size,
+            )?;
+
+            if base_asset_amount == 0 {
+                return Ok((0, 0));
+            }
+
+            let mut quote_asset_amount = calculate_constant_product_pool_amount_in(
+                base_asset_amount,
+                quote_reserve,
+                base_reserve,
+                fee_numerator,
+                fee_denominator,
+            )?;
+
+            if quote_asset_amount > taker_max_quote_asset_amount {
+                let max_base_asset_amount = calculate_constant_product_pool_amount_out(
+                    taker_max_quote_asset_amount,
+                    quote_reserve,
+                    base_reserve,
+                    fee_numerator,
+                    fee_denominator,
+                )?;
+
+                base_asset_amount = standardize_base_asset_amount(
+                    base_asset_amount.min(max_base_asset_amount),
+                    base_step_size,
+                )?;
+
+                if base_asset_amount == 0 {
+                    return Ok((0, 0));
+                }
+
+                quote_asset_amount = calculate_constant_product_pool_amount_in(
+                    base_asset_amount,
+                    quote_reserve,
+                    base_reserve,
+                    fee_numerator,
+                    fee_denominator,
+                )?;
+            }
+
+            Ok((base_asset_amount, quote_asset_amount))
+        }
+        PositionDirection::Short => {
+            // bid after swap = k / base_reserve_after^2 * fee_complement / fee_denominator >= taker_price
+            let max_base_reserve_squared = invariant
+                .safe_mul(U192::from(fee_complement))?
+                .safe_mul(U192::from(base_precision))?
+                .safe_div(U192::from(fee_denominator).safe_mul(U192::from(taker_price))?)?;
+
+            let max_base_reserve = max_base_reserve_squared.integer_sqrt().try_to_u64()?;
+
+            if max_base_reserve <= base_reserve {
+                return Ok((0, 0));
+            }
+
+            // base amount that makes it into the reserves is reduced by the fee
+            let max_base_asset_amount = max_base_reserve
+                .safe_sub(base_reserve)?
+                .cast::<u128>()?
+                .safe_mul(fee_denominator.cast()?)?
+                .safe_div(fee_complement.cast()?)?
+                .cast::<u64>()?;
+
+            let base_asset_amount = standardize_base_asset_amount(
+                max_base_asset_amount.min(taker_base_asset_amount),
+                base_step_size,
+            )?;
+
+            if base_asset_amount == 0 {
+                return Ok((0, 0));
+            }
+
+            let quote_asset_amount = calculate_constant_product_pool_amount_out(
+                base_asset_amount,
+                base_reserve,
+                quote_reserve,
+                fee_numerator,
+                fee_denominator,
+            )?;
+
+            Ok((base_asset_amount, quote_asset_amount))
+        }
+    }
+}
+
+pub struct ConstantProductPoolFulfillmentParams<'a, 'b> {
+    pub pool_program: &'a AccountInfo<'b>,
+    pub pool: &'a AccountInfo<'b>,
+    pub pool_signer: &'a AccountInfo<'b>,
+    pub pool_base_vault: Box<Account<'b, TokenAccount>>,
+    pub pool_quote_vault: Box<Account<'b, TokenAccount>>,
+    pub drift_signer: &'a AccountInfo<'b>,
+    pub base_market_vault: Box<Account<'b, TokenAccount>>,
+    pub quote_market_vault: Box<Account<'b, TokenAccount>>,
+    pub token_program: Program<'b, Token>,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    pub base_precision: u64,
+    pub base_step_size: u64,
+    pub quote_decimals: u32,
+    pub signer_nonce: u8,
+}
+
+/// Constructor for ConstantProductPoolFulfillmentParams
+impl<'a, 'b> ConstantProductPoolFulfillmentParams<'a, 'b> {
+    #[allow(clippy::type_complexity)]
+    pub fn new(
+        account_info_iter: &mut std::iter::Peekable<std::slice::Iter<'a, AccountInfo<'b>>>,
+        state: &State,
+        base_market: &SpotMarket,
+        quote_market: &SpotMarket,
+    ) -> DriftResult<Self> {
+        let account_info_vec = account_info_iter.take(10).collect::<Vec<_>>();
+        let account_infos = array_ref![account_info_vec, 0, 10];
+        let [pool_fulfillment_config, pool_program, pool, pool_signer, pool_base_vault, pool_quote_vault, drift_signer, base_market_vault, quote_market_vault, token_program] =
+            account_infos;
+
+        let pool_fulfillment_config_loader: AccountLoader<ConstantProductPoolFulfillmentConfig> =
+            AccountLoader::try_from(pool_fulfillment_config).map_err(|e| {
+                msg!("{:?}", e);
+                ErrorCode::InvalidFulfillmentConfig
+            })?;
+        let pool_fulfillment_config = load!(pool_fulfillment_config_loader)?;
+
+        validate!(
+            pool_fulfillment_config.status == SpotFulfillmentConfigStatus::Enabled,
+            ErrorCode::SpotFulfillmentConfigDisabled
+        )?;
+
+        validate!(
+            &state.signer == drift_signer.key,
+            ErrorCode::InvalidFulfillmentConfig
+        )?;
+
+        validate!(
+            pool_fulfillment_config.market_index == base_market.market_index,
+            ErrorCode::InvalidFulfillmentConfig,
+            "config market index {} does not equal base asset index {}",
+            pool_fulfillment_config.market_index,
+            base_market.market_index
+        )?;
+
+        validate!(
+            &base_market.vault == base_market_vault.key,
+            ErrorCode::InvalidFulfillmentConfig
+        )?;
+
+        validate!(
+            &quote_market.vault == quote_market_vault.key,
+            ErrorCode::InvalidFulfillmentConfig
+        )?;
+
+        validate!(
+            &pool_fulfillment_config.pool_program_id == pool_program.key,
+            ErrorCode::InvalidFulfillmentConfig
+        )?;
+
+        validate!(
+            &pool_fulfillment_config.pool == pool.key,
+            ErrorCode::InvalidFulfillmentConfig
+        )?;
+
+        validate!(
+            &pool_fulfillment_config.pool_signer == pool_signer.key,
+            ErrorCode::InvalidFulfillmentConfig
+        )?;
+
+        validate!(
+            &pool_fulfillment_config.pool_base_vault == pool_base_vault.key,
+            ErrorCode::InvalidFulfillmentConfig,
+            "Pool base vault key does not match config"
+        )?;
+
+        validate!(
+            &pool_fulfillment_config.pool_quote_vault == pool_quote_vault.key,
+            ErrorCode::InvalidFulfillmentConfig,
+            "Pool quote vault key does not match config"
+        )?;
+
+        let pool_state = load_constant_product_pool(pool, pool_program.key)?;
+
+        let pool_base_vault: Box<Account<TokenAccount>> =
+            Box::new(Account::try_from(pool_base_vault).map_err(|e| {
+                msg!("{:?}", e);
+                ErrorCode::InvalidFulfillmentConfig
+            })?);
+        let pool_quote_vault: Box<Account<TokenAccount>> =
+            Box::new(Account::try_from(pool_quote_vault).map_err(|e| {
+                msg!("{:?}", e);
+                ErrorCode::InvalidFulfillmentConfig
+            })?);
+
+        let base_market_vault: Box<Account<TokenAccount>> =
+            Box::new(Account::try_from(base_market_vault).map_err(|e| {
+                msg!("{:?}", e);
+                ErrorCode::InvalidFulfillmentConfig
+            })?);
+        let quote_market_vault: Box<Account<TokenAccount>> =
+            Box::new(Account::try_from(quote_market_vault).map_err(|e| {
+                msg!("{:?}", e);
+                ErrorCode::InvalidFulfillmentConfig
+            })?);
+
+        let token_program: Program<Token> = Program::try_from(token_program).map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::InvalidFulfillmentConfig
+        })?;
+
+        Ok(ConstantProductPoolFulfillmentParams {
+            pool_program,
+            pool,
+            pool_signer,
+            pool_base_vault,
+            pool_quote_vault,
+            drift_signer,
+            base_market_vault,
+            quote_market_vault,
+            token_program,
+            fee_numerator: pool_state.fee_numerator,
+            fee_denominator: pool_state.fee_denominator,
+            base_precision: base_market.get_precision(),
+            base_step_size: base_market.order_step_size,
+            quote_decimals: quote_market.decimals,
+            signer_nonce: state.signer_nonce,
+        })
+    }
+}
+
+/// CPI Wrappers into the constant product pool
+impl<'a, 'b> ConstantProductPoolFulfillmentParams<'a, 'b> {
+    pub fn reload_market_vaults(&mut self) -> DriftResult {
+        self.base_market_vault.reload().map_err(|_e| {
+            msg!("Failed to reload base_market_vault");
+            ErrorCode::FailedConstantProductPoolCPI
+        })?;
+        self.quote_market_vault.reload().map_err(|_e| {
+            msg!("Failed to reload quote_market_vault");
+            ErrorCode::FailedConstantProductPoolCPI
+        })?;
+
+        Ok(())
+    }
+
+    pub fn invoke_swap(
+        &self,
+        taker_direction: PositionDirection,
+        base_asset_amount: u64,
+        quote_asset_amount: u64,
+    ) -> DriftResult {
+        // longs buy an exact amount of base for at most quote_asset_amount
+        // shorts sell an exact amount of base for at least quote_asset_amount
+        let discriminator = match taker_direction {
+            PositionDirection::Long => CONSTANT_PRODUCT_POOL_SWAP_EXACT_OUT_DISCRIMINATOR,
+            PositionDirection::Short => CONSTANT_PRODUCT_POOL_SWAP_DISCRIMINATOR,
+        };
+
+        let (user_source, user_destination, pool_source_vault, pool_destination_vault) =
+            match taker_direction {
+                PositionDirection::Long => (
+                    self.quote_market_vault.to_account_info(),
+                    self.base_market_vault.to_account_info(),
+                    self.pool_quote_vault.to_account_info(),
+                    self.pool_base_vault.to_account_info(),
+                ),
+                PositionDirection::Short => (
+                    self.base_market_vault.to_account_info(),
+                    self.quote_market_vault.to_account_info(),
+                    self.pool_base_vault.to_account_info(),
+                    self.pool_quote_vault.to_account_info(),
+                ),
+            };
+
+        let mut data = Vec::with_capacity(24);
+        data.extend_from_slice(&discriminator);
+        data.extend_from_slice(&base_asset_amount.to_le_bytes());
+        data.extend_from_slice(&quote_asset_amount.to_le_bytes());
+
+        let instruction = Instruction {
+            program_id: *self.pool_program.key,
+            data,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.pool.key, false),
+                AccountMeta::new_readonly(*self.pool_signer.key, false),
+                AccountMeta::new_readonly(*self.drift_signer.key, true),
+                AccountMeta::new(*user_source.key, false),
+                AccountMeta::new(*user_destination.key, false),
+                AccountMeta::new(*pool_source_vault.key, false),
+                AccountMeta::new(*pool_destination_vault.key, false),
+                AccountMeta::new_readonly(*self.token_program.key, false),
+            ],
+        };
+
+        let account_infos = [
+            self.pool_program.clone(),
+            self.pool.clone(),
+            self.pool_signer.clone(),
+            self.drift_signer.clone(),
+            user_source,
+            user_destination,
+            pool_source_vault,
+            pool_destination_vault,
+            self.token_program.to_account_info(),
+        ];
+
+        let signer_seeds = get_signer_seeds(&self.signer_nonce);
+        let signers_seeds = &[&signer_seeds[..]];
+
+        invoke_signed_unchecked(&instruction, &account_infos, signers_seeds).map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::FailedConstantProductPoolCPI
+        })
+    }
+}
+
+impl<'a, 'b> SpotFulfillmentParams for ConstantProductPoolFulfillmentParams<'a, 'b> {
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn get_best_bid_and_ask(&self) -> DriftResult<(Option<u64>, Option<u64>)> {
+        if self.quote_decimals != 6 {
+            msg!("Quote decimals must be 6");
+            return Err(ErrorCode::InvalidPricePrecision);
+        }
+
+        calculate_constant_product_pool_bid_ask(
+            self.pool_base_vault.amount,
+            self.pool_quote_vault.amount,
+            self.fee_numerator,
+            self.fee_denominator,
+            self.base_precision,
+        )
+    }
+
+    fn fulfill_order(
+        &mut self,
+        taker_direction: PositionDirection,
+        taker_price: u64,
+        taker_base_asset_amount: u64,
+        taker_max_quote_asset_amount: u64,
+    ) -> DriftResult<ExternalSpotFill> {
+        let (base_asset_amount, quote_asset_amount) = calculate_constant_product_pool_fill_amounts(
+            taker_direction,
+            taker_price,
+            taker_base_asset_amount,
+            taker_max_quote_asset_amount,
+            self.pool_base_vault.amount,
+            self.pool_quote_vault.amount,
+            self.fee_numerator,
+            self.fee_denominator,
+            self.base_precision,
+            self.base_step_size,
+        )?;
+
+        if base_asset_amount == 0 || quote_asset_amount == 0 {
+            msg!("No base to fill against pool at taker price");
+            return Ok(ExternalSpotFill::empty());
+        }
+
+        // vaults may have changed if the order was partially filled by another venue
+        self.reload_market_vaults()?;
+
+        let base_before = self.base_market_vault.amount;
+        let quote_before = self.quote_market_vault.amount;
+
+        self.invoke_swap(taker_direction, base_asset_amount, quote_asset_amount)?;
+
+        self.reload_market_vaults()?;
+        self.pool_base_vault.reload().map_err(|_e| {
+            msg!("Failed to reload pool_base_vault");
+            ErrorCode::FailedConstantProductPoolCPI
+        })?;
+        self.pool_quote_vault.reload().map_err(|_e| {
+            msg!("Failed to reload pool_quote_vault");
+            ErrorCode::FailedConstantProductPoolCPI
+        })?;
+
+        let base_after = self.base_market_vault.amount;
+        let quote_after = self.quote_market_vault.amount;
+
+        let (base_update_direction, base_asset_amount_filled) = if base_after > base_before {
+            (SpotBalanceType::Deposit, base_after.safe_sub(base_before)?)
+        } else {
+            (SpotBalanceType::Borrow, base_before.safe_sub(base_after)?)
+        };
+
+        if base_asset_amount_filled == 0 {
+            msg!("No base filled on constant product pool");
+            return Ok(ExternalSpotFill::empty());
+        }
+
+        let fee_complement = self.fee_denominator.safe_sub(self.fee_numerator)?;
+
+        // pool fee is charged on the input token, convert it to quote for shorts
+        let (quote_update_direction, quote_asset_amount_filled, pool_fee) =
+            if base_update_direction == SpotBalanceType::Borrow {
+                let quote_asset_amount_delta = quote_after.safe_sub(quote_before)?;
+                let pool_fee = quote_asset_amount_delta
+                    .cast::<u128>()?
+                    .safe_mul(self.fee_numerator.cast()?)?
+                    .safe_div(fee_complement.cast()?)?
+                    .cast::<u64>()?;
+                (
+                    SpotBalanceType::Deposit,
+                    quote_asset_amount_delta.safe_add(pool_fee)?,
+                    pool_fee,
+                )
+            } else {
+                let quote_asset_amount_delta = quote_before.safe_sub(quote_after)?;
+                let pool_fee = quote_asset_amount_delta
+                    .cast::<u128>()?
+                    .safe_mul(self.fee_numerator.cast()?)?
+                    .safe_div(self.fee_denominator.cast()?)?
+                    .cast::<u64>()?;
+                (
+                    SpotBalanceType::Borrow,
+                    quote_asset_amount_delta.safe_sub(pool_fee)?,
+                    pool_fee,
+                )
+            };
+
+        Ok(ExternalSpotFill {
+            base_asset_amount_filled,
+            quote_asset_amount_filled,
+            base_update_direction,
+            quote_update_direction,
+            fee: pool_fee,
+            unsettled_referrer_rebate: 0,
+            settled_referrer_rebate: 0,
+        })
+    }
+
+    fn get_order_action_explanation(&self) -> DriftResult<OrderActionExplanation> {
+        Ok(OrderActionExplanation::OrderFillWithConstantProductPool)
+    }
+
+    fn validate_vault_amounts(
+        &mut self,
+        base_market: &Ref<SpotMarket>,
+        quote_market: &Ref<SpotMarket>,
+    ) -> DriftResult {
+        self.reload_market_vaults()?;
+        validate_spot_market_vault_amount(base_market, self.base_market_vault.amount)?;
+        validate_spot_market_vault_amount(quote_market, self.quote_market_vault.amount)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::{LAMPORTS_PER_SOL_U64, PRICE_PRECISION_U64};
+    use crate::state::fulfillment_params::constant_product_pool::{
+        calculate_constant_product_pool_amount_in, calculate_constant_product_pool_amount_out,
+        calculate_constant_product_pool_bid_ask, calculate_constant_product_pool_fill_amounts,
+    };
+
+    // 1000 SOL / 20000 USDC pool with 30bps fee
+    const BASE_RESERVE: u64 = 1000 * LAMPORTS_PER_SOL_U64;
+    const QUOTE_RESERVE: u64 = 20000 * PRICE_PRECISION_U64;
+    const FEE_NUMERATOR: u64 = 3;
+    const FEE_DENOMINATOR: u64 = 1000;
+    const STEP_SIZE: u64 = LAMPORTS_PER_SOL_U64 / 1000;
+
+    #[test]
+    fn bid_ask() {
+        let (bid, ask) = calculate_constant_product_pool_bid_ask(
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+
+        assert_eq!(bid, Some(19940000));
+        assert_eq!(ask, Some(20060181));
+
+        let (bid, ask) = calculate_constant_product_pool_bid_ask(
+            0,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+
+        assert_eq!(bid, None);
+        assert_eq!(ask, None);
+    }
+
+    #[test]
+    fn amount_in_covers_amount_out() {
+        let amount_out = 10 * LAMPORTS_PER_SOL_U64;
+        let amount_in = calculate_constant_product_pool_amount_in(
+            amount_out,
+            QUOTE_RESERVE,
+            BASE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+        )
+        .unwrap();
+
+        let amount_out_for_amount_in = calculate_constant_product_pool_amount_out(
+            amount_in,
+            QUOTE_RESERVE,
+            BASE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+        )
+        .unwrap();
+
+        assert!(amount_out_for_amount_in >= amount_out);
+    }
+
+    #[test]
+    fn long_fill_respects_limit_price() {
+        // limit below ask, no fill
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Long,
+            20 * PRICE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_U64,
+            u64::MAX,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+        assert_eq!((base, quote), (0, 0));
+
+        // limit allows full fill
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Long,
+            21 * PRICE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_U64,
+            u64::MAX,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+        assert_eq!(base, 10 * LAMPORTS_PER_SOL_U64);
+        assert!(quote * LAMPORTS_PER_SOL_U64 / base <= 21 * PRICE_PRECISION_U64);
+
+        // limit caps fill size
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Long,
+            21 * PRICE_PRECISION_U64,
+            100 * LAMPORTS_PER_SOL_U64,
+            u64::MAX,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+        assert!(base > 10 * LAMPORTS_PER_SOL_U64);
+        assert!(base < 100 * LAMPORTS_PER_SOL_U64);
+        assert_eq!(base % STEP_SIZE, 0);
+
+        let base_reserve_after = BASE_RESERVE - base;
+        let quote_reserve_after =
+            QUOTE_RESERVE + quote * (FEE_DENOMINATOR - FEE_NUMERATOR) / FEE_DENOMINATOR;
+        let (_, ask_after) = calculate_constant_product_pool_bid_ask(
+            base_reserve_after,
+            quote_reserve_after,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+        assert!(ask_after.unwrap() <= 21 * PRICE_PRECISION_U64);
+    }
+
+    #[test]
+    fn long_fill_respects_max_quote() {
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Long,
+            21 * PRICE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_U64,
+            100 * PRICE_PRECISION_U64,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+
+        assert!(quote <= 100 * PRICE_PRECISION_U64);
+        assert!(base > 4 * LAMPORTS_PER_SOL_U64);
+        assert!(base < 5 * LAMPORTS_PER_SOL_U64);
+        assert_eq!(base % STEP_SIZE, 0);
+    }
+
+    #[test]
+    fn short_fill_respects_limit_price() {
+        // limit above bid, no fill
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Short,
+            20 * PRICE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_U64,
+            u64::MAX,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+        assert_eq!((base, quote), (0, 0));
+
+        // limit allows full fill
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Short,
+            19 * PRICE_PRECISION_U64,
+            10 * LAMPORTS_PER_SOL_U64,
+            u64::MAX,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+        assert_eq!(base, 10 * LAMPORTS_PER_SOL_U64);
+        assert!(quote * LAMPORTS_PER_SOL_U64 / base >= 19 * PRICE_PRECISION_U64);
+
+        // limit caps fill size
+        let (base, quote) = calculate_constant_product_pool_fill_amounts(
+            PositionDirection::Short,
+            19 * PRICE_PRECISION_U64,
+            100 * LAMPORTS_PER_SOL_U64,
+            u64::MAX,
+            BASE_RESERVE,
+            QUOTE_RESERVE,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+            STEP_SIZE,
+        )
+        .unwrap();
+        assert!(base > 10 * LAMPORTS_PER_SOL_U64);
+        assert!(base < 100 * LAMPORTS_PER_SOL_U64);
+        assert_eq!(base % STEP_SIZE, 0);
+
+        let base_reserve_after =
+            BASE_RESERVE + base * (FEE_DENOMINATOR - FEE_NUMERATOR) / FEE_DENOMINATOR;
+        let quote_reserve_after = QUOTE_RESERVE - quote;
+        let (bid_after, _) = calculate_constant_product_pool_bid_ask(
+            base_reserve_after,
+            quote_reserve_after,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            LAMPORTS_PER_SOL_U64,
+        )
+        .unwrap();
+        assert!(bid_after.unwrap() >= 19 * PRICE_PRECISION_U64);
+    }
+}
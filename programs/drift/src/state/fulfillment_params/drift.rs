@@ -84,7 +84,7 @@ impl<'a> SpotFulfillmentParams for MatchFulfillmentParams<'a> {
     }
 
     fn validate_vault_amounts(
-        &self,
+        &mut self,
         base_market: &Ref<SpotMarket>,
         quote_market: &Ref<SpotMarket>,
     ) -> DriftResult<()> {
@@ -1,3 +1,4 @@
+pub mod constant_product_pool;
 pub mod drift;
 pub mod phoenix;
 pub mod serum;
@@ -184,13 +184,13 @@ pub struct PhoenixFulfillmentParams<'a, 'b> {
 /// Constructor for PhoenixFulfillmentParams
 impl<'a, 'b> PhoenixFulfillmentParams<'a, 'b> {
     #[allow(clippy::type_complexity)]
-    pub fn new<'c>(
-        account_info_iter: &'a mut std::iter::Peekable<std::slice::Iter<'c, AccountInfo<'b>>>,
+    pub fn new(
+        account_info_iter: &mut std::iter::Peekable<std::slice::Iter<'a, AccountInfo<'b>>>,
         state: &State,
         base_market: &SpotMarket,
         quote_market: &SpotMarket,
     ) -> DriftResult<Self> {
-        let account_info_vec = account_info_iter.collect::<Vec<_>>();
+        let account_info_vec = account_info_iter.take(10).collect::<Vec<_>>();
         let account_infos = array_ref![account_info_vec, 0, 10];
         let [phoenix_fulfillment_config, phoenix_program, phoenix_log_authority, phoenix_market, drift_signer, phoenix_base_vault, phoenix_quote_vault, base_market_vault, quote_market_vault, token_program] =
             account_infos;
@@ -307,6 +307,19 @@ impl<'a, 'b> PhoenixFulfillmentParams<'a, 'b> {
 }
 
 impl<'a, 'b> PhoenixFulfillmentParams<'a, 'b> {
+    pub fn reload_market_vaults(&mut self) -> DriftResult {
+        self.base_market_vault.reload().map_err(|_e| {
+            msg!("Failed to reload base_market_vault");
+            ErrorCode::FailedPhoenixCPI
+        })?;
+        self.quote_market_vault.reload().map_err(|_e| {
+            msg!("Failed to reload quote_market_vault");
+            ErrorCode::FailedPhoenixCPI
+        })?;
+
+        Ok(())
+    }
+
     pub fn invoke_new_order(&self, order_packet: OrderPacket) -> DriftResult {
         let base_mint = self.phoenix_market.header.base_params.mint_key;
         let quote_mint = self.phoenix_market.header.quote_params.mint_key;
@@ -387,6 +400,9 @@ impl<'a, 'b> SpotFulfillmentParams for PhoenixFulfillmentParams<'a, 'b> {
         taker_base_asset_amount: u64,
         taker_max_quote_asset_amount: u64,
     ) -> DriftResult<ExternalSpotFill> {
+        // vaults may have changed if the order was partially filled by another venue
+        self.reload_market_vaults()?;
+
         let market_data = self.phoenix_market.data.borrow();
         let (_, market_bytes) = market_data.split_at(size_of::<MarketHeader>());
         let header = &self.phoenix_market.header;
@@ -537,10 +553,11 @@ impl<'a, 'b> SpotFulfillmentParams for PhoenixFulfillmentParams<'a, 'b> {
 
     // Note: this trait method still feels a little out of place
     fn validate_vault_amounts(
-        &self,
+        &mut self,
         base_market: &Ref<SpotMarket>,
         quote_market: &Ref<SpotMarket>,
     ) -> DriftResult {
+        self.reload_market_vaults()?;
         validate_spot_market_vault_amount(base_market, self.base_market_vault.amount)?;
         validate_spot_market_vault_amount(quote_market, self.quote_market_vault.amount)?;
         Ok(())
@@ -211,14 +211,14 @@ impl<'a, 'b> Deref for SerumFulfillmentParams<'a, 'b> {
 /// Constructor for SerumFulfillmentParams
 impl<'a, 'b> SerumFulfillmentParams<'a, 'b> {
     #[allow(clippy::type_complexity)]
-    pub fn new<'c>(
-        account_info_iter: &'a mut std::iter::Peekable<std::slice::Iter<'c, AccountInfo<'b>>>,
+    pub fn new(
+        account_info_iter: &mut std::iter::Peekable<std::slice::Iter<'a, AccountInfo<'b>>>,
         state: &State,
         base_market: &SpotMarket,
         quote_market: &SpotMarket,
         now: i64,
     ) -> DriftResult<Self> {
-        let account_info_vec = account_info_iter.collect::<Vec<_>>();
+        let account_info_vec = account_info_iter.take(16).collect::<Vec<_>>();
         let account_infos = array_ref![account_info_vec, 0, 16];
         let [serum_fulfillment_config, serum_program, serum_market, serum_request_queue, serum_event_queue, serum_bids, serum_asks, serum_base_vault, serum_quote_vault, serum_open_orders, serum_signer, drift_signer, token_program, base_market_vault, quote_market_vault, srm_vault] =
             account_infos;
@@ -460,6 +460,19 @@ impl<'a, 'b> SerumFulfillmentParams<'a, 'b> {
             ErrorCode::FailedSerumCPI
         })
     }
+
+    pub fn reload_market_vaults(&mut self) -> DriftResult {
+        self.base_market_vault.reload().map_err(|_e| {
+            msg!("Failed to reload base_market_vault");
+            ErrorCode::FailedSerumCPI
+        })?;
+        self.quote_market_vault.reload().map_err(|_e| {
+            msg!("Failed to reload quote_market_vault");
+            ErrorCode::FailedSerumCPI
+        })?;
+
+        Ok(())
+    }
 }
 
 impl<'a, 'b> SpotFulfillmentParams for SerumFulfillmentParams<'a, 'b> {
@@ -538,6 +551,9 @@ impl<'a, 'b> SpotFulfillmentParams for SerumFulfillmentParams<'a, 'b> {
         taker_base_asset_amount: u64,
         taker_max_quote_asset_amount: u64,
     ) -> DriftResult<ExternalSpotFill> {
+        // vaults may have changed if the order was partially filled by another venue
+        self.reload_market_vaults()?;
+
         let market_state_before = self.load_serum_market()?;
 
         let serum_order_side = match taker_direction {
@@ -605,14 +621,7 @@ impl<'a, 'b> SpotFulfillmentParams for SerumFulfillmentParams<'a, 'b> {
 
         self.invoke_settle_funds()?;
 
-        self.base_market_vault.reload().map_err(|_e| {
-            msg!("Failed to reload base_market_vault");
-            ErrorCode::FailedSerumCPI
-        })?;
-        self.quote_market_vault.reload().map_err(|_e| {
-            msg!("Failed to reload quote_market_vault");
-            ErrorCode::FailedSerumCPI
-        })?;
+        self.reload_market_vaults()?;
 
         let base_after = self.base_market_vault.amount;
         let quote_after = self.quote_market_vault.amount;
@@ -683,10 +692,11 @@ impl<'a, 'b> SpotFulfillmentParams for SerumFulfillmentParams<'a, 'b> {
     }
 
     fn validate_vault_amounts(
-        &self,
+        &mut self,
         base_market: &Ref<SpotMarket>,
         quote_market: &Ref<SpotMarket>,
     ) -> DriftResult {
+        self.reload_market_vaults()?;
         validate_spot_market_vault_amount(base_market, self.base_market_vault.amount)?;
         validate_spot_market_vault_amount(quote_market, self.quote_market_vault.amount)?;
         Ok(())
@@ -3,10 +3,12 @@ use crate::error::ErrorCode;
 use crate::math_error;
 use crate::safe_decrement;
 use crate::safe_increment;
-use crate::state::spot_market::SpotMarket;
+use crate::state::spot_market::InsuranceFund;
 use crate::state::traits::Size;
+use crate::state::user::MarketType;
 use crate::validate;
 use anchor_lang::prelude::*;
+use borsh::{BorshDeserialize, BorshSerialize};
 
 #[account(zero_copy)]
 #[derive(Default, Eq, PartialEq, Debug)]
@@ -21,12 +23,41 @@ pub struct InsuranceFundStake {
     pub last_withdraw_request_ts: i64,
     pub cost_basis: i64,
     pub market_index: u16,
-    pub padding: [u8; 14],
+    /// Spot for a spot market's insurance fund, Perp for a perp market's dedicated insurance fund
+    pub market_type: MarketType,
+    /// The tranche of the spot market's insurance fund the stake is in
+    pub tranche: InsuranceFundTranche,
+    /// Whether revenue is paid out to the staker's `User` spot balance instead of compounding
+    pub claim_revenue: bool,
+    pub padding: [u8; 11],
+    /// The insurance fund's revenue_per_share the last time revenue was accrued to the stake
+    /// precision: IF_REVENUE_PER_SHARE_PRECISION
+    pub last_revenue_per_share: u128,
+    /// Total revenue earned by the stake, compounded or claimed
+    /// precision: token mint precision
+    pub cumulative_revenue: u64,
+    /// Revenue earned in claim mode that hasn't been paid out yet
+    /// precision: token mint precision
+    pub unclaimed_revenue: u64,
+}
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
+pub enum InsuranceFundTranche {
+    /// Covers losses after the junior tranche is exhausted
+    Senior,
+    /// Covers losses first in exchange for a larger share of revenue
+    Junior,
+}
+
+impl Default for InsuranceFundTranche {
+    fn default() -> Self {
+        InsuranceFundTranche::Senior
+    }
 }
 
 // implement SIZE const for InsuranceFundStake
 impl Size for InsuranceFundStake {
-    const SIZE: usize = 136;
+    const SIZE: usize = 168;
 }
 
 impl InsuranceFundStake {
@@ -41,24 +72,30 @@ impl InsuranceFundStake {
             if_base: 0,
             last_valid_ts: now,
             if_shares: 0,
-            padding: [0; 14],
+            market_type: MarketType::Spot,
+            tranche: InsuranceFundTranche::Senior,
+            claim_revenue: false,
+            padding: [0; 11],
+            last_revenue_per_share: 0,
+            cumulative_revenue: 0,
+            unclaimed_revenue: 0,
         }
     }
 
-    fn validate_base(&self, spot_market: &SpotMarket) -> DriftResult {
+    fn validate_base(&self, insurance_fund: &InsuranceFund) -> DriftResult {
         validate!(
-            self.if_base == spot_market.insurance_fund.shares_base,
+            self.if_base == insurance_fund.shares_base,
             ErrorCode::InvalidIFRebase,
             "if stake bases mismatch. user base: {} market base {}",
             self.if_base,
-            spot_market.insurance_fund.shares_base
+            insurance_fund.shares_base
         )?;
 
         Ok(())
     }
 
-    pub fn checked_if_shares(&self, spot_market: &SpotMarket) -> DriftResult<u128> {
-        self.validate_base(spot_market)?;
+    pub fn checked_if_shares(&self, insurance_fund: &InsuranceFund) -> DriftResult<u128> {
+        self.validate_base(insurance_fund)?;
         Ok(self.if_shares)
     }
 
@@ -66,20 +103,32 @@ impl InsuranceFundStake {
         self.if_shares
     }
 
-    pub fn increase_if_shares(&mut self, delta: u128, spot_market: &SpotMarket) -> DriftResult {
-        self.validate_base(spot_market)?;
+    pub fn increase_if_shares(
+        &mut self,
+        delta: u128,
+        insurance_fund: &InsuranceFund,
+    ) -> DriftResult {
+        self.validate_base(insurance_fund)?;
         safe_increment!(self.if_shares, delta);
         Ok(())
     }
 
-    pub fn decrease_if_shares(&mut self, delta: u128, spot_market: &SpotMarket) -> DriftResult {
-        self.validate_base(spot_market)?;
+    pub fn decrease_if_shares(
+        &mut self,
+        delta: u128,
+        insurance_fund: &InsuranceFund,
+    ) -> DriftResult {
+        self.validate_base(insurance_fund)?;
         safe_decrement!(self.if_shares, delta);
         Ok(())
     }
 
-    pub fn update_if_shares(&mut self, new_shares: u128, spot_market: &SpotMarket) -> DriftResult {
-        self.validate_base(spot_market)?;
+    pub fn update_if_shares(
+        &mut self,
+        new_shares: u128,
+        insurance_fund: &InsuranceFund,
+    ) -> DriftResult {
+        self.validate_base(insurance_fund)?;
         self.if_shares = new_shares;
 
         Ok(())
@@ -1,9 +1,14 @@
+pub mod amm_hedge;
 pub mod events;
 pub mod fulfillment;
 pub mod fulfillment_params;
 pub mod insurance_fund_stake;
 pub mod oracle;
 pub mod oracle_map;
+pub mod paused_operations;
+pub mod perp_lp_hedge;
+pub mod perp_lp_range;
+pub mod perp_lp_stats;
 pub mod perp_market;
 pub mod perp_market_map;
 pub mod spot_fulfillment_params;
@@ -11,6 +16,9 @@ pub mod spot_market;
 pub mod spot_market_map;
 #[allow(clippy::module_inception)]
 pub mod state;
+pub mod tokenized_lp_pool;
 pub mod traits;
 pub mod user;
+pub mod user_delegate;
 pub mod user_map;
+pub mod withdraw_policy;
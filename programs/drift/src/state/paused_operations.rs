@@ -0,0 +1,47 @@
+use enumflags2::BitFlags;
+
+use crate::error::DriftResult;
+use crate::math::safe_unwrap::SafeUnwrap;
+
+#[cfg(test)]
+mod tests;
+
+/// Operations that can be paused on individual perp or spot markets
+#[derive(BitFlags, Clone, Copy, PartialEq, Debug, Eq)]
+pub enum MarketOperation {
+    // Active = 0b00000000
+    /// spot: depositing
+    Deposit = 0b00000001,
+    /// spot: withdrawing and borrowing
+    Withdraw = 0b00000010,
+    /// perp: fills against the amm
+    AmmFill = 0b00000100,
+    /// fills against other users' orders. spot: includes fills against external markets
+    MatchFill = 0b00001000,
+    /// liquidations involving the market
+    Liquidation = 0b00010000,
+    /// perp: funding rate updates | spot: cumulative interest updates
+    Funding = 0b00100000,
+    /// perp: settling pnl
+    SettlePnl = 0b01000000,
+    /// perp: adding and removing lp shares
+    Lp = 0b10000000,
+    // Paused = 0b11111111
+}
+
+impl MarketOperation {
+    pub fn active() -> u8 {
+        BitFlags::<MarketOperation>::empty().bits() as u8
+    }
+
+    pub fn is_operation_paused(
+        paused_operations: u8,
+        operation: MarketOperation,
+    ) -> DriftResult<bool> {
+        Ok(
+            BitFlags::<MarketOperation>::from_bits(usize::from(paused_operations))
+                .safe_unwrap()?
+                .contains(operation),
+        )
+    }
+}
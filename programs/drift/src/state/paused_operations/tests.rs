@@ -0,0 +1,65 @@
+use crate::state::paused_operations::MarketOperation;
+
+#[test]
+fn is_operation_paused() {
+    let paused_operations = MarketOperation::active();
+    assert!(
+        !MarketOperation::is_operation_paused(paused_operations, MarketOperation::AmmFill).unwrap()
+    );
+
+    let paused_operations = MarketOperation::AmmFill as u8 | MarketOperation::Funding as u8;
+    assert!(
+        MarketOperation::is_operation_paused(paused_operations, MarketOperation::AmmFill).unwrap()
+    );
+    assert!(
+        MarketOperation::is_operation_paused(paused_operations, MarketOperation::Funding).unwrap()
+    );
+    assert!(
+        !MarketOperation::is_operation_paused(paused_operations, MarketOperation::MatchFill)
+            .unwrap()
+    );
+    assert!(!MarketOperation::is_operation_paused(paused_operations, MarketOperation::Lp).unwrap());
+
+    let paused_operations = u8::MAX;
+    assert!(
+        MarketOperation::is_operation_paused(paused_operations, MarketOperation::Deposit).unwrap()
+    );
+    assert!(MarketOperation::is_operation_paused(paused_operations, MarketOperation::Lp).unwrap());
+}
+
+#[test]
+fn legacy_market_status() {
+    use crate::state::perp_market::{MarketStatus, PerpMarket};
+
+    let mut perp_market = PerpMarket {
+        status: MarketStatus::FillPaused,
+        ..PerpMarket::default()
+    };
+    assert!(perp_market.status.is_active_or_legacy_paused());
+    assert!(perp_market
+        .is_operation_paused(MarketOperation::AmmFill)
+        .unwrap());
+    assert!(perp_market
+        .is_operation_paused(MarketOperation::MatchFill)
+        .unwrap());
+    assert!(!perp_market
+        .is_operation_paused(MarketOperation::Funding)
+        .unwrap());
+
+    perp_market.status = MarketStatus::FundingPaused;
+    assert!(perp_market
+        .is_operation_paused(MarketOperation::Funding)
+        .unwrap());
+    assert!(!perp_market
+        .is_operation_paused(MarketOperation::AmmFill)
+        .unwrap());
+
+    perp_market.status = MarketStatus::Active;
+    perp_market.paused_operations = MarketOperation::Withdraw as u8;
+    assert!(perp_market
+        .is_operation_paused(MarketOperation::Withdraw)
+        .unwrap());
+    assert!(!perp_market
+        .is_operation_paused(MarketOperation::Funding)
+        .unwrap());
+}
@@ -0,0 +1,39 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::state::traits::Size;
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct PerpLpHedge {
+    /// The lp user that opted in to keepers hedging their position in the market
+    pub user: Pubkey,
+    /// Keepers can place a reduce-only order once the position's base asset amount is above this
+    /// precision: BASE_PRECISION
+    pub base_asset_amount_threshold: u64,
+    pub last_hedge_ts: i64,
+    pub market_index: u16,
+    pub padding: [u8; 6],
+}
+
+impl Size for PerpLpHedge {
+    const SIZE: usize = 64;
+}
+
+impl PerpLpHedge {
+    pub fn update_base_asset_amount_threshold(
+        &mut self,
+        base_asset_amount_threshold: u64,
+    ) -> DriftResult {
+        validate!(
+            base_asset_amount_threshold > 0,
+            ErrorCode::InvalidLpHedgeOrder,
+            "base_asset_amount_threshold must be > 0"
+        )?;
+
+        self.base_asset_amount_threshold = base_asset_amount_threshold;
+
+        Ok(())
+    }
+}
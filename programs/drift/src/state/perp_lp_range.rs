@@ -0,0 +1,53 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::state::traits::Size;
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct PerpLpRange {
+    /// The user whose lp shares in the market follow this range
+    pub user: Pubkey,
+    /// The lp shares are only in the amm while the reserve price is within [lower, upper]
+    /// precision: PRICE_PRECISION
+    pub lower_reserve_price: u64,
+    /// precision: PRICE_PRECISION
+    pub upper_reserve_price: u64,
+    /// Lp shares taken out of the amm while the reserve price is out of range
+    /// They are added back to the user's position when the price re-enters the range
+    /// precision: AMM_RESERVE_PRECISION
+    pub parked_lp_shares: u64,
+    pub last_update_ts: i64,
+    pub market_index: u16,
+    pub padding: [u8; 6],
+}
+
+impl Size for PerpLpRange {
+    const SIZE: usize = 80;
+}
+
+impl PerpLpRange {
+    pub fn is_in_range(&self, reserve_price: u64) -> bool {
+        reserve_price >= self.lower_reserve_price && reserve_price <= self.upper_reserve_price
+    }
+
+    pub fn update_range(
+        &mut self,
+        lower_reserve_price: u64,
+        upper_reserve_price: u64,
+    ) -> DriftResult {
+        validate!(
+            lower_reserve_price > 0 && lower_reserve_price < upper_reserve_price,
+            ErrorCode::InvalidPerpLpRange,
+            "lower_reserve_price {} must be > 0 and < upper_reserve_price {}",
+            lower_reserve_price,
+            upper_reserve_price
+        )?;
+
+        self.lower_reserve_price = lower_reserve_price;
+        self.upper_reserve_price = upper_reserve_price;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use crate::state::traits::Size;
+use anchor_lang::prelude::*;
+
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct PerpLpStats {
+    /// The user whose lp position in the market is attributed
+    pub user: Pubkey,
+    /// The lp shares held since the last update
+    /// precision: AMM_RESERVE_PRECISION
+    pub lp_shares: u64,
+    /// The amm's base_asset_amount_per_lp at the last update
+    /// precision: AMM_RESERVE_PRECISION
+    pub last_base_asset_amount_per_lp: i64,
+    /// The amm's quote_asset_amount_per_lp at the last update
+    /// precision: QUOTE_PRECISION
+    pub last_quote_asset_amount_per_lp: i64,
+    /// The amm's fee_per_lp at the last update
+    /// precision: QUOTE_PRECISION
+    pub last_fee_per_lp: i64,
+    /// The cumulative funding rate for the side of the lp inventory at the last update
+    /// precision: FUNDING_RATE_PRECISION
+    pub last_cumulative_funding_rate: i64,
+    /// The oracle price the lp inventory was last marked at
+    /// precision: PRICE_PRECISION
+    pub last_oracle_price: i64,
+    /// The base asset amount the lp has taken on as the amm's counterparty
+    /// Capped by the position's base asset amount, so it shrinks as the user closes the position
+    /// precision: AMM_RESERVE_PRECISION
+    pub base_asset_amount: i64,
+    /// The cumulative fees earned by the lp shares
+    /// precision: QUOTE_PRECISION
+    pub fee_revenue: i64,
+    /// The cumulative funding paid (negative) or received (positive) on the lp inventory
+    /// precision: QUOTE_PRECISION
+    pub funding_payment: i64,
+    /// The cumulative pnl of the lp inventory marked to the oracle price
+    /// precision: QUOTE_PRECISION
+    pub inventory_pnl: i64,
+    pub last_update_ts: i64,
+    pub market_index: u16,
+    pub padding: [u8; 6],
+}
+
+impl Size for PerpLpStats {
+    const SIZE: usize = 136;
+}
@@ -25,7 +25,8 @@ use crate::math::stats;
 use crate::state::events::OrderActionExplanation;
 
 use crate::state::oracle::{HistoricalOracleData, OracleSource};
-use crate::state::spot_market::{AssetTier, SpotBalance, SpotBalanceType};
+use crate::state::paused_operations::MarketOperation;
+use crate::state::spot_market::{AssetTier, InsuranceFund, SpotBalance, SpotBalanceType};
 use crate::state::traits::{MarketIndexOffset, Size};
 use crate::{AMM_TO_QUOTE_PRECISION_RATIO, PRICE_PRECISION};
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -36,13 +37,13 @@ pub enum MarketStatus {
     Initialized,
     /// all operations allowed
     Active,
-    /// funding rate updates are paused
+    /// deprecated, use MarketOperation::Funding. pauses the same operations
     FundingPaused,
-    /// amm fills are prevented/blocked
+    /// deprecated, use MarketOperation::AmmFill. pauses fills and adding lp
     AmmPaused,
-    /// fills are blocked
+    /// deprecated, use MarketOperation::AmmFill and MarketOperation::MatchFill. pauses the same operations
     FillPaused,
-    /// perp: pause settling negative pnl | spot: pause depositing asset
+    /// deprecated, use MarketOperation::Withdraw. pauses the same operations
     WithdrawPaused,
     /// fills only able to reduce liability
     ReduceOnly,
@@ -58,6 +59,38 @@ impl Default for MarketStatus {
     }
 }
 
+impl MarketStatus {
+    /// Whether the market is active, including the deprecated paused statuses whose operations are
+    /// paused through `legacy_paused_operations`
+    pub fn is_active_or_legacy_paused(&self) -> bool {
+        matches!(
+            self,
+            MarketStatus::Active
+                | MarketStatus::FundingPaused
+                | MarketStatus::AmmPaused
+                | MarketStatus::FillPaused
+                | MarketStatus::WithdrawPaused
+        )
+    }
+
+    /// Maps the deprecated paused statuses onto the operations they pause
+    pub fn legacy_paused_operations(&self) -> u8 {
+        match self {
+            MarketStatus::FundingPaused => MarketOperation::Funding as u8,
+            MarketStatus::AmmPaused => {
+                MarketOperation::AmmFill as u8
+                    | MarketOperation::MatchFill as u8
+                    | MarketOperation::Lp as u8
+            }
+            MarketStatus::FillPaused => {
+                MarketOperation::AmmFill as u8 | MarketOperation::MatchFill as u8
+            }
+            MarketStatus::WithdrawPaused => MarketOperation::Withdraw as u8,
+            _ => MarketOperation::active(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
 pub enum ContractType {
     Perpetual,
@@ -195,10 +228,31 @@ pub struct PerpMarket {
     /// The contract tier determines how much insurance a market can receive, with more speculative markets receiving less insurance
     /// It also influences the order perp markets can be liquidated, with less speculative markets being liquidated first
     pub contract_tier: ContractTier,
-    pub padding1: bool,
+    /// Bitflags of the operations paused on the market, see MarketOperation
+    pub paused_operations: u8,
     /// The spot market that pnl is settled in
     pub quote_spot_market_index: u16,
-    pub padding: [u8; 48],
+    /// The ts when the circuit breaker cooldown ends. During cooldown only fills that reduce the taker's and
+    /// maker's positions are allowed. Circuit breakers only exist for perp markets, spot fills aren't gated
+    pub circuit_breaker_cooldown_end_ts: i64,
+    /// The max move of the oracle or mark price from its 5min twap before the circuit breaker trips
+    /// 0 disables the circuit breaker
+    /// precision: PERCENTAGE_PRECISION
+    pub circuit_breaker_threshold: u32,
+    /// How long the market stays in cooldown after the circuit breaker trips, in seconds
+    pub circuit_breaker_cooldown_period: u32,
+    /// The number of slots a user must be in liquidation before anyone can liquidate them against the amm
+    /// 0 disables amm liquidations
+    pub amm_liquidation_grace_period: u32,
+    pub padding: [u8; 28],
+    /// The market's dedicated insurance fund. Drawn on before the quote spot market's insurance fund
+    /// when resolving perp bankruptcies. Unused if the vault is the default pubkey
+    /// Appended past the original account size, markets created before it must be resized
+    pub insurance_fund: InsuranceFund,
+    /// Cumulative revenue settled to `insurance_fund` per share, scaled up on rebase
+    /// precision: IF_REVENUE_PER_SHARE_PRECISION
+    pub insurance_fund_revenue_per_share: u128,
+    pub padding1: [u8; 8],
 }
 
 impl Default for PerpMarket {
@@ -229,15 +283,22 @@ impl Default for PerpMarket {
             status: MarketStatus::default(),
             contract_type: ContractType::default(),
             contract_tier: ContractTier::default(),
-            padding1: false,
+            paused_operations: 0,
             quote_spot_market_index: 0,
-            padding: [0; 48],
+            circuit_breaker_cooldown_end_ts: 0,
+            circuit_breaker_threshold: 0,
+            circuit_breaker_cooldown_period: 0,
+            amm_liquidation_grace_period: 0,
+            padding: [0; 28],
+            insurance_fund: InsuranceFund::default(),
+            insurance_fund_revenue_per_share: 0,
+            padding1: [0; 8],
         }
     }
 }
 
 impl Size for PerpMarket {
-    const SIZE: usize = 1216;
+    const SIZE: usize = 1352;
 }
 
 impl MarketIndexOffset for PerpMarket {
@@ -258,6 +319,25 @@ impl PerpMarket {
         Ok(self.status == MarketStatus::ReduceOnly)
     }
 
+    pub fn is_operation_paused(&self, operation: MarketOperation) -> DriftResult<bool> {
+        MarketOperation::is_operation_paused(
+            self.paused_operations | self.status.legacy_paused_operations(),
+            operation,
+        )
+    }
+
+    pub fn is_circuit_breaker_enabled(&self) -> bool {
+        self.circuit_breaker_threshold != 0
+    }
+
+    pub fn is_in_circuit_breaker_cooldown(&self, now: i64) -> bool {
+        now < self.circuit_breaker_cooldown_end_ts
+    }
+
+    pub fn has_insurance_fund(&self) -> bool {
+        self.insurance_fund.vault != Pubkey::default()
+    }
+
     pub fn get_sanitize_clamp_denominator(self) -> DriftResult<Option<i64>> {
         Ok(match self.contract_tier {
             ContractTier::A => Some(10_i64),   // 10%
@@ -630,7 +710,26 @@ pub struct AMM {
     /// the target value for `base_asset_amount_per_lp`, used during AMM JIT with LP split
     /// precision: BASE_PRECISION
     pub target_base_asset_amount_per_lp: i32,
-    pub padding: [u8; 44],
+    pub padding1: [u8; 4],
+    /// the cumulative lp fees earned per lp share. the lp fee is also included in `quote_asset_amount_per_lp`
+    /// precision: QUOTE_PRECISION
+    pub fee_per_lp: i64,
+    /// the max mark/oracle twap spread funding is paid on, as a share of the oracle twap. 0 uses the default of 1/33 (~3%)
+    /// precision: PERCENTAGE_PRECISION
+    pub funding_max_price_spread: u32,
+    /// the interest rate component added to the premium each funding window (24 hours). positive means longs pay shorts
+    /// precision: PERCENTAGE_PRECISION
+    pub funding_interest_rate: i32,
+    /// the mark/oracle twap spread is divided by the dampener before the interest rate is added. 0 is treated as 1
+    pub funding_premium_dampener: u32,
+    /// whether funding accrues pro-rata to elapsed time whenever the amm updates instead of once per funding period
+    pub continuous_funding: bool,
+    pub padding2: [u8; 3],
+    /// the last unix_timestamp continuous funding was accrued
+    pub last_funding_accrual_ts: i64,
+    /// the funding imbalance revenue accrued since the last funding rate update, used for the formulaic k update
+    /// precision: QUOTE_PRECISION
+    pub funding_imbalance_revenue_since_last_funding: i64,
 }
 
 impl Default for AMM {
@@ -712,7 +811,15 @@ impl Default for AMM {
             oracle_source: OracleSource::default(),
             last_oracle_valid: false,
             target_base_asset_amount_per_lp: 0,
-            padding: [0; 44],
+            padding1: [0; 4],
+            fee_per_lp: 0,
+            funding_max_price_spread: 0,
+            funding_interest_rate: 0,
+            funding_premium_dampener: 0,
+            continuous_funding: false,
+            padding2: [0; 3],
+            last_funding_accrual_ts: 0,
+            funding_imbalance_revenue_since_last_funding: 0,
         }
     }
 }
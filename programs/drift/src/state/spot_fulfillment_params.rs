@@ -34,7 +34,7 @@ pub trait SpotFulfillmentParams {
     /// Called at the end of instructions calling fill_spot_order, validates that the token amount in each market's vault
     /// equals the markets deposits - borrows
     fn validate_vault_amounts(
-        &self,
+        &mut self,
         base_market: &Ref<SpotMarket>,
         quote_market: &Ref<SpotMarket>,
     ) -> DriftResult<()>;
@@ -94,7 +94,7 @@ impl SpotFulfillmentParams for TestFulfillmentParams {
     }
 
     fn validate_vault_amounts(
-        &self,
+        &mut self,
         _base_market: &Ref<SpotMarket>,
         _quote_market: &Ref<SpotMarket>,
     ) -> DriftResult<()> {
@@ -17,6 +17,7 @@ use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::{calculate_utilization, get_token_amount};
 
 use crate::state::oracle::{HistoricalIndexData, HistoricalOracleData, OracleSource};
+use crate::state::paused_operations::MarketOperation;
 use crate::state::perp_market::{MarketStatus, PoolBalance};
 use crate::state::traits::{MarketIndexOffset, Size};
 use crate::validate;
@@ -156,7 +157,9 @@ pub struct SpotMarket {
     pub status: MarketStatus,
     /// The asset tier affects how a deposit can be used as collateral and the priority for a borrow being liquidated
     pub asset_tier: AssetTier,
-    pub padding1: [u8; 6],
+    /// Bitflags of the operations paused on the market, see MarketOperation
+    pub paused_operations: u8,
+    pub padding1: [u8; 5],
     /// For swaps, the amount of token loaned out in the begin_swap ix
     /// precision: token mint precision
     pub flash_loan_amount: u64,
@@ -167,7 +170,21 @@ pub struct SpotMarket {
     /// The total fees received from swaps
     /// precision: token mint precision
     pub total_swap_fee: u64,
-    pub padding: [u8; 56],
+    /// Cumulative revenue settled to `insurance_fund` per share, scaled up on rebase
+    /// precision: IF_REVENUE_PER_SHARE_PRECISION
+    pub insurance_fund_revenue_per_share: u128,
+    pub padding: [u8; 40],
+    /// Optional junior tranche of the insurance fund. Absorbs bankruptcy losses before `insurance_fund`
+    /// and settles revenue with its own factors
+    /// Appended past the original account size, markets created before it must be resized
+    pub junior_insurance_fund: InsuranceFund,
+    /// Cumulative revenue settled to `junior_insurance_fund` per share, scaled up on rebase
+    /// precision: IF_REVENUE_PER_SHARE_PRECISION
+    pub junior_insurance_fund_revenue_per_share: u128,
+    /// Ledger of the revenue this market's revenue pool has accrued, by source
+    /// and where it has gone since
+    pub protocol_revenue: ProtocolRevenue,
+    pub padding2: [u8; 24],
 }
 
 impl Default for SpotMarket {
@@ -220,17 +237,23 @@ impl Default for SpotMarket {
             oracle_source: OracleSource::default(),
             status: MarketStatus::default(),
             asset_tier: AssetTier::default(),
-            padding1: [0; 6],
+            paused_operations: 0,
+            padding1: [0; 5],
             flash_loan_amount: 0,
             flash_loan_initial_token_amount: 0,
             total_swap_fee: 0,
-            padding: [0; 56],
+            insurance_fund_revenue_per_share: 0,
+            padding: [0; 40],
+            junior_insurance_fund: InsuranceFund::default(),
+            junior_insurance_fund_revenue_per_share: 0,
+            protocol_revenue: ProtocolRevenue::default(),
+            padding2: [0; 24],
         }
     }
 }
 
 impl Size for SpotMarket {
-    const SIZE: usize = 776;
+    const SIZE: usize = 1024;
 }
 
 impl MarketIndexOffset for SpotMarket {
@@ -238,6 +261,10 @@ impl MarketIndexOffset for SpotMarket {
 }
 
 impl SpotMarket {
+    pub fn has_junior_insurance_fund(&self) -> bool {
+        self.junior_insurance_fund.vault != Pubkey::default()
+    }
+
     pub fn is_active(&self, now: i64) -> DriftResult<bool> {
         let status_ok = !matches!(
             self.status,
@@ -251,13 +278,17 @@ impl SpotMarket {
         self.status == MarketStatus::ReduceOnly
     }
 
-    pub fn fills_enabled(&self) -> bool {
-        matches!(
-            self.status,
-            MarketStatus::Active
-                | MarketStatus::FundingPaused
-                | MarketStatus::ReduceOnly
-                | MarketStatus::WithdrawPaused
+    pub fn fills_enabled(&self) -> DriftResult<bool> {
+        Ok(
+            (self.status.is_active_or_legacy_paused() || self.status == MarketStatus::ReduceOnly)
+                && !self.is_operation_paused(MarketOperation::MatchFill)?,
+        )
+    }
+
+    pub fn is_operation_paused(&self, operation: MarketOperation) -> DriftResult<bool> {
+        MarketOperation::is_operation_paused(
+            self.paused_operations | self.status.legacy_paused_operations(),
+            operation,
         )
     }
 
@@ -520,3 +551,104 @@ pub struct InsuranceFund {
     pub total_factor: u32, // percentage of interest for total insurance
     pub user_factor: u32,  // percentage of interest for user staked insurance
 }
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
+pub enum ProtocolRevenueSource {
+    /// Fees paid by takers on swaps and spot fills
+    TakerFee,
+    /// Insurance fund fees taken from spot liquidations
+    LiquidationFee,
+    /// The stakers' cut of borrow interest
+    BorrowInterest,
+    /// Fees settled from perp market amm fee pools
+    AmmFee,
+}
+
+#[zero_copy]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct ProtocolRevenue {
+    /// precision: token mint precision
+    pub taker_fees: u64,
+    /// precision: token mint precision
+    pub liquidation_fees: u64,
+    /// precision: token mint precision
+    pub borrow_interest: u64,
+    /// precision: token mint precision
+    pub amm_fees: u64,
+    /// Revenue pulled back into perp market amm fee pools when they run a deficit
+    /// precision: token mint precision
+    pub amm_fee_pool_top_ups: u64,
+    /// Revenue settled to the insurance fund (senior and junior tranche)
+    /// Matches the sum of InsuranceFundRecord amounts for this market
+    /// precision: token mint precision
+    pub settled_to_insurance_fund: u64,
+    /// Revenue withdrawn by the protocol
+    /// precision: token mint precision
+    pub withdrawn: u64,
+    /// The max amount that can be withdrawn per withdraw period. 0 disables withdrawals
+    /// precision: token mint precision
+    pub max_withdraw_per_period: u64,
+    /// Amount withdrawn in the current withdraw period
+    /// precision: token mint precision
+    pub withdrawn_in_period: u64,
+    /// Length of the withdraw period in seconds
+    pub withdraw_period: i64,
+    /// Start of the current withdraw period
+    pub last_withdraw_period_start_ts: i64,
+    pub padding: [u8; 8],
+}
+
+impl ProtocolRevenue {
+    pub fn record_revenue(&mut self, source: ProtocolRevenueSource, amount: u128) -> DriftResult {
+        let amount = amount.cast::<u64>()?;
+        match source {
+            ProtocolRevenueSource::TakerFee => {
+                self.taker_fees = self.taker_fees.saturating_add(amount)
+            }
+            ProtocolRevenueSource::LiquidationFee => {
+                self.liquidation_fees = self.liquidation_fees.saturating_add(amount)
+            }
+            ProtocolRevenueSource::BorrowInterest => {
+                self.borrow_interest = self.borrow_interest.saturating_add(amount)
+            }
+            ProtocolRevenueSource::AmmFee => self.amm_fees = self.amm_fees.saturating_add(amount),
+        }
+
+        Ok(())
+    }
+
+    pub fn record_amm_fee_pool_top_up(&mut self, amount: u128) -> DriftResult {
+        self.amm_fee_pool_top_ups = self
+            .amm_fee_pool_top_ups
+            .saturating_add(amount.cast::<u64>()?);
+
+        Ok(())
+    }
+
+    pub fn record_settle_to_insurance_fund(&mut self, amount: u64) {
+        self.settled_to_insurance_fund = self.settled_to_insurance_fund.saturating_add(amount);
+    }
+
+    pub fn update_withdraw(&mut self, amount: u64, now: i64) -> DriftResult {
+        if now.safe_sub(self.last_withdraw_period_start_ts)? >= self.withdraw_period {
+            self.withdrawn_in_period = 0;
+            self.last_withdraw_period_start_ts = now;
+        }
+
+        let withdrawn_in_period = self.withdrawn_in_period.safe_add(amount)?;
+
+        validate!(
+            withdrawn_in_period <= self.max_withdraw_per_period,
+            ErrorCode::ProtocolRevenueWithdrawLimitExceeded,
+            "withdraw would exceed max_withdraw_per_period ({} > {})",
+            withdrawn_in_period,
+            self.max_withdraw_per_period
+        )?;
+
+        self.withdrawn_in_period = withdrawn_in_period;
+        self.withdrawn = self.withdrawn.safe_add(amount)?;
+
+        Ok(())
+    }
+}
@@ -3,9 +3,11 @@ use enumflags2::BitFlags;
 
 use crate::error::DriftResult;
 use crate::math::constants::{
-    FEE_DENOMINATOR, FEE_PERCENTAGE_DENOMINATOR, MAX_REFERRER_REWARD_EPOCH_UPPER_BOUND,
+    FEE_DENOMINATOR, FEE_PERCENTAGE_DENOMINATOR, LIQUIDATION_FEE_PRECISION,
+    MAX_REFERRER_REWARD_EPOCH_UPPER_BOUND,
 };
 use crate::math::safe_unwrap::SafeUnwrap;
+use crate::state::perp_market::ContractTier;
 use crate::state::traits::Size;
 use crate::PERCENTAGE_PRECISION_U64;
 
@@ -35,7 +37,20 @@ pub struct State {
     pub exchange_status: u8,
     pub liquidation_duration: u8,
     pub initial_pct_to_liquidate: u16,
-    pub padding: [u8; 14],
+    /// The number of slots for the perp liquidator fee to ramp from zero to the market's liquidator fee
+    /// after a user enters liquidation. 0 disables the auction and liquidators receive the full fee
+    pub liquidation_auction_duration: u16,
+    /// Bitmask of the contract tiers (1 << tier) whose perp bankruptcies are resolved by auto-deleveraging
+    /// profitable positions before socializing the remaining loss through funding
+    pub auto_deleverage_contract_tiers: u8,
+    /// Descending health thresholds. A HealthRecord is emitted when a user tracking their health crosses
+    /// one. The first is the threshold users' time below is tracked against. 0 is unused
+    pub health_bands: [u8; 4],
+    /// The minimum auto deleverage score, pnl percentage times leverage, a position needs to be
+    /// auto-deleveraged. 0 makes every profitable position eligible
+    /// precision: PERCENTAGE_PRECISION
+    pub auto_deleverage_score_threshold: u32,
+    pub padding: [u8; 3],
 }
 
 #[derive(BitFlags, Clone, Copy, PartialEq, Debug, Eq)]
@@ -73,6 +88,10 @@ impl State {
             .get_exchange_status()?
             .contains(ExchangeStatus::FundingPaused))
     }
+
+    pub fn is_auto_deleverage_enabled(&self, contract_tier: ContractTier) -> bool {
+        self.auto_deleverage_contract_tiers & (1 << contract_tier as u8) != 0
+    }
 }
 
 impl Size for State {
@@ -276,6 +295,22 @@ impl FeeStructure {
             referrer_reward_epoch_upper_bound: MAX_REFERRER_REWARD_EPOCH_UPPER_BOUND,
         }
     }
+
+    /// Every tier charges the perp market's liquidator fee, which the amm earns when it takes over a
+    /// liquidated position in place of a liquidator
+    pub fn amm_liquidation(liquidator_fee: u32) -> Self {
+        let fee_tier = FeeTier {
+            fee_numerator: liquidator_fee,
+            fee_denominator: LIQUIDATION_FEE_PRECISION,
+            ..FeeTier::default()
+        };
+        FeeStructure {
+            fee_tiers: [fee_tier; 10],
+            filler_reward_structure: OrderFillerRewardStructure::default(),
+            flat_filler_fee: 0,
+            referrer_reward_epoch_upper_bound: 0,
+        }
+    }
 }
 
 #[cfg(test)]
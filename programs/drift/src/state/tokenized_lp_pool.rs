@@ -0,0 +1,24 @@
+use crate::state::traits::Size;
+use anchor_lang::prelude::*;
+
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct TokenizedLpPool {
+    pub pubkey: Pubkey,
+    /// The spl mint for the pool's lp tokens
+    pub mint: Pubkey,
+    /// The user account owned by the pool. It holds the tokenized lp shares along with the
+    /// inventory and collateral backing them, and is margined and liquidated like any other user
+    pub user: Pubkey,
+    /// The supply of the pool's lp tokens. Always equal to the mint's supply
+    /// Each token is a claim on an equal slice of the pool user's lp shares, positions and collateral
+    /// precision: AMM_RESERVE_PRECISION
+    pub token_supply: u64,
+    pub market_index: u16,
+    pub padding: [u8; 6],
+}
+
+impl Size for TokenizedLpPool {
+    const SIZE: usize = 120;
+}
@@ -115,7 +115,19 @@ pub struct User {
     pub open_auctions: u8,
     /// Whether or not user has open order with auction
     pub has_open_auction: bool,
-    pub padding: [u8; 21],
+    /// Whether the user's margin is shared with the authority's other sub accounts in the group
+    pub is_in_shared_collateral_group: bool,
+    /// Whether the user's health snapshot is updated when their margin is computed
+    pub is_health_tracking_enabled: bool,
+    /// The user's health when margin was last computed, from 0 (liquidatable) to 100 (no margin requirement)
+    pub last_health: u8,
+    /// The number of UserDelegate accounts for the user. They must be closed before the user is deleted
+    pub number_of_user_delegates: u8,
+    pub padding: [u8; 1],
+    /// The slot the user's health was last computed
+    pub last_health_slot: u64,
+    /// The number of consecutive slots the user's health has been below the first health band
+    pub slots_below_health_threshold: u64,
 }
 
 impl User {
@@ -589,7 +601,7 @@ impl PerpPosition {
         }
 
         // compute lp metrics
-        let lp_metrics = calculate_settle_lp_metrics(&market.amm, self)?;
+        let lp_metrics = calculate_settle_lp_metrics(&market.amm, self, None)?;
 
         // compute settled position
         let base_asset_amount = self
@@ -611,7 +623,7 @@ impl PerpPosition {
             quote_asset_amount = quote_asset_amount.safe_sub(dust_base_asset_value.cast()?)?;
         }
 
-        let (lp_bids, lp_asks) = calculate_lp_open_bids_asks(self, market)?;
+        let (lp_bids, lp_asks) = calculate_lp_open_bids_asks(self, market, None)?;
 
         let open_bids = self.open_bids.safe_add(lp_bids)?;
 
@@ -766,7 +778,9 @@ pub struct Order {
     pub trigger_condition: OrderTriggerCondition,
     /// How many slots the auction lasts
     pub auction_duration: u8,
-    pub padding: [u8; 3],
+    /// Whether the order was placed by a keeper to hedge an lp position. The keeper reward is paid on fill
+    pub is_lp_hedge: bool,
+    pub padding: [u8; 2],
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug)]
@@ -1003,7 +1017,8 @@ impl Default for Order {
             auction_end_price: 0,
             auction_duration: 0,
             max_ts: 0,
-            padding: [0; 3],
+            is_lp_hedge: false,
+            padding: [0; 2],
         }
     }
 }
@@ -1102,7 +1117,11 @@ pub struct UserStats {
     pub number_of_sub_accounts_created: u16,
     /// Whether the user is a referrer. Sub account 0 can not be deleted if user is a referrer
     pub is_referrer: bool,
-    pub padding: [u8; 51],
+    /// Whether withdrawals are restricted by a WithdrawPolicy
+    pub has_withdraw_policy: bool,
+    /// The number of sub accounts in the shared collateral group
+    pub number_of_sub_accounts_in_shared_collateral_group: u16,
+    pub padding: [u8; 48],
 }
 
 impl Default for UserStats {
@@ -1122,7 +1141,9 @@ impl Default for UserStats {
             number_of_sub_accounts: 0,
             number_of_sub_accounts_created: 0,
             is_referrer: false,
-            padding: [0; 51],
+            has_withdraw_policy: false,
+            number_of_sub_accounts_in_shared_collateral_group: 0,
+            padding: [0; 48],
         }
     }
 }
@@ -0,0 +1,152 @@
+use anchor_lang::prelude::*;
+use enumflags2::BitFlags;
+
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::safe_unwrap::SafeUnwrap;
+use crate::state::traits::Size;
+use crate::state::user::MarketType;
+use crate::validate;
+
+#[cfg(test)]
+mod tests;
+
+pub const MAX_USER_DELEGATE_MARKETS: usize = 8;
+
+/// Actions a scoped delegate can take on behalf of a user
+#[derive(BitFlags, Clone, Copy, PartialEq, Debug, Eq)]
+pub enum DelegatePermission {
+    /// placing and modifying orders
+    PlaceOrder = 0b00000001,
+    /// cancelling orders
+    CancelOrder = 0b00000010,
+    /// adding and removing lp shares
+    Lp = 0b00000100,
+    /// swapping spot tokens
+    Swap = 0b00001000,
+    /// acting in every market. if not set, the delegate is restricted to the listed markets
+    AllMarkets = 0b00010000,
+}
+
+/// A signer that can act on behalf of a user with a restricted set of permissions
+///
+/// PDA of [b"user_delegate", user, delegate], so a user can have any number of delegates
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct UserDelegate {
+    /// The user account the delegate can act on
+    pub user: Pubkey,
+    /// The delegate's signing key
+    pub delegate: Pubkey,
+    /// The max notional value of an order the delegate can place. 0 is no limit
+    /// precision: QUOTE_PRECISION
+    pub max_order_notional: u64,
+    /// The perp markets the delegate can act in if AllMarkets isn't permitted
+    pub perp_market_indexes: [u16; 8],
+    /// The spot markets the delegate can act in if AllMarkets isn't permitted
+    pub spot_market_indexes: [u16; 8],
+    pub number_of_perp_markets: u8,
+    pub number_of_spot_markets: u8,
+    /// Bitflags of DelegatePermission
+    pub permissions: u8,
+    pub padding: [u8; 29],
+}
+
+impl Size for UserDelegate {
+    const SIZE: usize = 144;
+}
+
+impl UserDelegate {
+    pub fn update(
+        &mut self,
+        permissions: u8,
+        max_order_notional: u64,
+        perp_market_indexes: &[u16],
+        spot_market_indexes: &[u16],
+    ) -> DriftResult {
+        validate!(
+            BitFlags::<DelegatePermission>::from_bits(usize::from(permissions)).is_ok(),
+            ErrorCode::InvalidUserDelegate,
+            "invalid delegate permissions {}",
+            permissions
+        )?;
+
+        validate!(
+            perp_market_indexes.len() <= MAX_USER_DELEGATE_MARKETS
+                && spot_market_indexes.len() <= MAX_USER_DELEGATE_MARKETS,
+            ErrorCode::InvalidUserDelegate,
+            "delegate can be scoped to at most {} perp and {} spot markets",
+            MAX_USER_DELEGATE_MARKETS,
+            MAX_USER_DELEGATE_MARKETS
+        )?;
+
+        self.permissions = permissions;
+        self.max_order_notional = max_order_notional;
+
+        self.perp_market_indexes = [0; MAX_USER_DELEGATE_MARKETS];
+        self.perp_market_indexes[..perp_market_indexes.len()].copy_from_slice(perp_market_indexes);
+        self.number_of_perp_markets = perp_market_indexes.len() as u8;
+
+        self.spot_market_indexes = [0; MAX_USER_DELEGATE_MARKETS];
+        self.spot_market_indexes[..spot_market_indexes.len()].copy_from_slice(spot_market_indexes);
+        self.number_of_spot_markets = spot_market_indexes.len() as u8;
+
+        Ok(())
+    }
+
+    pub fn has_permission(&self, permission: DelegatePermission) -> DriftResult<bool> {
+        Ok(
+            BitFlags::<DelegatePermission>::from_bits(usize::from(self.permissions))
+                .safe_unwrap()?
+                .contains(permission),
+        )
+    }
+
+    pub fn validate_permission(&self, permission: DelegatePermission) -> DriftResult {
+        validate!(
+            self.has_permission(permission)?,
+            ErrorCode::DelegatePermissionDenied,
+            "delegate {} lacks {:?} permission",
+            self.delegate,
+            permission
+        )
+    }
+
+    pub fn can_act_in_market(
+        &self,
+        market_type: MarketType,
+        market_index: u16,
+    ) -> DriftResult<bool> {
+        if self.has_permission(DelegatePermission::AllMarkets)? {
+            return Ok(true);
+        }
+
+        let market_indexes = match market_type {
+            MarketType::Perp => &self.perp_market_indexes[..self.number_of_perp_markets as usize],
+            MarketType::Spot => &self.spot_market_indexes[..self.number_of_spot_markets as usize],
+        };
+
+        Ok(market_indexes.contains(&market_index))
+    }
+
+    pub fn validate_market(&self, market_type: MarketType, market_index: u16) -> DriftResult {
+        validate!(
+            self.can_act_in_market(market_type, market_index)?,
+            ErrorCode::DelegatePermissionDenied,
+            "delegate {} cant act in {:?} market {}",
+            self.delegate,
+            market_type,
+            market_index
+        )
+    }
+
+    pub fn validate_order_notional(&self, order_notional: u64) -> DriftResult {
+        validate!(
+            self.max_order_notional == 0 || order_notional <= self.max_order_notional,
+            ErrorCode::DelegatePermissionDenied,
+            "order notional {} exceeds delegate max order notional {}",
+            order_notional,
+            self.max_order_notional
+        )
+    }
+}
@@ -0,0 +1,100 @@
+use crate::state::user::MarketType;
+use crate::state::user_delegate::{DelegatePermission, UserDelegate};
+
+#[test]
+fn permissions() {
+    let mut user_delegate = UserDelegate::default();
+    user_delegate
+        .update(
+            DelegatePermission::PlaceOrder as u8 | DelegatePermission::CancelOrder as u8,
+            0,
+            &[],
+            &[],
+        )
+        .unwrap();
+
+    assert!(user_delegate
+        .validate_permission(DelegatePermission::PlaceOrder)
+        .is_ok());
+    assert!(user_delegate
+        .validate_permission(DelegatePermission::CancelOrder)
+        .is_ok());
+    assert!(user_delegate
+        .validate_permission(DelegatePermission::Lp)
+        .is_err());
+    assert!(user_delegate
+        .validate_permission(DelegatePermission::Swap)
+        .is_err());
+
+    // cancel only
+    user_delegate
+        .update(DelegatePermission::CancelOrder as u8, 0, &[], &[])
+        .unwrap();
+    assert!(user_delegate
+        .validate_permission(DelegatePermission::PlaceOrder)
+        .is_err());
+    assert!(user_delegate
+        .validate_permission(DelegatePermission::CancelOrder)
+        .is_ok());
+
+    // unknown bits
+    assert!(user_delegate.update(0b10000000, 0, &[], &[]).is_err());
+}
+
+#[test]
+fn markets() {
+    let mut user_delegate = UserDelegate::default();
+    user_delegate
+        .update(DelegatePermission::PlaceOrder as u8, 0, &[0, 2], &[1])
+        .unwrap();
+
+    assert!(user_delegate.validate_market(MarketType::Perp, 0).is_ok());
+    assert!(user_delegate.validate_market(MarketType::Perp, 2).is_ok());
+    assert!(user_delegate.validate_market(MarketType::Perp, 1).is_err());
+    assert!(user_delegate.validate_market(MarketType::Spot, 1).is_ok());
+    assert!(user_delegate.validate_market(MarketType::Spot, 0).is_err());
+
+    user_delegate
+        .update(
+            DelegatePermission::PlaceOrder as u8 | DelegatePermission::AllMarkets as u8,
+            0,
+            &[],
+            &[],
+        )
+        .unwrap();
+
+    assert!(user_delegate.validate_market(MarketType::Perp, 1).is_ok());
+    assert!(user_delegate.validate_market(MarketType::Spot, 5).is_ok());
+
+    // removing markets clears old entries
+    user_delegate
+        .update(DelegatePermission::PlaceOrder as u8, 0, &[3], &[])
+        .unwrap();
+    assert!(user_delegate.validate_market(MarketType::Perp, 0).is_err());
+    assert!(user_delegate.validate_market(MarketType::Perp, 3).is_ok());
+    assert!(user_delegate.validate_market(MarketType::Spot, 1).is_err());
+
+    assert!(user_delegate
+        .update(
+            DelegatePermission::PlaceOrder as u8,
+            0,
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8],
+            &[]
+        )
+        .is_err());
+}
+
+#[test]
+fn max_order_notional() {
+    let mut user_delegate = UserDelegate::default();
+    user_delegate
+        .update(DelegatePermission::PlaceOrder as u8, 0, &[], &[])
+        .unwrap();
+    assert!(user_delegate.validate_order_notional(u64::MAX).is_ok());
+
+    user_delegate
+        .update(DelegatePermission::PlaceOrder as u8, 1000, &[], &[])
+        .unwrap();
+    assert!(user_delegate.validate_order_notional(1000).is_ok());
+    assert!(user_delegate.validate_order_notional(1001).is_err());
+}
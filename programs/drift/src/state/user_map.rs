@@ -273,6 +273,39 @@ impl<'a> UserStatsMap<'a> {
     }
 }
 
+/// Loads the writable user accounts at the front of the remaining accounts
+pub fn load_user_map<'a>(
+    account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
+) -> DriftResult<UserMap<'a>> {
+    let mut user_map = UserMap::empty();
+
+    let user_discriminator: [u8; 8] = User::discriminator();
+    while let Some(user_account_info) = account_info_iter.peek() {
+        {
+            let data = user_account_info
+                .try_borrow_data()
+                .or(Err(ErrorCode::CouldNotLoadUserData))?;
+
+            if data.len() < User::SIZE || array_ref![data, 0, 8] != &user_discriminator {
+                break;
+            }
+        }
+
+        let user_account_info = account_info_iter.next().safe_unwrap()?;
+
+        if !user_account_info.is_writable {
+            return Err(ErrorCode::UserWrongMutability);
+        }
+
+        let user_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(user_account_info).or(Err(ErrorCode::InvalidUserAccount))?;
+
+        user_map.insert(*user_account_info.key, user_account_loader)?;
+    }
+
+    Ok(user_map)
+}
+
 pub fn load_user_maps<'a>(
     account_info_iter: &mut Peekable<Iter<AccountInfo<'a>>>,
 ) -> DriftResult<(UserMap<'a>, UserStatsMap<'a>)> {
@@ -0,0 +1,260 @@
+use anchor_lang::prelude::*;
+
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::safe_math::SafeMath;
+use crate::state::traits::Size;
+use crate::validate;
+
+#[cfg(test)]
+mod tests;
+
+pub const MAX_WITHDRAW_DESTINATIONS: usize = 8;
+
+/// Restricts where an authority's withdrawals can be sent
+///
+/// Withdrawals to allowlisted token account owners go through immediately. Withdrawals to any other
+/// owner must be requested first and can only be executed once the time lock has passed, giving the
+/// authority a window to cancel them
+///
+/// Policies can only be tightened once created. Removing a policy goes through the same time lock
+#[account(zero_copy)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct WithdrawPolicy {
+    pub authority: Pubkey,
+    /// The token account owners withdrawals can be sent to without a time lock
+    pub allowed_destinations: [Pubkey; 8],
+    /// A requested withdrawal to a destination not on the allowlist
+    pub pending_withdrawal: PendingWithdrawal,
+    /// How long a withdrawal to a new destination must wait after being requested
+    pub time_lock_period: i64,
+    pub number_of_allowed_destinations: u8,
+    pub padding1: [u8; 7],
+    /// The time the policy can be deleted after removal was requested. 0 if removal wasn't requested
+    pub removal_unlock_ts: i64,
+    pub padding: [u8; 16],
+}
+
+impl Size for WithdrawPolicy {
+    const SIZE: usize = 392;
+}
+
+#[zero_copy]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct PendingWithdrawal {
+    pub destination: Pubkey,
+    /// The amount left to withdraw
+    /// precision: token mint precision
+    pub amount: u64,
+    /// The time the withdrawal can be executed
+    pub unlock_ts: i64,
+    pub market_index: u16,
+    pub padding: [u8; 6],
+}
+
+impl PendingWithdrawal {
+    pub fn is_empty(&self) -> bool {
+        self.amount == 0
+    }
+}
+
+impl WithdrawPolicy {
+    pub fn get_allowed_destinations(&self) -> &[Pubkey] {
+        &self.allowed_destinations[..self.number_of_allowed_destinations as usize]
+    }
+
+    pub fn is_allowed_destination(&self, destination: &Pubkey) -> bool {
+        self.get_allowed_destinations().contains(destination)
+    }
+
+    pub fn initialize(
+        &mut self,
+        authority: Pubkey,
+        time_lock_period: i64,
+        allowed_destinations: &[Pubkey],
+    ) -> DriftResult {
+        self.authority = authority;
+        self.set(time_lock_period, allowed_destinations)
+    }
+
+    /// Updates the policy. Destinations can be removed and the time lock lengthened, but new
+    /// destinations must go through the time lock with request_withdrawal
+    pub fn update(
+        &mut self,
+        time_lock_period: i64,
+        allowed_destinations: &[Pubkey],
+    ) -> DriftResult {
+        validate!(
+            time_lock_period >= self.time_lock_period,
+            ErrorCode::InvalidWithdrawPolicy,
+            "time lock period cant be reduced from {} to {}",
+            self.time_lock_period,
+            time_lock_period
+        )?;
+
+        for destination in allowed_destinations {
+            validate!(
+                self.is_allowed_destination(destination),
+                ErrorCode::InvalidWithdrawPolicy,
+                "cant add destination {} to withdraw policy",
+                destination
+            )?;
+        }
+
+        self.set(time_lock_period, allowed_destinations)
+    }
+
+    fn set(&mut self, time_lock_period: i64, allowed_destinations: &[Pubkey]) -> DriftResult {
+        validate!(
+            time_lock_period >= 0,
+            ErrorCode::InvalidWithdrawPolicy,
+            "time lock period must be >= 0"
+        )?;
+
+        validate!(
+            allowed_destinations.len() <= MAX_WITHDRAW_DESTINATIONS,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy can have at most {} destinations",
+            MAX_WITHDRAW_DESTINATIONS
+        )?;
+
+        self.time_lock_period = time_lock_period;
+
+        self.allowed_destinations = [Pubkey::default(); MAX_WITHDRAW_DESTINATIONS];
+        self.allowed_destinations[..allowed_destinations.len()]
+            .copy_from_slice(allowed_destinations);
+        self.number_of_allowed_destinations = allowed_destinations.len() as u8;
+
+        Ok(())
+    }
+
+    pub fn request_withdrawal(
+        &mut self,
+        destination: Pubkey,
+        market_index: u16,
+        amount: u64,
+        now: i64,
+    ) -> DriftResult {
+        validate!(
+            self.pending_withdrawal.is_empty(),
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy already has a pending withdrawal"
+        )?;
+
+        validate!(
+            amount != 0,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdrawal amount must be > 0"
+        )?;
+
+        self.pending_withdrawal = PendingWithdrawal {
+            destination,
+            amount,
+            unlock_ts: now.safe_add(self.time_lock_period)?,
+            market_index,
+            padding: [0; 6],
+        };
+
+        Ok(())
+    }
+
+    pub fn cancel_withdrawal(&mut self) -> DriftResult {
+        validate!(
+            !self.pending_withdrawal.is_empty(),
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy has no pending withdrawal"
+        )?;
+
+        self.pending_withdrawal = PendingWithdrawal::default();
+
+        Ok(())
+    }
+
+    pub fn request_removal(&mut self, now: i64) -> DriftResult {
+        validate!(
+            self.removal_unlock_ts == 0,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy removal already requested"
+        )?;
+
+        self.removal_unlock_ts = now.safe_add(self.time_lock_period)?.max(1);
+
+        Ok(())
+    }
+
+    pub fn cancel_removal(&mut self) -> DriftResult {
+        validate!(
+            self.removal_unlock_ts != 0,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy removal not requested"
+        )?;
+
+        self.removal_unlock_ts = 0;
+
+        Ok(())
+    }
+
+    pub fn validate_removal(&self, now: i64) -> DriftResult {
+        validate!(
+            self.removal_unlock_ts != 0,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy removal not requested"
+        )?;
+
+        validate!(
+            now >= self.removal_unlock_ts,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy removal locked until {}",
+            self.removal_unlock_ts
+        )
+    }
+
+    /// Checks a withdrawal can be sent to destination. Withdrawals to destinations not on the
+    /// allowlist use up the unlocked pending withdrawal
+    pub fn validate_withdrawal(
+        &mut self,
+        destination: &Pubkey,
+        market_index: u16,
+        amount: u64,
+        now: i64,
+    ) -> DriftResult {
+        if self.is_allowed_destination(destination) {
+            return Ok(());
+        }
+
+        let pending_withdrawal = &mut self.pending_withdrawal;
+
+        validate!(
+            !pending_withdrawal.is_empty()
+                && pending_withdrawal.destination == *destination
+                && pending_withdrawal.market_index == market_index,
+            ErrorCode::WithdrawDestinationNotAllowed,
+            "destination {} not allowed by withdraw policy",
+            destination
+        )?;
+
+        validate!(
+            now >= pending_withdrawal.unlock_ts,
+            ErrorCode::WithdrawDestinationNotAllowed,
+            "pending withdrawal locked until {}",
+            pending_withdrawal.unlock_ts
+        )?;
+
+        validate!(
+            amount <= pending_withdrawal.amount,
+            ErrorCode::WithdrawDestinationNotAllowed,
+            "withdrawal amount {} > pending withdrawal amount {}",
+            amount,
+            pending_withdrawal.amount
+        )?;
+
+        pending_withdrawal.amount = pending_withdrawal.amount.safe_sub(amount)?;
+
+        if pending_withdrawal.is_empty() {
+            *pending_withdrawal = PendingWithdrawal::default();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use anchor_lang::prelude::Pubkey;
+
+use crate::state::withdraw_policy::WithdrawPolicy;
+
+#[test]
+fn update() {
+    let authority = Pubkey::new_unique();
+    let destination_a = Pubkey::new_unique();
+    let destination_b = Pubkey::new_unique();
+
+    let mut withdraw_policy = WithdrawPolicy::default();
+    withdraw_policy
+        .initialize(authority, 3600, &[destination_a, destination_b])
+        .unwrap();
+
+    assert!(withdraw_policy.is_allowed_destination(&destination_a));
+    assert!(withdraw_policy.is_allowed_destination(&destination_b));
+    assert!(!withdraw_policy.is_allowed_destination(&authority));
+
+    // cant reduce time lock
+    assert!(withdraw_policy.update(60, &[destination_a]).is_err());
+
+    // cant add new destination
+    let destination_c = Pubkey::new_unique();
+    assert!(withdraw_policy
+        .update(3600, &[destination_a, destination_c])
+        .is_err());
+
+    // can remove destination and lengthen time lock
+    withdraw_policy.update(7200, &[destination_b]).unwrap();
+    assert!(!withdraw_policy.is_allowed_destination(&destination_a));
+    assert!(withdraw_policy.is_allowed_destination(&destination_b));
+    assert_eq!(withdraw_policy.time_lock_period, 7200);
+}
+
+#[test]
+fn validate_withdrawal() {
+    let authority = Pubkey::new_unique();
+    let allowed_destination = Pubkey::new_unique();
+    let new_destination = Pubkey::new_unique();
+
+    let mut withdraw_policy = WithdrawPolicy::default();
+    withdraw_policy
+        .initialize(authority, 3600, &[allowed_destination])
+        .unwrap();
+
+    let now = 1000;
+    assert!(withdraw_policy
+        .validate_withdrawal(&allowed_destination, 0, u64::MAX, now)
+        .is_ok());
+    assert!(withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 100, now)
+        .is_err());
+
+    withdraw_policy
+        .request_withdrawal(new_destination, 0, 100, now)
+        .unwrap();
+    assert_eq!(withdraw_policy.pending_withdrawal.unlock_ts, now + 3600);
+
+    // only one pending withdrawal at a time
+    assert!(withdraw_policy
+        .request_withdrawal(new_destination, 0, 100, now)
+        .is_err());
+
+    // still locked
+    assert!(withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 100, now + 3599)
+        .is_err());
+
+    // wrong market
+    assert!(withdraw_policy
+        .validate_withdrawal(&new_destination, 1, 100, now + 3600)
+        .is_err());
+
+    // too much
+    assert!(withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 101, now + 3600)
+        .is_err());
+
+    withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 60, now + 3600)
+        .unwrap();
+    assert_eq!(withdraw_policy.pending_withdrawal.amount, 40);
+
+    withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 40, now + 3600)
+        .unwrap();
+    assert!(withdraw_policy.pending_withdrawal.is_empty());
+
+    assert!(withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 1, now + 3600)
+        .is_err());
+}
+
+#[test]
+fn cancel_withdrawal() {
+    let new_destination = Pubkey::new_unique();
+
+    let mut withdraw_policy = WithdrawPolicy::default();
+    withdraw_policy
+        .initialize(Pubkey::new_unique(), 3600, &[])
+        .unwrap();
+
+    assert!(withdraw_policy.cancel_withdrawal().is_err());
+
+    withdraw_policy
+        .request_withdrawal(new_destination, 0, 100, 0)
+        .unwrap();
+    withdraw_policy.cancel_withdrawal().unwrap();
+
+    assert!(withdraw_policy.pending_withdrawal.is_empty());
+    assert!(withdraw_policy
+        .validate_withdrawal(&new_destination, 0, 100, 3600)
+        .is_err());
+}
+
+#[test]
+fn removal() {
+    let authority = Pubkey::new_unique();
+
+    let mut withdraw_policy = WithdrawPolicy::default();
+    withdraw_policy.initialize(authority, 3600, &[]).unwrap();
+
+    // must be requested first
+    assert!(withdraw_policy.validate_removal(0).is_err());
+    assert!(withdraw_policy.cancel_removal().is_err());
+
+    withdraw_policy.request_removal(100).unwrap();
+    assert_eq!(withdraw_policy.removal_unlock_ts, 3700);
+    assert!(withdraw_policy.request_removal(100).is_err());
+
+    // time locked
+    assert!(withdraw_policy.validate_removal(3699).is_err());
+    assert!(withdraw_policy.validate_removal(3700).is_ok());
+
+    withdraw_policy.cancel_removal().unwrap();
+    assert_eq!(withdraw_policy.removal_unlock_ts, 0);
+    assert!(withdraw_policy.validate_removal(3700).is_err());
+}
@@ -0,0 +1,58 @@
+use crate::error::DriftResult;
+use crate::instructions::OrderParams;
+use crate::math::casting::Cast;
+use crate::math::constants::BASE_PRECISION;
+use crate::math::safe_math::SafeMath;
+use crate::state::oracle_map::OracleMap;
+use crate::state::perp_market_map::PerpMarketMap;
+use crate::state::spot_market_map::SpotMarketMap;
+use crate::state::user::MarketType;
+use crate::state::user_delegate::{DelegatePermission, UserDelegate};
+
+/// Checks a scoped delegate can place the order. The notional is valued at the order's limit price,
+/// or the oracle price if the order doesn't have one
+pub fn validate_delegate_order_params(
+    user_delegate: &UserDelegate,
+    params: &OrderParams,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+) -> DriftResult {
+    user_delegate.validate_permission(DelegatePermission::PlaceOrder)?;
+    user_delegate.validate_market(params.market_type, params.market_index)?;
+
+    if user_delegate.max_order_notional == 0 {
+        return Ok(());
+    }
+
+    let (oracle, base_precision) = match params.market_type {
+        MarketType::Perp => {
+            let perp_market = perp_market_map.get_ref(&params.market_index)?;
+            (perp_market.amm.oracle, BASE_PRECISION)
+        }
+        MarketType::Spot => {
+            let spot_market = spot_market_map.get_ref(&params.market_index)?;
+            (spot_market.oracle, spot_market.get_precision().cast()?)
+        }
+    };
+
+    let oracle_price = oracle_map.get_price_data(&oracle)?.price;
+
+    let price = match params.oracle_price_offset {
+        Some(oracle_price_offset) if oracle_price_offset != 0 => oracle_price
+            .safe_add(oracle_price_offset.cast()?)?
+            .max(0)
+            .unsigned_abs(),
+        _ if params.price != 0 => params.price,
+        _ => oracle_price.unsigned_abs(),
+    };
+
+    let order_notional = params
+        .base_asset_amount
+        .cast::<u128>()?
+        .safe_mul(price.cast()?)?
+        .safe_div(base_precision)?
+        .cast::<u64>()?;
+
+    user_delegate.validate_order_notional(order_notional)
+}
@@ -1,3 +1,4 @@
+pub mod delegate;
 pub mod fee_structure;
 pub mod margin;
 pub mod order;
@@ -1,6 +1,7 @@
 use crate::controller::position::PositionDirection;
 use crate::error::{DriftResult, ErrorCode};
 use crate::math::casting::Cast;
+use crate::math::constants::{ONE_HOUR, PERCENTAGE_PRECISION_U64, TWENTY_FOUR_HOUR};
 use crate::math::safe_math::SafeMath;
 
 use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
@@ -189,6 +190,50 @@ pub fn validate_perp_market(market: &PerpMarket) -> DriftResult {
         market.insurance_claim.revenue_withdraw_since_last_settle.unsigned_abs()
     )?;
 
+    validate_perp_market_funding_params(market)?;
+
+    Ok(())
+}
+
+pub fn validate_perp_market_funding_period(market: &PerpMarket) -> DriftResult {
+    validate!(
+        market.amm.funding_period >= ONE_HOUR && market.amm.funding_period <= TWENTY_FOUR_HOUR,
+        ErrorCode::InvalidPerpMarketFundingParams,
+        "funding_period={} must be between one hour and one day",
+        market.amm.funding_period
+    )?;
+
+    Ok(())
+}
+
+// a funding_max_price_spread or funding_premium_dampener of 0 means the default is used
+pub fn validate_perp_market_funding_params(market: &PerpMarket) -> DriftResult {
+    let amm = &market.amm;
+
+    let funding_max_price_spread = if amm.funding_max_price_spread == 0 {
+        // default clamp is 1/33 of the oracle twap
+        PERCENTAGE_PRECISION_U64.safe_div(33)?.cast::<u32>()?
+    } else {
+        // funding paid over a window can't be more than the initial margin
+        validate!(
+            amm.funding_max_price_spread < market.margin_ratio_initial.safe_mul(100)?,
+            ErrorCode::InvalidPerpMarketFundingParams,
+            "funding_max_price_spread={} must be < margin_ratio_initial={} * 100",
+            amm.funding_max_price_spread,
+            market.margin_ratio_initial
+        )?;
+
+        amm.funding_max_price_spread
+    };
+
+    validate!(
+        amm.funding_interest_rate.unsigned_abs() <= funding_max_price_spread,
+        ErrorCode::InvalidPerpMarketFundingParams,
+        "|funding_interest_rate|={} must be <= funding_max_price_spread={}",
+        amm.funding_interest_rate.unsigned_abs(),
+        funding_max_price_spread
+    )?;
+
     Ok(())
 }
 
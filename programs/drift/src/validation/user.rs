@@ -23,6 +23,19 @@ pub fn validate_user_deletion(user: &User, user_stats: &UserStats) -> DriftResul
         "user being liquidated"
     )?;
 
+    validate!(
+        !user.is_in_shared_collateral_group,
+        ErrorCode::UserCantBeDeleted,
+        "user in shared collateral group"
+    )?;
+
+    validate!(
+        user.number_of_user_delegates == 0,
+        ErrorCode::UserCantBeDeleted,
+        "user has {} user delegates",
+        user.number_of_user_delegates
+    )?;
+
     for perp_position in &user.perp_positions {
         validate!(
             perp_position.is_available(),
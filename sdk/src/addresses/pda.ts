@@ -145,6 +145,21 @@ export function getInsuranceFundStakeAccountPublicKey(
 	)[0];
 }
 
+export function getPerpLpStatsAccountPublicKey(
+	programId: PublicKey,
+	userAccountPublicKey: PublicKey,
+	marketIndex: number
+): PublicKey {
+	return PublicKey.findProgramAddressSync(
+		[
+			Buffer.from(anchor.utils.bytes.utf8.encode('perp_lp_stats')),
+			userAccountPublicKey.toBuffer(),
+			new anchor.BN(marketIndex).toArrayLike(Buffer, 'le', 2),
+		],
+		programId
+	)[0];
+}
+
 export function getDriftSignerPublicKey(programId: PublicKey): PublicKey {
 	return PublicKey.findProgramAddressSync(
 		[Buffer.from(anchor.utils.bytes.utf8.encode('drift_signer'))],
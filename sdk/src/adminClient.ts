@@ -145,6 +145,28 @@ export class AdminClient extends DriftClient {
 		return txSig;
 	}
 
+	/**
+	 * Reallocates a spot market created before the junior insurance fund and protocol revenue
+	 * ledger were appended to the account. Markets fail to load until they're resized, so every
+	 * existing market should be resized right after the program upgrade, before the exchange is
+	 * unpaused
+	 */
+	public async resizeSpotMarket(
+		marketIndex: number
+	): Promise<TransactionSignature> {
+		return await this.program.rpc.resizeSpotMarket(marketIndex, {
+			accounts: {
+				admin: this.wallet.publicKey,
+				state: await this.getStatePublicKey(),
+				spotMarket: await getSpotMarketPublicKey(
+					this.program.programId,
+					marketIndex
+				),
+				systemProgram: anchor.web3.SystemProgram.programId,
+			},
+		});
+	}
+
 	public async initializeSerumFulfillmentConfig(
 		marketIndex: number,
 		serumMarket: PublicKey,
@@ -305,6 +327,27 @@ export class AdminClient extends DriftClient {
 		return txSig;
 	}
 
+	/**
+	 * Reallocates a perp market created before the dedicated insurance fund was appended to the
+	 * account. Markets fail to load until they're resized, so every existing market should be
+	 * resized right after the program upgrade, before the exchange is unpaused
+	 */
+	public async resizePerpMarket(
+		marketIndex: number
+	): Promise<TransactionSignature> {
+		return await this.program.rpc.resizePerpMarket(marketIndex, {
+			accounts: {
+				admin: this.wallet.publicKey,
+				state: await this.getStatePublicKey(),
+				perpMarket: await getPerpMarketPublicKey(
+					this.program.programId,
+					marketIndex
+				),
+				systemProgram: anchor.web3.SystemProgram.programId,
+			},
+		});
+	}
+
 	public async moveAmmPrice(
 		perpMarketIndex: number,
 		baseAssetReserve: BN,
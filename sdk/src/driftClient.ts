@@ -5129,6 +5129,49 @@ export class DriftClient {
 		);
 	}
 
+	/**
+	 * Reallocates an insurance fund stake created before revenue accounting was appended to the
+	 * account. The stake can't be loaded until it's resized. Anyone can pay to resize a stake
+	 */
+	public async resizeInsuranceFundStake(
+		marketIndex: number,
+		authority = this.wallet.publicKey,
+		txParams?: TxParams
+	): Promise<TransactionSignature> {
+		const { txSig } = await this.sendTransaction(
+			await this.buildTransaction(
+				await this.getResizeInsuranceFundStakeIx(marketIndex, authority),
+				txParams
+			),
+			[],
+			this.opts
+		);
+		return txSig;
+	}
+
+	public async getResizeInsuranceFundStakeIx(
+		marketIndex: number,
+		authority = this.wallet.publicKey
+	): Promise<TransactionInstruction> {
+		const ifStakeAccountPublicKey = getInsuranceFundStakeAccountPublicKey(
+			this.program.programId,
+			authority,
+			marketIndex
+		);
+
+		return await this.program.instruction.resizeInsuranceFundStake(
+			marketIndex,
+			{
+				accounts: {
+					insuranceFundStake: ifStakeAccountPublicKey,
+					authority,
+					payer: this.wallet.publicKey,
+					systemProgram: anchor.web3.SystemProgram.programId,
+				},
+			}
+		);
+	}
+
 	public async getAddInsuranceFundStakeIx(
 		marketIndex: number,
 		amount: BN,
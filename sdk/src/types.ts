@@ -97,6 +97,11 @@ export class MarketType {
 	static readonly PERP = { perp: {} };
 }
 
+export class InsuranceFundTranche {
+	static readonly SENIOR = { senior: {} };
+	static readonly JUNIOR = { junior: {} };
+}
+
 export class OrderStatus {
 	static readonly INIT = { init: {} };
 	static readonly OPEN = { open: {} };
@@ -331,6 +336,9 @@ export type LPRecord = {
 	deltaBaseAssetAmount: BN;
 	deltaQuoteAssetAmount: BN;
 	pnl: BN;
+	feeRevenue: BN;
+	fundingPayment: BN;
+	inventoryPnl: BN;
 };
 
 export class LPAction {
@@ -581,6 +589,25 @@ export type PerpMarketAccount = {
 		quoteMaxInsurance: BN;
 	};
 	quoteSpotMarketIndex: number;
+	pausedOperations: number;
+	circuitBreakerCooldownEndTs: BN;
+	circuitBreakerThreshold: number;
+	circuitBreakerCooldownPeriod: number;
+	ammLiquidationGracePeriod: number;
+	insuranceFund: InsuranceFund;
+	insuranceFundRevenuePerShare: BN;
+};
+
+export type InsuranceFund = {
+	vault: PublicKey;
+	totalShares: BN;
+	userShares: BN;
+	sharesBase: BN;
+	unstakingPeriod: BN;
+	lastRevenueSettleTs: BN;
+	revenueSettlePeriod: BN;
+	totalFactor: number;
+	userFactor: number;
 };
 
 export type HistoricalOracleData = {
@@ -671,6 +698,28 @@ export type SpotMarketAccount = {
 	flashLoanInitialTokenAmount: BN;
 
 	ordersEnabled: boolean;
+
+	pausedOperations: number;
+
+	insuranceFundRevenuePerShare: BN;
+	juniorInsuranceFund: InsuranceFund;
+	juniorInsuranceFundRevenuePerShare: BN;
+
+	protocolRevenue: ProtocolRevenue;
+};
+
+export type ProtocolRevenue = {
+	takerFees: BN;
+	liquidationFees: BN;
+	borrowInterest: BN;
+	ammFees: BN;
+	ammFeePoolTopUps: BN;
+	settledToInsuranceFund: BN;
+	withdrawn: BN;
+	maxWithdrawPerPeriod: BN;
+	withdrawnInPeriod: BN;
+	withdrawPeriod: BN;
+	lastWithdrawPeriodStartTs: BN;
 };
 
 export type PoolBalance = {
@@ -1026,6 +1075,22 @@ export type OracleGuardRails = {
 
 export type MarginCategory = 'Initial' | 'Maintenance';
 
+export type PerpLpStats = {
+	user: PublicKey;
+	lpShares: BN;
+	lastBaseAssetAmountPerLp: BN;
+	lastQuoteAssetAmountPerLp: BN;
+	lastFeePerLp: BN;
+	lastCumulativeFundingRate: BN;
+	lastOraclePrice: BN;
+	baseAssetAmount: BN;
+	feeRevenue: BN;
+	fundingPayment: BN;
+	inventoryPnl: BN;
+	lastUpdateTs: BN;
+	marketIndex: number;
+};
+
 export type InsuranceFundStake = {
 	costBasis: BN;
 
@@ -1038,6 +1103,13 @@ export type InsuranceFundStake = {
 	lastWithdrawRequestShares: BN;
 	lastWithdrawRequestValue: BN;
 	lastWithdrawRequestTs: BN;
+
+	marketType: MarketType;
+	tranche: InsuranceFundTranche;
+	claimRevenue: boolean;
+	lastRevenuePerShare: BN;
+	cumulativeRevenue: BN;
+	unclaimedRevenue: BN;
 };
 
 export type SerumV3FulfillmentConfigAccount = {